@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use crate::parser::{r#type::DType, r#type::GType, Statement, Type, TypeAlias};
+
+/// Type aliases in scope, used to compare types by what they stand for
+/// rather than by how they are spelled.
+#[derive(Debug, Clone, Default)]
+pub struct AliasTable {
+    aliases: HashMap<String, TypeAlias>,
+}
+
+impl AliasTable {
+    pub fn new() -> AliasTable {
+        AliasTable::default()
+    }
+
+    pub fn from_statements(statements: &[Statement]) -> AliasTable {
+        let mut table = AliasTable::new();
+        table.collect(statements);
+        table
+    }
+
+    pub fn collect(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            if let Statement::TypeAlias(alias) = statement.unexported() {
+                self.insert(alias.clone());
+            }
+        }
+    }
+
+    pub fn insert(&mut self, alias: TypeAlias) {
+        self.aliases.insert(alias.name().clone(), alias);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TypeAlias> {
+        self.aliases.get(name)
+    }
+
+    pub fn clear(&mut self) {
+        self.aliases.clear();
+    }
+
+    /// Replaces every alias in `ty` by its target, recursively. Aliases used
+    /// with the wrong number of arguments, or that are cyclic, are left as
+    /// written.
+    pub fn expand(&self, ty: &Type) -> Type {
+        self.expand_with(ty, &mut vec![])
+    }
+
+    fn expand_with(&self, ty: &Type, visiting: &mut Vec<String>) -> Type {
+        let (name, args) = match ty {
+            Type::Custom(name) => (name, vec![]),
+            Type::Generic(gtype) => (
+                &gtype.name,
+                gtype
+                    .fields
+                    .iter()
+                    .map(|t| self.expand_with(t, visiting))
+                    .collect(),
+            ),
+            Type::Array(element, len) => {
+                return Type::Array(Box::new(self.expand_with(element, visiting)), *len)
+            }
+            Type::Slice(element) => {
+                return Type::Slice(Box::new(self.expand_with(element, visiting)))
+            }
+            Type::Function(params, ret) => {
+                return Type::Function(
+                    params
+                        .iter()
+                        .map(|t| self.expand_with(t, visiting))
+                        .collect(),
+                    Box::new(self.expand_with(ret, visiting)),
+                )
+            }
+            Type::Tuple(elements) => {
+                return Type::Tuple(
+                    elements
+                        .iter()
+                        .map(|t| self.expand_with(t, visiting))
+                        .collect(),
+                )
+            }
+            Type::Dependent(dtype) => {
+                return Type::Dependent(DType {
+                    params: dtype.params.clone(),
+                    body: Box::new(self.expand_with(&dtype.body, visiting)),
+                })
+            }
+            _ => return ty.clone(),
+        };
+        let target = match self.aliases.get(name) {
+            Some(alias) if !visiting.contains(name) => alias.instantiate(&args),
+            _ => None,
+        };
+        match target {
+            Some(target) => {
+                visiting.push(name.clone());
+                let expanded = self.expand_with(&target, visiting);
+                visiting.pop();
+                expanded
+            }
+            None if args.is_empty() => ty.clone(),
+            None => Type::Generic(GType::new(name.clone(), args)),
+        }
+    }
+
+    /// The chain of aliases leading from `name` back to itself, if any.
+    pub fn cycle(&self, name: &str) -> Option<Vec<String>> {
+        let mut path = vec![name.to_string()];
+        self.find_cycle(name, &mut path).then_some(path)
+    }
+
+    fn find_cycle(&self, start: &str, path: &mut Vec<String>) -> bool {
+        let current = path.last().unwrap().clone();
+        let Some(alias) = self.aliases.get(&current) else {
+            return false;
+        };
+        let names = Self::referenced_names(&alias.target)
+            .into_iter()
+            .filter(|n| !alias.params.contains(n));
+        for next in names {
+            if next == start {
+                path.push(next);
+                return true;
+            }
+            if path.contains(&next) || !self.aliases.contains_key(&next) {
+                continue;
+            }
+            path.push(next);
+            if self.find_cycle(start, path) {
+                return true;
+            }
+            path.pop();
+        }
+        false
+    }
+
+    fn referenced_names(ty: &Type) -> Vec<String> {
+        match ty {
+            Type::Custom(name) => vec![name.clone()],
+            Type::Generic(gtype) => std::iter::once(gtype.name.clone())
+                .chain(gtype.fields.iter().flat_map(Self::referenced_names))
+                .collect(),
+            Type::Dependent(dtype) => Self::referenced_names(&dtype.body),
+            Type::Array(element, _) | Type::Slice(element) => Self::referenced_names(element),
+            Type::Tuple(elements) => elements.iter().flat_map(Self::referenced_names).collect(),
+            Type::Function(params, ret) => params
+                .iter()
+                .chain(std::iter::once(&**ret))
+                .flat_map(Self::referenced_names)
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::VoeParser;
+
+    #[test]
+    fn test_expand_and_cycles() {
+        let source = "
+            type Meters = f64;
+            type Pair<T> = Tuple<T, T>;
+            type Path = Pair<Meters>;
+            type A = Vec<B>;
+            type B = A;
+        ";
+        let program = VoeParser.parse_program(source).unwrap();
+        let table = AliasTable::from_statements(&program.statements);
+        assert_eq!(
+            table.expand(&Type::Custom("Path".to_string())).to_string(),
+            "Tuple<f64, f64>"
+        );
+        assert_eq!(table.cycle("Path"), None);
+        assert_eq!(
+            table.cycle("A"),
+            Some(vec!["A".to_string(), "B".to_string(), "A".to_string()])
+        );
+        // Expansion of a cyclic alias terminates.
+        assert_eq!(
+            table.expand(&Type::Custom("A".to_string())).to_string(),
+            "Vec<A>"
+        );
+    }
+}
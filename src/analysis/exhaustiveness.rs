@@ -0,0 +1,287 @@
+//! Exhaustiveness and reachability of `match` arms, following Maranget's
+//! "Warnings for pattern matching": a pattern is useful with respect to the
+//! arms above it if some value matches it and none of them.
+
+use std::collections::HashMap;
+
+use crate::parser::{
+    atom::{Atom, AtomValue},
+    EnumDefinition, Pattern, Type,
+};
+
+#[derive(PartialEq, Debug, Clone)]
+enum Constructor {
+    Variant(String, String),
+    Bool(bool),
+    Literal(Atom),
+    /// The only constructor of a tuple type, with the tuple's length.
+    Tuple(usize),
+    /// `some`, `none`, `ok` or `err`.
+    Builtin(&'static str),
+}
+
+pub struct PatternMatrix<'a> {
+    enums: &'a HashMap<String, EnumDefinition>,
+}
+
+impl<'a> PatternMatrix<'a> {
+    pub fn new(enums: &'a HashMap<String, EnumDefinition>) -> PatternMatrix<'a> {
+        PatternMatrix { enums }
+    }
+
+    fn constructor(pattern: &Pattern) -> Option<Constructor> {
+        match pattern {
+            Pattern::Wildcard | Pattern::Binding(_) => None,
+            Pattern::Variant(name, variant, _) => {
+                Some(Constructor::Variant(name.clone(), variant.clone()))
+            }
+            Pattern::Literal(Atom {
+                value: AtomValue::Boolean(b),
+                ..
+            }) => Some(Constructor::Bool(*b)),
+            Pattern::Literal(atom) => Some(Constructor::Literal(atom.clone())),
+            Pattern::Tuple(fields) => Some(Constructor::Tuple(fields.len())),
+            Pattern::Some(_) => Some(Constructor::Builtin("some")),
+            Pattern::None => Some(Constructor::Builtin("none")),
+            Pattern::Ok(_) => Some(Constructor::Builtin("ok")),
+            Pattern::Err(_) => Some(Constructor::Builtin("err")),
+        }
+    }
+
+    fn arity(&self, constructor: &Constructor) -> usize {
+        match constructor {
+            Constructor::Variant(name, variant) => self
+                .enums
+                .get(name)
+                .and_then(|e| e.variant(variant))
+                .map_or(0, |v| v.fields.len()),
+            Constructor::Tuple(len) => *len,
+            Constructor::Builtin(name) => usize::from(*name != "none"),
+            Constructor::Bool(_) | Constructor::Literal(_) => 0,
+        }
+    }
+
+    /// Every constructor of the type the given constructors belong to, or
+    /// `None` if there are infinitely many.
+    fn all_constructors(&self, seen: &[Constructor]) -> Option<Vec<Constructor>> {
+        match seen.first()? {
+            Constructor::Bool(_) => Some(vec![Constructor::Bool(true), Constructor::Bool(false)]),
+            Constructor::Variant(name, _) => Some(
+                self.enums
+                    .get(name)?
+                    .variants()
+                    .iter()
+                    .map(|v| Constructor::Variant(name.clone(), v.name.clone()))
+                    .collect(),
+            ),
+            Constructor::Tuple(len) => Some(vec![Constructor::Tuple(*len)]),
+            Constructor::Builtin("some" | "none") => Some(vec![
+                Constructor::Builtin("some"),
+                Constructor::Builtin("none"),
+            ]),
+            Constructor::Builtin(_) => Some(vec![
+                Constructor::Builtin("ok"),
+                Constructor::Builtin("err"),
+            ]),
+            Constructor::Literal(_) => None,
+        }
+    }
+
+    fn head_constructors(matrix: &[Vec<Pattern>]) -> Vec<Constructor> {
+        let mut seen = vec![];
+        for row in matrix {
+            if let Some(c) = row.first().and_then(Self::constructor) {
+                if !seen.contains(&c) {
+                    seen.push(c);
+                }
+            }
+        }
+        seen
+    }
+
+    /// The row with its head replaced by the head's sub-patterns, if the
+    /// head can match `constructor`. Rows are only empty if their patterns
+    /// did not fit the matched type, and match nothing.
+    fn specialize(
+        row: &[Pattern],
+        constructor: &Constructor,
+        arity: usize,
+    ) -> Option<Vec<Pattern>> {
+        let mut fields = match row.first()? {
+            Pattern::Wildcard | Pattern::Binding(_) => vec![Pattern::Wildcard; arity],
+            Pattern::Variant(_, _, fields) | Pattern::Tuple(fields)
+                if Self::constructor(&row[0]).as_ref() == Some(constructor) =>
+            {
+                fields.clone()
+            }
+            Pattern::Some(inner) | Pattern::Ok(inner) | Pattern::Err(inner)
+                if Self::constructor(&row[0]).as_ref() == Some(constructor) =>
+            {
+                vec![(**inner).clone()]
+            }
+            p if Self::constructor(p).as_ref() == Some(constructor) => vec![],
+            _ => return None,
+        };
+        fields.extend_from_slice(&row[1..]);
+        Some(fields)
+    }
+
+    fn specialize_matrix(
+        matrix: &[Vec<Pattern>],
+        constructor: &Constructor,
+        arity: usize,
+    ) -> Vec<Vec<Pattern>> {
+        matrix
+            .iter()
+            .filter_map(|row| Self::specialize(row, constructor, arity))
+            .collect()
+    }
+
+    /// The rows whose head matches anything, without their head.
+    fn default_matrix(matrix: &[Vec<Pattern>]) -> Vec<Vec<Pattern>> {
+        matrix
+            .iter()
+            .filter(|row| row.first().is_some_and(|p| Self::constructor(p).is_none()))
+            .map(|row| row[1..].to_vec())
+            .collect()
+    }
+
+    /// Whether some value matched by `row` is not matched by any row of
+    /// `matrix`.
+    pub fn is_useful(&self, matrix: &[Vec<Pattern>], row: &[Pattern]) -> bool {
+        if row.is_empty() {
+            return matrix.is_empty();
+        }
+        if let Some(c) = Self::constructor(&row[0]) {
+            let arity = self.arity(&c);
+            return match Self::specialize(row, &c, arity) {
+                Some(row) => self.is_useful(&Self::specialize_matrix(matrix, &c, arity), &row),
+                None => false,
+            };
+        }
+        let seen = Self::head_constructors(matrix);
+        match self.all_constructors(&seen) {
+            Some(all) if all.iter().all(|c| seen.contains(c)) => all.iter().any(|c| {
+                let arity = self.arity(c);
+                let row = Self::specialize(row, c, arity).unwrap();
+                self.is_useful(&Self::specialize_matrix(matrix, c, arity), &row)
+            }),
+            _ => self.is_useful(&Self::default_matrix(matrix), &row[1..]),
+        }
+    }
+
+    fn rebuild(&self, constructor: &Constructor, mut witness: Vec<Pattern>) -> Vec<Pattern> {
+        let rest = witness.split_off(self.arity(constructor));
+        let head = match constructor {
+            Constructor::Variant(name, variant) => {
+                Pattern::Variant(name.clone(), variant.clone(), witness)
+            }
+            Constructor::Bool(b) => {
+                Pattern::Literal(Atom::new(false, AtomValue::Boolean(*b), Some(Type::Bool)))
+            }
+            Constructor::Literal(atom) => Pattern::Literal(atom.clone()),
+            Constructor::Tuple(_) => Pattern::Tuple(witness),
+            Constructor::Builtin("none") => Pattern::None,
+            Constructor::Builtin(name) => {
+                let inner = Box::new(witness.remove(0));
+                match *name {
+                    "some" => Pattern::Some(inner),
+                    "ok" => Pattern::Ok(inner),
+                    _ => Pattern::Err(inner),
+                }
+            }
+        };
+        let mut row = vec![head];
+        row.extend(rest);
+        row
+    }
+
+    /// A row of `width` patterns matched by no row of `matrix`, if any.
+    pub fn missing(&self, matrix: &[Vec<Pattern>], width: usize) -> Option<Vec<Pattern>> {
+        if width == 0 {
+            return matrix.is_empty().then(Vec::new);
+        }
+        let seen = Self::head_constructors(matrix);
+        let all = self.all_constructors(&seen);
+        if let Some(all) = &all {
+            if all.iter().all(|c| seen.contains(c)) {
+                return all.iter().find_map(|c| {
+                    let arity = self.arity(c);
+                    let specialized = Self::specialize_matrix(matrix, c, arity);
+                    self.missing(&specialized, arity + width - 1)
+                        .map(|w| self.rebuild(c, w))
+                });
+            }
+        }
+        let rest = self.missing(&Self::default_matrix(matrix), width - 1)?;
+        let head = match all.and_then(|all| all.into_iter().find(|c| !seen.contains(c))) {
+            Some(c) => {
+                let wildcards = vec![Pattern::Wildcard; self.arity(&c)];
+                self.rebuild(&c, wildcards).remove(0)
+            }
+            None => Pattern::Wildcard,
+        };
+        let mut row = vec![head];
+        row.extend(rest);
+        Some(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{EnumVariant, Type};
+
+    fn variant(name: &str, fields: Vec<Pattern>) -> Pattern {
+        Pattern::Variant("Shape".to_string(), name.to_string(), fields)
+    }
+
+    #[test]
+    fn test_missing_and_useful() {
+        let mut enums = HashMap::new();
+        enums.insert(
+            "Shape".to_string(),
+            EnumDefinition::new(
+                "Shape".to_string(),
+                vec![
+                    EnumVariant::new("Circle".to_string(), vec![Type::F64]),
+                    EnumVariant::new("Pair".to_string(), vec![Type::Bool, Type::Bool]),
+                ],
+            ),
+        );
+        let matrix = PatternMatrix::new(&enums);
+        let t = || Pattern::Literal(Atom::new(false, AtomValue::Boolean(true), Some(Type::Bool)));
+        let rows = vec![
+            vec![variant("Circle", vec![Pattern::Wildcard])],
+            vec![variant("Pair", vec![t(), Pattern::Wildcard])],
+        ];
+        let witness = matrix.missing(&rows, 1).unwrap();
+        assert_eq!(witness[0].to_string(), "Shape::Pair(false, _)");
+
+        let mut rows = rows;
+        rows.push(vec![variant(
+            "Pair",
+            vec![Pattern::Binding("a".to_string()), Pattern::Wildcard],
+        )]);
+        assert!(matrix.missing(&rows, 1).is_none());
+        assert!(!matrix.is_useful(&rows, &[variant("Pair", vec![t(), t()])]));
+        assert!(!matrix.is_useful(&rows, &[Pattern::Wildcard]));
+
+        // A variant of an unknown enum has no fields as far as the matrix
+        // knows, so specializing by it leaves rows empty.
+        let unknown = Pattern::Variant(
+            "Other".to_string(),
+            "Rect".to_string(),
+            vec![Pattern::Binding("w".to_string()), Pattern::Wildcard],
+        );
+        let rows = vec![
+            vec![Pattern::Binding("x".to_string())],
+            vec![Pattern::Err(Box::new(Pattern::Wildcard))],
+            vec![unknown],
+        ];
+        matrix.missing(&rows, 1);
+        for i in 0..rows.len() {
+            matrix.is_useful(&rows[..i], &rows[i]);
+        }
+    }
+}
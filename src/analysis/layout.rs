@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+
+use crate::parser::{ClassDefinition, EnumDefinition, Program, Statement, Type};
+
+use super::{AliasTable, Diagnostic};
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct FieldLayout {
+    pub name: String,
+    pub ty: Type,
+    pub offset: usize,
+}
+
+/// The in-memory representation of a class, laid out like a C struct:
+/// fields in declaration order, each aligned to its natural alignment.
+#[derive(PartialEq, Debug, Clone)]
+pub struct StructLayout {
+    pub name: String,
+    pub size: usize,
+    pub align: usize,
+    pub fields: Vec<FieldLayout>,
+}
+
+impl StructLayout {
+    pub fn field(&self, name: &str) -> Option<&FieldLayout> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+/// The in-memory representation of an enum, laid out as a tagged union:
+/// a tag numbering the variant, then the fields of that variant laid out
+/// like a class, starting at the same offset for every variant.
+#[derive(PartialEq, Debug, Clone)]
+pub struct EnumLayout {
+    pub name: String,
+    pub size: usize,
+    pub align: usize,
+    /// The size of the tag, which is at offset 0.
+    pub tag_size: usize,
+    /// One layout per variant, in declaration order, with fields named by
+    /// position and offsets from the start of the enum.
+    pub variants: Vec<StructLayout>,
+}
+
+impl EnumLayout {
+    pub fn variant(&self, name: &str) -> Option<&StructLayout> {
+        self.variants.iter().find(|v| v.name == name)
+    }
+}
+
+/// The layouts of the classes and enums of a module.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct TypeLayouts {
+    pub classes: HashMap<String, StructLayout>,
+    pub enums: HashMap<String, EnumLayout>,
+}
+
+struct LayoutBuilder<'a> {
+    classes: HashMap<&'a str, &'a ClassDefinition>,
+    enums: HashMap<&'a str, &'a EnumDefinition>,
+    aliases: AliasTable,
+    layouts: TypeLayouts,
+    in_progress: Vec<String>,
+}
+
+/// Lays out `fields` one after another from `offset`, each aligned to its
+/// natural alignment, giving their layouts, the offset after the last one
+/// and their largest alignment.
+fn place_fields<'a>(
+    builder: &mut LayoutBuilder,
+    fields: impl Iterator<Item = (String, &'a Type)>,
+    mut offset: usize,
+) -> Result<(Vec<FieldLayout>, usize, usize), Diagnostic> {
+    let mut layouts = vec![];
+    let mut align = 1;
+    for (name, ty) in fields {
+        let ty = builder.aliases.expand(ty);
+        let (field_size, field_align) = builder.size_align(&ty)?;
+        offset = offset.next_multiple_of(field_align);
+        layouts.push(FieldLayout { name, ty, offset });
+        offset += field_size;
+        align = align.max(field_align);
+    }
+    Ok((layouts, offset, align))
+}
+
+impl LayoutBuilder<'_> {
+    /// Size and alignment of a value of type `ty`.
+    fn size_align(&mut self, ty: &Type) -> Result<(usize, usize), Diagnostic> {
+        match ty {
+            Type::U8 | Type::I8 | Type::Bool => Ok((1, 1)),
+            Type::U16 | Type::I16 => Ok((2, 2)),
+            Type::U32 | Type::I32 | Type::F32 => Ok((4, 4)),
+            Type::U64 | Type::I64 | Type::F64 => Ok((8, 8)),
+            Type::U128 | Type::I128 => Ok((16, 16)),
+            // Pointer and length.
+            Type::String => Ok((16, 8)),
+            Type::Array(element, len) => {
+                let (size, align) = self.size_align(element)?;
+                Ok((size * len, align))
+            }
+            // A pointer to the first element and the length.
+            Type::Slice(_) => Ok((16, 8)),
+            // A pointer to the code and one to the closure's environment.
+            Type::Function(..) => Ok((16, 8)),
+            // Laid out like a class with the elements as fields.
+            Type::Tuple(elements) => {
+                let (mut size, mut align) = (0usize, 1usize);
+                for element in elements {
+                    let (element_size, element_align) = self.size_align(element)?;
+                    size = size.next_multiple_of(element_align) + element_size;
+                    align = align.max(element_align);
+                }
+                Ok((size.next_multiple_of(align), align))
+            }
+            // A tag byte, then the value if there is one.
+            Type::Generic(_) if ty.optional_inner().is_some() => {
+                let (size, align) = self.size_align(ty.optional_inner().unwrap())?;
+                Ok(((align + size).next_multiple_of(align), align))
+            }
+            // A tag byte, then the value or the error.
+            Type::Generic(_) if ty.result_types().is_some() => {
+                let (ok, err) = ty.result_types().unwrap();
+                let (ok_size, ok_align) = self.size_align(ok)?;
+                let (err_size, err_align) = self.size_align(err)?;
+                let align = ok_align.max(err_align);
+                Ok((
+                    (align + ok_size.max(err_size)).next_multiple_of(align),
+                    align,
+                ))
+            }
+            Type::Unit => Ok((0, 1)),
+            Type::Custom(name) if self.classes.contains_key(name.as_str()) => {
+                let layout = self.layout(name)?;
+                Ok((layout.size, layout.align))
+            }
+            Type::Custom(name) if self.enums.contains_key(name.as_str()) => {
+                let layout = self.enum_layout(name)?;
+                Ok((layout.size, layout.align))
+            }
+            _ => Err(Diagnostic::error(format!(
+                "type `{}` has no fixed layout",
+                ty
+            ))),
+        }
+    }
+
+    /// Marks `name` as being laid out, failing if it already is, which
+    /// means the type contains itself.
+    fn enter(&mut self, kind: &str, name: &str) -> Result<(), Diagnostic> {
+        if self.in_progress.iter().any(|n| n == name) {
+            return Err(Diagnostic::error(format!(
+                "{} `{}` contains itself and has infinite size",
+                kind, name
+            )));
+        }
+        self.in_progress.push(name.to_string());
+        Ok(())
+    }
+
+    fn layout(&mut self, name: &str) -> Result<StructLayout, Diagnostic> {
+        if let Some(layout) = self.layouts.classes.get(name) {
+            return Ok(layout.clone());
+        }
+        self.enter("class", name)?;
+
+        let class = self.classes[name];
+        let unit = Type::Unit;
+        let (fields, end, align) = place_fields(
+            self,
+            class.fields().iter().map(|field| {
+                (
+                    field.name().clone(),
+                    field.var_type().as_ref().unwrap_or(&unit),
+                )
+            }),
+            0,
+        )?;
+
+        self.in_progress.pop();
+        let layout = StructLayout {
+            name: name.to_string(),
+            size: end.next_multiple_of(align),
+            align,
+            fields,
+        };
+        self.layouts
+            .classes
+            .insert(name.to_string(), layout.clone());
+        Ok(layout)
+    }
+
+    fn enum_layout(&mut self, name: &str) -> Result<EnumLayout, Diagnostic> {
+        if let Some(layout) = self.layouts.enums.get(name) {
+            return Ok(layout.clone());
+        }
+        self.enter("enum", name)?;
+
+        let enumeration = self.enums[name];
+        let tag_size: usize = match enumeration.variants().len() {
+            0..=0x100 => 1,
+            0x101..=0x1_0000 => 2,
+            _ => 4,
+        };
+        // Every payload starts at the same offset, after the tag and
+        // aligned for the most aligned field of any variant.
+        let mut payload_align = 1;
+        for variant in enumeration.variants() {
+            for field in &variant.fields {
+                let field = self.aliases.expand(field);
+                payload_align = payload_align.max(self.size_align(&field)?.1);
+            }
+        }
+        let payload_offset = tag_size.next_multiple_of(payload_align);
+
+        let (mut size, align) = (payload_offset, tag_size.max(payload_align));
+        let mut variants = vec![];
+        for variant in enumeration.variants() {
+            let (fields, end, variant_align) = place_fields(
+                self,
+                variant
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, ty)| (i.to_string(), ty)),
+                payload_offset,
+            )?;
+            size = size.max(end);
+            variants.push(StructLayout {
+                name: variant.name.clone(),
+                size: end.next_multiple_of(variant_align),
+                align: variant_align,
+                fields,
+            });
+        }
+
+        self.in_progress.pop();
+        let layout = EnumLayout {
+            name: name.to_string(),
+            size: size.next_multiple_of(align),
+            align,
+            tag_size,
+            variants,
+        };
+        self.layouts.enums.insert(name.to_string(), layout.clone());
+        Ok(layout)
+    }
+}
+
+/// Computes the layout of every top-level class and enum of `program`, and
+/// of those in `imports` that it uses, for use by code generators.
+pub fn type_layouts(program: &Program, imports: &[Statement]) -> Result<TypeLayouts, Diagnostic> {
+    let mut classes = HashMap::new();
+    let mut enums = HashMap::new();
+    let mut aliases = AliasTable::from_statements(imports);
+    aliases.collect(&program.statements);
+    for statement in imports.iter().chain(&program.statements) {
+        match statement.unexported() {
+            Statement::Class(class) => {
+                classes.insert(class.name().as_str(), class);
+            }
+            Statement::Enum(enumeration) => {
+                enums.insert(enumeration.name().as_str(), enumeration);
+            }
+            _ => {}
+        }
+    }
+    let mut builder = LayoutBuilder {
+        classes,
+        enums,
+        aliases,
+        layouts: TypeLayouts::default(),
+        in_progress: vec![],
+    };
+    for statement in &program.statements {
+        match statement.unexported() {
+            Statement::Class(class) => {
+                builder.layout(class.name())?;
+            }
+            Statement::Enum(enumeration) => {
+                builder.enum_layout(enumeration.name())?;
+            }
+            _ => {}
+        }
+    }
+    Ok(builder.layouts)
+}
+
+/// Computes the struct layout of every top-level class.
+pub fn class_layouts(program: &Program) -> Result<HashMap<String, StructLayout>, Diagnostic> {
+    type_layouts(program, &[]).map(|layouts| layouts.classes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::VoeParser;
+
+    #[test]
+    fn test_class_layouts() {
+        let source = "
+            type Real = f64;
+            class Inner { flag: bool, value: Real }
+            class Outer { tag: u8, inner: Inner, count: u16 }
+        ";
+        let program = VoeParser.parse_program(source).unwrap();
+        let layouts = class_layouts(&program).unwrap();
+
+        let inner = &layouts["Inner"];
+        assert_eq!((inner.size, inner.align), (16, 8));
+        assert_eq!(inner.field("value").unwrap().offset, 8);
+
+        let outer = &layouts["Outer"];
+        assert_eq!((outer.size, outer.align), (32, 8));
+        assert_eq!(outer.field("inner").unwrap().offset, 8);
+        assert_eq!(outer.field("count").unwrap().offset, 24);
+
+        let program = VoeParser
+            .parse_program("class List { next: List }")
+            .unwrap();
+        assert!(class_layouts(&program).is_err());
+    }
+
+    #[test]
+    fn test_enum_layouts() {
+        let source = "
+            enum Shape { Circle(f64), Rect(u8, u32), Empty }
+            enum Wide { Big(u128), Small(i8) }
+            class Holder { tag: u8, shape: Shape }
+        ";
+        let program = VoeParser.parse_program(source).unwrap();
+        let layouts = type_layouts(&program, &[]).unwrap();
+
+        let shape = &layouts.enums["Shape"];
+        assert_eq!((shape.size, shape.align, shape.tag_size), (16, 8, 1));
+        assert_eq!(shape.variant("Circle").unwrap().fields[0].offset, 8);
+        let rect = shape.variant("Rect").unwrap();
+        assert_eq!((rect.fields[0].offset, rect.fields[1].offset), (8, 12));
+        assert!(shape.variant("Empty").unwrap().fields.is_empty());
+
+        let wide = &layouts.enums["Wide"];
+        assert_eq!((wide.size, wide.align), (32, 16));
+        assert_eq!(layouts.classes["Holder"].field("shape").unwrap().offset, 8);
+
+        let program = VoeParser
+            .parse_program("enum List { Cons(i32, List), Nil }")
+            .unwrap();
+        assert_eq!(
+            type_layouts(&program, &[]).unwrap_err().message,
+            "enum `List` contains itself and has infinite size"
+        );
+    }
+
+    #[test]
+    fn test_array_and_tuple_layouts() {
+        let source = "class Buffer { len: u8, data: [u16; 5], view: [u8], pair: (u8, u32) }";
+        let program = VoeParser.parse_program(source).unwrap();
+        let buffer = &class_layouts(&program).unwrap()["Buffer"];
+        assert_eq!(buffer.field("data").unwrap().offset, 2);
+        assert_eq!(buffer.field("view").unwrap().offset, 16);
+        assert_eq!(buffer.field("pair").unwrap().offset, 32);
+        assert_eq!((buffer.size, buffer.align), (40, 8));
+
+        let program = VoeParser
+            .parse_program("class Slot { id: u8?, value: f64?, empty: ()?, r: Result<u8, u16> }")
+            .unwrap();
+        let slot = &class_layouts(&program).unwrap()["Slot"];
+        assert_eq!(slot.field("value").unwrap().offset, 8);
+        assert_eq!(slot.field("empty").unwrap().offset, 24);
+        assert_eq!(slot.field("r").unwrap().offset, 26);
+        assert_eq!((slot.size, slot.align), (32, 8));
+    }
+}
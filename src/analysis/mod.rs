@@ -0,0 +1,78 @@
+use crate::parser::lexer::Span;
+use crate::parser::{Program, Statement};
+
+mod aliases;
+pub use aliases::AliasTable;
+mod exhaustiveness;
+pub use exhaustiveness::PatternMatrix;
+mod layout;
+pub use layout::{class_layouts, type_layouts, EnumLayout, FieldLayout, StructLayout, TypeLayouts};
+mod resolver;
+pub use resolver::{Definition, DefinitionId, DefinitionKind, ResolvedNames, Resolver};
+mod type_checker;
+pub use type_checker::{Closure, Instantiation, MethodTarget, TypeChecker};
+
+/// Functions provided by the compiler rather than declared in source. A
+/// declaration of the same name hides them.
+pub const INTRINSICS: &[&str] = &["len"];
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// The code the problem is in, if the analysis knew where its nodes
+    /// are in the source.
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    pub fn error(message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            message,
+            span: None,
+        }
+    }
+
+    pub fn warning(message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            message,
+            span: None,
+        }
+    }
+
+    pub fn with_span(self, span: Option<Span>) -> Diagnostic {
+        Diagnostic { span, ..self }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: {}", self.message),
+            Severity::Warning => write!(f, "warning: {}", self.message),
+        }
+    }
+}
+
+/// A read-only pass over the AST that reports problems instead of rewriting.
+pub trait Analysis {
+    fn analyze(&mut self, program: &Program) -> Vec<Diagnostic>;
+
+    /// Analyzes one module of a larger program, given the declarations it
+    /// imports from other modules.
+    fn analyze_module(&mut self, program: &Program, _imports: &[Statement]) -> Vec<Diagnostic> {
+        self.analyze(program)
+    }
+}
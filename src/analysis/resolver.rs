@@ -0,0 +1,663 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::{
+    atom::{Atom, AtomValue},
+    ArmBody, Block, Conditional, Expression, FunctionDefinition, Iterable, Lambda, MatchArm,
+    Pattern, Program, Statement, VariableDeclaration,
+};
+
+use crate::parser::ast_index::Spans;
+use crate::parser::lexer::Span;
+
+use super::{Analysis, Diagnostic, INTRINSICS};
+
+/// Identifies one declaration, so that uses of a name can be told apart
+/// from uses of another declaration with the same name.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+pub struct DefinitionId(pub usize);
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum DefinitionKind {
+    Variable,
+    Parameter,
+    /// A name bound by a `match` pattern.
+    Binding,
+    Function,
+    Class,
+    Interface,
+    Enum,
+    TypeAlias,
+    /// A function provided by the compiler, such as `len`.
+    Intrinsic,
+}
+
+impl DefinitionKind {
+    /// Whether the definition holds a value at runtime, as opposed to
+    /// naming an item.
+    fn is_local(&self) -> bool {
+        matches!(
+            self,
+            DefinitionKind::Variable | DefinitionKind::Parameter | DefinitionKind::Binding
+        )
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Definition {
+    pub name: String,
+    pub kind: DefinitionKind,
+}
+
+/// The result of name resolution: every declaration, and what each use of
+/// a name refers to.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct ResolvedNames {
+    /// Indexed by `DefinitionId`.
+    pub definitions: Vec<Definition>,
+    /// Every resolved use of a name, in source order.
+    pub uses: Vec<(String, DefinitionId)>,
+}
+
+impl ResolvedNames {
+    pub fn definition(&self, id: DefinitionId) -> &Definition {
+        &self.definitions[id.0]
+    }
+
+    /// The number of times a definition is used.
+    pub fn uses_of(&self, id: DefinitionId) -> usize {
+        self.uses.iter().filter(|(_, d)| *d == id).count()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+    names: HashMap<String, DefinitionId>,
+    /// Variables declared further down in the scope.
+    pending: HashSet<String>,
+}
+
+/// Checks that every name refers to a declaration in scope.
+#[derive(Debug, Default)]
+pub struct Resolver {
+    scopes: Vec<Scope>,
+    names: ResolvedNames,
+    /// What each name in the program declares or refers to, by the address
+    /// of the name.
+    sites: HashMap<*const u8, DefinitionId>,
+    /// Where the nodes of the program are, for placing diagnostics.
+    spans: Spans,
+    /// Spans of the statements and expressions being resolved, innermost
+    /// last.
+    locations: Vec<Span>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver::default()
+    }
+
+    /// Places diagnostics at the nodes of the program that `spans` knows.
+    pub fn with_spans(self, spans: Spans) -> Resolver {
+        Resolver { spans, ..self }
+    }
+
+    /// The names resolved by the last analysis.
+    pub fn names(&self) -> &ResolvedNames {
+        &self.names
+    }
+
+    /// The definition that `name` declares or refers to, where `name`
+    /// borrows from the program last analyzed. Names are told apart by
+    /// address, so each `x` finds the declaration it resolved to.
+    pub fn resolution(&self, name: &str) -> Option<DefinitionId> {
+        self.sites.get(&name.as_ptr()).copied()
+    }
+
+    /// Reports `diagnostic`, placed at the innermost node being resolved
+    /// unless it has a place already.
+    fn report(&mut self, diagnostic: Diagnostic) {
+        let span = diagnostic.span.or(self.locations.last().copied());
+        self.diagnostics.push(diagnostic.with_span(span));
+    }
+
+    /// Runs `resolve` with the problems it finds placed at `span`, if known.
+    fn located(&mut self, span: Option<Span>, resolve: impl FnOnce(&mut Self)) {
+        let Some(span) = span else {
+            return resolve(self);
+        };
+        self.locations.push(span);
+        resolve(self);
+        self.locations.pop();
+    }
+
+    fn define(&mut self, name: &str, kind: DefinitionKind) -> DefinitionId {
+        let id = DefinitionId(self.names.definitions.len());
+        self.names.definitions.push(Definition {
+            name: name.to_string(),
+            kind,
+        });
+        self.sites.insert(name.as_ptr(), id);
+        let scope = self.scopes.last_mut().unwrap();
+        scope.pending.remove(name);
+        scope.names.insert(name.to_string(), id);
+        id
+    }
+
+    /// Defines a variable or pattern binding, warning if it hides another.
+    fn define_local(&mut self, name: &str, kind: DefinitionKind) {
+        if let Some(id) = self.lookup(name) {
+            if self.names.definition(id).kind.is_local() {
+                self.report(Diagnostic::warning(format!(
+                    "`{}` shadows an earlier declaration",
+                    name
+                )));
+            }
+        }
+        self.define(name, kind);
+    }
+
+    fn lookup(&self, name: &str) -> Option<DefinitionId> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.names.get(name))
+            .copied()
+    }
+
+    /// Records a use of `name`. Returns whether it resolved.
+    fn refer(&mut self, name: &str) -> bool {
+        match self.lookup(name) {
+            Some(id) => {
+                self.sites.insert(name.as_ptr(), id);
+                self.names.uses.push((name.to_string(), id));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Records a use of `name`, reporting it if it does not resolve.
+    fn refer_or_report(&mut self, name: &str, what: &str) {
+        if self.refer(name) {
+            return;
+        }
+        let pending = self.scopes.iter().any(|s| s.pending.contains(name));
+        self.report(Diagnostic::error(if pending {
+            format!("`{}` is used before its declaration", name)
+        } else {
+            format!("cannot find {} `{}` in this scope", what, name)
+        }));
+    }
+
+    /// Defines the items of a scope up front, so they can be used before
+    /// the point where they are declared, and notes its variables.
+    fn declare_items(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            let kind = match statement.unexported() {
+                Statement::Function(_) => DefinitionKind::Function,
+                Statement::Class(_) => DefinitionKind::Class,
+                Statement::Interface(_) => DefinitionKind::Interface,
+                Statement::Enum(_) => DefinitionKind::Enum,
+                Statement::TypeAlias(_) => DefinitionKind::TypeAlias,
+                Statement::VariableDeclaration(vd) => {
+                    let scope = self.scopes.last_mut().unwrap();
+                    scope
+                        .pending
+                        .extend(vd.pattern.bindings().into_iter().cloned());
+                    continue;
+                }
+                _ => continue,
+            };
+            let name = statement.declared_name().unwrap();
+            if self.scopes.last().unwrap().names.contains_key(name) {
+                let span = self.spans.statement(statement);
+                self.report(
+                    Diagnostic::error(format!("`{}` is defined more than once", name))
+                        .with_span(span),
+                );
+            }
+            self.define(name, kind);
+        }
+    }
+
+    fn resolve_statements(&mut self, statements: &[Statement]) {
+        self.declare_items(statements);
+        for statement in statements {
+            self.resolve_statement(statement);
+        }
+    }
+
+    fn resolve_block(&mut self, block: &Block) {
+        self.scopes.push(Scope::default());
+        self.resolve_statements(block.statements());
+        if let Some(value) = &block.value {
+            self.resolve_expression(value);
+        }
+        self.scopes.pop();
+    }
+
+    fn resolve_conditional(&mut self, cond: &Conditional) {
+        self.resolve_expression(&cond.condition);
+        match &cond.pattern {
+            Some(pattern) => {
+                self.scopes.push(Scope::default());
+                self.resolve_pattern(pattern);
+                self.resolve_block(&cond.then_block);
+                self.scopes.pop();
+            }
+            None => self.resolve_block(&cond.then_block),
+        }
+        if let Some(block) = &cond.else_block {
+            self.resolve_block(block);
+        }
+    }
+
+    fn resolve_statement(&mut self, statement: &Statement) {
+        let span = self.spans.statement(statement);
+        self.located(span, |r| r.resolve_unlocated(statement));
+    }
+
+    fn resolve_unlocated(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Function(fd) => self.resolve_function(fd),
+            Statement::VariableDeclaration(vd) => {
+                if let Some(value) = &vd.value {
+                    self.resolve_expression(value);
+                }
+                for name in vd.pattern.bindings() {
+                    self.define_local(name, DefinitionKind::Variable);
+                }
+            }
+            Statement::Expression(expr) => self.resolve_expression(expr),
+            Statement::Assignment(assignment) => {
+                self.resolve_expression(&assignment.value);
+                self.resolve_expression(&assignment.target);
+            }
+            Statement::Conditional(cond) => self.resolve_conditional(cond),
+            Statement::Block(block) => self.resolve_block(block),
+            Statement::While(w) => {
+                self.resolve_expression(&w.condition);
+                self.resolve_block(&w.body);
+            }
+            Statement::For(l) => {
+                match &l.iterable {
+                    Iterable::Range(range) => {
+                        self.resolve_expression(&range.start);
+                        self.resolve_expression(&range.end);
+                        if let Some(step) = &range.step {
+                            self.resolve_expression(step);
+                        }
+                    }
+                    Iterable::Expression(expr) => self.resolve_expression(expr),
+                }
+                self.scopes.push(Scope::default());
+                self.resolve_pattern(&l.pattern);
+                self.resolve_block(&l.body);
+                self.scopes.pop();
+            }
+            Statement::Impl(ib) => {
+                self.refer(ib.class());
+                for method in ib.methods() {
+                    self.resolve_function(method);
+                }
+            }
+            Statement::Export(statement) => self.resolve_statement(statement),
+            Statement::Class(_)
+            | Statement::Interface(_)
+            | Statement::Enum(_)
+            | Statement::TypeAlias(_)
+            | Statement::Module(_)
+            | Statement::Error(_)
+            | Statement::Import(_)
+            | Statement::Use(_) => {}
+        }
+    }
+
+    fn resolve_function(&mut self, fd: &FunctionDefinition) {
+        let owner = format!("`{}`", fd.name());
+        // `forall` parameters enclose the inputs, so an input of the same
+        // name, which is what gives the parameter its value, is not a
+        // duplicate.
+        self.scopes.push(Scope::default());
+        let dependent_params: Vec<&VariableDeclaration> = fd.dependent_params().iter().collect();
+        self.define_params(&dependent_params, &owner);
+        self.scopes.push(Scope::default());
+        let params: Vec<&VariableDeclaration> = fd.inputs().iter().collect();
+        self.define_params(&params, &owner);
+        self.resolve_block(fd.body());
+        self.scopes.pop();
+        self.scopes.pop();
+    }
+
+    fn resolve_lambda(&mut self, lambda: &Lambda) {
+        self.scopes.push(Scope::default());
+        let params: Vec<&VariableDeclaration> = lambda.params.iter().collect();
+        self.define_params(&params, "a closure");
+        match &lambda.body {
+            ArmBody::Expression(expr) => self.resolve_expression(expr),
+            ArmBody::Block(block) => self.resolve_block(block),
+        }
+        self.scopes.pop();
+    }
+
+    /// Defines parameters in the current scope. `owner` describes what they
+    /// belong to.
+    fn define_params(&mut self, params: &[&VariableDeclaration], owner: &str) {
+        for param in params {
+            if self.scopes.last().unwrap().names.contains_key(param.name()) {
+                self.report(Diagnostic::error(format!(
+                    "parameter `{}` is declared more than once in {}",
+                    param.name(),
+                    owner
+                )));
+            }
+            self.define(param.name(), DefinitionKind::Parameter);
+        }
+    }
+
+    fn resolve_expression(&mut self, expr: &Expression) {
+        let span = self.spans.expression(expr);
+        self.located(span, |r| r.resolve_unlocated_expression(expr));
+    }
+
+    fn resolve_unlocated_expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Atom(atom) => self.resolve_atom(atom),
+            Expression::BinaryOperation(lhs, _, rhs) => {
+                self.resolve_expression(lhs);
+                self.resolve_expression(rhs);
+            }
+            Expression::Unary(_, operand) => self.resolve_expression(operand),
+            Expression::New(class, fields) => {
+                // Unknown classes and enums are reported by the type checker.
+                self.refer(class);
+                for (_, value) in fields {
+                    self.resolve_expression(value);
+                }
+            }
+            Expression::FieldAccess(object, _) => self.resolve_expression(object),
+            Expression::Tuple(elements) | Expression::Array(elements) => {
+                for element in elements {
+                    self.resolve_expression(element);
+                }
+            }
+            Expression::Repeat(value, _)
+            | Expression::Some(value)
+            | Expression::IsSome(value)
+            | Expression::IsNone(value)
+            | Expression::Try(value)
+            | Expression::Ok(value)
+            | Expression::Err(value) => self.resolve_expression(value),
+            Expression::None => {}
+            Expression::Lambda(lambda) => self.resolve_lambda(lambda),
+            Expression::Index(array, index) => {
+                self.resolve_expression(array);
+                self.resolve_expression(index);
+            }
+            Expression::MethodCall(object, _, args) => {
+                self.resolve_expression(object);
+                for arg in args {
+                    self.resolve_expression(arg);
+                }
+            }
+            Expression::Variant(name, _, args) => {
+                self.refer(name);
+                for arg in args {
+                    self.resolve_expression(arg);
+                }
+            }
+            Expression::Match(scrutinee, arms) => {
+                self.resolve_expression(scrutinee);
+                for arm in arms {
+                    self.resolve_arm(arm);
+                }
+            }
+            Expression::If(cond) => self.resolve_conditional(cond),
+            Expression::Block(block) => self.resolve_block(block),
+        }
+    }
+
+    fn resolve_arm(&mut self, arm: &MatchArm) {
+        self.scopes.push(Scope::default());
+        self.resolve_pattern(&arm.pattern);
+        match &arm.body {
+            ArmBody::Expression(expr) => self.resolve_expression(expr),
+            ArmBody::Block(block) => self.resolve_statements(block.statements()),
+        }
+        self.scopes.pop();
+    }
+
+    fn resolve_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Binding(name) => self.define_local(name, DefinitionKind::Binding),
+            Pattern::Variant(name, _, fields) => {
+                self.refer(name);
+                for field in fields {
+                    self.resolve_pattern(field);
+                }
+            }
+            Pattern::Tuple(fields) => {
+                for field in fields {
+                    self.resolve_pattern(field);
+                }
+            }
+            Pattern::Some(inner) | Pattern::Ok(inner) | Pattern::Err(inner) => {
+                self.resolve_pattern(inner)
+            }
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::None => {}
+        }
+    }
+
+    fn resolve_atom(&mut self, atom: &Atom) {
+        match &atom.value {
+            AtomValue::Identity(name) => self.refer_or_report(name, "value"),
+            AtomValue::Call(name, args) => {
+                self.refer_or_report(name, "function");
+                for arg in args {
+                    self.resolve_expression(arg);
+                }
+            }
+            AtomValue::ParExpr(expr) => self.resolve_expression(expr),
+            _ => {}
+        }
+    }
+}
+
+impl Analysis for Resolver {
+    fn analyze(&mut self, program: &Program) -> Vec<Diagnostic> {
+        self.analyze_module(program, &[])
+    }
+
+    fn analyze_module(&mut self, program: &Program, imports: &[Statement]) -> Vec<Diagnostic> {
+        self.scopes = vec![Scope::default()];
+        self.names = ResolvedNames::default();
+        self.sites.clear();
+        // Intrinsics live in a scope of their own so that the module may
+        // declare items with the same names.
+        for intrinsic in INTRINSICS {
+            self.define(intrinsic, DefinitionKind::Intrinsic);
+        }
+        self.scopes.push(Scope::default());
+        for statement in imports {
+            if let Statement::VariableDeclaration(vd) = statement {
+                self.define(vd.name(), DefinitionKind::Variable);
+            }
+        }
+        self.declare_items(imports);
+        self.resolve_statements(&program.statements);
+        std::mem::take(&mut self.diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::VoeParser;
+
+    fn resolve(source: &str) -> (Vec<String>, ResolvedNames) {
+        let program = VoeParser.parse_program(source).expect("parse failed");
+        let mut resolver = Resolver::new();
+        let diagnostics = resolver.analyze(&program);
+        (
+            diagnostics.iter().map(|d| d.to_string()).collect(),
+            resolver.names().clone(),
+        )
+    }
+
+    #[test]
+    fn test_resolve_names() {
+        let (diagnostics, names) = resolve(
+            "
+            let x = 1i32;
+            fn f(a: i32) -> i32 { let x = a + later(); x; }
+            fn later() -> i32 {}
+            let y = x + f(x);
+            ",
+        );
+        assert_eq!(
+            diagnostics,
+            vec!["warning: `x` shadows an earlier declaration"]
+        );
+        // The `x` inside `f` and the top-level `x` are different definitions.
+        let xs: Vec<DefinitionId> = names
+            .uses
+            .iter()
+            .filter(|(name, _)| name == "x")
+            .map(|(_, id)| *id)
+            .collect();
+        assert_eq!(xs.len(), 3);
+        assert_ne!(xs[0], xs[1]);
+        assert_eq!(xs[1], xs[2]);
+        assert_eq!(names.definition(xs[0]).kind, DefinitionKind::Variable);
+        assert_eq!(names.uses_of(xs[1]), 2);
+    }
+
+    #[test]
+    fn test_resolution_errors() {
+        let (diagnostics, _) = resolve(
+            "
+            let c = zzz;
+            let d = e;
+            let e = 1i32;
+            fn g(a: i32, a: i32) -> i32 { missing(a); }
+            fn g() -> i32 {}
+            ",
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                "error: `g` is defined more than once",
+                "error: cannot find value `zzz` in this scope",
+                "error: `e` is used before its declaration",
+                "error: parameter `a` is declared more than once in `g`",
+                "error: cannot find function `missing` in this scope",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dependent_params() {
+        // The input `n` gives the `forall` parameter `n` its value.
+        let (diagnostics, names) = resolve(
+            "
+            forall n: u32. fn zeros(n: u32) -> Vec<i32, n> { n; }
+            forall n: u32, m: u32. fn pair(n: u32, m: u32) -> Vec<i32, n> {}
+            forall n: u32, n: u32. fn twice(n: u32) -> Vec<i32, n> {}
+            let a: Vec<i32, 3> = zeros(3);
+            ",
+        );
+        assert_eq!(
+            diagnostics,
+            vec!["error: parameter `n` is declared more than once in `twice`"]
+        );
+        // The use resolves to the input, defined right after the `forall`
+        // parameter.
+        let (_, n) = names.uses.iter().find(|(name, _)| name == "n").unwrap();
+        assert_eq!(names.definition(*n).kind, DefinitionKind::Parameter);
+        assert_eq!(
+            n.0,
+            names
+                .definitions
+                .iter()
+                .position(|d| d.name == "n")
+                .unwrap()
+                + 1
+        );
+    }
+
+    #[test]
+    fn test_block_scopes() {
+        let (diagnostics, names) = resolve(
+            "
+            let x = 1i32;
+            { let y = x; let x = 2i32; }
+            let z = y;
+            let w = { let v = 3i32; v + x };
+            let u = v;
+            ",
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                "warning: `x` shadows an earlier declaration",
+                "error: cannot find value `y` in this scope",
+                "error: cannot find value `v` in this scope",
+            ]
+        );
+        // The `x` in `w` is the outer one; the shadowing ended with its block.
+        let xs: Vec<DefinitionId> = names
+            .uses
+            .iter()
+            .filter(|(name, _)| name == "x")
+            .map(|(_, id)| *id)
+            .collect();
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0], xs[1]);
+    }
+
+    #[test]
+    fn test_intrinsics() {
+        let (diagnostics, names) = resolve("let a = [1i32; 4]; let n = len(a); a[n] = 0i32;");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        let (_, id) = names.uses.iter().find(|(name, _)| name == "len").unwrap();
+        assert_eq!(names.definition(*id).kind, DefinitionKind::Intrinsic);
+
+        // A declaration hides the intrinsic rather than clashing with it.
+        let (diagnostics, names) = resolve("fn len(x: i32) -> i32 {} let n = len(1i32);");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        let (_, id) = names.uses.iter().find(|(name, _)| name == "len").unwrap();
+        assert_eq!(names.definition(*id).kind, DefinitionKind::Function);
+    }
+
+    #[test]
+    fn test_resolution_by_site() {
+        let program = VoeParser
+            .parse_program("let x = 1i32; fn f(x: i32) -> i32 { x; } let y = x;")
+            .unwrap();
+        let mut resolver = Resolver::new();
+        resolver.analyze(&program);
+        let declared = |i: usize| program.statements[i].declared_name().unwrap();
+        let Statement::Function(fd) = &program.statements[1] else {
+            panic!("expected a function");
+        };
+        let Statement::Expression(Expression::Atom(inner)) = &fd.body().statements()[0] else {
+            panic!("expected an expression");
+        };
+        let Statement::VariableDeclaration(vd) = &program.statements[2] else {
+            panic!("expected a declaration");
+        };
+        let Some(Expression::Atom(outer)) = &vd.value else {
+            panic!("expected an atom");
+        };
+        let use_of = |atom: &Atom| match &atom.value {
+            AtomValue::Identity(name) => resolver.resolution(name),
+            _ => None,
+        };
+        // Each `x` finds its own declaration, although the names are equal.
+        assert_eq!(use_of(outer), resolver.resolution(declared(0)));
+        assert_eq!(use_of(inner), resolver.resolution(fd.inputs()[0].name()));
+        assert_ne!(use_of(inner), use_of(outer));
+        assert!(resolver.resolution("x").is_none());
+    }
+}
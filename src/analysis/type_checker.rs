@@ -0,0 +1,2764 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::{
+    atom::{Atom, AtomValue},
+    ArmBody, Assignment, Block, ClassDefinition, Conditional, EnumDefinition, Expression, ForLoop,
+    FunctionDefinition, ImplBlock, InterfaceDefinition, Iterable, Lambda, MatchArm,
+    MethodSignature, Operator, Pattern, Program, Range, Statement, Type, TypeAlias, TypeParam,
+    UnaryOp, VariableDeclaration, WhileLoop,
+};
+
+use crate::parser::ast_index::Spans;
+use crate::parser::lexer::Span;
+
+use super::aliases::AliasTable;
+use super::exhaustiveness::PatternMatrix;
+use super::{Analysis, Diagnostic, INTRINSICS};
+use crate::ast_passes::ConstantFolding;
+
+/// Generic types the language provides, with their number of type
+/// arguments.
+const BUILTIN_GENERICS: &[(&str, usize)] = &[("Option", 1), ("Result", 2)];
+
+#[derive(Debug, Clone)]
+struct FunctionSignature {
+    type_params: Vec<TypeParam>,
+    dependent_params: Vec<String>,
+    inputs: Vec<VariableDeclaration>,
+    return_type: Type,
+}
+
+impl From<&FunctionDefinition> for FunctionSignature {
+    fn from(fd: &FunctionDefinition) -> Self {
+        FunctionSignature {
+            type_params: fd.type_params().clone(),
+            dependent_params: fd
+                .dependent_params()
+                .iter()
+                .map(|p| p.name().clone())
+                .collect(),
+            inputs: fd.inputs().clone(),
+            return_type: fd.return_type().clone(),
+        }
+    }
+}
+
+impl From<&MethodSignature> for FunctionSignature {
+    fn from(ms: &MethodSignature) -> Self {
+        FunctionSignature {
+            type_params: vec![],
+            dependent_params: vec![],
+            inputs: ms.inputs.clone(),
+            return_type: ms.return_type.clone(),
+        }
+    }
+}
+
+impl FunctionSignature {
+    /// Names that are bound per call: generic type parameters and dependent
+    /// value parameters.
+    fn call_params(&self) -> Vec<String> {
+        self.type_params
+            .iter()
+            .map(|p| p.name.clone())
+            .chain(self.dependent_params.iter().cloned())
+            .collect()
+    }
+
+    fn takes_self(&self) -> bool {
+        self.inputs
+            .first()
+            .is_some_and(|i| i.name() == "self" && i.var_type().is_none())
+    }
+
+    /// The signature of a value of type `fn(params) -> return_type`.
+    fn of_function_type(params: &[Type], return_type: &Type) -> FunctionSignature {
+        FunctionSignature {
+            type_params: vec![],
+            dependent_params: vec![],
+            inputs: params
+                .iter()
+                .map(|ty| VariableDeclaration::new(String::new(), Some(ty.clone()), None))
+                .collect(),
+            return_type: return_type.clone(),
+        }
+    }
+
+    /// The type of the function used as a value. Generic and dependent
+    /// functions have none, since their uses are instantiated per call.
+    fn value_type(&self) -> Option<Type> {
+        if !self.call_params().is_empty() {
+            return None;
+        }
+        let params = self
+            .inputs
+            .iter()
+            .map(|i| i.var_type().clone())
+            .collect::<Option<Vec<_>>>()?;
+        Some(Type::Function(params, Box::new(self.return_type.clone())))
+    }
+
+    /// The signature as seen by callers of a method, without `self`.
+    fn without_self(&self) -> FunctionSignature {
+        FunctionSignature {
+            type_params: self.type_params.clone(),
+            dependent_params: self.dependent_params.clone(),
+            inputs: self.inputs[1..].to_vec(),
+            return_type: self.return_type.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MethodInfo {
+    signature: FunctionSignature,
+    /// The interface the method was implemented for, if any.
+    interface: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ClassInfo {
+    fields: Vec<VariableDeclaration>,
+    methods: HashMap<String, MethodInfo>,
+    interfaces: Vec<String>,
+}
+
+impl ClassInfo {
+    fn field(&self, name: &str) -> Option<&Type> {
+        self.fields
+            .iter()
+            .find(|f| f.name() == name)
+            .and_then(|f| f.var_type().as_ref())
+    }
+}
+
+/// The implementation a method call dispatches to statically.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct MethodTarget {
+    pub class: String,
+    pub method: String,
+    pub interface: Option<String>,
+}
+
+/// A generic function used with concrete type arguments, which a back end
+/// has to monomorphize.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Instantiation {
+    pub function: String,
+    pub type_args: Vec<Type>,
+}
+
+/// A lambda and the variables it captures from enclosing blocks, which a
+/// back end stores in an environment struct passed along with the code.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Closure {
+    /// Lambdas are numbered in the order they appear in the module.
+    pub id: usize,
+    pub captures: Vec<VariableDeclaration>,
+}
+
+impl Closure {
+    /// The environment struct of the closure, as a class that can be laid
+    /// out with `class_layouts`.
+    pub fn environment(&self) -> ClassDefinition {
+        ClassDefinition::new(format!("Closure{}Env", self.id), self.captures.clone())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TypeChecker {
+    scopes: Vec<HashMap<String, Type>>,
+    functions: HashMap<String, FunctionSignature>,
+    classes: HashMap<String, ClassInfo>,
+    interfaces: HashMap<String, InterfaceDefinition>,
+    enums: HashMap<String, EnumDefinition>,
+    aliases: AliasTable,
+    /// Aliases whose targets name types that do not exist, with the names
+    /// their own targets use. Aliases that are only invalid through another
+    /// alias have none, so each unknown name is reported once.
+    invalid_aliases: HashMap<String, Vec<String>>,
+    /// Type parameters of the generic functions being checked.
+    type_params: Vec<TypeParam>,
+    instantiations: Vec<Instantiation>,
+    closures: Vec<Closure>,
+    /// Lambdas being checked, innermost last, with the index of the scope
+    /// holding their parameters.
+    open_closures: Vec<(usize, Closure)>,
+    /// Declared return types of the functions and lambdas being checked,
+    /// innermost last.
+    return_types: Vec<Option<Type>>,
+    /// Indices of the scopes that bind options narrowed to their values.
+    narrowing_scopes: Vec<usize>,
+    /// Types inferred for the expressions of the program, by address.
+    expression_types: HashMap<*const Expression, Type>,
+    /// Types of the variables, parameters and bindings of the program, by
+    /// the address of their names.
+    binding_types: HashMap<*const u8, Type>,
+    /// Where the nodes of the program are, for placing diagnostics.
+    spans: Spans,
+    /// Spans of the statements and expressions being checked, innermost
+    /// last.
+    locations: Vec<Span>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl TypeChecker {
+    pub fn new() -> TypeChecker {
+        TypeChecker::default()
+    }
+
+    /// Places diagnostics at the nodes of the program that `spans` knows.
+    pub fn with_spans(self, spans: Spans) -> TypeChecker {
+        TypeChecker { spans, ..self }
+    }
+
+    /// Resolves a call of `method` on a value of type `receiver` to the
+    /// implementation that runs. Generic receivers must be substituted
+    /// with a concrete class first.
+    pub fn resolve_method(&self, receiver: &Type, method: &str) -> Option<MethodTarget> {
+        let Type::Custom(class) = receiver else {
+            return None;
+        };
+        let info = self.classes.get(class)?.methods.get(method)?;
+        Some(MethodTarget {
+            class: class.clone(),
+            method: method.to_string(),
+            interface: info.interface.clone(),
+        })
+    }
+
+    /// Every instantiation of a generic function found in the program.
+    pub fn instantiations(&self) -> &[Instantiation] {
+        &self.instantiations
+    }
+
+    /// Every lambda in the program, by id.
+    pub fn closures(&self) -> &[Closure] {
+        &self.closures
+    }
+
+    /// The type inferred for `expr`, which must be part of the program last
+    /// analyzed. Expressions are told apart by address, so equal ones in
+    /// different scopes keep their own types.
+    pub fn type_of(&self, expr: &Expression) -> Option<&Type> {
+        self.expression_types.get(&(expr as *const Expression))
+    }
+
+    /// The type of the variable, parameter or binding declared by `name`,
+    /// which must borrow from the program last analyzed.
+    pub fn binding_type(&self, name: &str) -> Option<&Type> {
+        self.binding_types.get(&name.as_ptr())
+    }
+
+    fn error(&mut self, message: String) {
+        let span = self.locations.last().copied();
+        self.diagnostics
+            .push(Diagnostic::error(message).with_span(span));
+    }
+
+    /// Runs `check` with the problems it finds placed at `span`, if known.
+    fn located<T>(&mut self, span: Option<Span>, check: impl FnOnce(&mut Self) -> T) -> T {
+        let Some(span) = span else {
+            return check(self);
+        };
+        self.locations.push(span);
+        let result = check(self);
+        self.locations.pop();
+        result
+    }
+
+    /// Looks up a variable used as a value, recording it as a capture of
+    /// the lambdas it is used in but declared outside of. Module-level
+    /// variables are not captured.
+    fn lookup_variable(&mut self, name: &str) -> Option<Type> {
+        let (depth, ty) = self
+            .scopes
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(depth, scope)| Some((depth, scope.get(name)?.clone())))?;
+        if depth > 0 {
+            for (closure_depth, closure) in &mut self.open_closures {
+                if depth < *closure_depth && closure.captures.iter().all(|c| c.name() != name) {
+                    closure.captures.push(VariableDeclaration::new(
+                        name.to_string(),
+                        Some(ty.clone()),
+                        None,
+                    ));
+                }
+            }
+        }
+        Some(ty)
+    }
+
+    /// The variables that hold a value whenever `condition` evaluates to
+    /// `outcome`, with the types of those values.
+    fn narrowings(&mut self, condition: &Expression, outcome: bool) -> Vec<(String, Type)> {
+        match condition {
+            Expression::IsSome(option) if outcome => self.narrowed_variable(option),
+            Expression::IsNone(option) if !outcome => self.narrowed_variable(option),
+            Expression::BinaryOperation(lhs, Operator::LogicalAnd, rhs) if outcome => {
+                let mut narrowed = self.narrowings(lhs, outcome);
+                narrowed.extend(self.narrowings(rhs, outcome));
+                narrowed
+            }
+            Expression::BinaryOperation(lhs, Operator::LogicalOr, rhs) if !outcome => {
+                let mut narrowed = self.narrowings(lhs, outcome);
+                narrowed.extend(self.narrowings(rhs, outcome));
+                narrowed
+            }
+            Expression::Unary(UnaryOp::Not, operand) => self.narrowings(operand, !outcome),
+            Expression::Atom(Atom {
+                negative: false,
+                value: AtomValue::ParExpr(inner),
+                ..
+            }) => self.narrowings(inner, outcome),
+            _ => vec![],
+        }
+    }
+
+    fn narrowed_variable(&mut self, option: &Expression) -> Vec<(String, Type)> {
+        let Expression::Atom(Atom {
+            negative: false,
+            value: AtomValue::Identity(name),
+            ..
+        }) = option
+        else {
+            return vec![];
+        };
+        let Some(ty) = self.lookup_variable(name) else {
+            return vec![];
+        };
+        match self.aliases.expand(&ty).optional_inner() {
+            Some(inner) => vec![(name.clone(), inner.clone())],
+            None => vec![],
+        }
+    }
+
+    /// Runs `check` with the variables in `narrowed` bound to the values
+    /// of their options.
+    fn with_narrowings<T>(
+        &mut self,
+        narrowed: Vec<(String, Type)>,
+        check: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        if narrowed.is_empty() {
+            return check(self);
+        }
+        self.narrowing_scopes.push(self.scopes.len());
+        self.scopes.push(narrowed.into_iter().collect());
+        let result = check(self);
+        self.scopes.pop();
+        self.narrowing_scopes.pop();
+        result
+    }
+
+    /// Unbinds `name` from the values of the options it was narrowed to.
+    fn forget_narrowing(&mut self, name: &str) {
+        while let Some(depth) = self.scopes.iter().rposition(|s| s.contains_key(name)) {
+            if !self.narrowing_scopes.contains(&depth) {
+                break;
+            }
+            self.scopes[depth].remove(name);
+        }
+    }
+
+    /// Forgets the narrowings of the variables `body` assigns to, which a
+    /// later iteration of a loop would otherwise see narrowed.
+    fn enter_loop(&mut self, body: &Block) {
+        let mut assigned = HashSet::new();
+        assigned_in_block(body, &mut assigned);
+        for name in &assigned {
+            self.forget_narrowing(name);
+        }
+    }
+
+    fn bind(&mut self, name: &str, ty: Type) {
+        self.binding_types.insert(name.as_ptr(), ty.clone());
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), ty);
+        }
+    }
+
+    /// Whether a value of type `found` may be stored where `expected` is
+    /// required. Aliases are compared by their targets, and numeric values
+    /// widen implicitly, as in `Type::join`.
+    fn is_assignable(&self, expected: &Type, found: &Type) -> bool {
+        // An invalid alias was reported where it is defined.
+        if self.is_erroneous(expected) || self.is_erroneous(found) {
+            return true;
+        }
+        let (expected, found) = (self.aliases.expand(expected), self.aliases.expand(found));
+        if let (Type::Slice(element), Some(found_element)) = (&expected, found.element_type()) {
+            // Arrays of any length, and slices, are passed as slices.
+            return **element == *found_element;
+        }
+        expected == found || found.join(&expected).as_ref() == Some(&expected)
+    }
+
+    /// Registers functions, classes and methods so they can be used before
+    /// the point where they are declared.
+    fn collect_declarations(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            match statement.unexported() {
+                Statement::Function(fd) => {
+                    self.functions
+                        .insert(fd.name().clone(), FunctionSignature::from(fd));
+                }
+                Statement::Class(class) => {
+                    self.classes.entry(class.name().clone()).or_default().fields =
+                        class.fields().clone();
+                }
+                Statement::Interface(interface) => {
+                    self.interfaces
+                        .insert(interface.name().clone(), interface.clone());
+                }
+                Statement::Enum(enumeration) => {
+                    self.enums
+                        .insert(enumeration.name().clone(), enumeration.clone());
+                }
+                Statement::TypeAlias(alias) => self.aliases.insert(alias.clone()),
+                _ => {}
+            }
+        }
+        // Methods go in a second pass so impls may precede their class.
+        for statement in statements {
+            let Statement::Impl(ib) = statement.unexported() else {
+                continue;
+            };
+            let Some(info) = self.classes.get_mut(ib.class()) else {
+                continue;
+            };
+            if let Some(interface) = ib.interface() {
+                info.interfaces.push(interface.clone());
+            }
+            let mut duplicates = vec![];
+            for method in ib.methods() {
+                let method_info = MethodInfo {
+                    signature: FunctionSignature::from(method),
+                    interface: ib.interface().clone(),
+                };
+                if info
+                    .methods
+                    .insert(method.name().clone(), method_info)
+                    .is_some()
+                {
+                    duplicates.push(method.name().clone());
+                }
+            }
+            let span = self.spans.statement(statement);
+            self.located(span, |tc| {
+                for method in duplicates {
+                    tc.error(format!(
+                        "method `{}` is defined more than once for `{}`",
+                        method,
+                        ib.class()
+                    ));
+                }
+            });
+        }
+        self.find_invalid_aliases(statements);
+    }
+
+    /// Records the aliases among `statements` that name unknown types,
+    /// directly or through other aliases.
+    fn find_invalid_aliases(&mut self, statements: &[Statement]) {
+        let aliases: Vec<&TypeAlias> = statements
+            .iter()
+            .filter_map(|s| match s.unexported() {
+                Statement::TypeAlias(alias) => Some(alias),
+                _ => None,
+            })
+            .collect();
+        for alias in &aliases {
+            let mut unknown = vec![];
+            self.unknown_types(&alias.target, &mut alias.params.clone(), &mut unknown);
+            if !unknown.is_empty() {
+                self.invalid_aliases.insert(alias.name().clone(), unknown);
+            }
+        }
+        loop {
+            let invalid: Vec<String> = aliases
+                .iter()
+                .filter(|alias| {
+                    !self.invalid_aliases.contains_key(alias.name())
+                        && self.is_erroneous(&alias.target)
+                })
+                .map(|alias| alias.name().clone())
+                .collect();
+            if invalid.is_empty() {
+                break;
+            }
+            for name in invalid {
+                self.invalid_aliases.insert(name, vec![]);
+            }
+        }
+    }
+
+    /// Collects the names `ty` uses as types that are not declared, apart
+    /// from those in `bound`. Generic types are not declared, so only their
+    /// arguments are looked at.
+    fn unknown_types(&self, ty: &Type, bound: &mut Vec<String>, unknown: &mut Vec<String>) {
+        match ty {
+            Type::Custom(name) => {
+                let known = bound.contains(name)
+                    || self.classes.contains_key(name)
+                    || self.enums.contains_key(name)
+                    || self.interfaces.contains_key(name)
+                    || self.aliases.get(name).is_some();
+                if !known && !unknown.contains(name) {
+                    unknown.push(name.clone());
+                }
+            }
+            Type::Generic(gtype) => {
+                for field in &gtype.fields {
+                    self.unknown_types(field, bound, unknown);
+                }
+            }
+            Type::Array(element, _) | Type::Slice(element) => {
+                self.unknown_types(element, bound, unknown)
+            }
+            Type::Tuple(elements) => {
+                for element in elements {
+                    self.unknown_types(element, bound, unknown);
+                }
+            }
+            Type::Function(params, ret) => {
+                for param in params {
+                    self.unknown_types(param, bound, unknown);
+                }
+                self.unknown_types(ret, bound, unknown);
+            }
+            Type::Dependent(dtype) => {
+                let depth = bound.len();
+                bound.extend(dtype.params.iter().map(|p| p.name().clone()));
+                self.unknown_types(&dtype.body, bound, unknown);
+                bound.truncate(depth);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether `ty` uses an invalid alias, so that errors about it would
+    /// repeat the one reported for the alias.
+    fn is_erroneous(&self, ty: &Type) -> bool {
+        if self.invalid_aliases.is_empty() {
+            return false;
+        }
+        match ty {
+            Type::Custom(name) => self.invalid_aliases.contains_key(name),
+            Type::Generic(gtype) => {
+                self.invalid_aliases.contains_key(&gtype.name)
+                    || gtype.fields.iter().any(|t| self.is_erroneous(t))
+            }
+            Type::Array(element, _) | Type::Slice(element) => self.is_erroneous(element),
+            Type::Tuple(elements) => elements.iter().any(|t| self.is_erroneous(t)),
+            Type::Function(params, ret) => {
+                params.iter().any(|t| self.is_erroneous(t)) || self.is_erroneous(ret)
+            }
+            Type::Dependent(dtype) => self.is_erroneous(&dtype.body),
+            _ => false,
+        }
+    }
+
+    /// Whether `ty` satisfies the interface bound `interface`.
+    fn implements(&self, ty: &Type, interface: &str) -> bool {
+        let Type::Custom(name) = ty else {
+            return false;
+        };
+        if let Some(info) = self.classes.get(name) {
+            return info.interfaces.iter().any(|i| i == interface);
+        }
+        self.type_params
+            .iter()
+            .rev()
+            .find(|p| &p.name == name)
+            .is_some_and(|p| p.bounds.iter().any(|b| b == interface))
+    }
+
+    /// Checks the block, returning the type of its value, or `()` if it
+    /// has none.
+    fn check_block(&mut self, block: &Block) -> Option<Type> {
+        self.scopes.push(HashMap::new());
+        self.collect_declarations(block.statements());
+        for statement in block.statements() {
+            self.check_statement(statement);
+        }
+        let ty = match &block.value {
+            Some(value) => self.infer_expression(value),
+            None => Some(Type::Unit),
+        };
+        self.scopes.pop();
+        ty
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        let span = self.spans.statement(statement);
+        self.located(span, |tc| tc.check_unlocated(statement));
+    }
+
+    fn check_unlocated(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Function(fd) => self.check_function_definition(fd),
+            Statement::VariableDeclaration(vd) => self.check_variable_declaration(vd),
+            Statement::Expression(expr) => {
+                self.infer_expression(expr);
+            }
+            Statement::Assignment(assignment) => self.check_assignment(assignment),
+            Statement::Block(block) => {
+                self.check_block(block);
+            }
+            Statement::Conditional(cond) => {
+                self.check_conditional(cond);
+            }
+            Statement::While(w) => self.check_while(w),
+            Statement::For(l) => self.check_for(l),
+            Statement::Class(class) => self.check_class(class),
+            Statement::Impl(ib) => self.check_impl(ib),
+            Statement::Interface(interface) => self.check_interface(interface),
+            Statement::Enum(enumeration) => self.check_enum(enumeration),
+            Statement::TypeAlias(alias) => self.check_type_alias(alias),
+            Statement::Export(statement) => self.check_statement(statement),
+            // Resolved by the module loader.
+            Statement::Module(_)
+            | Statement::Import(_)
+            | Statement::Use(_)
+            | Statement::Error(_) => {}
+        }
+    }
+
+    fn check_type_alias(&mut self, alias: &TypeAlias) {
+        if let Some(unknown) = self.invalid_aliases.get(alias.name()).cloned() {
+            for name in unknown {
+                self.error(format!(
+                    "type alias `{}` refers to unknown type `{}`",
+                    alias.name(),
+                    name
+                ));
+            }
+        } else if let Some(cycle) = self.aliases.cycle(alias.name()) {
+            self.error(format!(
+                "type alias `{}` is cyclic: {}",
+                alias.name(),
+                cycle.join(" -> ")
+            ));
+        } else {
+            self.check_type_annotation(&alias.target);
+        }
+    }
+
+    /// Reports aliases used with the wrong number of type arguments.
+    fn check_type_annotation(&mut self, ty: &Type) {
+        let (name, args) = match ty {
+            Type::Custom(name) => (name, &vec![]),
+            Type::Generic(gtype) => (&gtype.name, &gtype.fields),
+            Type::Dependent(dtype) => return self.check_type_annotation(&dtype.body),
+            Type::Array(element, _) | Type::Slice(element) => {
+                return self.check_type_annotation(element)
+            }
+            Type::Tuple(elements) => {
+                for element in elements {
+                    self.check_type_annotation(element);
+                }
+                return;
+            }
+            Type::Function(params, ret) => {
+                for param in params {
+                    self.check_type_annotation(param);
+                }
+                return self.check_type_annotation(ret);
+            }
+            _ => return,
+        };
+        for arg in args {
+            self.check_type_annotation(arg);
+        }
+        let Some(expected) = self.aliases.get(name).map(|a| a.params.len()) else {
+            let builtin = BUILTIN_GENERICS.iter().find(|(n, _)| n == name);
+            if let Some((_, expected)) = builtin.filter(|(_, n)| *n != args.len()) {
+                self.error(format!(
+                    "`{}` takes {} type argument(s) but {} were supplied",
+                    name,
+                    expected,
+                    args.len()
+                ));
+            }
+            return;
+        };
+        if expected != args.len() {
+            self.error(format!(
+                "type alias `{}` takes {} type argument(s) but {} were supplied",
+                name,
+                expected,
+                args.len()
+            ));
+        }
+    }
+
+    fn check_function_definition(&mut self, fd: &FunctionDefinition) {
+        self.functions
+            .insert(fd.name().clone(), FunctionSignature::from(fd));
+        if FunctionSignature::from(fd).takes_self() {
+            self.error(format!(
+                "`self` parameter is only allowed in methods, found in `{}`",
+                fd.name()
+            ));
+        }
+        self.check_function_body(fd, None);
+    }
+
+    fn check_function_body(&mut self, fd: &FunctionDefinition, self_type: Option<&Type>) {
+        for param in fd.inputs() {
+            if let Some(ty) = param.var_type() {
+                self.check_type_annotation(ty);
+            }
+        }
+        self.check_type_annotation(fd.return_type());
+        for param in fd.type_params() {
+            for bound in &param.bounds {
+                if !self.interfaces.contains_key(bound) {
+                    self.error(format!(
+                        "unknown interface `{}` in bound of `{}`",
+                        bound, param.name
+                    ));
+                }
+            }
+        }
+        self.type_params.extend(fd.type_params().iter().cloned());
+        self.scopes.push(HashMap::new());
+        for param in fd.dependent_params().iter().chain(fd.inputs()) {
+            match (param.var_type(), self_type) {
+                (Some(ty), _) => self.bind(param.name(), ty.clone()),
+                (None, Some(ty)) => self.bind(param.name(), ty.clone()),
+                (None, None) => {}
+            }
+        }
+        self.return_types.push(Some(fd.return_type().clone()));
+        self.check_block(fd.body());
+        self.return_types.pop();
+        self.scopes.pop();
+        self.type_params
+            .truncate(self.type_params.len() - fd.type_params().len());
+    }
+
+    fn check_class(&mut self, class: &ClassDefinition) {
+        let mut seen: Vec<&String> = vec![];
+        for field in class.fields() {
+            if seen.contains(&field.name()) {
+                self.error(format!(
+                    "field `{}` is declared more than once in class `{}`",
+                    field.name(),
+                    class.name()
+                ));
+            }
+            seen.push(field.name());
+        }
+    }
+
+    fn check_impl(&mut self, ib: &ImplBlock) {
+        if !self.classes.contains_key(ib.class()) {
+            self.error(format!("`impl` for unknown class `{}`", ib.class()));
+            return;
+        }
+        if let Some(interface) = ib.interface() {
+            self.check_conformance(interface, ib);
+        }
+        let self_type = Type::Custom(ib.class().clone());
+        for method in ib.methods() {
+            if !FunctionSignature::from(method).takes_self() {
+                self.error(format!(
+                    "method `{}` of `{}` must take `self` as its first parameter",
+                    method.name(),
+                    ib.class()
+                ));
+            }
+            self.check_function_body(method, Some(&self_type));
+        }
+    }
+
+    fn check_interface(&mut self, interface: &InterfaceDefinition) {
+        let mut seen: Vec<&String> = vec![];
+        for method in interface.methods() {
+            if seen.contains(&method.name()) {
+                self.error(format!(
+                    "method `{}` is declared more than once in interface `{}`",
+                    method.name(),
+                    interface.name()
+                ));
+            }
+            seen.push(method.name());
+            if !FunctionSignature::from(method).takes_self() {
+                self.error(format!(
+                    "method `{}` of interface `{}` must take `self` as its first parameter",
+                    method.name(),
+                    interface.name()
+                ));
+            }
+        }
+    }
+
+    fn check_enum(&mut self, enumeration: &EnumDefinition) {
+        let mut seen: Vec<&String> = vec![];
+        for variant in enumeration.variants() {
+            if seen.contains(&&variant.name) {
+                self.error(format!(
+                    "variant `{}` is declared more than once in enum `{}`",
+                    variant.name,
+                    enumeration.name()
+                ));
+            }
+            seen.push(&variant.name);
+        }
+    }
+
+    /// Checks that `ib` implements exactly the methods of `interface`, with
+    /// matching signatures.
+    fn check_conformance(&mut self, interface: &str, ib: &ImplBlock) {
+        let Some(definition) = self.interfaces.get(interface).cloned() else {
+            self.error(format!("unknown interface `{}`", interface));
+            return;
+        };
+        for required in definition.methods() {
+            match ib.methods().iter().find(|m| m.name() == required.name()) {
+                None => self.error(format!(
+                    "`{}` does not implement method `{}` of interface `{}`",
+                    ib.class(),
+                    required.name(),
+                    interface
+                )),
+                Some(method) if !required.matches(method) => self.error(format!(
+                    "method `{}` of `{}` does not match interface `{}`: expected `{}`",
+                    required.name(),
+                    ib.class(),
+                    interface,
+                    required
+                )),
+                Some(_) => {}
+            }
+        }
+        for method in ib.methods() {
+            if !definition
+                .methods()
+                .iter()
+                .any(|m| m.name() == method.name())
+            {
+                self.error(format!(
+                    "method `{}` is not a member of interface `{}`",
+                    method.name(),
+                    interface
+                ));
+            }
+        }
+    }
+
+    fn check_variable_declaration(&mut self, vd: &VariableDeclaration) {
+        if let Some(ty) = vd.var_type() {
+            self.check_type_annotation(ty);
+        }
+        let found = vd
+            .value
+            .as_ref()
+            .and_then(|e| self.infer_expected(e, vd.var_type().as_ref()));
+        let ty = match (vd.var_type(), found) {
+            (Some(expected), Some(found)) => {
+                if !self.is_assignable(expected, &found) {
+                    self.error(format!(
+                        "mismatched types in `let {}`: expected `{}`, found `{}`",
+                        vd.pattern, expected, found
+                    ));
+                }
+                Some(expected.clone())
+            }
+            (Some(expected), None) => Some(expected.clone()),
+            (None, found) => found,
+        };
+        self.check_distinct_bindings(&vd.pattern);
+        self.check_pattern(&vd.pattern, ty.as_ref());
+    }
+
+    fn check_assignment(&mut self, assignment: &Assignment) {
+        if let Expression::Atom(Atom {
+            value: AtomValue::Identity(name),
+            ..
+        }) = &assignment.target
+        {
+            // The new value may be `none`, so the variable is an option again.
+            self.forget_narrowing(name);
+        }
+        let expected = self.infer_expression(&assignment.target);
+        let found = self.infer_expected(&assignment.value, expected.as_ref());
+        let (Some(expected), Some(found)) = (expected, found) else {
+            return;
+        };
+        if !self.is_assignable(&expected, &found) {
+            self.error(format!(
+                "mismatched types in assignment to `{}`: expected `{}`, found `{}`",
+                assignment.target, expected, found
+            ));
+        }
+    }
+
+    /// Checks both branches, returning the types of their values. The
+    /// `else` type is `None` if there is no `else`.
+    fn check_conditional(&mut self, cond: &Conditional) -> (Option<Type>, Option<Option<Type>>) {
+        if let Some(pattern) = &cond.pattern {
+            let ty = self.infer_expression(&cond.condition);
+            self.check_distinct_bindings(pattern);
+            self.scopes.push(HashMap::new());
+            self.check_pattern(pattern, ty.as_ref());
+            let then_type = self.check_block(&cond.then_block);
+            self.scopes.pop();
+            let else_type = cond
+                .else_block
+                .as_ref()
+                .map(|block| self.check_block(block));
+            return (then_type, else_type);
+        }
+        self.check_condition("if", &cond.condition);
+        let narrowed = self.narrowings(&cond.condition, true);
+        let then_type = self.with_narrowings(narrowed, |tc| tc.check_block(&cond.then_block));
+        let else_type = cond.else_block.as_ref().map(|block| {
+            let narrowed = self.narrowings(&cond.condition, false);
+            self.with_narrowings(narrowed, |tc| tc.check_block(block))
+        });
+        (then_type, else_type)
+    }
+
+    /// An `if` used as a value has the joined type of its branches.
+    fn check_if_expression(&mut self, cond: &Conditional) -> Option<Type> {
+        let (then_type, else_type) = self.check_conditional(cond);
+        let Some(else_type) = else_type else {
+            self.error("`if` used as a value must have an `else` branch".to_string());
+            return None;
+        };
+        match (then_type, else_type) {
+            (Some(a), Some(b)) if a == b => Some(a),
+            (Some(a), Some(b)) => match a.join(&b) {
+                Some(joined) => Some(joined),
+                None => {
+                    self.error(format!(
+                        "`if` and `else` have incompatible types: `{}` and `{}`",
+                        a, b
+                    ));
+                    Some(a)
+                }
+            },
+            (a, b) => a.or(b),
+        }
+    }
+
+    fn check_condition(&mut self, keyword: &str, condition: &Expression) {
+        if let Some(ty) = self.infer_expression(condition) {
+            if self.aliases.expand(&ty) != Type::Bool {
+                self.error(format!(
+                    "`{}` condition must be `bool`, found `{}`",
+                    keyword, ty
+                ));
+            }
+        }
+    }
+
+    fn check_while(&mut self, w: &WhileLoop) {
+        // The condition is checked again after each iteration.
+        self.enter_loop(&w.body);
+        self.check_condition("while", &w.condition);
+        let narrowed = self.narrowings(&w.condition, true);
+        self.with_narrowings(narrowed, |tc| tc.check_block(&w.body));
+    }
+
+    fn check_for(&mut self, l: &ForLoop) {
+        let element = match &l.iterable {
+            Iterable::Range(range) => self.check_range(range),
+            Iterable::Expression(expr) => {
+                let ty = self.infer_expression(expr);
+                let element = ty.as_ref().and_then(|ty| {
+                    let element = self.aliases.expand(ty).element_type().cloned();
+                    if element.is_none() {
+                        self.error(format!("cannot iterate over a value of type `{}`", ty));
+                    }
+                    element
+                });
+                element
+            }
+        };
+        self.enter_loop(&l.body);
+        self.check_distinct_bindings(&l.pattern);
+        self.scopes.push(HashMap::new());
+        self.check_pattern(&l.pattern, element.as_ref());
+        self.check_block(&l.body);
+        self.scopes.pop();
+    }
+
+    /// Checks the bounds and step of a range, returning the type of its
+    /// values.
+    fn check_range(&mut self, range: &Range) -> Option<Type> {
+        let mut ty: Option<Type> = None;
+        let parts = [Some(&range.start), Some(&range.end), range.step.as_ref()];
+        for part in parts.into_iter().flatten() {
+            let Some(found) = self.infer_expression(part) else {
+                continue;
+            };
+            let found = self.aliases.expand(&found);
+            if !found.is_integral() {
+                self.error(format!(
+                    "range `{}` must have integer bounds, found `{}`",
+                    range, found
+                ));
+                continue;
+            }
+            ty = match ty {
+                None => Some(found),
+                Some(ty) if ty == found => Some(ty),
+                Some(ty) => match ty.join(&found) {
+                    Some(joined) => Some(joined),
+                    None => {
+                        self.error(format!(
+                            "mismatched types in range `{}`: `{}` and `{}`",
+                            range, ty, found
+                        ));
+                        Some(ty)
+                    }
+                },
+            };
+        }
+        let step = range
+            .step
+            .clone()
+            .map(|s| ConstantFolding.fold_expression(s));
+        if step.as_ref().and_then(Self::constant_value) == Some(0) {
+            self.error(format!("range `{}` has a step of zero", range));
+        }
+        ty
+    }
+
+    fn infer_expression(&mut self, expr: &Expression) -> Option<Type> {
+        let span = self.spans.expression(expr);
+        let ty = self.located(span, |tc| tc.infer_unrecorded(expr));
+        // Values of invalid aliases are of unknown type, like those of
+        // expressions that have errors.
+        let ty = ty.filter(|ty| !self.is_erroneous(ty));
+        self.record(expr, ty)
+    }
+
+    /// Notes the type inferred for `expr`, for `type_of`.
+    fn record(&mut self, expr: &Expression, ty: Option<Type>) -> Option<Type> {
+        if let Some(ty) = &ty {
+            self.expression_types.insert(expr, ty.clone());
+        }
+        ty
+    }
+
+    fn infer_unrecorded(&mut self, expr: &Expression) -> Option<Type> {
+        match expr {
+            Expression::Atom(atom) => self.infer_atom(atom),
+            Expression::BinaryOperation(lhs, op, rhs) => {
+                let lty = self.infer_expression(lhs);
+                // `x is some && x > 0` uses the value of `x` on the right.
+                let narrowed = match op {
+                    Operator::LogicalAnd => self.narrowings(lhs, true),
+                    Operator::LogicalOr => self.narrowings(lhs, false),
+                    _ => vec![],
+                };
+                let rty = self.with_narrowings(narrowed, |tc| tc.infer_expression(rhs));
+                let (lty, rty) = match (lty, rty) {
+                    (Some(lty), Some(rty)) => {
+                        (self.aliases.expand(&lty), self.aliases.expand(&rty))
+                    }
+                    (lty, rty) => {
+                        return if op.is_comparison() || op.is_logical() {
+                            Some(Type::Bool)
+                        } else {
+                            lty.or(rty)
+                        }
+                    }
+                };
+                let (lty, rty) = (self.aliases.expand(&lty), self.aliases.expand(&rty));
+                if op.is_logical() {
+                    if lty != Type::Bool || rty != Type::Bool {
+                        self.error(format!(
+                            "cannot apply `{}` to `{}` and `{}`: operands must be `bool`",
+                            op, lty, rty
+                        ));
+                        return None;
+                    }
+                    return Some(Type::Bool);
+                }
+                if op.is_shift() {
+                    if !lty.is_integral() || !rty.is_integral() {
+                        self.error(format!(
+                            "cannot shift `{}` by `{}`: operands must be integers",
+                            lty, rty
+                        ));
+                        return None;
+                    }
+                    return Some(lty);
+                }
+                let joined = if lty == rty {
+                    Some(lty.clone())
+                } else {
+                    lty.join(&rty)
+                };
+                if joined.is_none() {
+                    self.error(format!(
+                        "mismatched operand types for `{}`: `{}` and `{}`",
+                        op, lty, rty
+                    ));
+                    return None;
+                }
+                if op.is_comparison() {
+                    Some(Type::Bool)
+                } else {
+                    joined
+                }
+            }
+            Expression::Unary(op, operand) => {
+                let ty = self.infer_expression(operand)?;
+                let ty = self.aliases.expand(&ty);
+                let valid = match op {
+                    UnaryOp::Neg => ty.is_signed() || ty.is_decimal(),
+                    UnaryOp::Not => ty == Type::Bool,
+                    UnaryOp::BitNot => ty.is_integral(),
+                };
+                if !valid {
+                    self.error(format!("cannot apply unary `{}` to `{}`", op, ty));
+                    return None;
+                }
+                Some(ty)
+            }
+            Expression::New(class, fields) => self.check_new(class, fields),
+            Expression::FieldAccess(object, field) if field.parse::<usize>().is_ok() => {
+                self.check_tuple_field(object, field)
+            }
+            Expression::FieldAccess(object, field) => {
+                let class = self.infer_class(object)?;
+                let ty = self.classes[&class].field(field).cloned();
+                if ty.is_none() {
+                    self.error(format!("class `{}` has no field `{}`", class, field));
+                }
+                ty
+            }
+            Expression::Variant(name, variant, args) => self.check_variant(name, variant, args),
+            Expression::Match(scrutinee, arms) => self.check_match(scrutinee, arms),
+            Expression::If(cond) => self.check_if_expression(cond),
+            Expression::Block(block) => self.check_block(block),
+            Expression::Lambda(lambda) => self.check_lambda(lambda),
+            Expression::Tuple(elements) => {
+                // Every element is checked, even after one fails to type.
+                let types: Vec<Option<Type>> =
+                    elements.iter().map(|e| self.infer_expression(e)).collect();
+                Some(Type::Tuple(types.into_iter().collect::<Option<_>>()?))
+            }
+            Expression::Array(elements) => self.check_array(elements),
+            Expression::Repeat(value, len) => {
+                Some(Type::Array(Box::new(self.infer_expression(value)?), *len))
+            }
+            Expression::Index(array, index) => self.check_index(array, index),
+            Expression::Some(value) => Some(Type::option(self.infer_expression(value)?)),
+            // The other half of the type is only known from context; see
+            // `infer_expected`.
+            Expression::None => None,
+            Expression::Ok(value) | Expression::Err(value) => {
+                self.infer_expression(value);
+                None
+            }
+            Expression::IsSome(option) | Expression::IsNone(option) => {
+                if let Some(ty) = self.infer_expression(option) {
+                    if self.aliases.expand(&ty).optional_inner().is_none() {
+                        self.error(format!(
+                            "cannot test whether `{}` is some: `{}` is not an option",
+                            option, ty
+                        ));
+                    }
+                }
+                Some(Type::Bool)
+            }
+            Expression::Try(option) => self.check_try(option),
+            Expression::MethodCall(object, method, args) => {
+                let receiver = self.infer_expression(object)?;
+                let sig = self.method_signature(&receiver, method)?;
+                if !sig.takes_self() {
+                    return None;
+                }
+                self.check_arguments(
+                    &format!("{}.{}", receiver, method),
+                    &sig.without_self(),
+                    args,
+                )
+            }
+        }
+    }
+
+    /// Looks up a method on a class, or on the interfaces bounding a generic
+    /// type parameter.
+    fn method_signature(&mut self, receiver: &Type, method: &str) -> Option<FunctionSignature> {
+        let Type::Custom(name) = &self.aliases.expand(receiver) else {
+            self.error(format!("`{}` has no methods", receiver));
+            return None;
+        };
+        if let Some(info) = self.classes.get(name) {
+            let sig = info.methods.get(method).map(|m| m.signature.clone());
+            if sig.is_none() {
+                self.error(format!("class `{}` has no method `{}`", name, method));
+            }
+            return sig;
+        }
+        let Some(param) = self.type_params.iter().rev().find(|p| &p.name == name) else {
+            self.error(format!("`{}` has no methods", receiver));
+            return None;
+        };
+        let sig = param.bounds.iter().find_map(|bound| {
+            self.interfaces
+                .get(bound)?
+                .methods()
+                .iter()
+                .find(|m| m.name() == method)
+                .map(FunctionSignature::from)
+        });
+        if sig.is_none() {
+            self.error(format!(
+                "no method `{}` in the bounds of type parameter `{}`",
+                method, name
+            ));
+        }
+        sig
+    }
+
+    /// Infers the type of the receiver of a field access or method call,
+    /// returning the name of its class.
+    fn infer_class(&mut self, object: &Expression) -> Option<String> {
+        let ty = self.infer_expression(object)?;
+        match self.aliases.expand(&ty) {
+            Type::Custom(name) if self.classes.contains_key(&name) => Some(name),
+            ty => {
+                self.error(format!("`{}` is not a class type", ty));
+                None
+            }
+        }
+    }
+
+    /// Field types of `Enum::Variant`, reporting unknown names.
+    fn variant_fields(&mut self, name: &str, variant: &str) -> Option<Vec<Type>> {
+        let Some(enumeration) = self.enums.get(name) else {
+            self.error(format!("unknown enum `{}`", name));
+            return None;
+        };
+        let fields = enumeration.variant(variant).map(|v| v.fields.clone());
+        if fields.is_none() {
+            self.error(format!("enum `{}` has no variant `{}`", name, variant));
+        }
+        fields
+    }
+
+    fn check_variant(&mut self, name: &str, variant: &str, args: &[Expression]) -> Option<Type> {
+        let arg_types: Vec<Option<Type>> = args.iter().map(|a| self.infer_expression(a)).collect();
+        let fields = self.variant_fields(name, variant)?;
+        if fields.len() != args.len() {
+            self.error(format!(
+                "`{}::{}` has {} field(s) but {} were supplied",
+                name,
+                variant,
+                fields.len(),
+                args.len()
+            ));
+        }
+        for (i, (expected, found)) in fields.iter().zip(arg_types).enumerate() {
+            if let Some(found) = found {
+                if !self.is_assignable(expected, &found) {
+                    self.error(format!(
+                        "field {} of `{}::{}`: expected `{}`, found `{}`",
+                        i + 1,
+                        name,
+                        variant,
+                        expected,
+                        found
+                    ));
+                }
+            }
+        }
+        Some(Type::Custom(name.to_string()))
+    }
+
+    /// Checks a pattern against the type of the value it matches, binding
+    /// the names it introduces in the current scope.
+    fn check_pattern(&mut self, pattern: &Pattern, expected: Option<&Type>) {
+        match pattern {
+            Pattern::Wildcard => {}
+            Pattern::Binding(name) => {
+                if let Some(ty) = expected {
+                    self.bind(name, ty.clone());
+                }
+            }
+            Pattern::Literal(atom) => {
+                if let (Some(expected), Some(found)) = (expected, atom.get_type()) {
+                    if !self.is_assignable(expected, &found) {
+                        self.error(format!(
+                            "mismatched types in pattern `{}`: expected `{}`, found `{}`",
+                            pattern, expected, found
+                        ));
+                    }
+                }
+            }
+            Pattern::Tuple(fields) => {
+                let types = match expected.map(|e| self.aliases.expand(e)) {
+                    Some(Type::Tuple(types)) if types.len() == fields.len() => Some(types),
+                    Some(expected) => {
+                        self.error(format!(
+                            "mismatched types in pattern `{}`: expected `{}`, found a tuple of {} element(s)",
+                            pattern,
+                            expected,
+                            fields.len()
+                        ));
+                        None
+                    }
+                    None => None,
+                };
+                for (i, field) in fields.iter().enumerate() {
+                    let ty = types.as_ref().and_then(|t| t.get(i));
+                    self.check_pattern(field, ty);
+                }
+            }
+            Pattern::Some(_) | Pattern::None => {
+                let inner = match expected.map(|e| self.aliases.expand(e)) {
+                    Some(expected) if expected.optional_inner().is_none() => {
+                        self.error(format!(
+                            "mismatched types in pattern `{}`: expected `{}`, found an option",
+                            pattern, expected
+                        ));
+                        None
+                    }
+                    expected => expected.and_then(|e| e.optional_inner().cloned()),
+                };
+                if let Pattern::Some(inner_pattern) = pattern {
+                    self.check_pattern(inner_pattern, inner.as_ref());
+                }
+            }
+            Pattern::Ok(inner_pattern) | Pattern::Err(inner_pattern) => {
+                let types = match expected.map(|e| self.aliases.expand(e)) {
+                    Some(expected) if expected.result_types().is_none() => {
+                        self.error(format!(
+                            "mismatched types in pattern `{}`: expected `{}`, found a result",
+                            pattern, expected
+                        ));
+                        None
+                    }
+                    expected => expected.and_then(|e| {
+                        let (ok, err) = e.result_types()?;
+                        Some((ok.clone(), err.clone()))
+                    }),
+                };
+                let ty = types.map(|(ok, err)| match pattern {
+                    Pattern::Ok(_) => ok,
+                    _ => err,
+                });
+                self.check_pattern(inner_pattern, ty.as_ref());
+            }
+            Pattern::Variant(name, variant, fields) => {
+                let found = Type::Custom(name.clone());
+                if let Some(expected) = expected.filter(|e| self.aliases.expand(e) != found) {
+                    self.error(format!(
+                        "mismatched types in pattern `{}`: expected `{}`, found `{}`",
+                        pattern, expected, found
+                    ));
+                }
+                let types = self.variant_fields(name, variant);
+                if let Some(types) = &types {
+                    if types.len() != fields.len() {
+                        self.error(format!(
+                            "pattern `{}` has {} field(s) but `{}::{}` has {}",
+                            pattern,
+                            fields.len(),
+                            name,
+                            variant,
+                            types.len()
+                        ));
+                    }
+                }
+                for (i, field) in fields.iter().enumerate() {
+                    let ty = types.as_ref().and_then(|t| t.get(i));
+                    self.check_pattern(field, ty);
+                }
+            }
+        }
+    }
+
+    fn check_distinct_bindings(&mut self, pattern: &Pattern) {
+        let names = pattern.bindings();
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                self.error(format!(
+                    "`{}` is bound more than once in pattern `{}`",
+                    name, pattern
+                ));
+            }
+        }
+    }
+
+    fn check_match(&mut self, scrutinee: &Expression, arms: &[MatchArm]) -> Option<Type> {
+        let scrutinee_type = self.infer_expression(scrutinee);
+        let mut result: Option<Type> = None;
+        let mut patterns_fit = scrutinee_type.is_some();
+        for arm in arms {
+            self.check_distinct_bindings(&arm.pattern);
+            self.scopes.push(HashMap::new());
+            let reported = self.diagnostics.len();
+            self.check_pattern(&arm.pattern, scrutinee_type.as_ref());
+            patterns_fit &= self.diagnostics.len() == reported;
+            let ty = match &arm.body {
+                ArmBody::Expression(expr) => self.infer_expression(expr),
+                ArmBody::Block(block) => {
+                    self.check_block(block);
+                    Some(Type::Unit)
+                }
+            };
+            self.scopes.pop();
+            result = match (result, ty) {
+                (Some(a), Some(b)) if a == b => Some(a),
+                (Some(a), Some(b)) => match a.join(&b) {
+                    Some(joined) => Some(joined),
+                    None => {
+                        self.error(format!(
+                            "`match` arms have incompatible types: `{}` and `{}`",
+                            a, b
+                        ));
+                        Some(a)
+                    }
+                },
+                (a, b) => a.or(b),
+            };
+        }
+        // Arms of unknown or mismatched types cannot be compared with each
+        // other, and their errors are reported already.
+        if !patterns_fit {
+            return result;
+        }
+
+        let enums = std::mem::take(&mut self.enums);
+        let matrix = PatternMatrix::new(&enums);
+        let mut rows: Vec<Vec<Pattern>> = vec![];
+        for arm in arms {
+            let row = vec![arm.pattern.clone()];
+            if !matrix.is_useful(&rows, &row) {
+                self.diagnostics.push(Diagnostic::warning(format!(
+                    "unreachable `match` arm `{}`",
+                    arm.pattern
+                )));
+            }
+            rows.push(row);
+        }
+        if let Some(witness) = matrix.missing(&rows, 1) {
+            self.error(format!(
+                "non-exhaustive `match`: `{}` not covered",
+                witness[0]
+            ));
+        }
+        self.enums = enums;
+        result
+    }
+
+    fn check_new(&mut self, class: &str, fields: &[(String, Expression)]) -> Option<Type> {
+        let Some(info) = self.classes.get(class).cloned() else {
+            self.error(format!("unknown class `{}`", class));
+            return None;
+        };
+        let mut seen: Vec<&String> = vec![];
+        for (name, value) in fields {
+            let found = self.infer_expression(value);
+            if seen.contains(&name) {
+                self.error(format!("field `{}` is initialized more than once", name));
+            }
+            seen.push(name);
+            let Some(expected) = info.field(name) else {
+                self.error(format!("class `{}` has no field `{}`", class, name));
+                continue;
+            };
+            if let Some(found) = found {
+                if !self.is_assignable(expected, &found) {
+                    self.error(format!(
+                        "mismatched types for field `{}.{}`: expected `{}`, found `{}`",
+                        class, name, expected, found
+                    ));
+                }
+            }
+        }
+        let missing: Vec<String> = info
+            .fields
+            .iter()
+            .filter(|f| !seen.contains(&f.name()))
+            .map(|f| format!("`{}`", f.name()))
+            .collect();
+        if !missing.is_empty() {
+            self.error(format!(
+                "missing field(s) {} in `new {}`",
+                missing.join(", "),
+                class
+            ));
+        }
+        Some(Type::Custom(class.to_string()))
+    }
+
+    fn check_lambda(&mut self, lambda: &Lambda) -> Option<Type> {
+        for param in &lambda.params {
+            if let Some(ty) = param.var_type() {
+                self.check_type_annotation(ty);
+            }
+        }
+        if let Some(ty) = &lambda.return_type {
+            self.check_type_annotation(ty);
+        }
+        let id = self.closures.len() + self.open_closures.len();
+        self.open_closures.push((
+            self.scopes.len(),
+            Closure {
+                id,
+                captures: vec![],
+            },
+        ));
+        self.scopes.push(HashMap::new());
+        for param in &lambda.params {
+            if let Some(ty) = param.var_type() {
+                self.bind(param.name(), ty.clone());
+            }
+        }
+        self.return_types.push(lambda.return_type.clone());
+        let ret = match &lambda.body {
+            ArmBody::Expression(expr) => self.infer_expression(expr),
+            ArmBody::Block(block) => {
+                self.check_block(block);
+                Some(lambda.return_type.clone().unwrap_or(Type::Unit))
+            }
+        };
+        self.return_types.pop();
+        self.scopes.pop();
+        let (_, closure) = self.open_closures.pop().unwrap();
+        self.closures.push(closure);
+        let params = lambda
+            .params
+            .iter()
+            .map(|p| p.var_type().clone())
+            .collect::<Option<Vec<_>>>()?;
+        Some(Type::Function(params, Box::new(ret?)))
+    }
+
+    /// Infers the type of `expr` where a value of type `expected` is
+    /// required, which fills in what `none`, `ok(..)` and `err(..)` leave
+    /// open, like the error type of `ok(1i32)`. Where `expected` does not
+    /// fit, the open half is written `_`.
+    fn infer_expected(&mut self, expr: &Expression, expected: Option<&Type>) -> Option<Type> {
+        let Some(expected) = expected.map(|e| self.aliases.expand(e)) else {
+            return self.infer_expression(expr);
+        };
+        let open = || Type::Custom("_".to_string());
+        let ty = match expr {
+            Expression::None => Some(match expected.optional_inner() {
+                Some(_) => expected,
+                None => Type::option(open()),
+            }),
+            Expression::Some(value) => {
+                let inner = expected.optional_inner().cloned();
+                Some(Type::option(self.infer_expected(value, inner.as_ref())?))
+            }
+            Expression::Ok(value) => {
+                let (ok, err) = match expected.result_types() {
+                    Some((ok, err)) => (Some(ok.clone()), err.clone()),
+                    None => (None, open()),
+                };
+                Some(Type::result(self.infer_expected(value, ok.as_ref())?, err))
+            }
+            Expression::Err(error) => {
+                let (ok, err) = match expected.result_types() {
+                    Some((ok, err)) => (ok.clone(), Some(err.clone())),
+                    None => (open(), None),
+                };
+                Some(Type::result(ok, self.infer_expected(error, err.as_ref())?))
+            }
+            _ => return self.infer_expression(expr),
+        };
+        self.record(expr, ty)
+    }
+
+    /// Checks `value?`, which returns `none` or the error from the
+    /// enclosing function when `value` holds no value.
+    fn check_try(&mut self, value: &Expression) -> Option<Type> {
+        let ret = match self.return_types.last() {
+            Some(Some(ret)) => Some(self.aliases.expand(ret)),
+            _ => None,
+        };
+        let ty = self.infer_expression(value)?;
+        let ty = self.aliases.expand(&ty);
+        if let Some(inner) = ty.optional_inner() {
+            if ret.is_none_or(|r| r.optional_inner().is_none()) {
+                self.error(format!(
+                    "`{}?` can only be used in a function that returns an option",
+                    value
+                ));
+            }
+            return Some(inner.clone());
+        }
+        let Some((ok, err)) = ty.result_types() else {
+            self.error(format!("`?` expects an option or a result, found `{}`", ty));
+            return None;
+        };
+        match ret.as_ref().and_then(|r| r.result_types()) {
+            Some((_, ret_err)) if !self.is_assignable(ret_err, err) => {
+                self.error(format!(
+                    "mismatched error types for `{}?`: the function returns `{}`, found `{}`",
+                    value, ret_err, err
+                ));
+            }
+            Some(_) => {}
+            None => self.error(format!(
+                "`{}?` can only be used in a function that returns a result",
+                value
+            )),
+        }
+        Some(ok.clone())
+    }
+
+    /// Checks `tuple.0`, `tuple.1`, ...
+    fn check_tuple_field(&mut self, object: &Expression, field: &str) -> Option<Type> {
+        let ty = self.infer_expression(object)?;
+        let Type::Tuple(elements) = self.aliases.expand(&ty) else {
+            self.error(format!("`{}` is not a tuple type", ty));
+            return None;
+        };
+        let element = elements.get(field.parse::<usize>().ok()?).cloned();
+        if element.is_none() {
+            self.error(format!("tuple `{}` has no field `{}`", ty, field));
+        }
+        element
+    }
+
+    fn check_array(&mut self, elements: &[Expression]) -> Option<Type> {
+        let types: Vec<Type> = elements
+            .iter()
+            .filter_map(|e| self.infer_expression(e))
+            .collect();
+        let mut element: Option<Type> = None;
+        for found in types {
+            let Some(expected) = &element else {
+                element = Some(found);
+                continue;
+            };
+            let (expected, found) = (self.aliases.expand(expected), self.aliases.expand(&found));
+            if expected == found {
+                continue;
+            }
+            match expected.join(&found) {
+                Some(joined) => element = Some(joined),
+                None => {
+                    self.error(format!(
+                        "mismatched types in array literal: expected `{}`, found `{}`",
+                        expected, found
+                    ));
+                    return None;
+                }
+            }
+        }
+        Some(Type::Array(Box::new(element?), elements.len()))
+    }
+
+    /// Checks `array[index]`. Indices that fold to a constant are checked
+    /// against the length of the array here; any others are checked when
+    /// the program runs.
+    fn check_index(&mut self, array: &Expression, index: &Expression) -> Option<Type> {
+        let array_ty = self
+            .infer_expression(array)
+            .map(|t| self.aliases.expand(&t));
+        if let Some(index_ty) = self.infer_expression(index) {
+            let index_ty = self.aliases.expand(&index_ty);
+            if !index_ty.is_integral() {
+                self.error(format!(
+                    "array index must be an integer, found `{}`",
+                    index_ty
+                ));
+            }
+        }
+        let array_ty = array_ty?;
+        let Some(element) = array_ty.element_type().cloned() else {
+            self.error(format!("cannot index into a value of type `{}`", array_ty));
+            return None;
+        };
+        if let Type::Array(_, len) = array_ty {
+            let folded = ConstantFolding.fold_expression(index.clone());
+            if let Some(value) = Self::constant_value(&folded) {
+                if value < 0 || value >= len as i128 {
+                    self.error(format!(
+                        "index {} is out of bounds for `{}`",
+                        value, array_ty
+                    ));
+                }
+            }
+        }
+        Some(element)
+    }
+
+    fn check_intrinsic(&mut self, name: &str, args: &[Expression]) -> Option<Type> {
+        let arg_types: Vec<Option<Type>> = args.iter().map(|a| self.infer_expression(a)).collect();
+        if arg_types.len() != 1 {
+            self.error(format!(
+                "`{}` takes 1 argument(s) but {} were supplied",
+                name,
+                args.len()
+            ));
+            return None;
+        }
+        match name {
+            "len" => {
+                let ty = self.aliases.expand(arg_types[0].as_ref()?);
+                if ty.element_type().is_none() {
+                    self.error(format!("`len` expects an array, found `{}`", ty));
+                    return None;
+                }
+                Some(Type::U64)
+            }
+            _ => None,
+        }
+    }
+
+    fn infer_atom(&mut self, atom: &Atom) -> Option<Type> {
+        match &atom.value {
+            AtomValue::Identity(name) => match self.lookup_variable(name) {
+                Some(ty) => Some(ty),
+                None => self.functions.get(name)?.value_type(),
+            },
+            AtomValue::ParExpr(expr) => self.infer_expression(expr),
+            AtomValue::Call(name, args) => self.check_call(name, args),
+            _ => atom.get_type(),
+        }
+    }
+
+    /// The value of an integer literal argument, if it is one.
+    fn constant_value(expr: &Expression) -> Option<i128> {
+        let Expression::Atom(atom) = expr else {
+            return None;
+        };
+        match &atom.value {
+            AtomValue::Integer(_) => atom.signed_value(),
+            AtomValue::ParExpr(inner) => {
+                Self::constant_value(inner).map(|i| if atom.negative { -i } else { i })
+            }
+            _ => None,
+        }
+    }
+
+    /// Matches an argument type against a parameter type, binding the
+    /// function's dependent parameters as they are encountered.
+    fn unify(
+        &self,
+        expected: &Type,
+        found: &Type,
+        params: &[String],
+        bindings: &mut HashMap<String, Type>,
+    ) -> bool {
+        if self.is_erroneous(expected) || self.is_erroneous(found) {
+            return true;
+        }
+        let (expected, found) = (&self.aliases.expand(expected), &self.aliases.expand(found));
+        match (expected, found) {
+            (Type::Custom(name), _) if params.contains(name) => match bindings.get(name) {
+                Some(bound) => bound == found,
+                None => {
+                    bindings.insert(name.clone(), found.clone());
+                    true
+                }
+            },
+            (Type::Array(e, n), Type::Array(f, m)) => n == m && self.unify(e, f, params, bindings),
+            (Type::Slice(e), Type::Array(f, _) | Type::Slice(f)) => {
+                self.unify(e, f, params, bindings)
+            }
+            (Type::Function(ep, er), Type::Function(fp, fr)) => {
+                ep.len() == fp.len()
+                    && ep
+                        .iter()
+                        .zip(fp)
+                        .all(|(e, f)| self.unify(e, f, params, bindings))
+                    && self.unify(er, fr, params, bindings)
+            }
+            (Type::Tuple(e), Type::Tuple(f)) => {
+                e.len() == f.len()
+                    && e.iter()
+                        .zip(f)
+                        .all(|(e, f)| self.unify(e, f, params, bindings))
+            }
+            (Type::Generic(e), Type::Generic(f)) => {
+                e.name == f.name
+                    && e.fields.len() == f.fields.len()
+                    && e.fields
+                        .iter()
+                        .zip(&f.fields)
+                        .all(|(e, f)| self.unify(e, f, params, bindings))
+            }
+            _ => self.is_assignable(expected, found),
+        }
+    }
+
+    fn check_call(&mut self, name: &str, args: &[Expression]) -> Option<Type> {
+        // Variables hide functions of the same name.
+        if let Some(ty) = self.lookup_variable(name) {
+            let Type::Function(params, ret) = self.aliases.expand(&ty) else {
+                self.error(format!("`{}` is not a function, found `{}`", name, ty));
+                for arg in args {
+                    self.infer_expression(arg);
+                }
+                return None;
+            };
+            let sig = FunctionSignature::of_function_type(&params, &ret);
+            return self.check_arguments(name, &sig, args);
+        }
+        let Some(sig) = self.functions.get(name).cloned() else {
+            if INTRINSICS.contains(&name) {
+                return self.check_intrinsic(name, args);
+            }
+            for arg in args {
+                self.infer_expression(arg);
+            }
+            return None;
+        };
+        self.check_arguments(name, &sig, args)
+    }
+
+    fn check_arguments(
+        &mut self,
+        name: &str,
+        sig: &FunctionSignature,
+        args: &[Expression],
+    ) -> Option<Type> {
+        let params = sig.call_params();
+        let arg_types: Vec<Option<Type>> = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let expected = sig
+                    .inputs
+                    .get(i)
+                    .and_then(|input| input.var_type().as_ref())
+                    .filter(|ty| !ty.mentions(&params));
+                self.infer_expected(arg, expected)
+            })
+            .collect();
+        if sig.inputs.len() != args.len() {
+            self.error(format!(
+                "`{}` takes {} argument(s) but {} were supplied",
+                name,
+                sig.inputs.len(),
+                args.len()
+            ));
+            return None;
+        }
+
+        // A dependent parameter shadowed by an ordinary one of the same name
+        // takes the argument's value when it is a constant.
+        let mut bindings = HashMap::new();
+        for (input, arg) in sig.inputs.iter().zip(args) {
+            if sig.dependent_params.contains(input.name()) {
+                if let Some(value) = Self::constant_value(arg) {
+                    bindings.insert(input.name().clone(), Type::Const(value));
+                }
+            }
+        }
+
+        for (i, (input, found)) in sig.inputs.iter().zip(arg_types).enumerate() {
+            let (Some(expected), Some(found)) = (input.var_type(), found) else {
+                continue;
+            };
+            if !self.unify(expected, &found, &params, &mut bindings) {
+                self.error(format!(
+                    "argument {} of `{}`: expected `{}`, found `{}`",
+                    i + 1,
+                    name,
+                    expected.substitute(&bindings),
+                    found
+                ));
+            }
+        }
+
+        if !sig.type_params.is_empty() {
+            self.check_bounds(name, sig, &bindings);
+        }
+
+        let return_type = sig.return_type.substitute(&bindings);
+        if return_type.mentions(&params) {
+            // Not enough was known at the call site to fix the result type.
+            None
+        } else {
+            Some(return_type)
+        }
+    }
+
+    /// Checks the inferred type arguments of a generic call against their
+    /// bounds and records the instantiation.
+    fn check_bounds(
+        &mut self,
+        name: &str,
+        sig: &FunctionSignature,
+        bindings: &HashMap<String, Type>,
+    ) {
+        let mut type_args = vec![];
+        for param in &sig.type_params {
+            let Some(ty) = bindings.get(&param.name) else {
+                return;
+            };
+            for bound in &param.bounds {
+                if !self.implements(ty, bound) {
+                    self.error(format!(
+                        "`{}` does not implement interface `{}`, required by `{}` in `{}`",
+                        ty, bound, param.name, name
+                    ));
+                }
+            }
+            type_args.push(ty.clone());
+        }
+        // Instantiations over the caller's own type parameters are recorded
+        // when the caller itself is instantiated.
+        let in_scope: Vec<String> = self.type_params.iter().map(|p| p.name.clone()).collect();
+        if type_args.iter().any(|t| t.mentions(&in_scope)) {
+            return;
+        }
+        let instantiation = Instantiation {
+            function: name.to_string(),
+            type_args,
+        };
+        if !self.instantiations.contains(&instantiation) {
+            self.instantiations.push(instantiation);
+        }
+    }
+}
+
+/// Adds the variables `block` assigns to, in any of its nested blocks and
+/// lambdas, to `assigned`.
+fn assigned_in_block(block: &Block, assigned: &mut HashSet<String>) {
+    for statement in &block.statements {
+        assigned_in_statement(statement, assigned);
+    }
+    if let Some(value) = &block.value {
+        assigned_in_expression(value, assigned);
+    }
+}
+
+fn assigned_in_statement(statement: &Statement, assigned: &mut HashSet<String>) {
+    match statement {
+        Statement::VariableDeclaration(vd) => {
+            if let Some(value) = &vd.value {
+                assigned_in_expression(value, assigned);
+            }
+        }
+        Statement::Expression(expr) => assigned_in_expression(expr, assigned),
+        Statement::Assignment(assignment) => {
+            if let Expression::Atom(Atom {
+                value: AtomValue::Identity(name),
+                ..
+            }) = &assignment.target
+            {
+                assigned.insert(name.clone());
+            }
+            assigned_in_expression(&assignment.target, assigned);
+            assigned_in_expression(&assignment.value, assigned);
+        }
+        Statement::Conditional(cond) => assigned_in_conditional(cond, assigned),
+        Statement::Block(block) => assigned_in_block(block, assigned),
+        Statement::While(w) => {
+            assigned_in_expression(&w.condition, assigned);
+            assigned_in_block(&w.body, assigned);
+        }
+        Statement::For(l) => {
+            match &l.iterable {
+                Iterable::Range(range) => {
+                    let parts = [Some(&range.start), Some(&range.end), range.step.as_ref()];
+                    for part in parts.into_iter().flatten() {
+                        assigned_in_expression(part, assigned);
+                    }
+                }
+                Iterable::Expression(expr) => assigned_in_expression(expr, assigned),
+            }
+            assigned_in_block(&l.body, assigned);
+        }
+        // Declarations cannot assign to the variables around them.
+        _ => {}
+    }
+}
+
+fn assigned_in_conditional(cond: &Conditional, assigned: &mut HashSet<String>) {
+    assigned_in_expression(&cond.condition, assigned);
+    assigned_in_block(&cond.then_block, assigned);
+    if let Some(block) = &cond.else_block {
+        assigned_in_block(block, assigned);
+    }
+}
+
+fn assigned_in_expression(expr: &Expression, assigned: &mut HashSet<String>) {
+    let all = |exprs: &[Expression], assigned: &mut HashSet<String>| {
+        for expr in exprs {
+            assigned_in_expression(expr, assigned);
+        }
+    };
+    let body = |body: &ArmBody, assigned: &mut HashSet<String>| match body {
+        ArmBody::Expression(expr) => assigned_in_expression(expr, assigned),
+        ArmBody::Block(block) => assigned_in_block(block, assigned),
+    };
+    match expr {
+        Expression::BinaryOperation(lhs, _, rhs) | Expression::Index(lhs, rhs) => {
+            assigned_in_expression(lhs, assigned);
+            assigned_in_expression(rhs, assigned);
+        }
+        Expression::Unary(_, operand)
+        | Expression::FieldAccess(operand, _)
+        | Expression::Repeat(operand, _)
+        | Expression::Some(operand)
+        | Expression::Ok(operand)
+        | Expression::Err(operand)
+        | Expression::IsSome(operand)
+        | Expression::IsNone(operand)
+        | Expression::Try(operand) => assigned_in_expression(operand, assigned),
+        Expression::Atom(atom) => match &atom.value {
+            AtomValue::ParExpr(inner) => assigned_in_expression(inner, assigned),
+            AtomValue::Call(_, args) => all(args, assigned),
+            _ => {}
+        },
+        Expression::New(_, fields) => {
+            for (_, value) in fields {
+                assigned_in_expression(value, assigned);
+            }
+        }
+        Expression::MethodCall(object, _, args) => {
+            assigned_in_expression(object, assigned);
+            all(args, assigned);
+        }
+        Expression::Variant(_, _, args) | Expression::Tuple(args) | Expression::Array(args) => {
+            all(args, assigned)
+        }
+        Expression::Match(scrutinee, arms) => {
+            assigned_in_expression(scrutinee, assigned);
+            for arm in arms {
+                body(&arm.body, assigned);
+            }
+        }
+        Expression::If(cond) => assigned_in_conditional(cond, assigned),
+        Expression::Block(block) => assigned_in_block(block, assigned),
+        Expression::Lambda(lambda) => body(&lambda.body, assigned),
+        Expression::None => {}
+    }
+}
+
+impl Analysis for TypeChecker {
+    fn analyze(&mut self, program: &Program) -> Vec<Diagnostic> {
+        self.analyze_module(program, &[])
+    }
+
+    fn analyze_module(&mut self, program: &Program, imports: &[Statement]) -> Vec<Diagnostic> {
+        self.scopes = vec![HashMap::new()];
+        self.functions.clear();
+        self.classes.clear();
+        self.interfaces.clear();
+        self.enums.clear();
+        self.aliases.clear();
+        self.invalid_aliases.clear();
+        self.type_params.clear();
+        self.instantiations.clear();
+        self.closures.clear();
+        self.expression_types.clear();
+        self.binding_types.clear();
+        // Imported declarations were checked in their own module.
+        self.collect_declarations(imports);
+        for statement in imports {
+            if let Statement::VariableDeclaration(vd) = statement {
+                if let Some(ty) = vd.var_type() {
+                    self.bind(vd.name(), ty.clone());
+                }
+            }
+        }
+        self.collect_declarations(&program.statements);
+        for statement in &program.statements {
+            self.check_statement(statement);
+        }
+        // Inner lambdas are finished before the lambdas around them.
+        self.closures.sort_by_key(|c| c.id);
+        std::mem::take(&mut self.diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::VoeParser;
+
+    fn check(source: &str) -> Vec<Diagnostic> {
+        let program = VoeParser.parse_program(source).expect("parse failed");
+        TypeChecker::new().analyze(&program)
+    }
+
+    #[test]
+    fn test_dependent_return_type() {
+        let source = "
+            forall n: u32. fn zeros(n: u32) -> Vec<i32, n> {}
+            let a: Vec<i32, 3> = zeros(3);
+            let b: Vec<i32, 4> = zeros(3);
+        ";
+        let diagnostics = check(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0]
+            .message
+            .contains("expected `Vec<i32, 4>`, found `Vec<i32, 3>`"));
+    }
+
+    #[test]
+    fn test_dependent_arguments_agree() {
+        let source = "
+            forall n: u32. fn zeros(n: u32) -> Vec<i32, n> {}
+            forall n: u32. fn dot(a: Vec<i32, n>, b: Vec<i32, n>) -> i32 {}
+            let x = zeros(3);
+            let y = zeros(3);
+            let z = zeros(4);
+            let ok: i32 = dot(x, y);
+            let bad = dot(x, z);
+        ";
+        let diagnostics = check(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("argument 2 of `dot`"));
+    }
+
+    #[test]
+    fn test_classes() {
+        let source = "
+            class Point { x: i32, y: i32 }
+            impl Point {
+                fn scaled(self, k: i32) -> Point {
+                    let p: Point = new Point { x: self.x * k, y: self.y * k };
+                }
+            }
+            let p = new Point { x: 1i32, y: 2i32 };
+            let q: Point = p.scaled(2i32);
+            let x: i32 = q.x;
+        ";
+        assert!(check(source).is_empty());
+
+        let source = "
+            class Point { x: i32, y: i32 }
+            impl Point { fn norm(self) -> f64 {} }
+            let p = new Point { x: 1i32, z: 2i32 };
+            let a = p.z;
+            let b = p.norm(1i32);
+            let c = p.area();
+        ";
+        let messages: Vec<String> = check(source).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "class `Point` has no field `z`",
+                "missing field(s) `y` in `new Point`",
+                "class `Point` has no field `z`",
+                "`Point.norm` takes 0 argument(s) but 1 were supplied",
+                "class `Point` has no method `area`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interfaces() {
+        let prelude = "
+            interface Shape { fn area(self) -> f64; fn scale(self, k: f64) -> f64; }
+            class Circle { r: f64 }
+            class Label { text: string }
+        ";
+        let good = "
+            impl Shape for Circle {
+                fn area(self) -> f64 {}
+                fn scale(self, factor: f64) -> f64 {}
+            }
+            fn total<T: Shape>(a: T, b: T) -> f64 {
+                let x: f64 = a.area() + b.scale(2.0f64);
+            }
+            let c = new Circle { r: 1.0f64 };
+            let t: f64 = total(c, c);
+        ";
+        let mut checker = TypeChecker::new();
+        let program = VoeParser
+            .parse_program(&format!("{}{}", prelude, good))
+            .unwrap();
+        assert!(checker.analyze(&program).is_empty());
+        assert_eq!(
+            checker.instantiations(),
+            &[Instantiation {
+                function: "total".to_string(),
+                type_args: vec![Type::Custom("Circle".to_string())],
+            }]
+        );
+        assert_eq!(
+            checker.resolve_method(&Type::Custom("Circle".to_string()), "area"),
+            Some(MethodTarget {
+                class: "Circle".to_string(),
+                method: "area".to_string(),
+                interface: Some("Shape".to_string()),
+            })
+        );
+
+        let bad = "
+            impl Shape for Circle {
+                fn area(self) -> f32 {}
+                fn perimeter(self) -> f64 {}
+            }
+            fn total<T: Shape>(a: T) -> f64 { a.perimeter(); }
+            let l = new Label { text: \"x\" };
+            let t = total(l);
+        ";
+        let messages: Vec<String> = check(&format!("{}{}", prelude, bad))
+            .into_iter()
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "method `area` of `Circle` does not match interface `Shape`: expected `fn area(self) -> f64;`",
+                "`Circle` does not implement method `scale` of interface `Shape`",
+                "method `perimeter` is not a member of interface `Shape`",
+                "no method `perimeter` in the bounds of type parameter `T`",
+                "`Label` does not implement interface `Shape`, required by `T` in `total`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_match() {
+        let prelude = "
+            enum Shape { Circle(f64), Rect(f64, f64), Empty }
+            enum Wrap { Some(Shape), None }
+            let w = Wrap::Some(Shape::Circle(1.0f64));
+        ";
+        let good = "
+            let a: f64 = match w {
+                Wrap::Some(Shape::Circle(r)) => r,
+                Wrap::Some(Shape::Rect(x, 0.0f64)) => x,
+                Wrap::Some(Shape::Rect(x, y)) => x * y,
+                Wrap::Some(Shape::Empty) => 0.0f64,
+                Wrap::None => 0.0f64,
+            };
+            let b: i32 = match true { true => 1i32, false => 0i32 };
+        ";
+        assert!(check(&format!("{}{}", prelude, good)).is_empty());
+
+        let bad = "
+            let a = match w {
+                Wrap::Some(Shape::Circle(r)) => r,
+                Wrap::Some(Shape::Rect(x, y)) => true,
+                Wrap::Some(Shape::Circle(_)) => 2.0f64,
+                Wrap::None => 0.0f64,
+            };
+            let b = match 3i32 { 1i32 => 1i32, Shape::Empty => 2i32 };
+        ";
+        let messages: Vec<String> = check(&format!("{}{}", prelude, bad))
+            .into_iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "error: `match` arms have incompatible types: `f64` and `bool`",
+                "warning: unreachable `match` arm `Wrap::Some(Shape::Circle(_))`",
+                "error: non-exhaustive `match`: `Wrap::Some(Shape::Empty)` not covered",
+                "error: mismatched types in pattern `Shape::Empty`: expected `i32`, found `Shape`",
+            ]
+        );
+
+        // Arms are not compared when the scrutinee has no type, which the
+        // resolver reports, or when their patterns do not fit it.
+        let unknown = "
+            fn f() -> i32 {
+                let m = match zz { x => 1i32, err(_) => 0i32, Shape::Rect(w, _) => 1i32, };
+                m;
+            }
+        ";
+        let messages: Vec<String> = check(unknown).into_iter().map(|d| d.message).collect();
+        assert_eq!(messages, vec!["unknown enum `Shape`"]);
+    }
+
+    #[test]
+    fn test_tuples() {
+        let good = "
+            fn divmod(a: i32, b: i32) -> (i32, i32) { (a / b, a % b); }
+            fn swap<A, B>(p: (A, B)) -> (B, A) {}
+            let (q, r) = divmod(7i32, 2i32);
+            let s: i32 = q + r + divmod(9i32, 4i32).0;
+            let (flag, _): (bool, f64) = swap((1.5f64, true));
+            let t: bool = flag && match (flag, q > 2i32) {
+                (true, true) => true,
+                (_, false) => false,
+                (false, _) => true,
+            };
+        ";
+        assert!(check(good).is_empty(), "{:?}", check(good));
+
+        let bad = "
+            let p = (1i32, true);
+            let (a, b, c) = p;
+            let (x, x) = p;
+            let n: i32 = p.1;
+            let m = p.2;
+            let k = 3i32.0;
+            let u = match p { (1i32, true) => 1i32, (_, false) => 2i32 };
+        ";
+        let messages: Vec<String> = check(bad).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "mismatched types in pattern `(a, b, c)`: expected `(i32, bool)`, found a tuple of 3 element(s)",
+                "`x` is bound more than once in pattern `(x, x)`",
+                "mismatched types in `let n`: expected `i32`, found `bool`",
+                "tuple `(i32, bool)` has no field `2`",
+                "`i32` is not a tuple type",
+                "non-exhaustive `match`: `(_, true)` not covered",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_options() {
+        let good = "
+            fn find(xs: [i32], key: i32) -> i32? { none; }
+            fn first_even(xs: [i32]) -> Option<i32> {
+                let x = find(xs, 2i32)?;
+                some(x * 2i32);
+            }
+            fn sum(a: i32?, b: i32?) -> i32 {
+                let c: i32 = 0i32;
+                if a is some && b is some {
+                    c = a + b;
+                }
+                if a is none || !(b is some) {
+                    c = 1i32;
+                } else {
+                    c = a * b;
+                    a = none;
+                }
+                c;
+            }
+        ";
+        assert!(check(good).is_empty(), "{:?}", check(good));
+
+        let bad = "
+            fn get() -> i32 { find(1i32)?; }
+            fn find(key: i32) -> i32? { key?; }
+            let a: i32? = some(true);
+            let b: i32 = a;
+            if a is some { a = none; let c: i32 = a; }
+            let d = 1i32 is none;
+            fn e(x: Option<i32, bool>) -> () {}
+        ";
+        let messages: Vec<String> = check(bad).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "`find(1i32)?` can only be used in a function that returns an option",
+                "`?` expects an option or a result, found `i32`",
+                "mismatched types in `let a`: expected `i32?`, found `bool?`",
+                "mismatched types in `let b`: expected `i32`, found `i32?`",
+                "mismatched types in `let c`: expected `i32`, found `i32?`",
+                "cannot test whether `1i32` is some: `i32` is not an option",
+                "`Option` takes 1 type argument(s) but 2 were supplied",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_results() {
+        let good = "
+            fn parse(s: string) -> Result<i32, string> { ok(1i32); }
+            fn halve(n: i32) -> Result<i32, string> {
+                if n % 2i32 == 1i32 { err(\"odd\"); }
+                ok(n / 2i32);
+            }
+            fn both(s: string) -> Result<(i32, i32), string> {
+                let n = parse(s)?;
+                let r: Result<(i32, i32), string> = ok((n, halve(n)?));
+                r = err(\"none\");
+                r;
+            }
+            fn describe(r: Result<i32?, string>) -> i32 {
+                if let ok(some(n)) = r { n; }
+                match r {
+                    ok(some(n)) => n,
+                    ok(none) => 0i32,
+                    err(_) => -1i32,
+                };
+            }
+        ";
+        assert!(check(good).is_empty(), "{:?}", check(good));
+
+        let bad = "
+            fn parse(s: string) -> Result<i32, string> { ok(1i32); }
+            fn code(s: string) -> Result<i32, i32> { parse(s)?; }
+            fn first(s: string) -> i32? { parse(s)?; }
+            let a: Result<i32, string> = ok(true);
+            let b: Result<i32, string> = err(1i32);
+            let c: i32 = ok(1i32);
+            describe(err(2i32));
+            fn describe(r: Result<i32, string>) -> i32 {
+                match r { ok(n) => n, err(\"x\") => 0i32 };
+                if let some(n) = r { n; }
+            }
+            fn d(x: Result<i32>) -> () {}
+        ";
+        let messages: Vec<String> = check(bad).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "mismatched error types for `parse(s)?`: the function returns `i32`, found `string`",
+                "`parse(s)?` can only be used in a function that returns a result",
+                "mismatched types in `let a`: expected `Result<i32, string>`, found `Result<bool, string>`",
+                "mismatched types in `let b`: expected `Result<i32, string>`, found `Result<i32, i32>`",
+                "mismatched types in `let c`: expected `i32`, found `Result<i32, _>`",
+                "argument 1 of `describe`: expected `Result<i32, string>`, found `Result<i32, i32>`",
+                "non-exhaustive `match`: `err(_)` not covered",
+                "mismatched types in pattern `some(n)`: expected `Result<i32, string>`, found an option",
+                "`Result` takes 2 type argument(s) but 1 were supplied",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_loops() {
+        let good = "
+            fn sum(xs: [i32], pairs: [(i32, bool); 2], n: i64) -> i32 {
+                let total: i32 = 0i32;
+                for x in xs { total = total + x; }
+                for (a, flag) in pairs { if flag { total = total + a; } }
+                for i in 0i64..=n step 2i64 { let j: i64 = i; }
+                let next: i32? = some(1i32);
+                while next is some { total = total + next; next = none; }
+                total;
+            }
+        ";
+        assert!(check(good).is_empty(), "{:?}", check(good));
+
+        // Narrowings from outside a loop do not survive into a later
+        // iteration that follows an assignment.
+        let reassigned = "
+            fn f(x: i32?, c: bool, xs: [i32]) -> () {
+                if x is some { while c { let y: i32 = x; x = none; } }
+                if x is some { for i in xs { if c { let y: i32 = x; } x = none; } }
+                if x is some { while x > 0i32 { x = none; } }
+            }
+        ";
+        let messages: Vec<String> = check(reassigned).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "mismatched types in `let y`: expected `i32`, found `i32?`",
+                "mismatched types in `let y`: expected `i32`, found `i32?`",
+                "mismatched operand types for `>`: `i32?` and `i32`",
+            ]
+        );
+
+        let bad = "
+            for x in 5i32 {}
+            for i in 0i32..1.5f64 {}
+            for i in 0i32..10u64 {}
+            for i in 0i32..10i32 step 0i32 {}
+            for (a, a) in [(1i32, 2i32)] {}
+            while 1i32 {}
+        ";
+        let messages: Vec<String> = check(bad).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "cannot iterate over a value of type `i32`",
+                "range `0i32..1.5f64` must have integer bounds, found `f64`",
+                "mismatched types in range `0i32..10u64`: `i32` and `u64`",
+                "range `0i32..10i32 step 0i32` has a step of zero",
+                "`a` is bound more than once in pattern `(a, a)`",
+                "`while` condition must be `bool`, found `i32`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_if_expressions() {
+        let good = "
+            fn pick(c: bool, x: i8?) -> i64 {
+                let a: i32 = if c { 1i32 } else { 2i32 };
+                let b = if c { 1i8 } else if !c { 2i16 } else { let n: i64 = 3i64; n };
+                let d: i8 = if x is some { x } else { 0i8 };
+                let e = if let some(v) = x { v } else { 0i8 };
+                b;
+            }
+        ";
+        assert!(check(good).is_empty(), "{:?}", check(good));
+
+        let bad = "
+            let a = if true { 1i32 };
+            let b = if true { 1i32 } else { \"one\" };
+            let c: bool = if true { 1i32 } else { 2i32 };
+            let d = if 1i32 { true } else { false };
+        ";
+        let messages: Vec<String> = check(bad).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "`if` used as a value must have an `else` branch",
+                "`if` and `else` have incompatible types: `i32` and `string`",
+                "mismatched types in `let c`: expected `bool`, found `i32`",
+                "`if` condition must be `bool`, found `i32`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        let good = "
+            fn f(c: bool) -> i64 {
+                let x: i32 = 1i32;
+                { let x: bool = true; let y: bool = x && c; }
+                let z: i32 = x;
+                let w: i64 = { let v: i16 = 2i16; v } + 1i64;
+                w;
+            }
+        ";
+        assert!(check(good).is_empty(), "{:?}", check(good));
+
+        let bad = "
+            let a: string = { let n: i32 = 1i32; n };
+            { let b: i32 = true; }
+        ";
+        let messages: Vec<String> = check(bad).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "mismatched types in `let a`: expected `string`, found `i32`",
+                "mismatched types in `let b`: expected `i32`, found `bool`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        let source = "
+            fn apply(f: fn(i32) -> i32, x: i32) -> i32 { f(x); }
+            fn twice<T>(f: fn(T) -> T, x: T) -> T { f(f(x)); }
+            fn inc(x: i32) -> i32 { x + 1i32; }
+            fn run(k: i32) -> i32 {
+                let offset = k * 2i32;
+                let add = |x: i32| x + offset + k;
+                let nested = |y: i32| -> i32 { let g = |z: i32| z + offset; g(y); };
+                let a: fn(i32) -> bool = |x: i32| x + 1i32;
+                add(1i32, 2i32);
+                offset(1i32);
+                apply(|x: bool| x, 1i32) + apply(add, 1i32) + twice(inc, nested(2i32));
+            }
+        ";
+        let program = VoeParser.parse_program(source).expect("parse failed");
+        let mut checker = TypeChecker::new();
+        let messages: Vec<String> = checker
+            .analyze(&program)
+            .into_iter()
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "mismatched types in `let a`: expected `fn(i32) -> bool`, found `fn(i32) -> i32`",
+                "`add` takes 1 argument(s) but 2 were supplied",
+                "`offset` is not a function, found `i32`",
+                "argument 1 of `apply`: expected `fn(i32) -> i32`, found `fn(bool) -> bool`",
+            ]
+        );
+
+        let captures: Vec<Vec<String>> = checker
+            .closures()
+            .iter()
+            .map(|c| c.captures.iter().map(|v| v.name().clone()).collect())
+            .collect();
+        assert_eq!(
+            captures,
+            vec![
+                vec!["offset", "k"],
+                vec!["offset"],
+                vec!["offset"],
+                vec![],
+                vec![],
+            ]
+        );
+        let environment = checker.closures()[0].environment();
+        assert_eq!(
+            environment.to_string(),
+            "class Closure0Env {\n    offset: i32,\n    k: i32,\n}"
+        );
+        assert_eq!(
+            checker.instantiations(),
+            &[Instantiation {
+                function: "twice".to_string(),
+                type_args: vec![Type::I32],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_type_aliases() {
+        let source = "
+            type Meters = f64;
+            type Distance = Meters;
+            type Pair<T> = Tuple<T, T>;
+            fn make() -> Pair<Meters> {}
+            let d: Distance = 1.5f64;
+            let m: Meters = d;
+            let p: Tuple<f64, f64> = make();
+            let ok: bool = d > m;
+        ";
+        assert_eq!(check(source), vec![]);
+
+        let source = "
+            type Meters = f64;
+            type Pair<T> = Tuple<T, T>;
+            type A = Vec<B>;
+            type B = A;
+            let m: Meters = true;
+            let p: Pair<i32, i32> = 1i32;
+        ";
+        let messages: Vec<String> = check(source).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "type alias `A` is cyclic: A -> B -> A",
+                "type alias `B` is cyclic: B -> A -> B",
+                "mismatched types in `let m`: expected `Meters`, found `bool`",
+                "type alias `Pair` takes 1 type argument(s) but 2 were supplied",
+                "mismatched types in `let p`: expected `Pair<i32, i32>`, found `i32`",
+            ]
+        );
+
+        // An unknown target is reported once, not at every use.
+        let source = "
+            type Meters = Feet;
+            type Span = Tuple<Meters, Meters>;
+            fn grow(m: Meters) -> Span {}
+            let m: Meters = 1i32;
+            let s: Span = grow(true);
+            let n: i32 = m;
+        ";
+        let messages: Vec<String> = check(source).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec!["type alias `Meters` refers to unknown type `Feet`"]
+        );
+    }
+
+    #[test]
+    fn test_imported_declarations() {
+        let imports = VoeParser
+            .parse_program("fn area(side: f64) -> f64 {} let PI: f64 = 3.14f64;")
+            .unwrap()
+            .statements;
+        let program = VoeParser
+            .parse_program("let a: f64 = area(PI); let b: bool = area(1.5f64);")
+            .unwrap();
+        let diagnostics = TypeChecker::new().analyze_module(&program, &imports);
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::error(
+                "mismatched types in `let b`: expected `bool`, found `f64`".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_unary_and_shift_operators() {
+        let source = "
+            let a: i32 = -1i32;
+            let b: bool = !(a > 0i32);
+            let c: u8 = ~1u8 << 2i32;
+            let u: u8 = 1u8;
+            let d = -u;
+            let e = !a;
+            let f = 1.5f64 >> 1i32;
+        ";
+        let messages: Vec<String> = check(source).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "cannot apply unary `-` to `u8`",
+                "cannot apply unary `!` to `i32`",
+                "cannot shift `f64` by `i32`: operands must be integers",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        let source = "
+            let a: i32 = 1i32;
+            let b: bool = a > 0i32 && a < 10i32 || a == 20i32;
+            let c = a && b;
+        ";
+        let messages: Vec<String> = check(source).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec!["cannot apply `&&` to `i32` and `bool`: operands must be `bool`"]
+        );
+    }
+
+    #[test]
+    fn test_arrays() {
+        let source = "
+            fn sum(xs: [i32]) -> i32 {}
+            fn first<T>(xs: [T]) -> T {}
+            let a: [i32; 4] = [1i32, 2i32, 3i32, 4i32];
+            let n: u64 = len(a);
+            let s: i32 = sum(a) + first(a) + a[3];
+            a[0] = a[1 + 2];
+            let i = 2i32;
+            a[i * 2i32] = 0i32;
+            a[4] = 1i32;
+            a[1] = true;
+            let b = [1i32, true];
+            let c = s[0];
+            let d = a[1.5f64];
+            let e: [bool; 2] = [false; 3];
+        ";
+        let messages: Vec<String> = check(source).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "index 4 is out of bounds for `[i32; 4]`",
+                "mismatched types in assignment to `a[1]`: expected `i32`, found `bool`",
+                "mismatched types in array literal: expected `i32`, found `bool`",
+                "cannot index into a value of type `i32`",
+                "array index must be an integer, found `f64`",
+                "mismatched types in `let e`: expected `[bool; 2]`, found `[bool; 3]`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mismatched_operands() {
+        let diagnostics = check("let a = 1i8 + 1u8;");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(check("let a: i32 = 1i8 + 3i16 * 5i32;").is_empty());
+    }
+
+    #[test]
+    fn test_types_by_address() {
+        let program = VoeParser
+            .parse_program("let a = 1i8; let b = { let a = true; a }; let c: i32? = none;")
+            .unwrap();
+        let mut checker = TypeChecker::new();
+        assert!(checker.analyze(&program).is_empty());
+        let values: Vec<&Expression> = program
+            .statements
+            .iter()
+            .map(|s| match s {
+                Statement::VariableDeclaration(vd) => vd.value.as_ref().unwrap(),
+                _ => panic!("expected a declaration"),
+            })
+            .collect();
+        let Expression::Block(block) = values[1] else {
+            panic!("expected a block");
+        };
+        let inner = block.value.as_ref().unwrap();
+        assert_eq!(checker.type_of(values[0]), Some(&Type::I8));
+        assert_eq!(checker.type_of(inner), Some(&Type::Bool));
+        // `none` takes its type from the declaration.
+        assert_eq!(checker.type_of(values[2]), Some(&Type::option(Type::I32)));
+        let Statement::VariableDeclaration(outer) = &program.statements[0] else {
+            unreachable!();
+        };
+        assert_eq!(checker.binding_type(outer.name()), Some(&Type::I8));
+        assert_eq!(checker.binding_type("a"), None);
+    }
+}
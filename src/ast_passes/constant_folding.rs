@@ -1,71 +1,114 @@
 use crate::parser::{
     atom::{Atom, AtomValue},
-    Block, Conditional, Expression, FunctionDefinition, Operator, Program, Statement,
-    VariableDeclaration,
+    ArmBody, Assignment, Block, Conditional, Expression, ForLoop, FunctionDefinition, ImplBlock,
+    Iterable, Lambda, MatchArm, Operator, Program, Range, Statement, Type, UnaryOp,
+    VariableDeclaration, WhileLoop,
 };
 
 use super::ASTPass;
 
+/// Truncates `value` to the bits of the integral type `ty`, as the result of
+/// an operation on values of that type would be.
+fn wrap(value: i128, ty: &Type) -> Option<i128> {
+    let Some(bits) = ty.bit_width() else {
+        return Some(value);
+    };
+    // An `i128` holds every 128-bit value wrapped but the unsigned ones
+    // above `i128::MAX`.
+    if bits == 128 {
+        return (ty.is_signed() || value >= 0).then_some(value);
+    }
+    let value = value & ((1i128 << bits) - 1);
+    if ty.is_signed() && value >> (bits - 1) == 1 {
+        Some(value - (1i128 << bits))
+    } else {
+        Some(value)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct ConstantFolding;
 
 impl ConstantFolding {
     fn fold_statement(&self, statement: Statement) -> Statement {
         match statement {
-            Statement::Function(fd) => self.fold_function_definition(fd),
+            Statement::Function(fd) => Statement::Function(self.fold_function_definition(fd)),
             Statement::VariableDeclaration(vd) => self.fold_variable_declaration(vd),
             Statement::Expression(expr) => Statement::expression(self.fold_expression(expr)),
-            Statement::Conditional(cond) => self.fold_conditional(cond),
+            Statement::Assignment(assignment) => Statement::assignment(Assignment::new(
+                self.fold_expression(assignment.target),
+                self.fold_expression(assignment.value),
+            )),
+            Statement::Conditional(cond) => Statement::Conditional(self.fold_conditional(cond)),
+            Statement::Block(block) => Statement::Block(self.fold_block(block)),
+            Statement::While(w) => Statement::while_loop(WhileLoop::new(
+                self.fold_expression(w.condition),
+                self.fold_block(w.body),
+            )),
+            Statement::For(l) => {
+                let iterable = match l.iterable {
+                    Iterable::Range(range) => Iterable::Range(Box::new(Range {
+                        start: self.fold_expression(range.start),
+                        end: self.fold_expression(range.end),
+                        step: range.step.map(|s| self.fold_expression(s)),
+                        ..*range
+                    })),
+                    Iterable::Expression(expr) => Iterable::Expression(self.fold_expression(expr)),
+                };
+                Statement::for_loop(ForLoop::new(l.pattern, iterable, self.fold_block(l.body)))
+            }
+            Statement::Class(class) => Statement::Class(class),
+            Statement::Impl(ib) => Statement::Impl(ImplBlock {
+                methods: ib
+                    .methods
+                    .into_iter()
+                    .map(|m| self.fold_function_definition(m))
+                    .collect(),
+                ..ib
+            }),
+            Statement::Interface(interface) => Statement::Interface(interface),
+            Statement::Enum(enumeration) => Statement::Enum(enumeration),
+            Statement::TypeAlias(alias) => Statement::TypeAlias(alias),
+            Statement::Module(path) => Statement::Module(path),
+            Statement::Error(text) => Statement::Error(text),
+            Statement::Import(import) => Statement::Import(import),
+            Statement::Use(u) => Statement::Use(u),
+            Statement::Export(s) => Statement::export(self.fold_statement(*s)),
         }
     }
 
-    fn fold_function_definition(&self, fd: FunctionDefinition) -> Statement {
-        let FunctionDefinition {
-            name,
-            inputs,
-            return_type,
-            body,
-        } = fd;
-        let processed_body: Vec<Statement> = body
-            .statements
-            .into_iter()
-            .map(|s| self.fold_statement(s))
-            .collect();
-        Statement::Function(FunctionDefinition::new(
-            name,
-            inputs,
-            return_type,
-            Block::new(processed_body),
-        ))
+    fn fold_block(&self, block: Block) -> Block {
+        Block::new(
+            block
+                .statements
+                .into_iter()
+                .map(|s| self.fold_statement(s))
+                .collect(),
+        )
+        .with_value(block.value.map(|v| self.fold_expression(*v)))
+    }
+
+    fn fold_function_definition(&self, fd: FunctionDefinition) -> FunctionDefinition {
+        FunctionDefinition {
+            body: self.fold_block(fd.body),
+            ..fd
+        }
     }
 
     fn fold_variable_declaration(&self, vd: VariableDeclaration) -> Statement {
         let VariableDeclaration {
-            name,
+            pattern,
             var_type,
             value,
         } = vd;
-        let mut ty = var_type;
         let processed_value = value.map(|expr| self.fold_expression(expr));
-        if let Some(expr) = &processed_value {
-            ty = expr.return_type();
-            if let Expression::Atom(atom) = expr {
-                if let AtomValue::Integer(value) = atom.value {
-                    return Statement::VariableDeclaration(VariableDeclaration::new(
-                        name,
-                        ty.clone(),
-                        Some(Expression::Atom(Atom::from_i128(value, ty))),
-                    ));
-                } else if let AtomValue::Float(value) = atom.value {
-                    return Statement::VariableDeclaration(VariableDeclaration::new(
-                        name,
-                        ty.clone(),
-                        Some(Expression::Atom(Atom::from_f64(value, ty))),
-                    ));
-                }
-            }
-        }
-        Statement::VariableDeclaration(VariableDeclaration::new(name, ty, processed_value))
+        // The folded value's type only fills in a missing annotation.
+        let ty = var_type.or_else(|| processed_value.as_ref().and_then(|expr| expr.return_type()));
+        Statement::VariableDeclaration(VariableDeclaration::destructuring(
+            pattern,
+            ty,
+            processed_value,
+        ))
     }
 
     fn fold_atom(&self, atom: Atom) -> Atom {
@@ -73,6 +116,7 @@ impl ConstantFolding {
             negative,
             value,
             ty,
+            radix,
         } = atom;
         match value {
             AtomValue::ParExpr(einner) => {
@@ -83,7 +127,37 @@ impl ConstantFolding {
                     Atom::new(negative, AtomValue::ParExpr(Box::new(eproc)), ty)
                 }
             }
-            _ => Atom::new(negative, value, ty),
+            _ => Atom::new(negative, value, ty).with_radix(radix),
+        }
+    }
+
+    fn fold_unary(&self, op: UnaryOp, operand: Expression) -> Expression {
+        // Parentheses around a folded constant are no longer needed.
+        let operand = match operand {
+            Expression::Atom(Atom {
+                negative: false,
+                value: AtomValue::ParExpr(inner),
+                ..
+            }) if matches!(*inner, Expression::Atom(_)) => *inner,
+            operand => operand,
+        };
+        let Expression::Atom(atom) = &operand else {
+            return Expression::unary(op, operand);
+        };
+        match (op, &atom.value, &atom.ty) {
+            (UnaryOp::Neg, AtomValue::Integer(_) | AtomValue::Float(_), _) => Expression::Atom(
+                Atom::new(!atom.negative, atom.value.clone(), atom.ty.clone()),
+            ),
+            (UnaryOp::Not, AtomValue::Boolean(b), _) => {
+                Expression::Atom(Atom::new(false, AtomValue::Boolean(!b), Some(Type::Bool)))
+            }
+            (UnaryOp::BitNot, AtomValue::Integer(_), Some(ty)) if ty.is_integral() => {
+                match atom.signed_value().and_then(|value| wrap(!value, ty)) {
+                    Some(value) => Expression::Atom(Atom::from_i128(value, Some(ty.clone()))),
+                    None => Expression::unary(op, operand),
+                }
+            }
+            _ => Expression::unary(op, operand),
         }
     }
 
@@ -91,80 +165,79 @@ impl ConstantFolding {
         if let Some(lty) = &lhs.ty {
             if let Some(rty) = &rhs.ty {
                 if lty.is_integral() && rty.is_integral() {
-                    let Atom {
-                        negative: lhs_neg,
-                        value: lhs_val,
-                        ty: _,
-                    } = self.fold_atom(lhs.clone());
-                    let Atom {
-                        negative: rhs_neg,
-                        value: rhs_val,
-                        ty: _,
-                    } = self.fold_atom(rhs.clone());
-
-                    let AtomValue::Integer(lhs_val) = lhs_val else {
-                        return Expression::BinaryOperation(
+                    let lhs = self.fold_atom(lhs.clone());
+                    let rhs = self.fold_atom(rhs.clone());
+                    let unfolded = || {
+                        Expression::BinaryOperation(
                             Box::new(Expression::Atom(Atom::new(
-                                lhs_neg,
-                                lhs_val,
+                                lhs.negative,
+                                lhs.value.clone(),
                                 Some(lty.clone()),
                             ))),
-                            op,
+                            op.clone(),
                             Box::new(Expression::Atom(Atom::new(
-                                rhs_neg,
-                                rhs_val,
+                                rhs.negative,
+                                rhs.value.clone(),
                                 Some(rty.clone()),
                             ))),
-                        );
+                        )
                     };
-                    let AtomValue::Integer(rhs_val) = rhs_val else {
-                        return Expression::BinaryOperation(
-                            Box::new(Expression::Atom(Atom::new(
-                                lhs_neg,
-                                AtomValue::Integer(lhs_val),
-                                Some(lty.clone()),
-                            ))),
-                            op,
-                            Box::new(Expression::Atom(Atom::new(
-                                rhs_neg,
-                                rhs_val,
-                                Some(rty.clone()),
-                            ))),
-                        );
+                    // Values too large for an `i128` are left for the back
+                    // end, as are results that overflow one.
+                    let (Some(lhs_val), Some(rhs_val)) = (lhs.signed_value(), rhs.signed_value())
+                    else {
+                        return unfolded();
                     };
 
-                    let lhs_val = if lhs_neg { -lhs_val } else { lhs_val };
-                    let rhs_val = if rhs_neg { -rhs_val } else { rhs_val };
-
+                    // Results out of the range of their type are left for
+                    // the back end too.
+                    let fits = |value: &i128| {
+                        lty.join(rty)
+                            .is_none_or(|ty| wrap(*value, &ty) == Some(*value))
+                    };
+                    let value = match op {
+                        Operator::Add => lhs_val.checked_add(rhs_val),
+                        Operator::Subtract => lhs_val.checked_sub(rhs_val),
+                        Operator::Multiply => lhs_val.checked_mul(rhs_val),
+                        Operator::Divide => lhs_val.checked_div(rhs_val),
+                        Operator::Modulo => lhs_val.checked_rem(rhs_val),
+                        _ => None,
+                    }
+                    .filter(fits);
+                    if let Some(value) = value {
+                        return Expression::Atom(Atom::from_i128(value, lty.join(rty)));
+                    }
                     match op {
-                        Operator::Add => {
-                            Expression::Atom(Atom::from_i128(lhs_val + rhs_val, lty.join(rty)))
-                        }
-                        Operator::Subtract => {
-                            Expression::Atom(Atom::from_i128(lhs_val - rhs_val, lty.join(rty)))
-                        }
-                        Operator::Multiply => {
-                            Expression::Atom(Atom::from_i128(lhs_val * rhs_val, lty.join(rty)))
-                        }
-                        Operator::Divide => {
-                            Expression::Atom(Atom::from_i128(lhs_val / rhs_val, lty.join(rty)))
+                        Operator::And | Operator::Or | Operator::Xor if lty.join(rty).is_some() => {
+                            let ty = lty.join(rty).unwrap();
+                            let value = match op {
+                                Operator::And => lhs_val & rhs_val,
+                                Operator::Or => lhs_val | rhs_val,
+                                _ => lhs_val ^ rhs_val,
+                            };
+                            match wrap(value, &ty) {
+                                Some(value) => Expression::Atom(Atom::from_i128(value, Some(ty))),
+                                None => unfolded(),
+                            }
                         }
-                        Operator::Modulo => {
-                            Expression::Atom(Atom::from_i128(lhs_val % rhs_val, lty.join(rty)))
+                        // Shifting by the width of the type or more is left
+                        // for the back end to reject.
+                        Operator::ShiftLeft | Operator::ShiftRight
+                            if (0..lty.bit_width().unwrap() as i128).contains(&rhs_val) =>
+                        {
+                            let value = if op == Operator::ShiftLeft {
+                                lhs_val << rhs_val
+                            } else {
+                                lhs_val >> rhs_val
+                            };
+                            match wrap(value, lty) {
+                                Some(value) => {
+                                    Expression::Atom(Atom::from_i128(value, Some(lty.clone())))
+                                }
+                                None => unfolded(),
+                            }
                         }
-                        _ => Expression::BinaryOperation(
-                            Box::new(Expression::Atom(Atom::new(
-                                lhs_neg,
-                                AtomValue::Integer(lhs_val),
-                                Some(lty.clone()),
-                            ))),
-                            op,
-                            Box::new(Expression::Atom(Atom::new(
-                                rhs_neg,
-                                AtomValue::Integer(rhs_val),
-                                Some(rty.clone()),
-                            ))),
-                        ),
+                        _ => unfolded(),
                     }
                 } else if lty.is_decimal() && rty.is_decimal() {
                     // Implement decimal operations here
@@ -172,11 +245,13 @@ impl ConstantFolding {
                         negative: lhs_neg,
                         value: lhs_val,
                         ty: _,
+                        radix: _,
                     } = &lhs;
                     let Atom {
                         negative: rhs_neg,
                         value: rhs_val,
                         ty: _,
+                        radix: _,
                     } = &rhs;
 
                     let AtomValue::Float(lhs_val) = lhs_val else {
@@ -242,13 +317,18 @@ impl ConstantFolding {
         }
     }
 
-    fn fold_expression(&self, expr: Expression) -> Expression {
+    /// Folds the constant parts of `expr`. Analyses use this to evaluate
+    /// expressions whose value they need to know, like array indices.
+    pub fn fold_expression(&self, expr: Expression) -> Expression {
         match expr {
+            Expression::If(cond) => self.fold_if(*cond),
+            Expression::Block(block) => Self::block_expression(self.fold_block(*block)),
             Expression::Atom(atom) => {
                 let Atom {
                     negative,
                     value,
                     ty,
+                    radix,
                 } = atom;
                 match value {
                     AtomValue::ParExpr(einner) => Expression::Atom(Atom::new(
@@ -256,10 +336,19 @@ impl ConstantFolding {
                         AtomValue::ParExpr(Box::new(self.fold_expression(*einner))),
                         ty,
                     )),
+                    AtomValue::Call(name, args) => Expression::Atom(Atom::new(
+                        negative,
+                        AtomValue::Call(
+                            name,
+                            args.into_iter().map(|a| self.fold_expression(a)).collect(),
+                        ),
+                        ty,
+                    )),
                     _ => Expression::Atom(Atom {
                         negative,
                         value,
                         ty,
+                        radix,
                     }),
                 }
             }
@@ -287,33 +376,129 @@ impl ConstantFolding {
                     (lhs, op, rhs) => Expression::BinaryOperation(Box::new(lhs), op, Box::new(rhs)),
                 }
             }
+            Expression::Unary(op, operand) => self.fold_unary(op, self.fold_expression(*operand)),
+            Expression::New(class, fields) => Expression::New(
+                class,
+                fields
+                    .into_iter()
+                    .map(|(name, value)| (name, self.fold_expression(value)))
+                    .collect(),
+            ),
+            Expression::FieldAccess(object, field) => {
+                Expression::FieldAccess(Box::new(self.fold_expression(*object)), field)
+            }
+            Expression::Tuple(elements) => Expression::Tuple(
+                elements
+                    .into_iter()
+                    .map(|e| self.fold_expression(e))
+                    .collect(),
+            ),
+            Expression::Array(elements) => Expression::Array(
+                elements
+                    .into_iter()
+                    .map(|e| self.fold_expression(e))
+                    .collect(),
+            ),
+            Expression::Repeat(value, len) => {
+                Expression::Repeat(Box::new(self.fold_expression(*value)), len)
+            }
+            Expression::Index(array, index) => Expression::Index(
+                Box::new(self.fold_expression(*array)),
+                Box::new(self.fold_expression(*index)),
+            ),
+            Expression::Some(value) => Expression::Some(Box::new(self.fold_expression(*value))),
+            Expression::IsSome(option) => {
+                Expression::IsSome(Box::new(self.fold_expression(*option)))
+            }
+            Expression::IsNone(option) => {
+                Expression::IsNone(Box::new(self.fold_expression(*option)))
+            }
+            Expression::Try(option) => Expression::Try(Box::new(self.fold_expression(*option))),
+            Expression::None => Expression::None,
+            Expression::Ok(value) => Expression::Ok(Box::new(self.fold_expression(*value))),
+            Expression::Err(error) => Expression::Err(Box::new(self.fold_expression(*error))),
+            Expression::MethodCall(object, method, args) => Expression::MethodCall(
+                Box::new(self.fold_expression(*object)),
+                method,
+                args.into_iter().map(|a| self.fold_expression(a)).collect(),
+            ),
+            Expression::Variant(name, variant, args) => Expression::Variant(
+                name,
+                variant,
+                args.into_iter().map(|a| self.fold_expression(a)).collect(),
+            ),
+            Expression::Match(scrutinee, arms) => Expression::Match(
+                Box::new(self.fold_expression(*scrutinee)),
+                arms.into_iter()
+                    .map(|arm| MatchArm {
+                        body: self.fold_body(arm.body),
+                        ..arm
+                    })
+                    .collect(),
+            ),
+            Expression::Lambda(lambda) => Expression::Lambda(Box::new(Lambda {
+                body: self.fold_body(lambda.body),
+                ..*lambda
+            })),
+        }
+    }
+
+    fn fold_body(&self, body: ArmBody) -> ArmBody {
+        match body {
+            ArmBody::Expression(e) => ArmBody::Expression(self.fold_expression(e)),
+            ArmBody::Block(b) => ArmBody::Block(self.fold_block(b)),
         }
     }
 
-    fn fold_conditional(&self, cond: Conditional) -> Statement {
+    fn fold_conditional(&self, cond: Conditional) -> Conditional {
         let Conditional {
+            pattern,
             condition,
             then_block,
             else_block,
         } = cond;
-        let processed_condition = self.fold_expression(condition);
-        let processed_then_block: Vec<Statement> = then_block
-            .statements
-            .into_iter()
-            .map(|s| self.fold_statement(s))
-            .collect();
-        let processed_else_block: Option<Vec<Statement>> = else_block.map(|block| {
-            block
-                .statements
-                .into_iter()
-                .map(|s| self.fold_statement(s))
-                .collect()
-        });
-        Statement::Conditional(Conditional::new(
-            processed_condition,
-            Block::new(processed_then_block),
-            processed_else_block.map(Block::new),
-        ))
+        Conditional::new(
+            self.fold_expression(condition),
+            self.fold_block(then_block),
+            else_block.map(|block| self.fold_block(block)),
+        )
+        .with_pattern(pattern)
+    }
+
+    /// An `if` on a constant condition becomes the branch taken.
+    fn fold_if(&self, cond: Conditional) -> Expression {
+        let cond = self.fold_conditional(cond);
+        let taken = match (&cond.pattern, &cond.condition) {
+            (
+                None,
+                Expression::Atom(Atom {
+                    value: AtomValue::Boolean(b),
+                    ..
+                }),
+            ) => {
+                if *b {
+                    Some(&cond.then_block)
+                } else {
+                    cond.else_block.as_ref()
+                }
+            }
+            _ => None,
+        };
+        match taken {
+            Some(block) if block.value.is_some() => Self::block_expression(block.clone()),
+            _ => Expression::If(Box::new(cond)),
+        }
+    }
+
+    /// A block with no statements is just its value.
+    fn block_expression(block: Block) -> Expression {
+        match block {
+            Block {
+                statements,
+                value: Some(value),
+            } if statements.is_empty() => *value,
+            block => Expression::Block(Box::new(block)),
+        }
     }
 }
 
@@ -326,3 +511,54 @@ impl ASTPass for ConstantFolding {
         Program::new(processed_statements)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::VoeParser;
+
+    fn fold(source: &str) -> String {
+        let program = VoeParser.parse_program(source).unwrap();
+        ConstantFolding.run(program).to_string()
+    }
+
+    #[test]
+    fn test_fold_unary_and_bitwise() {
+        assert_eq!(fold("let a = -(2i32 - 5i32);"), "let a: i32 = 3i32;");
+        assert_eq!(fold("let b = !true;"), "let b: bool = false;");
+        assert_eq!(fold("let c = ~0u8;"), "let c: u8 = 255u8;");
+        assert_eq!(fold("let d = ~5i32;"), "let d: i32 = -6i32;");
+        assert_eq!(fold("let e = 1u8 << 9u8;"), "let e: u8 = 1u8 << 9u8;");
+        assert_eq!(fold("let f = 0xF0u8 ~ 0xFFu8 | 1u8;"), "let f: u8 = 15u8;");
+        assert_eq!(fold("let g = 1i32 << 4i32 >> 2i32;"), "let g: i32 = 4i32;");
+        assert_eq!(fold("let h = -x;"), "let h = -x;");
+    }
+
+    #[test]
+    fn test_fold_keeps_annotations() {
+        assert_eq!(fold("let m: Meters = 1.0f64;"), "let m: Meters = 1.0f64;");
+        assert_eq!(fold("let big: i64 = 1i32;"), "let big: i64 = 1i32;");
+        assert_eq!(fold("let n = 1i32 + 2i32;"), "let n: i32 = 3i32;");
+    }
+
+    #[test]
+    fn test_fold_leaves_overflow() {
+        assert_eq!(fold("let a = 254u8 + 1u8;"), "let a: u8 = 255u8;");
+        assert_eq!(fold("let b = 255u8 + 1u8;"), "let b: u8 = 255u8 + 1u8;");
+        assert_eq!(fold("let c = -128i8 / -1i8;"), "let c: i8 = -128i8 / -1i8;");
+        assert_eq!(fold("let d = 0u32 - 1u32;"), "let d: u32 = 0u32 - 1u32;");
+    }
+
+    #[test]
+    fn test_fold_if() {
+        let source = "let a = if true { 1i32 } else { 2i32 };";
+        assert_eq!(fold(source), "let a: i32 = 1i32;");
+        let source = "let b = if !true { 1i32 } else if x { 2i32 } else { 3i32 };";
+        assert_eq!(
+            fold(source),
+            "let b = if x {\n    2i32\n} else {\n    3i32\n};"
+        );
+        let source = "let c = if true { f(); 1i32 } else { 2i32 };";
+        assert_eq!(fold(source), "let c: i32 = {\n    f();\n    1i32\n};");
+    }
+}
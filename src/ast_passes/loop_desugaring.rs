@@ -0,0 +1,676 @@
+//! Rewrites `for` loops into `while` loops over an index variable, so that
+//! later passes and back ends only handle one kind of loop.
+
+use std::collections::HashSet;
+
+use crate::parser::{
+    atom::{Atom, AtomValue},
+    lexer::{Lexer, TokenKind},
+    ArmBody, Assignment, Block, Conditional, Expression, ForLoop, FunctionDefinition, ImplBlock,
+    Iterable, MatchArm, Operator, Pattern, Program, Range, Statement, Type, VariableDeclaration,
+    WhileLoop,
+};
+
+use super::{ASTPass, ConstantFolding};
+
+#[derive(Debug, Clone)]
+pub struct LoopDesugaring {
+    /// Ranges with constant bounds and at most this many values are
+    /// unrolled instead of looped over.
+    unroll_limit: usize,
+    /// Number of loops rewritten so far, used to name their variables.
+    loops: usize,
+    /// Every name in the program, which the variables of rewritten loops
+    /// must not capture.
+    names: HashSet<String>,
+}
+
+/// The variables a rewritten loop may declare, as in `for_index_0`.
+const LOOP_VARIABLES: &[&str] = &["index", "end", "step", "more", "array"];
+
+impl Default for LoopDesugaring {
+    fn default() -> Self {
+        LoopDesugaring {
+            unroll_limit: 8,
+            loops: 0,
+            names: HashSet::new(),
+        }
+    }
+}
+
+fn variable(name: &str) -> Expression {
+    Expression::Atom(Atom::new(
+        false,
+        AtomValue::Identity(name.to_string()),
+        None,
+    ))
+}
+
+fn declare(pattern: Pattern, value: Expression) -> Statement {
+    Statement::VariableDeclaration(VariableDeclaration::destructuring(
+        pattern,
+        None,
+        Some(value),
+    ))
+}
+
+fn loop_variable(kind: &str, id: usize) -> String {
+    format!("for_{}_{}", kind, id)
+}
+
+/// The names in `program`, read back from its source text so that none is
+/// missed.
+fn names(program: &Program) -> HashSet<String> {
+    let source = program.to_string();
+    let (tokens, _) = Lexer::new(&source).tokenize();
+    tokens
+        .iter()
+        .filter(|t| t.kind == TokenKind::Ident)
+        .map(|t| {
+            let name = &source[t.span.start..t.span.end];
+            name.strip_prefix("r#").unwrap_or(name).to_string()
+        })
+        .collect()
+}
+
+/// The value and type of an integer literal.
+fn constant(expr: &Expression) -> Option<(i128, Option<Type>)> {
+    match expr {
+        Expression::Atom(atom) => Some((atom.signed_value()?, atom.ty.clone())),
+        _ => None,
+    }
+}
+
+impl LoopDesugaring {
+    pub fn new() -> LoopDesugaring {
+        LoopDesugaring::default()
+    }
+
+    pub fn with_unroll_limit(self, unroll_limit: usize) -> LoopDesugaring {
+        LoopDesugaring {
+            unroll_limit,
+            ..self
+        }
+    }
+
+    fn desugar_statements(&mut self, statements: Vec<Statement>) -> Vec<Statement> {
+        statements
+            .into_iter()
+            .flat_map(|s| self.desugar_statement(s))
+            .collect()
+    }
+
+    fn desugar_block(&mut self, block: Block) -> Block {
+        Block::new(self.desugar_statements(block.statements))
+            .with_value(block.value.map(|v| self.desugar_expression(*v)))
+    }
+
+    fn desugar_conditional(&mut self, cond: Conditional) -> Conditional {
+        Conditional {
+            condition: self.desugar_expression(cond.condition),
+            then_block: self.desugar_block(cond.then_block),
+            else_block: cond.else_block.map(|b| self.desugar_block(b)),
+            ..cond
+        }
+    }
+
+    fn desugar_function(&mut self, fd: FunctionDefinition) -> FunctionDefinition {
+        FunctionDefinition {
+            body: self.desugar_block(fd.body),
+            ..fd
+        }
+    }
+
+    fn desugar_statement(&mut self, statement: Statement) -> Vec<Statement> {
+        let statement = match statement {
+            Statement::For(l) => return self.desugar_for(*l),
+            Statement::Function(fd) => Statement::Function(self.desugar_function(fd)),
+            Statement::VariableDeclaration(vd) => {
+                Statement::VariableDeclaration(VariableDeclaration {
+                    value: vd.value.map(|e| self.desugar_expression(e)),
+                    ..vd
+                })
+            }
+            Statement::Expression(expr) => Statement::Expression(self.desugar_expression(expr)),
+            Statement::Assignment(assignment) => Statement::Assignment(Assignment::new(
+                self.desugar_expression(assignment.target),
+                self.desugar_expression(assignment.value),
+            )),
+            Statement::Conditional(cond) => Statement::Conditional(self.desugar_conditional(cond)),
+            Statement::Block(block) => Statement::Block(self.desugar_block(block)),
+            Statement::While(w) => Statement::While(WhileLoop::new(
+                self.desugar_expression(w.condition),
+                self.desugar_block(w.body),
+            )),
+            Statement::Impl(ib) => Statement::Impl(ImplBlock {
+                methods: ib
+                    .methods
+                    .into_iter()
+                    .map(|m| self.desugar_function(m))
+                    .collect(),
+                ..ib
+            }),
+            Statement::Export(s) => {
+                return self
+                    .desugar_statement(*s)
+                    .into_iter()
+                    .map(Statement::export)
+                    .collect()
+            }
+            s => s,
+        };
+        vec![statement]
+    }
+
+    /// Desugars the loops in the blocks of lambdas and `match` arms.
+    fn desugar_expression(&mut self, expr: Expression) -> Expression {
+        match expr {
+            Expression::BinaryOperation(lhs, op, rhs) => {
+                let lhs = Box::new(self.desugar_expression(*lhs));
+                Expression::BinaryOperation(lhs, op, Box::new(self.desugar_expression(*rhs)))
+            }
+            Expression::Unary(op, operand) => {
+                Expression::Unary(op, Box::new(self.desugar_expression(*operand)))
+            }
+            Expression::FieldAccess(object, field) => {
+                Expression::FieldAccess(Box::new(self.desugar_expression(*object)), field)
+            }
+            Expression::Repeat(value, len) => {
+                Expression::Repeat(Box::new(self.desugar_expression(*value)), len)
+            }
+            Expression::Index(array, index) => {
+                let array = Box::new(self.desugar_expression(*array));
+                Expression::Index(array, Box::new(self.desugar_expression(*index)))
+            }
+            Expression::Some(value) => Expression::Some(Box::new(self.desugar_expression(*value))),
+            Expression::Ok(value) => Expression::Ok(Box::new(self.desugar_expression(*value))),
+            Expression::Err(error) => Expression::Err(Box::new(self.desugar_expression(*error))),
+            Expression::IsSome(option) => {
+                Expression::IsSome(Box::new(self.desugar_expression(*option)))
+            }
+            Expression::IsNone(option) => {
+                Expression::IsNone(Box::new(self.desugar_expression(*option)))
+            }
+            Expression::Try(value) => Expression::Try(Box::new(self.desugar_expression(*value))),
+            Expression::Atom(Atom {
+                negative,
+                value: AtomValue::ParExpr(inner),
+                ty,
+                ..
+            }) => Expression::Atom(Atom::new(
+                negative,
+                AtomValue::ParExpr(Box::new(self.desugar_expression(*inner))),
+                ty,
+            )),
+            Expression::Atom(Atom {
+                negative,
+                value: AtomValue::Call(name, args),
+                ty,
+                ..
+            }) => Expression::Atom(Atom::new(
+                negative,
+                AtomValue::Call(name, self.desugar_expressions(args)),
+                ty,
+            )),
+            Expression::New(class, fields) => Expression::New(
+                class,
+                fields
+                    .into_iter()
+                    .map(|(name, value)| (name, self.desugar_expression(value)))
+                    .collect(),
+            ),
+            Expression::MethodCall(object, method, args) => {
+                let object = Box::new(self.desugar_expression(*object));
+                Expression::MethodCall(object, method, self.desugar_expressions(args))
+            }
+            Expression::Variant(name, variant, args) => {
+                Expression::Variant(name, variant, self.desugar_expressions(args))
+            }
+            Expression::Tuple(elements) => Expression::Tuple(self.desugar_expressions(elements)),
+            Expression::Array(elements) => Expression::Array(self.desugar_expressions(elements)),
+            Expression::Match(scrutinee, arms) => {
+                let scrutinee = Box::new(self.desugar_expression(*scrutinee));
+                let arms = arms
+                    .into_iter()
+                    .map(|arm| MatchArm::new(arm.pattern, self.desugar_body(arm.body)))
+                    .collect();
+                Expression::Match(scrutinee, arms)
+            }
+            Expression::Lambda(mut lambda) => {
+                lambda.body = self.desugar_body(lambda.body);
+                Expression::Lambda(lambda)
+            }
+            Expression::If(cond) => Expression::If(Box::new(self.desugar_conditional(*cond))),
+            Expression::Block(block) => Expression::Block(Box::new(self.desugar_block(*block))),
+            expr => expr,
+        }
+    }
+
+    fn desugar_expressions(&mut self, exprs: Vec<Expression>) -> Vec<Expression> {
+        exprs
+            .into_iter()
+            .map(|e| self.desugar_expression(e))
+            .collect()
+    }
+
+    fn desugar_body(&mut self, body: ArmBody) -> ArmBody {
+        match body {
+            ArmBody::Expression(expr) => ArmBody::Expression(self.desugar_expression(expr)),
+            ArmBody::Block(block) => ArmBody::Block(self.desugar_block(block)),
+        }
+    }
+
+    /// A number for the next loop whose variables are named nowhere in the
+    /// program.
+    fn fresh_id(&mut self) -> usize {
+        while LOOP_VARIABLES
+            .iter()
+            .any(|kind| self.names.contains(&loop_variable(kind, self.loops)))
+        {
+            self.loops += 1;
+        }
+        self.loops += 1;
+        self.loops - 1
+    }
+
+    fn desugar_for(&mut self, l: ForLoop) -> Vec<Statement> {
+        let id = self.fresh_id();
+        let body = self.desugar_block(l.body);
+        match l.iterable {
+            Iterable::Range(range) => {
+                let range = Range {
+                    start: self.desugar_expression(range.start),
+                    end: self.desugar_expression(range.end),
+                    step: range.step.map(|s| self.desugar_expression(s)),
+                    ..*range
+                };
+                match self.unroll(&l.pattern, &range, &body) {
+                    Some(iterations) => iterations,
+                    None => Self::range_loop(id, l.pattern, range, body),
+                }
+            }
+            Iterable::Expression(array) => {
+                let array = self.desugar_expression(array);
+                Self::array_loop(id, l.pattern, array, body)
+            }
+        }
+    }
+
+    /// Copies of the body, one per value of a constant range, or `None` if
+    /// the range is not constant or has too many values. Each copy is a
+    /// block of its own, so its variables stay local to it.
+    fn unroll(&self, pattern: &Pattern, range: &Range, body: &Block) -> Option<Vec<Statement>> {
+        let folding = ConstantFolding;
+        let (start, start_ty) = constant(&folding.fold_expression(range.start.clone()))?;
+        let (end, end_ty) = constant(&folding.fold_expression(range.end.clone()))?;
+        let (step, _) = match &range.step {
+            Some(step) => constant(&folding.fold_expression(step.clone()))?,
+            None => (1, None),
+        };
+        if step == 0 {
+            return None;
+        }
+        let ty = start_ty.or(end_ty);
+        let mut values = vec![];
+        let mut value = start;
+        while (step > 0 && value < end || step < 0 && value > end)
+            || (range.inclusive && value == end)
+        {
+            if values.len() == self.unroll_limit {
+                return None;
+            }
+            values.push(value);
+            // Past the largest `i128`, so past any end.
+            let Some(next) = value.checked_add(step) else {
+                break;
+            };
+            value = next;
+        }
+        let iterations = values
+            .into_iter()
+            .map(|value| {
+                let mut statements = vec![declare(
+                    pattern.clone(),
+                    Expression::Atom(Atom::from_i128(value, ty.clone())),
+                )];
+                statements.extend(body.statements.iter().cloned());
+                Statement::Block(Block::new(statements))
+            })
+            .collect();
+        Some(iterations)
+    }
+
+    /// ```text
+    /// let for_index_0 = start;
+    /// let for_end_0 = end;
+    /// while for_index_0 < for_end_0 {
+    ///     let pattern = for_index_0;
+    ///     ...
+    ///     for_index_0 = for_index_0 + 1;
+    /// }
+    /// ```
+    ///
+    /// Other ranges may end near the largest or smallest value of their
+    /// type, so they test for the last value before stepping past it:
+    ///
+    /// ```text
+    /// let for_more_0 = for_index_0 <= for_end_0;
+    /// while for_more_0 {
+    ///     let pattern = for_index_0;
+    ///     ...
+    ///     for_more_0 = for_end_0 - for_index_0 >= step;
+    ///     if for_more_0 {
+    ///         for_index_0 = for_index_0 + step;
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// Ranges count down with a negative step, which is tested for when
+    /// running if the step is not constant.
+    fn range_loop(id: usize, pattern: Pattern, range: Range, body: Block) -> Vec<Statement> {
+        let index = loop_variable("index", id);
+        let end = loop_variable("end", id);
+        // The type of the range, if either bound is a typed literal.
+        let ty = [&range.start, &range.end]
+            .into_iter()
+            .find_map(|bound| constant(bound).and_then(|(_, ty)| ty));
+        let mut statements = vec![
+            declare(Pattern::Binding(index.clone()), range.start),
+            declare(Pattern::Binding(end.clone()), range.end),
+        ];
+        let folded_step = range
+            .step
+            .clone()
+            .map(|s| ConstantFolding.fold_expression(s));
+        let step = match (range.step, folded_step.as_ref().and_then(constant)) {
+            (None, _) => Expression::Atom(Atom::from_i128(1, ty.clone())),
+            (Some(_), Some(_)) => folded_step.unwrap(),
+            (Some(step), None) => {
+                let name = loop_variable("step", id);
+                statements.push(declare(Pattern::Binding(name.clone()), step));
+                variable(&name)
+            }
+        };
+        let inclusive = range.inclusive;
+        let next = Assignment::new(
+            variable(&index),
+            Expression::binary(variable(&index), Operator::Add, step.clone()),
+        );
+        let constant_step = constant(&step).map(|(step, _)| step);
+        if !inclusive && constant_step == Some(1) {
+            statements.push(Self::while_loop(
+                Expression::binary(variable(&index), Operator::LessThan, variable(&end)),
+                declare(pattern, variable(&index)),
+                body,
+                vec![Statement::Assignment(next)],
+            ));
+            return statements;
+        }
+
+        // Whether there is a first value, and whether there is one after
+        // the current value, counting up or down.
+        let first = |descending: bool| {
+            let op = match (descending, inclusive) {
+                (false, false) => Operator::LessThan,
+                (false, true) => Operator::LessThanOrEqual,
+                (true, false) => Operator::GreaterThan,
+                (true, true) => Operator::GreaterThanOrEqual,
+            };
+            Expression::binary(variable(&index), op, variable(&end))
+        };
+        let has_next = |descending: bool| {
+            if inclusive && constant_step.is_some_and(|step| step.abs() == 1) {
+                let op = match descending {
+                    false => Operator::LessThan,
+                    true => Operator::GreaterThan,
+                };
+                return Expression::binary(variable(&index), op, variable(&end));
+            }
+            // The distance left cannot overflow the way the next value can.
+            let op = match (descending, inclusive) {
+                (false, false) => Operator::GreaterThan,
+                (false, true) => Operator::GreaterThanOrEqual,
+                (true, false) => Operator::LessThan,
+                (true, true) => Operator::LessThanOrEqual,
+            };
+            let left = Expression::binary(variable(&end), Operator::Subtract, variable(&index));
+            Expression::binary(left, op, step.clone())
+        };
+
+        let more = loop_variable("more", id);
+        let set_more =
+            |value: Expression| Statement::Assignment(Assignment::new(variable(&more), value));
+        // The direction of a variable step is only known when running.
+        let next_test = match constant_step {
+            Some(step) => {
+                statements.push(declare(Pattern::Binding(more.clone()), first(step < 0)));
+                set_more(has_next(step < 0))
+            }
+            None => {
+                let negative = Expression::binary(
+                    step.clone(),
+                    Operator::LessThan,
+                    Expression::Atom(Atom::from_i128(0, ty.clone())),
+                );
+                statements.push(declare(Pattern::Binding(more.clone()), first(false)));
+                statements.push(Statement::Conditional(Conditional::new(
+                    negative.clone(),
+                    Block::new(vec![set_more(first(true))]),
+                    None,
+                )));
+                Statement::Conditional(Conditional::new(
+                    negative,
+                    Block::new(vec![set_more(has_next(true))]),
+                    Some(Block::new(vec![set_more(has_next(false))])),
+                ))
+            }
+        };
+        statements.push(Self::while_loop(
+            variable(&more),
+            declare(pattern, variable(&index)),
+            body,
+            vec![
+                next_test,
+                Statement::Conditional(Conditional::new(
+                    variable(&more),
+                    Block::new(vec![Statement::Assignment(next)]),
+                    None,
+                )),
+            ],
+        ));
+        statements
+    }
+
+    /// ```text
+    /// let for_array_0 = array;
+    /// let for_index_0 = 0u64;
+    /// while for_index_0 < len(for_array_0) {
+    ///     let pattern = for_array_0[for_index_0];
+    ///     ...
+    ///     for_index_0 = for_index_0 + 1u64;
+    /// }
+    /// ```
+    fn array_loop(id: usize, pattern: Pattern, array: Expression, body: Block) -> Vec<Statement> {
+        let index = loop_variable("index", id);
+        let name = loop_variable("array", id);
+        let u64_constant = |i| Expression::Atom(Atom::from_i128(i, Some(Type::U64)));
+        let len = Expression::Atom(Atom::new(
+            false,
+            AtomValue::Call("len".to_string(), vec![variable(&name)]),
+            None,
+        ));
+        let element = Expression::Index(Box::new(variable(&name)), Box::new(variable(&index)));
+        let next = Expression::binary(variable(&index), Operator::Add, u64_constant(1));
+        vec![
+            declare(Pattern::Binding(name.clone()), array),
+            declare(Pattern::Binding(index.clone()), u64_constant(0)),
+            Self::while_loop(
+                Expression::binary(variable(&index), Operator::LessThan, len),
+                declare(pattern, element),
+                body,
+                vec![Statement::Assignment(Assignment::new(
+                    variable(&index),
+                    next,
+                ))],
+            ),
+        ]
+    }
+
+    fn while_loop(
+        condition: Expression,
+        binding: Statement,
+        body: Block,
+        increment: Vec<Statement>,
+    ) -> Statement {
+        let mut statements = vec![binding];
+        statements.extend(body.statements);
+        statements.extend(increment);
+        Statement::While(WhileLoop::new(condition, Block::new(statements)))
+    }
+}
+
+impl ASTPass for LoopDesugaring {
+    fn run(&mut self, program: Program) -> Program {
+        self.names = names(&program);
+        Program::new(self.desugar_statements(program.statements))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::VoeParser;
+
+    fn desugar(source: &str) -> String {
+        let program = VoeParser.parse_program(source).expect("parse failed");
+        LoopDesugaring::new()
+            .with_unroll_limit(2)
+            .run(program)
+            .to_string()
+    }
+
+    #[test]
+    fn test_desugar_loops() {
+        assert_eq!(
+            desugar("for i in 0..n {\n    f(i);\n}"),
+            "let for_index_0 = 0;\nlet for_end_0 = n;\n\
+             while for_index_0 < for_end_0 {\n    let i = for_index_0;\n    f(i);\n    \
+             for_index_0 = for_index_0 + 1;\n}"
+        );
+        assert_eq!(
+            desugar("for i in n..=0 step -k {\n    f(i);\n}")
+                .lines()
+                .map(str::trim)
+                .collect::<Vec<_>>(),
+            [
+                "let for_index_0 = n;",
+                "let for_end_0 = 0;",
+                "let for_step_0 = -k;",
+                "let for_more_0 = for_index_0 <= for_end_0;",
+                "if for_step_0 < 0 {",
+                "for_more_0 = for_index_0 >= for_end_0;",
+                "}",
+                "while for_more_0 {",
+                "let i = for_index_0;",
+                "f(i);",
+                "if for_step_0 < 0 {",
+                "for_more_0 = for_end_0 - for_index_0 <= for_step_0;",
+                "} else {",
+                "for_more_0 = for_end_0 - for_index_0 >= for_step_0;",
+                "}",
+                "if for_more_0 {",
+                "for_index_0 = for_index_0 + for_step_0;",
+                "}",
+                "}",
+            ]
+        );
+        assert_eq!(
+            desugar("fn g(xs: [i32]) -> () {\n    for (a, _) in xs {\n        f(a);\n    }\n}")
+                .lines()
+                .map(str::trim)
+                .collect::<Vec<_>>(),
+            [
+                "fn g(xs: [i32]) -> () {",
+                "let for_array_0 = xs;",
+                "let for_index_0 = 0u64;",
+                "while for_index_0 < len(for_array_0) {",
+                "let (a, _) = for_array_0[for_index_0];",
+                "f(a);",
+                "for_index_0 = for_index_0 + 1u64;",
+                "}",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inclusive_ranges_stop_before_overflowing() {
+        let lines = desugar("for i in 0u8..=255u8 {\n    f(i);\n}");
+        let lines: Vec<&str> = lines.lines().map(str::trim).collect();
+        assert_eq!(
+            lines[3..],
+            [
+                "while for_more_0 {",
+                "let i = for_index_0;",
+                "f(i);",
+                "for_more_0 = for_index_0 < for_end_0;",
+                "if for_more_0 {",
+                "for_index_0 = for_index_0 + 1u8;",
+                "}",
+                "}",
+            ]
+        );
+        assert!(desugar("for i in 9i8..=-120i8 step -3i8 {\n    f(i);\n}")
+            .contains("for_more_0 = for_end_0 - for_index_0 <= -3i8;"));
+    }
+
+    #[test]
+    fn test_stepped_ranges_stop_before_overflowing() {
+        let lines = desugar("for i in 0u8..n step 100u8 {\n    f(i);\n}");
+        let lines: Vec<&str> = lines.lines().map(str::trim).collect();
+        assert_eq!(
+            lines[2..],
+            [
+                "let for_more_0 = for_index_0 < for_end_0;",
+                "while for_more_0 {",
+                "let i = for_index_0;",
+                "f(i);",
+                "for_more_0 = for_end_0 - for_index_0 > 100u8;",
+                "if for_more_0 {",
+                "for_index_0 = for_index_0 + 100u8;",
+                "}",
+                "}",
+            ]
+        );
+        assert!(desugar("for i in n..0i8 step -1i8 {\n    f(i);\n}")
+            .contains("for_more_0 = for_end_0 - for_index_0 < -1i8;"));
+    }
+
+    #[test]
+    fn test_loop_variables_do_not_capture_names() {
+        let desugared = desugar(
+            "let for_index_0 = 1;\nlet for_end_1 = 2;\n\
+             for i in 0..n {\n    f(i, for_index_0);\n}\nfor x in xs {\n    f(x);\n}",
+        );
+        assert!(desugared.contains("while for_index_2 < for_end_2 {"));
+        assert!(desugared.contains("f(i, for_index_0);"));
+        assert!(desugared.contains("while for_index_3 < len(for_array_3) {"));
+    }
+
+    #[test]
+    fn test_unroll_constant_ranges() {
+        assert_eq!(
+            desugar("for i in 4i32..=2i32 step -2i32 {\n    f(i);\n}"),
+            "{\n    let i = 4i32;\n    f(i);\n}\n{\n    let i = 2i32;\n    f(i);\n}"
+        );
+        assert_eq!(desugar("for i in 0..0 {\n    f(i);\n}"), "");
+        // Stepping past the largest `i128` ends the range.
+        let max = i128::MAX;
+        assert_eq!(
+            desugar(&format!("for i in {max}i128..={max}i128 {{\n    f(i);\n}}")),
+            format!("{{\n    let i = {max}i128;\n    f(i);\n}}")
+        );
+        // Three values are over the limit.
+        assert!(desugar("for i in 0..3 {\n    f(i);\n}").starts_with("let for_index_0 = 0;"));
+    }
+}
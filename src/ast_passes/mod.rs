@@ -2,6 +2,8 @@ use crate::parser::Program;
 
 mod constant_folding;
 pub use constant_folding::ConstantFolding;
+mod loop_desugaring;
+pub use loop_desugaring::LoopDesugaring;
 
 pub trait ASTPass {
     fn run(&mut self, program: Program) -> Program;
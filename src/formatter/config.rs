@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The name of the file `voe fmt` reads its settings from.
+pub const CONFIG_FILE: &str = "voefmt.toml";
+
+/// Settings for the formatter, read from a `voefmt.toml` like
+///
+/// ```toml
+/// # Spaces per level of indentation.
+/// indent_width = 2
+/// max_width = 80
+/// ```
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct FormatConfig {
+    pub indent_width: usize,
+    /// The longest a line may be before it is broken.
+    pub max_width: usize,
+}
+
+impl Default for FormatConfig {
+    fn default() -> FormatConfig {
+        FormatConfig {
+            indent_width: 4,
+            max_width: 100,
+        }
+    }
+}
+
+impl FormatConfig {
+    /// Parses the contents of a config file. Only `key = integer` lines,
+    /// comments and blank lines are allowed, and every key is optional.
+    pub fn parse(text: &str) -> Result<FormatConfig, String> {
+        let mut config = FormatConfig::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected `key = value`", i + 1))?;
+            let (key, value) = (key.trim(), value.trim());
+            let value: usize = value
+                .parse()
+                .map_err(|_| format!("line {}: `{}` is not a number", i + 1, value))?;
+            match key {
+                "indent_width" => config.indent_width = value,
+                "max_width" => config.max_width = value,
+                _ => return Err(format!("line {}: unknown setting `{}`", i + 1, key)),
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> Result<FormatConfig, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read `{}`: {}", path.display(), err))?;
+        FormatConfig::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    /// The config file for a source file in `dir`: the first `voefmt.toml`
+    /// in it or one of its ancestors.
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .map(|dir| dir.join(CONFIG_FILE))
+            .find(|path| path.is_file())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = FormatConfig::parse("# Narrow.\nmax_width = 60\n\nindent_width=2 # two\n");
+        assert_eq!(
+            config,
+            Ok(FormatConfig {
+                indent_width: 2,
+                max_width: 60,
+            })
+        );
+        assert_eq!(FormatConfig::parse(""), Ok(FormatConfig::default()));
+        assert_eq!(
+            FormatConfig::parse("tabs = 1"),
+            Err("line 1: unknown setting `tabs`".to_string())
+        );
+        assert_eq!(
+            FormatConfig::parse("max_width = wide"),
+            Err("line 1: `wide` is not a number".to_string())
+        );
+    }
+}
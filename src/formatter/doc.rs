@@ -0,0 +1,204 @@
+//! Documents that lay themselves out to fit a line width, after Wadler's
+//! "A prettier printer". A `Group` is printed flat when it fits on the rest
+//! of the line, and otherwise with each of its own `Line`s broken.
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Doc {
+    Text(String),
+    /// A space, or a newline if the enclosing group is broken.
+    Line,
+    /// Nothing, or a newline if the enclosing group is broken.
+    SoftLine,
+    /// A newline, which breaks every group around it.
+    HardLine,
+    /// A newline unless at the start of a line already.
+    FreshLine,
+    Indent(Box<Doc>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+    /// Text only printed if the enclosing group is broken.
+    IfBreak(String),
+    /// Text held back until the end of the line, like a trailing comment.
+    /// It breaks every group around it.
+    LineSuffix(String),
+}
+
+impl Doc {
+    pub fn text(text: impl Into<String>) -> Doc {
+        Doc::Text(text.into())
+    }
+
+    pub fn indent(doc: Doc) -> Doc {
+        Doc::Indent(Box::new(doc))
+    }
+
+    pub fn group(doc: Doc) -> Doc {
+        Doc::Group(Box::new(doc))
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Prints `doc` in lines of at most `width` columns where it can, indenting
+/// by `indent_width` spaces per level.
+pub fn print(doc: &Doc, width: usize, indent_width: usize) -> String {
+    let mut out = String::new();
+    // Column of the next character, and whether the current line still
+    // needs its indentation.
+    let mut column = 0;
+    let mut line_start = true;
+    let mut suffix = String::new();
+    let mut stack = vec![(0, Mode::Break, doc)];
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) | Doc::IfBreak(text) => {
+                if matches!(doc, Doc::IfBreak(_)) && mode == Mode::Flat || text.is_empty() {
+                    continue;
+                }
+                if line_start {
+                    out.push_str(&" ".repeat(indent));
+                    column = indent;
+                    line_start = false;
+                }
+                out.push_str(text);
+                column += text.chars().count();
+            }
+            Doc::Line | Doc::SoftLine if mode == Mode::Flat => {
+                if *doc == Doc::Line {
+                    out.push(' ');
+                    column += 1;
+                }
+            }
+            Doc::FreshLine if line_start => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine | Doc::FreshLine => {
+                out.push_str(&suffix);
+                suffix.clear();
+                while out.ends_with(' ') {
+                    out.pop();
+                }
+                out.push('\n');
+                column = 0;
+                line_start = true;
+            }
+            Doc::Indent(inner) => stack.push((indent + indent_width, mode, inner)),
+            Doc::Group(inner) => {
+                let fits = mode == Mode::Flat
+                    || fits(
+                        width.saturating_sub(column),
+                        &[(indent, Mode::Flat, inner)],
+                        &stack,
+                    );
+                let mode = if fits { Mode::Flat } else { Mode::Break };
+                stack.push((indent, mode, inner));
+            }
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+            Doc::LineSuffix(text) => suffix.push_str(text),
+        }
+    }
+    out.push_str(&suffix);
+    out
+}
+
+/// Whether `docs`, printed flat, and what follows them up to the next
+/// newline fit in `width` columns.
+fn fits<'a>(
+    mut width: usize,
+    docs: &[(usize, Mode, &'a Doc)],
+    rest: &[(usize, Mode, &'a Doc)],
+) -> bool {
+    let mut stack: Vec<_> = docs
+        .iter()
+        .map(|&(_, mode, doc)| (mode, doc, true))
+        .collect();
+    let mut rest = rest.iter().rev();
+    loop {
+        let (mode, doc, measured) = match stack.pop() {
+            Some(item) => item,
+            None => match rest.next() {
+                Some(&(_, mode, doc)) => (mode, doc, false),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(text) => match width.checked_sub(text.chars().count()) {
+                Some(left) => width = left,
+                None => return false,
+            },
+            Doc::IfBreak(text) if mode == Mode::Break => match width.checked_sub(text.len()) {
+                Some(left) => width = left,
+                None => return false,
+            },
+            Doc::IfBreak(_) => {}
+            Doc::Line if mode == Mode::Flat => match width.checked_sub(1) {
+                Some(left) => width = left,
+                None => return false,
+            },
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine => return true,
+            Doc::HardLine | Doc::FreshLine | Doc::LineSuffix(_) => return !measured,
+            Doc::Indent(inner) => stack.push((mode, inner, measured)),
+            // Groups after the one being measured may still break.
+            Doc::Group(inner) => {
+                stack.push((if measured { Mode::Flat } else { mode }, inner, measured))
+            }
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (mode, doc, measured))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(args: &[&str]) -> Doc {
+        let mut items = vec![];
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                items.extend([Doc::text(","), Doc::Line]);
+            }
+            items.push(Doc::text(*arg));
+        }
+        Doc::Concat(vec![
+            Doc::text("f("),
+            Doc::group(Doc::Concat(vec![
+                Doc::indent(Doc::Concat(vec![
+                    Doc::SoftLine,
+                    Doc::Concat(items),
+                    Doc::IfBreak(",".to_string()),
+                ])),
+                Doc::SoftLine,
+            ])),
+            Doc::text(");"),
+        ])
+    }
+
+    #[test]
+    fn test_groups_break_to_fit() {
+        let doc = call(&["aaaa", "bbbb"]);
+        assert_eq!(print(&doc, 20, 4), "f(aaaa, bbbb);");
+        assert_eq!(print(&doc, 10, 4), "f(\n    aaaa,\n    bbbb,\n);");
+        // The `);` after the group counts towards its width.
+        assert_eq!(print(&doc, 14, 2), "f(aaaa, bbbb);");
+        assert_eq!(print(&doc, 13, 2), "f(\n  aaaa,\n  bbbb,\n);");
+    }
+
+    #[test]
+    fn test_line_suffix_breaks_group() {
+        let doc = Doc::Concat(vec![
+            Doc::group(Doc::Concat(vec![
+                Doc::text("a"),
+                Doc::LineSuffix(" // note".to_string()),
+                Doc::Line,
+                Doc::text("b"),
+            ])),
+            Doc::HardLine,
+            Doc::text("c"),
+            Doc::LineSuffix(" // end".to_string()),
+        ]);
+        assert_eq!(print(&doc, 80, 4), "a // note\nb\nc // end");
+    }
+}
@@ -0,0 +1,872 @@
+//! The formatter behind `voe fmt`. It prints the lossless syntax tree
+//! rather than the AST, so comments survive and tokens keep the spelling
+//! they were written with, and lays the result out with `doc` so that long
+//! expressions and lists are broken to fit the configured width.
+//!
+//! Each comment belongs to a token: to the one before it when they share a
+//! line, and otherwise to the one after it. Blank lines between statements
+//! and other items on lines of their own are kept, at most one at a time.
+
+mod config;
+pub use config::{FormatConfig, CONFIG_FILE};
+mod doc;
+use doc::Doc;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::cst::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken};
+use crate::parser::lexer::{Keyword, TokenKind};
+use crate::parser::{SyntaxError, VoeParser};
+
+/// Formats `source`, which must be free of syntax errors.
+pub fn format_source(source: &str, config: &FormatConfig) -> Result<String, Vec<SyntaxError>> {
+    let parse = VoeParser.parse_tree(source);
+    if !parse.errors.is_empty() {
+        return Err(parse.errors);
+    }
+    let mut formatter = Formatter::new(&parse.tree);
+    let doc = formatter.program(&parse.tree);
+    let printed = doc::print(&doc, config.max_width, config.indent_width);
+    let printed = printed.trim_end();
+    Ok(if printed.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", printed)
+    })
+}
+
+/// A comment on a line of its own.
+struct Comment {
+    text: String,
+    /// Whether a blank line comes before it.
+    blank_before: bool,
+}
+
+/// How a list of comma-separated items ends when it is broken over lines.
+#[derive(PartialEq, Clone, Copy)]
+enum TrailingComma {
+    /// Never, for lists where the grammar allows none.
+    Never,
+    /// Only when broken.
+    IfBroken,
+    /// Always when there is a single item, as in the tuple `(a,)`.
+    IfSingle,
+}
+
+struct Formatter {
+    /// Comments on lines of their own, by the start of the token after them.
+    leading: HashMap<usize, Vec<Comment>>,
+    /// Comments after a token on its line, by the start of the token.
+    trailing: HashMap<usize, String>,
+    /// Comments after the last token.
+    dangling: Vec<Comment>,
+    /// Starts of tokens with a blank line directly before them.
+    blank_before: HashSet<usize>,
+}
+
+impl Formatter {
+    fn new(tree: &SyntaxNode) -> Formatter {
+        let mut formatter = Formatter {
+            leading: HashMap::new(),
+            trailing: HashMap::new(),
+            dangling: vec![],
+            blank_before: HashSet::new(),
+        };
+        let mut previous: Option<SyntaxToken> = None;
+        let mut pending = vec![];
+        // Newlines since the last token or comment.
+        let mut newlines = 0;
+        for token in tree.tokens() {
+            match token.kind() {
+                TokenKind::Whitespace => newlines += token.text().matches('\n').count(),
+                TokenKind::Comment => {
+                    let text = token.text().trim_end().to_string();
+                    match &previous {
+                        Some(previous) if newlines == 0 && pending.is_empty() => {
+                            formatter.trailing.insert(previous.span().start, text);
+                        }
+                        _ => pending.push(Comment {
+                            text,
+                            blank_before: newlines > 1,
+                        }),
+                    }
+                    newlines = 0;
+                }
+                _ => {
+                    if newlines > 1 {
+                        formatter.blank_before.insert(token.span().start);
+                    }
+                    if !pending.is_empty() {
+                        formatter
+                            .leading
+                            .insert(token.span().start, std::mem::take(&mut pending));
+                    }
+                    previous = Some(token);
+                    newlines = 0;
+                }
+            }
+        }
+        formatter.dangling = pending;
+        formatter
+    }
+
+    fn program(&mut self, program: &SyntaxNode) -> Doc {
+        let items = program
+            .children()
+            .into_iter()
+            .map(|node| (node, Doc::Concat(vec![])))
+            .collect();
+        let mut docs = vec![self.lines(items)];
+        let dangling = std::mem::take(&mut self.dangling);
+        docs.push(self.comments(dangling, !program.children().is_empty()));
+        Doc::Concat(docs)
+    }
+
+    fn node(&mut self, node: &SyntaxNode) -> Doc {
+        match node.kind() {
+            SyntaxKind::Block => self.block(node, true),
+            SyntaxKind::If => self.conditional(node),
+            SyntaxKind::Class | SyntaxKind::Enum | SyntaxKind::Match => {
+                self.braced_lines(node, Some(","))
+            }
+            SyntaxKind::Interface | SyntaxKind::Impl => self.braced_lines(node, None),
+            SyntaxKind::New | SyntaxKind::Import => self.braced_group(node),
+            SyntaxKind::ArgList => self.list(node, TrailingComma::Never),
+            SyntaxKind::Array => self.list(node, TrailingComma::IfBroken),
+            SyntaxKind::Tuple => self.list(node, TrailingComma::IfSingle),
+            SyntaxKind::ParamList if self.follows_paren(node) => self.params(node),
+            SyntaxKind::Binary => self.binary(node),
+            _ => self.spaced(node),
+        }
+    }
+
+    fn element(&mut self, element: &SyntaxElement) -> Doc {
+        match element {
+            SyntaxElement::Node(node) => self.node(node),
+            SyntaxElement::Token(token) => self.token(token),
+        }
+    }
+
+    fn token(&mut self, token: &SyntaxToken) -> Doc {
+        self.token_as(token, Doc::text(token.text()))
+    }
+
+    /// Prints `doc` in place of `token`, with the token's comments.
+    fn token_as(&mut self, token: &SyntaxToken, doc: Doc) -> Doc {
+        let mut docs = vec![];
+        if let Some(comments) = self.leading.remove(&token.span().start) {
+            for (i, comment) in comments.into_iter().enumerate() {
+                docs.push(Doc::FreshLine);
+                if i > 0 && comment.blank_before {
+                    docs.push(Doc::HardLine);
+                }
+                docs.push(Doc::text(comment.text));
+                docs.push(Doc::HardLine);
+            }
+            if self.blank_before.contains(&token.span().start) {
+                docs.push(Doc::HardLine);
+            }
+        }
+        docs.push(doc);
+        if let Some(comment) = self.trailing.remove(&token.span().start) {
+            docs.push(Doc::LineSuffix(format!(" {}", comment)));
+        }
+        Doc::Concat(docs)
+    }
+
+    /// `comments` on lines of their own, each after a newline if `after` is
+    /// set, and otherwise after the first.
+    fn comments(&mut self, comments: Vec<Comment>, after: bool) -> Doc {
+        let mut docs = vec![];
+        for (i, comment) in comments.into_iter().enumerate() {
+            if after || i > 0 {
+                docs.push(Doc::HardLine);
+                if comment.blank_before {
+                    docs.push(Doc::HardLine);
+                }
+            }
+            docs.push(Doc::text(comment.text));
+        }
+        Doc::Concat(docs)
+    }
+
+    /// Each of `items` on a line of its own and followed by its separator,
+    /// with the comments before it.
+    fn lines(&mut self, items: Vec<(SyntaxNode, Doc)>) -> Doc {
+        let mut docs = vec![];
+        for (i, (node, separator)) in items.into_iter().enumerate() {
+            let start = first_token(&node).span().start;
+            let comments = self.leading.remove(&start).unwrap_or_default();
+            let blank = match comments.first() {
+                Some(comment) => comment.blank_before,
+                None => self.blank_before.contains(&start),
+            };
+            if i > 0 {
+                docs.push(Doc::HardLine);
+                if blank {
+                    docs.push(Doc::HardLine);
+                }
+            }
+            if !comments.is_empty() {
+                docs.push(self.comments(comments, false));
+                docs.push(Doc::HardLine);
+                if self.blank_before.contains(&start) {
+                    docs.push(Doc::HardLine);
+                }
+            }
+            docs.push(self.node(&node));
+            docs.push(separator);
+        }
+        Doc::Concat(docs)
+    }
+
+    /// Items on lines of their own between `open` and `close`, indented,
+    /// with the comments before `close` kept inside.
+    fn body(
+        &mut self,
+        open: &SyntaxToken,
+        items: Vec<(SyntaxNode, Doc)>,
+        close: &SyntaxToken,
+    ) -> Doc {
+        let has_items = !items.is_empty();
+        let comments = self.leading.remove(&close.span().start).unwrap_or_default();
+        if !has_items && comments.is_empty() && !self.trailing.contains_key(&open.span().start) {
+            return Doc::Concat(vec![self.token(open), self.token(close)]);
+        }
+        let mut inner = vec![];
+        if has_items || !comments.is_empty() {
+            inner.push(Doc::HardLine);
+            inner.push(self.lines(items));
+            inner.push(self.comments(comments, has_items));
+        }
+        Doc::Concat(vec![
+            self.token(open),
+            Doc::indent(Doc::Concat(inner)),
+            Doc::HardLine,
+            self.token(close),
+        ])
+    }
+
+    /// `{ ... }`, on one line if it holds nothing but a short value. Unless
+    /// `grouped`, whether it is broken is up to the group around it.
+    fn block(&mut self, node: &SyntaxNode, grouped: bool) -> Doc {
+        let elements = significant(node);
+        let (open, close) = match (elements.first(), elements.last()) {
+            (Some(SyntaxElement::Token(open)), Some(SyntaxElement::Token(close))) => {
+                (open.clone(), close.clone())
+            }
+            _ => unreachable!("a block is delimited by braces"),
+        };
+        let children = node.children();
+        if let [value] = children.as_slice() {
+            let start = first_token(value).span().start;
+            let uncommented = !self.leading.contains_key(&start)
+                && !self.leading.contains_key(&close.span().start);
+            if value.kind().is_expression() && uncommented {
+                let doc = Doc::Concat(vec![
+                    self.token(&open),
+                    Doc::indent(Doc::Concat(vec![Doc::Line, self.node(value)])),
+                    Doc::Line,
+                    self.token(&close),
+                ]);
+                return if grouped { Doc::group(doc) } else { doc };
+            }
+        }
+        let items = children
+            .into_iter()
+            .map(|node| (node, Doc::Concat(vec![])))
+            .collect();
+        self.body(&open, items, &close)
+    }
+
+    /// `if c { a } else { b }`. The blocks of a chain of `else if`s are
+    /// broken together, so it is either on one line or has every block on
+    /// lines of its own.
+    fn conditional(&mut self, node: &SyntaxNode) -> Doc {
+        let elements = significant(node);
+        let is_else = |e: &SyntaxElement| matches!(e, SyntaxElement::Token(t) if t.kind() == TokenKind::Keyword(Keyword::Else));
+        let mut docs = vec![];
+        for (i, element) in elements.iter().enumerate() {
+            if i > 0 && space_between(node.kind(), &elements, i) {
+                docs.push(Doc::text(" "));
+            }
+            let branch = i + 1 == elements.len() || is_else(&elements[i + 1]);
+            docs.push(match element {
+                SyntaxElement::Node(block) if branch && block.kind() == SyntaxKind::Block => {
+                    self.block(block, false)
+                }
+                _ => self.element(element),
+            });
+        }
+        match node.parent() {
+            Some(parent) if parent.kind() == SyntaxKind::If => Doc::Concat(docs),
+            _ => Doc::group(Doc::Concat(docs)),
+        }
+    }
+
+    /// A header, then items separated by `separator` on lines of their own
+    /// between braces, as in a class or `match`.
+    fn braced_lines(&mut self, node: &SyntaxNode, separator: Option<&str>) -> Doc {
+        let elements = significant(node);
+        let open = brace_position(&elements);
+        let mut header = self.spaced_elements(node.kind(), &elements[..open]);
+        let (open, items, close) = self.comma_items(&elements[open..]);
+        let items = items
+            .into_iter()
+            .map(|(item, comma)| {
+                let separator = match (comma, separator) {
+                    (Some(comma), Some(separator)) => self.token_as(&comma, Doc::text(separator)),
+                    (None, Some(separator)) => Doc::text(separator),
+                    (_, None) => Doc::Concat(vec![]),
+                };
+                (item, separator)
+            })
+            .collect();
+        header.push(Doc::text(" "));
+        header.push(self.body(&open, items, &close));
+        Doc::Concat(header)
+    }
+
+    /// A header, then comma-separated items between braces, on one line if
+    /// they fit, as in `new A { x: 1 }`.
+    fn braced_group(&mut self, node: &SyntaxNode) -> Doc {
+        let elements = significant(node);
+        let start = brace_position(&elements);
+        let end = elements
+            .iter()
+            .rposition(|e| matches!(e, SyntaxElement::Token(t) if t.kind() == TokenKind::RBrace))
+            .unwrap();
+        let mut docs = self.spaced_elements(node.kind(), &elements[..start]);
+        docs.push(Doc::text(" "));
+        let (open, items, close) = self.comma_items(&elements[start..=end]);
+        docs.push(self.token(&open));
+        if items.is_empty() {
+            docs.push(self.token(&close));
+        } else {
+            let items = self.separated(items, TrailingComma::IfBroken);
+            docs.push(Doc::group(Doc::Concat(vec![
+                Doc::indent(Doc::Concat(vec![Doc::Line, items])),
+                Doc::Line,
+                self.token(&close),
+            ])));
+        }
+        if end + 1 < elements.len() {
+            docs.push(Doc::text(" "));
+            docs.extend(self.spaced_elements(node.kind(), &elements[end + 1..]));
+        }
+        Doc::Concat(docs)
+    }
+
+    /// `(a, b)` or `[a, b]`, broken one item per line if it does not fit.
+    fn list(&mut self, node: &SyntaxNode, trailing: TrailingComma) -> Doc {
+        let elements = significant(node);
+        let (open, items, close) = self.comma_items(&elements);
+        if items.is_empty() {
+            return Doc::Concat(vec![self.token(&open), self.token(&close)]);
+        }
+        let items = self.separated(items, trailing);
+        Doc::Concat(vec![
+            self.token(&open),
+            Doc::group(Doc::Concat(vec![
+                Doc::indent(Doc::Concat(vec![Doc::SoftLine, items])),
+                Doc::SoftLine,
+            ])),
+            self.token(&close),
+        ])
+    }
+
+    /// The parameters of a function, between parentheses outside the node.
+    fn params(&mut self, node: &SyntaxNode) -> Doc {
+        let mut elements = significant(node);
+        if elements.is_empty() {
+            return Doc::Concat(vec![]);
+        }
+        let mut items = vec![];
+        while !elements.is_empty() {
+            let SyntaxElement::Node(param) = elements.remove(0) else {
+                unreachable!("parameters are separated by commas");
+            };
+            let comma = match elements.first() {
+                Some(SyntaxElement::Token(comma)) => Some(comma.clone()),
+                _ => None,
+            };
+            if comma.is_some() {
+                elements.remove(0);
+            }
+            items.push((param, comma));
+        }
+        let items = self.separated(items, TrailingComma::Never);
+        Doc::group(Doc::Concat(vec![
+            Doc::indent(Doc::Concat(vec![Doc::SoftLine, items])),
+            Doc::SoftLine,
+        ]))
+    }
+
+    /// Items and their commas, with a line after each comma.
+    fn separated(
+        &mut self,
+        items: Vec<(SyntaxNode, Option<SyntaxToken>)>,
+        trailing: TrailingComma,
+    ) -> Doc {
+        let count = items.len();
+        let mut docs = vec![];
+        for (i, (item, comma)) in items.into_iter().enumerate() {
+            docs.push(self.node(&item));
+            let last = i + 1 == count;
+            let text = match trailing {
+                _ if !last => Doc::text(","),
+                TrailingComma::IfSingle if count == 1 => Doc::text(","),
+                TrailingComma::IfBroken => Doc::IfBreak(",".to_string()),
+                _ => Doc::Concat(vec![]),
+            };
+            docs.push(match comma {
+                Some(comma) => self.token_as(&comma, text),
+                None => text,
+            });
+            if !last {
+                docs.push(Doc::Line);
+            }
+        }
+        Doc::Concat(docs)
+    }
+
+    /// Splits `open item, item, ... close` into its parts.
+    fn comma_items(
+        &mut self,
+        elements: &[SyntaxElement],
+    ) -> (
+        SyntaxToken,
+        Vec<(SyntaxNode, Option<SyntaxToken>)>,
+        SyntaxToken,
+    ) {
+        let token = |e: &SyntaxElement| match e {
+            SyntaxElement::Token(token) => token.clone(),
+            SyntaxElement::Node(_) => unreachable!("lists are delimited by tokens"),
+        };
+        let open = token(&elements[0]);
+        let close = token(&elements[elements.len() - 1]);
+        let mut items: Vec<(SyntaxNode, Option<SyntaxToken>)> = vec![];
+        for element in &elements[1..elements.len() - 1] {
+            match element {
+                SyntaxElement::Node(node) => items.push((node.clone(), None)),
+                SyntaxElement::Token(comma) => items.last_mut().unwrap().1 = Some(comma.clone()),
+            }
+        }
+        (open, items, close)
+    }
+
+    /// `a + b + c`, broken after each operator if it does not fit.
+    fn binary(&mut self, node: &SyntaxNode) -> Doc {
+        let mut operands = vec![];
+        let mut operators = vec![];
+        let mut current = node.clone();
+        loop {
+            let elements = significant(&current);
+            let [SyntaxElement::Node(lhs), SyntaxElement::Token(op), SyntaxElement::Node(rhs)] =
+                elements.as_slice()
+            else {
+                unreachable!("a binary operation has two operands");
+            };
+            operands.push(rhs.clone());
+            operators.push(op.clone());
+            // Chains of the same operator are broken together.
+            let chained = lhs.kind() == SyntaxKind::Binary
+                && matches!(significant(lhs).get(1), Some(SyntaxElement::Token(t)) if t.kind() == op.kind());
+            if !chained {
+                operands.push(lhs.clone());
+                break;
+            }
+            current = lhs.clone();
+        }
+        operands.reverse();
+        operators.reverse();
+        let first = self.node(&operands[0]);
+        let mut rest = vec![];
+        for (op, operand) in operators.iter().zip(&operands[1..]) {
+            rest.push(Doc::text(" "));
+            rest.push(self.token(op));
+            rest.push(Doc::Line);
+            rest.push(self.node(operand));
+        }
+        Doc::group(Doc::Concat(vec![first, Doc::indent(Doc::Concat(rest))]))
+    }
+
+    /// The node's parts, with spaces between them where they belong.
+    fn spaced(&mut self, node: &SyntaxNode) -> Doc {
+        let elements = significant(node);
+        Doc::Concat(self.spaced_elements(node.kind(), &elements))
+    }
+
+    fn spaced_elements(&mut self, kind: SyntaxKind, elements: &[SyntaxElement]) -> Vec<Doc> {
+        let mut docs = vec![];
+        for (i, element) in elements.iter().enumerate() {
+            if i > 0 && space_between(kind, elements, i) {
+                docs.push(Doc::text(" "));
+            }
+            docs.push(self.element(element));
+        }
+        docs
+    }
+
+    /// Whether `node` comes right after a `(`, as the parameters of a
+    /// function do but those after `forall` do not.
+    fn follows_paren(&self, node: &SyntaxNode) -> bool {
+        let Some(parent) = node.parent() else {
+            return false;
+        };
+        let elements = significant(&parent);
+        let position = elements
+            .iter()
+            .position(|e| matches!(e, SyntaxElement::Node(n) if n == node));
+        matches!(
+            position.and_then(|i| i.checked_sub(1)).map(|i| &elements[i]),
+            Some(SyntaxElement::Token(t)) if t.kind() == TokenKind::LParen
+        )
+    }
+}
+
+/// The children of `node` other than trivia.
+fn significant(node: &SyntaxNode) -> Vec<SyntaxElement> {
+    node.children_with_tokens()
+        .into_iter()
+        .filter(|e| !matches!(e, SyntaxElement::Token(t) if t.kind().is_trivia()))
+        .collect()
+}
+
+fn first_token(node: &SyntaxNode) -> SyntaxToken {
+    node.tokens()
+        .into_iter()
+        .find(|t| !t.kind().is_trivia())
+        .expect("empty node")
+}
+
+/// Where the first `{` among `elements` is.
+fn brace_position(elements: &[SyntaxElement]) -> usize {
+    elements
+        .iter()
+        .position(|e| matches!(e, SyntaxElement::Token(t) if t.kind() == TokenKind::LBrace))
+        .expect("no `{`")
+}
+
+/// Whether a space goes between `elements[i - 1]` and `elements[i]`, which
+/// are children of a node of `kind`.
+fn space_between(kind: SyntaxKind, elements: &[SyntaxElement], i: usize) -> bool {
+    let token = |e: &SyntaxElement| match e {
+        SyntaxElement::Token(token) => Some(token.kind()),
+        SyntaxElement::Node(_) => None,
+    };
+    let node = |e: &SyntaxElement| match e {
+        SyntaxElement::Node(node) => Some(node.kind()),
+        SyntaxElement::Token(_) => None,
+    };
+    let (prev, next) = (token(&elements[i - 1]), token(&elements[i]));
+    let is_open = |kind| {
+        matches!(
+            kind,
+            Some(TokenKind::LParen | TokenKind::LBracket | TokenKind::ColonColon)
+        )
+    };
+    if matches!(
+        next,
+        Some(
+            TokenKind::Comma
+                | TokenKind::Semicolon
+                | TokenKind::Colon
+                | TokenKind::RParen
+                | TokenKind::RBracket
+                | TokenKind::ColonColon
+                | TokenKind::Question
+                | TokenKind::Suffix
+        )
+    ) || is_open(prev)
+    {
+        return false;
+    }
+    match kind {
+        // `a.b`, but `forall n: usize. fn ...`
+        SyntaxKind::FieldAccess | SyntaxKind::MethodCall => {
+            next != Some(TokenKind::Dot)
+                && prev != Some(TokenKind::Dot)
+                && node(&elements[i]) != Some(SyntaxKind::ArgList)
+        }
+        SyntaxKind::Function
+        | SyntaxKind::MethodSignature
+        | SyntaxKind::Variant
+        | SyntaxKind::Some
+        | SyntaxKind::Ok
+        | SyntaxKind::Err
+        | SyntaxKind::Pattern
+            if next == Some(TokenKind::LParen) =>
+        {
+            false
+        }
+        SyntaxKind::Function | SyntaxKind::TypeAlias
+            if node(&elements[i]) == Some(SyntaxKind::TypeParams) =>
+        {
+            false
+        }
+        SyntaxKind::Function | SyntaxKind::Type if next == Some(TokenKind::Dot) => false,
+        SyntaxKind::Type | SyntaxKind::TypeParams => {
+            !matches!(
+                next,
+                Some(TokenKind::Lt | TokenKind::Gt | TokenKind::LParen)
+            ) && prev != Some(TokenKind::Lt)
+        }
+        SyntaxKind::Call | SyntaxKind::VariantExpression => {
+            node(&elements[i]) != Some(SyntaxKind::ArgList)
+        }
+        SyntaxKind::Index => next != Some(TokenKind::LBracket),
+        SyntaxKind::Unary | SyntaxKind::Literal => false,
+        SyntaxKind::Range => !matches!(
+            (prev, next),
+            (Some(TokenKind::DotDot | TokenKind::DotDotEq), _)
+                | (_, Some(TokenKind::DotDot | TokenKind::DotDotEq))
+        ),
+        // `|a: i32, b: i32| a + b`
+        SyntaxKind::Lambda => {
+            !(i == 1 && prev == Some(TokenKind::Pipe) || next == Some(TokenKind::Pipe))
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(source: &str) -> String {
+        format_source(source, &FormatConfig::default()).unwrap()
+    }
+
+    /// Formats `source`, checking that the result means the same and is
+    /// left alone when formatted again.
+    fn check(source: &str, config: &FormatConfig) -> String {
+        let formatted = format_source(source, config).unwrap();
+        let before = VoeParser.parse_program(source).unwrap();
+        let after = VoeParser.parse_program(&formatted).unwrap();
+        assert_eq!(before, after, "formatting changed\n{}", formatted);
+        assert_eq!(format_source(&formatted, config).unwrap(), formatted);
+        formatted
+    }
+
+    const SAMPLE: &str = r#"// A sample.
+module shapes;
+import {area,  Point as P} from "geometry";
+use math::sqrt;
+
+class Circle { radius: f64, center: (f64,f64) }
+enum Shape { Circle(f64), Square(f64, f64), Empty }
+interface Named { fn name(self) -> String; }
+
+impl Named for Circle {
+    fn name(self) -> String { let s = "circle\n"; } // the name
+
+
+    fn scale<T: Show + Eq>(self, factor: f64) -> Circle { let c = new Circle {radius: self.radius * factor, center: self.center}; }
+}
+
+type Pair<T> = (T, T);
+forall n: usize. fn zeros(v: [i32; 3]) -> () {}
+
+fn main() -> () {
+    let xs: Vec<Vec<i32>> = [1, 2, 3];
+    let t = (1,);
+    // Sum them.
+    for i in 0..10 step 2 {
+        total = total + xs[i] * -1i32;
+    }
+    while !done && count < 10 { count = count + 1; }
+    let m = match s { Shape::Circle(r) => r ^ 2.0, Shape::Empty => { 0.0 }, _ => -1.0 };
+    let f = |a: i32, b: i32| a + b;
+    let y = if ready { some(x) } else { none };
+    if let some(v) = maybe { print(v.len(), t.0); }
+    let z = value?.field.method(1) is some;
+}
+"#;
+
+    #[test]
+    fn test_format_sample() {
+        let formatted = check(SAMPLE, &FormatConfig::default());
+        assert_eq!(
+            formatted,
+            r#"// A sample.
+module shapes;
+import { area, Point as P } from "geometry";
+use math::sqrt;
+
+class Circle {
+    radius: f64,
+    center: (f64, f64),
+}
+enum Shape {
+    Circle(f64),
+    Square(f64, f64),
+    Empty,
+}
+interface Named {
+    fn name(self) -> String;
+}
+
+impl Named for Circle {
+    fn name(self) -> String {
+        let s = "circle\n";
+    } // the name
+
+    fn scale<T: Show + Eq>(self, factor: f64) -> Circle {
+        let c = new Circle { radius: self.radius * factor, center: self.center };
+    }
+}
+
+type Pair<T> = (T, T);
+forall n: usize. fn zeros(v: [i32; 3]) -> () {}
+
+fn main() -> () {
+    let xs: Vec<Vec<i32>> = [1, 2, 3];
+    let t = (1,);
+    // Sum them.
+    for i in 0..10 step 2 {
+        total = total + xs[i] * -1i32;
+    }
+    while !done && count < 10 {
+        count = count + 1;
+    }
+    let m = match s {
+        Shape::Circle(r) => r ^ 2.0,
+        Shape::Empty => { 0.0 },
+        _ => -1.0,
+    };
+    let f = |a: i32, b: i32| a + b;
+    let y = if ready { some(x) } else { none };
+    if let some(v) = maybe {
+        print(v.len(), t.0);
+    }
+    let z = value?.field.method(1) is some;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_format_breaks_long_lines() {
+        let source = "fn configure(width: i32, height: i32, depth: i32) -> () { \
+                      let total = compute(width, height) + compute(height, depth) + compute(depth, width); }";
+        let config = FormatConfig {
+            indent_width: 2,
+            max_width: 40,
+        };
+        assert_eq!(
+            check(source, &config),
+            "fn configure(
+  width: i32,
+  height: i32,
+  depth: i32
+) -> () {
+  let total = compute(width, height) +
+    compute(height, depth) +
+    compute(depth, width);
+}
+"
+        );
+        let source = "let point = new Point { x: first_coordinate, y: second_coordinate };";
+        assert_eq!(
+            check(source, &config),
+            "let point = new Point {
+  x: first_coordinate,
+  y: second_coordinate,
+};
+"
+        );
+    }
+
+    #[test]
+    fn test_format_keeps_comments() {
+        let source = "
+
+// Leading.
+
+// Second.
+let a = 1; // after a
+fn f() -> () {
+    // Inside.
+    let b = call(x, // the x
+        y);
+
+    // Last.
+}
+// The end.
+";
+        assert_eq!(
+            check(source, &FormatConfig::default()),
+            "// Leading.
+
+// Second.
+let a = 1; // after a
+fn f() -> () {
+    // Inside.
+    let b = call(
+        x, // the x
+        y
+    );
+
+    // Last.
+}
+// The end.
+"
+        );
+        assert_eq!(
+            check("// Only this.\n", &FormatConfig::default()),
+            "// Only this.\n"
+        );
+        assert_eq!(format(""), "");
+    }
+
+    #[test]
+    fn test_format_rejects_syntax_errors() {
+        let errors = format_source("let = 1;", &FormatConfig::default()).unwrap_err();
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_format_corpus() {
+        for source in [
+            include_str!("../../test.voe"),
+            "let x = t.0.1 + .5 - -1.5e3f64 ^ 2i32 ^ 3i32 * -y.len() % 0b1_0i8;",
+            "let s = r##\"a \"# b\"## + \"tab\\t \\u{1F600} \\\n    joined\";",
+            "let f = |x: i32, y: i32| x | y ~ x & y; let g = || -> i32 { h(); };",
+            "let m = match r { ok(n) => n, err(_) => { 0i32 } + 1i32, Shape::Rect(w, _) => w, };",
+            "forall n: u32. fn zeros<T: A + B, U>(self, xs: [T; 0x10]) -> fn(T) -> [T]? {}",
+            "export let a = [1i32; 3]; import { a as b, c, } from r\"m.voe\"; use a::b::c as d;",
+            "for (i, _) in 0..=10 step 2i32 { f(i); } while x is some { x = next(x?); }",
+            "if let some((a, -1i32, \"s\", true)) = p { f(); } else { g(); }",
+            "let r#match = new Point { x: 1i32, y: (2i32), }; let t = (1i32,);",
+        ] {
+            for max_width in [100, 20] {
+                check(
+                    source,
+                    &FormatConfig {
+                        indent_width: 4,
+                        max_width,
+                    },
+                );
+            }
+        }
+
+        // The blocks of an `if` chain are broken together.
+        let source = "let a = if ready { first } else if done { second } else { third };";
+        let config = FormatConfig {
+            indent_width: 4,
+            max_width: 40,
+        };
+        assert_eq!(
+            check(source, &config),
+            "let a = if ready {
+    first
+} else if done {
+    second
+} else {
+    third
+};
+"
+        );
+    }
+}
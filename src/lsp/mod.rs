@@ -0,0 +1,842 @@
+//! A language server for editors, speaking the language server protocol
+//! over standard input and output. It keeps the documents the editor has
+//! open, reports their problems whenever they change, and answers hover,
+//! go-to-definition, document symbol and formatting requests.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+mod json;
+pub use json::Json;
+
+use crate::analysis::{Analysis, Diagnostic, Resolver, Severity, TypeChecker};
+use crate::formatter::{format_source, FormatConfig};
+use crate::modules::{ModuleLoader, ModuleSource};
+use crate::parser::ast_index::{AstIndex, Name};
+use crate::parser::cst::Parse;
+use crate::parser::function::format_params;
+use crate::parser::lexer::Span;
+use crate::parser::{FunctionDefinition, VoeParser};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// The symbol kinds of the protocol used for functions.
+const SYMBOL_METHOD: usize = 6;
+const SYMBOL_FUNCTION: usize = 12;
+
+/// Serves requests read from `input` until the editor says to exit.
+pub fn run(mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut server = Server::new();
+    while let Some(body) = read_message(&mut input)? {
+        let replies = match Json::parse(&body) {
+            Ok(message) => server.handle(&message),
+            Err(err) => vec![error_response(&Json::Null, PARSE_ERROR, &err)],
+        };
+        for reply in replies {
+            let body = reply.to_string();
+            write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+        }
+        output.flush()?;
+        if server.exited {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The body of the next message, after its headers, or `None` at the end
+/// of the input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// An open document, parsed and analyzed as of its last change.
+struct Document {
+    text: String,
+    parse: Parse,
+    /// The analyses of the document, run on the statements that parse.
+    analyses: (Resolver, TypeChecker),
+    diagnostics: Vec<Json>,
+}
+
+impl Document {
+    fn new(uri: &str, text: String) -> Document {
+        let parse = VoeParser.parse_tree(&text);
+        let (analyses, diagnostics) = analyze(&path_of(uri), &text, &parse);
+        let mut document = Document {
+            diagnostics: vec![],
+            analyses,
+            parse,
+            text,
+        };
+        let mut published: Vec<Json> = document
+            .parse
+            .errors
+            .iter()
+            .map(|err| document.diagnostic(err.span, Severity::Error, &err.message))
+            .collect();
+        published.extend(diagnostics.iter().map(|d| {
+            let span = d.span.unwrap_or(Span::new(0, 0));
+            document.diagnostic(span, d.severity, &d.message)
+        }));
+        document.diagnostics = published;
+        document
+    }
+
+    fn diagnostic(&self, span: Span, severity: Severity, message: &str) -> Json {
+        let severity = match severity {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+        };
+        Json::object([
+            ("range", range(&self.text, span)),
+            ("severity", Json::from(severity as usize)),
+            ("source", "voe".into()),
+            ("message", message.into()),
+        ])
+    }
+}
+
+/// Runs the analyses the compiler runs on the module at `path`, whose
+/// source is `text`, with the declarations it imports, on the statements
+/// of `parse`. Like the compiler, only a module that loads without problems
+/// has its analyses' diagnostics reported; otherwise the problems loading
+/// it are, apart from its own syntax errors, which `parse` has.
+fn analyze(path: &Path, text: &str, parse: &Parse) -> ((Resolver, TypeChecker), Vec<Diagnostic>) {
+    let (graph, mut problems) = ModuleLoader::new(Unsaved { path, text }).load_recovering(path);
+    // The document is loaded first, so its syntax errors come first.
+    problems.drain(..parse.errors.len().min(problems.len()));
+    let imports = graph.imported_declarations(0);
+    let spans = AstIndex::new(&parse.tree, &parse.program).spans();
+    let mut resolver = Resolver::new().with_spans(spans.clone());
+    let mut checker = TypeChecker::new().with_spans(spans);
+    let mut diagnostics = resolver.analyze_module(&parse.program, &imports);
+    diagnostics.extend(checker.analyze_module(&parse.program, &imports));
+    if problems.is_empty() && parse.errors.is_empty() {
+        problems = diagnostics;
+    }
+    ((resolver, checker), problems)
+}
+
+/// Module sources as the editor has them: the text of the document being
+/// edited, which may not be saved yet, and every other file from disk.
+struct Unsaved<'a> {
+    path: &'a Path,
+    text: &'a str,
+}
+
+impl ModuleSource for Unsaved<'_> {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        if path == self.path {
+            Ok(self.text.to_string())
+        } else {
+            std::fs::read_to_string(path)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+    exited: bool,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server::default()
+    }
+
+    /// Handles one message from the editor, returning the messages to send
+    /// back: the response to a request and any notifications.
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message.get("method").as_str().unwrap_or_default();
+        let params = message.get("params");
+        let id = message.get("id");
+        let uri = params
+            .get("textDocument")
+            .get("uri")
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        if *id == Json::Null {
+            // A notification, which gets no response.
+            return match method {
+                "textDocument/didOpen" => {
+                    let text = params.get("textDocument").get("text").as_str();
+                    self.update(uri, text.unwrap_or_default().to_string())
+                }
+                "textDocument/didChange" => {
+                    // The server asks for whole documents, so the last change
+                    // holds all of the text.
+                    let text = params
+                        .get("contentChanges")
+                        .as_array()
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change.get("text").as_str());
+                    match text {
+                        Some(text) => self.update(uri, text.to_string()),
+                        None => vec![],
+                    }
+                }
+                "textDocument/didClose" => {
+                    self.documents.remove(&uri);
+                    vec![publish_diagnostics(&uri, vec![])]
+                }
+                "exit" => {
+                    self.exited = true;
+                    vec![]
+                }
+                _ => vec![],
+            };
+        }
+        if self.shut_down {
+            return vec![error_response(
+                id,
+                INVALID_REQUEST,
+                "the server is shut down",
+            )];
+        }
+        let document = self.documents.get(&uri);
+        let offset = document.and_then(|d| offset(&d.text, params.get("position")));
+        let result = match (method, document) {
+            ("initialize", _) => initialize_result(),
+            ("shutdown", _) => {
+                self.shut_down = true;
+                Json::Null
+            }
+            ("textDocument/hover", Some(document)) => offset
+                .and_then(|offset| hover(document, offset))
+                .unwrap_or(Json::Null),
+            ("textDocument/definition", Some(document)) => offset
+                .and_then(|offset| definition(document, offset))
+                .map_or(Json::Null, |span| {
+                    Json::object([
+                        ("uri", uri.as_str().into()),
+                        ("range", range(&document.text, span)),
+                    ])
+                }),
+            ("textDocument/documentSymbol", Some(document)) => document_symbols(document),
+            ("textDocument/formatting", Some(document)) => formatting(&uri, document),
+            (
+                "textDocument/hover"
+                | "textDocument/definition"
+                | "textDocument/documentSymbol"
+                | "textDocument/formatting",
+                None,
+            ) => Json::Null,
+            _ => {
+                let message = format!("unknown method `{}`", method);
+                return vec![error_response(id, METHOD_NOT_FOUND, &message)];
+            }
+        };
+        vec![Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id.clone()),
+            ("result", result),
+        ])]
+    }
+
+    fn update(&mut self, uri: String, text: String) -> Vec<Json> {
+        let document = Document::new(&uri, text);
+        let notification = publish_diagnostics(&uri, document.diagnostics.clone());
+        self.documents.insert(uri, document);
+        vec![notification]
+    }
+}
+
+fn initialize_result() -> Json {
+    Json::object([
+        (
+            "capabilities",
+            Json::object([
+                // Whole documents are sent on every change.
+                ("textDocumentSync", Json::from(1)),
+                ("hoverProvider", true.into()),
+                ("definitionProvider", true.into()),
+                ("documentSymbolProvider", true.into()),
+                ("documentFormattingProvider", true.into()),
+            ]),
+        ),
+        ("serverInfo", Json::object([("name", "voe".into())])),
+    ])
+}
+
+fn error_response(id: &Json, code: i64, message: &str) -> Json {
+    Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("id", id.clone()),
+        (
+            "error",
+            Json::object([
+                ("code", Json::Number(code as f64)),
+                ("message", message.into()),
+            ]),
+        ),
+    ])
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Json>) -> Json {
+    Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", "textDocument/publishDiagnostics".into()),
+        (
+            "params",
+            Json::object([("uri", uri.into()), ("diagnostics", diagnostics.into())]),
+        ),
+    ])
+}
+
+/// The declaration of the name at `offset`.
+fn definition(document: &Document, offset: usize) -> Option<Span> {
+    let (resolver, _) = &document.analyses;
+    let index = AstIndex::new(&document.parse.tree, &document.parse.program);
+    let name = index.name_at(offset)?;
+    declaration(&index, resolver, &name).map(|name| name.span)
+}
+
+/// Where `name` is declared in the document. Intrinsics and imported
+/// names have no declaration in it.
+fn declaration<'a>(index: &AstIndex<'a>, resolver: &Resolver, name: &Name) -> Option<Name<'a>> {
+    let id = resolver.resolution(name.name)?;
+    index
+        .names
+        .iter()
+        .find(|n| n.declares && resolver.resolution(n.name) == Some(id))
+        .copied()
+}
+
+/// The type of the identifier or expression at `offset`, or the signature
+/// of a function named there.
+fn hover(document: &Document, offset: usize) -> Option<Json> {
+    let (resolver, checker) = &document.analyses;
+    let index = AstIndex::new(&document.parse.tree, &document.parse.program);
+    let expression = index.expression_at(offset);
+    let named = index.name_at(offset).and_then(|name| {
+        // A use has the type of its expression, which may be narrowed.
+        if let Some((span, expr)) = expression.filter(|(span, _)| *span == name.span) {
+            let ty = checker.type_of(expr)?;
+            return Some((span, format!("{}: {}", name.name, ty)));
+        }
+        let declared = declaration(&index, resolver, &name)?;
+        let function = index.functions.iter().find(|f| f.name == declared.span);
+        let text = match function {
+            Some(function) => signature(function.definition),
+            None => format!("{}: {}", name.name, checker.binding_type(declared.name)?),
+        };
+        Some((name.span, text))
+    });
+    let (span, text) = named.or_else(|| {
+        let (span, expr) = expression?;
+        Some((span, checker.type_of(expr)?.to_string()))
+    })?;
+    Some(Json::object([
+        (
+            "contents",
+            Json::object([
+                ("kind", "markdown".into()),
+                ("value", format!("```voe\n{}\n```", text).into()),
+            ]),
+        ),
+        ("range", range(&document.text, span)),
+    ]))
+}
+
+/// `fn name(params) -> T`, with the `forall` and type parameters of
+/// functions that have them.
+fn signature(fd: &FunctionDefinition) -> String {
+    let mut signature = String::new();
+    if fd.is_dependent() {
+        signature.push_str(&format!(
+            "forall {}. ",
+            format_params(fd.dependent_params())
+        ));
+    }
+    signature.push_str(&format!("fn {}", fd.name()));
+    if fd.is_generic() {
+        let params: Vec<String> = fd.type_params().iter().map(|p| p.to_string()).collect();
+        signature.push_str(&format!("<{}>", params.join(", ")));
+    }
+    signature.push_str(&format!(
+        "({}) -> {}",
+        format_params(fd.inputs()),
+        fd.return_type()
+    ));
+    signature
+}
+
+/// The functions and methods of the document. Those in statements that do
+/// not parse are left out.
+fn document_symbols(document: &Document) -> Json {
+    let index = AstIndex::new(&document.parse.tree, &document.parse.program);
+    let symbols = index
+        .functions
+        .iter()
+        .map(|function| {
+            let kind = if function.is_method {
+                SYMBOL_METHOD
+            } else {
+                SYMBOL_FUNCTION
+            };
+            Json::object([
+                ("name", function.definition.name().as_str().into()),
+                ("detail", signature(function.definition).into()),
+                ("kind", kind.into()),
+                ("range", range(&document.text, function.span)),
+                ("selectionRange", range(&document.text, function.name)),
+            ])
+        })
+        .collect::<Vec<_>>();
+    symbols.into()
+}
+
+/// An edit replacing the whole document with it formatted, with the
+/// settings of the `voefmt.toml` nearest to it. Documents with syntax
+/// errors are left alone.
+fn formatting(uri: &str, document: &Document) -> Json {
+    let path = path_of(uri);
+    let config = path
+        .parent()
+        .and_then(FormatConfig::find)
+        .and_then(|config| FormatConfig::load(&config).ok())
+        .unwrap_or_default();
+    let Ok(formatted) = format_source(&document.text, &config) else {
+        return Json::Null;
+    };
+    if formatted == document.text {
+        return Json::Array(vec![]);
+    }
+    let whole = Span::new(0, document.text.len());
+    Json::Array(vec![Json::object([
+        ("range", range(&document.text, whole)),
+        ("newText", formatted.into()),
+    ])])
+}
+
+/// The path of a `file:` URI. Other URIs, like those of unsaved files,
+/// are used as they are.
+fn path_of(uri: &str) -> PathBuf {
+    let Some(path) = uri.strip_prefix("file://") else {
+        return PathBuf::from(uri);
+    };
+    let mut bytes = vec![];
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = (byte == b'%')
+            .then(|| std::str::from_utf8(tail.get(..2)?).ok())
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// The protocol's position of byte `offset` in `text`. Columns count UTF-16
+/// code units, as the protocol does by default.
+fn position(text: &str, offset: usize) -> Json {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    Json::object([
+        ("line", before.matches('\n').count().into()),
+        ("character", character.into()),
+    ])
+}
+
+fn range(text: &str, span: Span) -> Json {
+    Json::object([
+        ("start", position(text, span.start)),
+        ("end", position(text, span.end)),
+    ])
+}
+
+/// The byte offset of a position in `text`. Positions past the end of a
+/// line are at its end.
+fn offset(text: &str, position: &Json) -> Option<usize> {
+    let line = position.get("line").as_usize()?;
+    let character = position.get("character").as_usize()?;
+    let line_start = match line {
+        0 => 0,
+        _ => text.match_indices('\n').nth(line - 1)?.0 + 1,
+    };
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return Some(line_start + i);
+        }
+        units += c.len_utf16();
+    }
+    Some(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "untitled:test.voe";
+
+    /// Runs the server on `messages`, framed as an editor would send them,
+    /// and returns every message it sent back.
+    fn exchange(messages: &[Json]) -> Vec<Json> {
+        let mut input = vec![];
+        for message in messages {
+            let body = message.to_string();
+            write!(input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        }
+        let mut output = vec![];
+        run(&input[..], &mut output).unwrap();
+        let mut output = &output[..];
+        let mut replies = vec![];
+        while let Some(body) = read_message(&mut output).unwrap() {
+            replies.push(Json::parse(&body).unwrap());
+        }
+        replies
+    }
+
+    fn request(id: usize, method: &str, params: Json) -> Json {
+        Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id.into()),
+            ("method", method.into()),
+            ("params", params),
+        ])
+    }
+
+    fn notification(method: &str, params: Json) -> Json {
+        Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", method.into()),
+            ("params", params),
+        ])
+    }
+
+    fn open(text: &str) -> Json {
+        notification(
+            "textDocument/didOpen",
+            Json::object([(
+                "textDocument",
+                Json::object([
+                    ("uri", URI.into()),
+                    ("languageId", "voe".into()),
+                    ("version", 1.into()),
+                    ("text", text.into()),
+                ]),
+            )]),
+        )
+    }
+
+    /// A request about the document at `line` and `character`.
+    fn at(id: usize, method: &str, line: usize, character: usize) -> Json {
+        request(
+            id,
+            method,
+            Json::object([
+                ("textDocument", Json::object([("uri", URI.into())])),
+                (
+                    "position",
+                    Json::object([("line", line.into()), ("character", character.into())]),
+                ),
+            ]),
+        )
+    }
+
+    fn result(replies: &[Json], id: usize) -> &Json {
+        let reply = replies.iter().find(|r| r.get("id").as_usize() == Some(id));
+        reply.expect("no response").get("result")
+    }
+
+    fn diagnostics(replies: &[Json]) -> Vec<(usize, usize, String)> {
+        let notification = replies
+            .iter()
+            .rev()
+            .find(|r| r.get("method").as_str() == Some("textDocument/publishDiagnostics"))
+            .expect("no diagnostics");
+        let diagnostics = notification.get("params").get("diagnostics");
+        diagnostics
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| {
+                let start = d.get("range").get("start");
+                (
+                    start.get("line").as_usize().unwrap(),
+                    start.get("character").as_usize().unwrap(),
+                    d.get("message").as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn hover_text(replies: &[Json], id: usize) -> Option<String> {
+        let value = result(replies, id).get("contents").get("value").as_str()?;
+        Some(
+            value
+                .trim_start_matches("```voe\n")
+                .trim_end_matches("\n```")
+                .to_string(),
+        )
+    }
+
+    #[test]
+    fn test_lifecycle() {
+        let replies = exchange(&[
+            request(1, "initialize", Json::object([])),
+            notification("initialized", Json::object([])),
+            request(2, "workspace/symbol", Json::object([])),
+            request(3, "shutdown", Json::Null),
+            request(4, "textDocument/hover", Json::object([])),
+            notification("exit", Json::Null),
+            // Nothing after `exit` is read.
+            request(5, "shutdown", Json::Null),
+        ]);
+        assert_eq!(replies.len(), 4);
+        let capabilities = result(&replies, 1).get("capabilities");
+        assert_eq!(capabilities.get("textDocumentSync").as_usize(), Some(1));
+        assert_eq!(capabilities.get("hoverProvider"), &Json::Bool(true));
+        assert_eq!(
+            capabilities.get("documentFormattingProvider"),
+            &Json::Bool(true)
+        );
+        assert_eq!(
+            replies[1].get("error").get("message").as_str(),
+            Some("unknown method `workspace/symbol`")
+        );
+        assert_eq!(result(&replies, 3), &Json::Null);
+        assert_eq!(
+            replies[3].get("error").get("message").as_str(),
+            Some("the server is shut down")
+        );
+        assert_eq!(replies[3].get("id").as_usize(), Some(4));
+    }
+
+    #[test]
+    fn test_diagnostics_on_change() {
+        let change = |text: &str| {
+            notification(
+                "textDocument/didChange",
+                Json::object([
+                    (
+                        "textDocument",
+                        Json::object([("uri", URI.into()), ("version", 2.into())]),
+                    ),
+                    (
+                        "contentChanges",
+                        Json::Array(vec![Json::object([("text", text.into())])]),
+                    ),
+                ]),
+            )
+        };
+        let replies = exchange(&[open("let a = 1i32;\nlet b = a +;\n")]);
+        assert_eq!(
+            diagnostics(&replies),
+            vec![(1, 11, "expected expression, found `;`".to_string())]
+        );
+        let replies = exchange(&[
+            open("let a = 1i32;\n"),
+            change("let a = 1i32;\nlet b: bool = a;\nlet c = missing;\n"),
+        ]);
+        assert_eq!(replies.len(), 2);
+        assert!(diagnostics(&replies[..1]).is_empty());
+        assert_eq!(
+            diagnostics(&replies),
+            vec![
+                (
+                    2,
+                    8,
+                    "cannot find value `missing` in this scope".to_string()
+                ),
+                (
+                    1,
+                    0,
+                    "mismatched types in `let b`: expected `bool`, found `i32`".to_string()
+                ),
+            ]
+        );
+
+        // Problems are placed where they are, not at the first code their
+        // messages quote.
+        let replies = exchange(&[open(
+            "fn f(x: i32) -> i32 { x; }
+let y = 1i32 + f(true);
+",
+        )]);
+        let found = diagnostics(&replies);
+        assert_eq!(found.len(), 1, "{:?}", found);
+        assert_eq!((found[0].0, found[0].1), (1, 15));
+        assert!(found[0].2.contains("argument 1 of `f`"), "{:?}", found);
+
+        // Problems loading imports are at the import.
+        let replies = exchange(&[open("let a = 1i32;\nimport { f } from \"nofile\";\n")]);
+        let found = diagnostics(&replies);
+        assert_eq!(found.len(), 1, "{:?}", found);
+        assert_eq!((found[0].0, found[0].1), (1, 0));
+        assert!(found[0].2.starts_with("cannot read module"), "{:?}", found);
+    }
+
+    #[test]
+    fn test_documents_with_syntax_errors() {
+        let source = "\
+fn twice(x: i32) -> i32 {
+    let y = x * 2i32;
+}
+let broken = ;
+let n = twice(3i32);
+";
+        let replies = exchange(&[
+            open(source),
+            at(1, "textDocument/hover", 1, 8),
+            at(2, "textDocument/hover", 4, 4),
+            at(3, "textDocument/definition", 4, 9),
+        ]);
+        // Only the syntax error is reported, but the rest is analyzed.
+        assert_eq!(
+            diagnostics(&replies),
+            vec![(3, 13, "expected expression, found `;`".to_string())]
+        );
+        assert_eq!(hover_text(&replies, 1).unwrap(), "y: i32");
+        assert_eq!(hover_text(&replies, 2).unwrap(), "n: i32");
+        let start = result(&replies, 3).get("range").get("start");
+        assert_eq!(start.get("line").as_usize(), Some(0));
+        assert_eq!(start.get("character").as_usize(), Some(3));
+    }
+
+    #[test]
+    fn test_hover_and_definition() {
+        let source = "\
+fn twice(x: i32) -> i32 {
+    let y = x * 2i32;
+}
+let x = true;
+let n = twice(3i32) + 1i32;
+let f = |v: i64| v;
+";
+        let replies = exchange(&[
+            open(source),
+            at(1, "textDocument/hover", 1, 12),
+            at(2, "textDocument/hover", 1, 8),
+            at(3, "textDocument/hover", 4, 10),
+            at(4, "textDocument/hover", 4, 14),
+            at(5, "textDocument/hover", 3, 4),
+            at(6, "textDocument/hover", 5, 17),
+            at(7, "textDocument/hover", 4, 4),
+            at(8, "textDocument/definition", 1, 12),
+            at(9, "textDocument/definition", 4, 9),
+            at(10, "textDocument/definition", 4, 20),
+        ]);
+        assert!(diagnostics(&replies).is_empty());
+        // The `x` in `twice` is its parameter, not the variable.
+        assert_eq!(hover_text(&replies, 1).unwrap(), "x: i32");
+        assert_eq!(hover_text(&replies, 2).unwrap(), "y: i32");
+        assert_eq!(hover_text(&replies, 3).unwrap(), "fn twice(x: i32) -> i32");
+        assert_eq!(hover_text(&replies, 4).unwrap(), "i32");
+        assert_eq!(hover_text(&replies, 5).unwrap(), "x: bool");
+        assert_eq!(hover_text(&replies, 6).unwrap(), "v: i64");
+        assert_eq!(hover_text(&replies, 7).unwrap(), "n: i32");
+        let start = |id| {
+            let start = result(&replies, id).get("range").get("start");
+            (
+                start.get("line").as_usize(),
+                start.get("character").as_usize(),
+            )
+        };
+        assert_eq!(start(8), (Some(0), Some(9)));
+        assert_eq!(start(9), (Some(0), Some(3)));
+        assert_eq!(result(&replies, 9).get("uri").as_str(), Some(URI));
+        // Literals name nothing.
+        assert_eq!(result(&replies, 10), &Json::Null);
+    }
+
+    #[test]
+    fn test_symbols_and_formatting() {
+        let source = "class P { x: i32 }\nimpl P {\n    fn get(self) -> i32 { self.x; }\n}\nfn main()->(){let p=new P{x:1i32};}\n";
+        let document = Json::object([("textDocument", Json::object([("uri", URI.into())]))]);
+        let replies = exchange(&[
+            open(source),
+            request(1, "textDocument/documentSymbol", document.clone()),
+            request(2, "textDocument/formatting", document),
+        ]);
+        let symbols: Vec<(String, usize, Option<usize>)> = result(&replies, 1)
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| {
+                (
+                    s.get("detail").as_str().unwrap().to_string(),
+                    s.get("kind").as_usize().unwrap(),
+                    s.get("selectionRange").get("start").get("line").as_usize(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            symbols,
+            vec![
+                ("fn get(self) -> i32".to_string(), SYMBOL_METHOD, Some(2)),
+                ("fn main() -> ()".to_string(), SYMBOL_FUNCTION, Some(4)),
+            ]
+        );
+        let edits = result(&replies, 2).as_array().unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(
+            edits[0].get("newText").as_str().unwrap(),
+            format_source(source, &FormatConfig::default()).unwrap()
+        );
+        assert_eq!(
+            edits[0].get("range").get("end").get("line").as_usize(),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn test_positions() {
+        let text = "ab\né😀x\n";
+        let offsets: Vec<usize> = [(0, 1), (1, 0), (1, 1), (1, 3), (1, 9), (2, 0)]
+            .iter()
+            .map(|&(line, character)| {
+                let position =
+                    Json::object([("line", line.into()), ("character", character.into())]);
+                offset(text, &position).unwrap()
+            })
+            .collect();
+        assert_eq!(offsets, vec![1, 3, 5, 9, 10, 11]);
+        assert_eq!(position(text, 9).get("character").as_usize(), Some(3));
+        assert_eq!(
+            path_of("file:///home/me/my%20code.voe"),
+            PathBuf::from("/home/me/my code.voe")
+        );
+    }
+}
@@ -1,25 +1,49 @@
 #![allow(clippy::result_large_err)] // For now. Will be removed later.
 
+#[cfg(feature = "pest")]
 extern crate pest;
+#[cfg(feature = "pest")]
 extern crate pest_derive;
 
 use clap::Parser;
-use pest::error::Error;
 use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 
+pub mod analysis;
+use analysis::{type_layouts, Analysis, Diagnostic, Resolver, TypeChecker, TypeLayouts};
 pub mod ast_passes;
-use ast_passes::{ASTPass, ConstantFolding};
+use ast_passes::{ASTPass, ConstantFolding, LoopDesugaring};
+pub mod formatter;
+use formatter::FormatConfig;
+pub mod lsp;
+pub mod modules;
+use modules::{FileSystem, ModuleGraph, ModuleLoader};
 pub mod parser;
-use parser::{Program, Rule, VoeParser};
+use parser::Program;
 
 struct VoeCompiler {
-    parser: VoeParser,
+    analyses: Vec<Box<dyn Analysis>>,
     ast_passes: Vec<Box<dyn ASTPass>>,
 }
 
 impl VoeCompiler {
-    pub fn parse(&self, source: &str) -> Result<Program, Error<Rule>> {
-        self.parser.parse_program(source)
+    pub fn load(&self, entry: &Path) -> Result<ModuleGraph, Vec<Diagnostic>> {
+        ModuleLoader::new(FileSystem).load(entry)
+    }
+    pub fn run_analyses(&mut self, graph: &ModuleGraph, module: usize) -> Vec<Diagnostic> {
+        let imports = graph.imported_declarations(module);
+        let mut diagnostics = vec![];
+        for analysis in &mut self.analyses {
+            diagnostics.extend(analysis.analyze_module(&graph.modules[module].program, &imports));
+        }
+        diagnostics
+    }
+    pub fn layouts(&self, graph: &ModuleGraph, module: usize) -> Result<TypeLayouts, Diagnostic> {
+        type_layouts(
+            &graph.modules[module].program,
+            &graph.imported_declarations(module),
+        )
     }
     pub fn run_ast_passes(&mut self, program: Program) -> Program {
         let mut program = program;
@@ -31,45 +55,193 @@ impl VoeCompiler {
 }
 
 #[derive(clap::Parser, Debug)]
-#[command(version, about, long_about)]
+#[command(version, about, long_about, subcommand_negates_reqs = true)]
 struct Args {
-    #[arg(short, long)]
-    source: String,
-    #[arg(short, long)]
-    output: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(short, long, required = true)]
+    source: Option<String>,
+    #[arg(short, long, required = true)]
+    output: Option<String>,
     #[arg(short, long)]
     debug: bool,
 }
 
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Formats source files in place, or standard input to standard output
+    /// if no files are given.
+    Fmt {
+        files: Vec<PathBuf>,
+        /// Only check that the files are formatted, failing if any is not.
+        #[arg(long)]
+        check: bool,
+        /// The settings to use, instead of the `voefmt.toml` nearest to
+        /// each file.
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Runs a language server for editors, speaking the language server
+    /// protocol over standard input and output.
+    Lsp,
+}
+
 fn main() -> Result<(), ()> {
     let args = Args::parse();
-
-    // Fetch file string.
-    let unparsed_file = fs::read_to_string(args.source).expect("cannot read file");
-    if args.debug {
-        println!("Original program:\n\n{}\n", unparsed_file);
+    match args.command {
+        Some(Command::Fmt {
+            files,
+            check,
+            config,
+        }) => format(&files, check, config.as_deref()),
+        Some(Command::Lsp) => lsp::run(io::stdin().lock(), io::stdout().lock()).map_err(|err| {
+            eprintln!("voe lsp: {}", err);
+        }),
+        None => compile(&args.source.unwrap(), &args.output.unwrap(), args.debug),
     }
+}
 
+fn compile(source: &str, output: &str, debug: bool) -> Result<(), ()> {
     // Create compiler struct
     let mut compiler = VoeCompiler {
-        parser: VoeParser,
-        ast_passes: vec![Box::new(ConstantFolding)],
+        analyses: vec![Box::new(Resolver::new()), Box::new(TypeChecker::new())],
+        ast_passes: vec![Box::new(LoopDesugaring::new()), Box::new(ConstantFolding)],
     };
 
-    // Create AST from file string.
-    let file = compiler.parse(&unparsed_file).expect("unsuccessful parse");
-    if args.debug {
-        println!("Parsed program:\n\n{}\n", file);
+    // Load the source file and every module it imports.
+    let graph = compiler.load(Path::new(source)).map_err(|diagnostics| {
+        for diagnostic in &diagnostics {
+            eprintln!("{}", diagnostic);
+        }
+    })?;
+    if debug {
+        for module in &graph.modules {
+            println!("Parsed module `{}`:\n\n{}\n", module.name, module.program);
+        }
+    }
+
+    // Check every module, dependencies first, before transforming, and lay
+    // out the types of those that check.
+    let mut failed = false;
+    for module in graph.order() {
+        let path = graph.modules[module].path.display();
+        let diagnostics = compiler.run_analyses(&graph, module);
+        for diagnostic in &diagnostics {
+            eprintln!("{}: {}", path, diagnostic);
+        }
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            failed = true;
+            continue;
+        }
+        match compiler.layouts(&graph, module) {
+            Ok(layouts) if debug => print_layouts(&graph.modules[module].name, &layouts),
+            Ok(_) => {}
+            Err(diagnostic) => {
+                eprintln!("{}: {}", path, diagnostic);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        return Err(());
     }
+    let file = graph.entry().program.clone();
 
     // Run AST passes.
     let file = compiler.run_ast_passes(file);
-    if args.debug {
+    if debug {
         println!("After AST passes:\n\n{}\n", file);
     }
 
-    fs::write(args.output, format!("{}", file)).map_err(|err| {
+    fs::write(output, format!("{}", file)).map_err(|err| {
         println!("{}", err);
     })?;
     Ok(())
 }
+
+fn print_layouts(module: &str, layouts: &TypeLayouts) {
+    let mut lines = vec![];
+    for layout in layouts.classes.values() {
+        lines.push(format!(
+            "class {}: size {}, align {}",
+            layout.name, layout.size, layout.align
+        ));
+    }
+    for layout in layouts.enums.values() {
+        lines.push(format!(
+            "enum {}: size {}, align {}, tag {}",
+            layout.name, layout.size, layout.align, layout.tag_size
+        ));
+    }
+    if !lines.is_empty() {
+        lines.sort();
+        println!("Layouts in module `{}`:\n\n{}\n", module, lines.join("\n"));
+    }
+}
+
+fn format(files: &[PathBuf], check: bool, config: Option<&Path>) -> Result<(), ()> {
+    let load_config = |dir: &Path| {
+        let path = config
+            .map(Path::to_path_buf)
+            .or_else(|| FormatConfig::find(dir));
+        match path {
+            Some(path) => FormatConfig::load(&path).map_err(|err| eprintln!("error: {}", err)),
+            None => Ok(FormatConfig::default()),
+        }
+    };
+    let format_text = |source: &str, config: &FormatConfig, name: &str| {
+        formatter::format_source(source, config).map_err(|errors| {
+            for err in errors {
+                eprintln!("error: {}", err.with_path(name));
+            }
+        })
+    };
+
+    if files.is_empty() {
+        let mut source = String::new();
+        io::stdin()
+            .read_to_string(&mut source)
+            .map_err(|err| eprintln!("error: cannot read standard input: {}", err))?;
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let formatted = format_text(&source, &load_config(&cwd)?, "<stdin>")?;
+        if check {
+            return if formatted == source { Ok(()) } else { Err(()) };
+        }
+        print!("{}", formatted);
+        return Ok(());
+    }
+
+    let mut failed = false;
+    for file in files {
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("error: cannot read `{}`: {}", file.display(), err);
+                failed = true;
+                continue;
+            }
+        };
+        let dir = file.parent().unwrap_or(Path::new("."));
+        let Ok(formatted) = load_config(dir)
+            .and_then(|config| format_text(&source, &config, &file.display().to_string()))
+        else {
+            failed = true;
+            continue;
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{} is not formatted", file.display());
+            failed = true;
+        } else if let Err(err) = fs::write(file, formatted) {
+            eprintln!("error: cannot write `{}`: {}", file.display(), err);
+            failed = true;
+        }
+    }
+    if failed {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
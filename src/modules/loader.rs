@@ -0,0 +1,553 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use super::{
+    declares_type, signature_type_names, ImportedSymbol, Module, ModuleGraph, SymbolTable,
+};
+use crate::analysis::Diagnostic;
+use crate::parser::ast_index::{AstIndex, Spans};
+use crate::parser::cst::Parse;
+use crate::parser::lexer::Span;
+use crate::parser::{ImportItem, Program, Statement, VoeParser};
+
+/// Where the loader reads module sources from.
+pub trait ModuleSource {
+    fn read(&self, path: &Path) -> std::io::Result<String>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FileSystem;
+
+impl ModuleSource for FileSystem {
+    fn read(&self, path: &Path) -> std::io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+}
+
+impl ModuleSource for HashMap<PathBuf, String> {
+    fn read(&self, path: &Path) -> std::io::Result<String> {
+        self.get(path)
+            .cloned()
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))
+    }
+}
+
+/// An import of `items` from the module at `path`, by the statement at
+/// `span` of the importing module.
+struct ImportRequest {
+    path: PathBuf,
+    items: Vec<ImportItem>,
+    span: Option<Span>,
+}
+
+/// Loads a module and, transitively, every module it imports from.
+pub struct ModuleLoader<S: ModuleSource> {
+    parser: VoeParser,
+    source: S,
+}
+
+impl<S: ModuleSource> ModuleLoader<S> {
+    pub fn new(source: S) -> ModuleLoader<S> {
+        ModuleLoader {
+            parser: VoeParser,
+            source,
+        }
+    }
+
+    pub fn load(&self, entry: &Path) -> Result<ModuleGraph, Vec<Diagnostic>> {
+        let (graph, diagnostics) = self.load_recovering(entry);
+        if diagnostics.is_empty() {
+            Ok(graph)
+        } else {
+            Err(diagnostics)
+        }
+    }
+
+    /// Loads what it can of the modules, returning every problem found
+    /// along with them. Modules that cannot be read are empty, and those
+    /// with syntax errors keep the statements that parse. Problems come in
+    /// the order modules are loaded, so those of the entry module first.
+    ///
+    /// Problems are placed in the source of the entry module: those with a
+    /// module it imports, directly or not, are at the import they are found
+    /// through.
+    pub fn load_recovering(&self, entry: &Path) -> (ModuleGraph, Vec<Diagnostic>) {
+        let mut diagnostics = vec![];
+        let mut modules = vec![];
+        let mut requests = vec![];
+        let mut spans = vec![];
+        let mut indices: HashMap<PathBuf, usize> = HashMap::new();
+        // The import of the entry module each module is found through.
+        let mut origins = vec![None];
+        // Modules that failed to load, which have no declarations.
+        let mut failed = vec![];
+        let mut pending = vec![normalize(entry)];
+        indices.insert(pending[0].clone(), 0);
+
+        // Modules are numbered in the order they are first imported, so the
+        // entry module is always first.
+        let mut next = 0;
+        while next < pending.len() {
+            let index = next;
+            let path = pending[index].clone();
+            next += 1;
+            let mut found = vec![];
+            let (module, module_requests, module_spans) = match self.load_module(&path, &mut found)
+            {
+                Ok(loaded) => loaded,
+                Err(diagnostic) => {
+                    found.push(diagnostic);
+                    failed.push(index);
+                    (
+                        Module {
+                            name: module_name(&path),
+                            path: path.clone(),
+                            program: Program::new(vec![]),
+                            symbols: SymbolTable::default(),
+                        },
+                        vec![],
+                        Spans::default(),
+                    )
+                }
+            };
+            diagnostics.extend(found.into_iter().map(|d| in_entry(d, index, &origins)));
+            for request in &module_requests {
+                if !indices.contains_key(&request.path) {
+                    indices.insert(request.path.clone(), pending.len());
+                    pending.push(request.path.clone());
+                    origins.push(if index == 0 {
+                        request.span
+                    } else {
+                        origins[index]
+                    });
+                }
+            }
+            modules.push(module);
+            requests.push(module_requests);
+            spans.push(module_spans);
+        }
+
+        let dependencies = requests
+            .iter()
+            .map(|rs| {
+                let mut deps: Vec<usize> = rs.iter().map(|r| indices[&r.path]).collect();
+                deps.sort();
+                deps.dedup();
+                deps
+            })
+            .collect();
+        let mut graph = ModuleGraph {
+            modules,
+            dependencies,
+        };
+        if let Some(cycle) = graph.cycle() {
+            let diagnostic = Diagnostic::error(format!(
+                "import cycle: {}",
+                cycle
+                    .iter()
+                    .map(|&m| graph.modules[m].name.clone())
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            ));
+            diagnostics.push(diagnostic.with_span(cycle.iter().find_map(|&m| origins[m])));
+        }
+
+        for (index, module) in graph.modules.iter_mut().enumerate() {
+            module.symbols.exports = exports(&module.program);
+            let found = private_types(module, &spans[index]);
+            diagnostics.extend(found.into_iter().map(|d| in_entry(d, index, &origins)));
+        }
+        for (index, module_requests) in requests.iter().enumerate() {
+            for request in module_requests {
+                let source = indices[&request.path];
+                // A declaration may be missing only because its module
+                // failed to load or parse, which has already been reported.
+                if failed.contains(&source) || graph.modules[source].program.has_errors() {
+                    continue;
+                }
+                for item in &request.items {
+                    if let Err(diagnostic) = graph.import(index, source, item) {
+                        let diagnostic = diagnostic.with_span(request.span);
+                        diagnostics.push(in_entry(diagnostic, index, &origins));
+                    }
+                }
+            }
+        }
+        (graph, diagnostics)
+    }
+
+    /// Reads and parses one module, along with where its statements are.
+    /// Syntax errors are added to `diagnostics`, and the statements that
+    /// did parse are kept so that the module's imports are still followed.
+    fn load_module(
+        &self,
+        path: &Path,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<(Module, Vec<ImportRequest>, Spans), Diagnostic> {
+        let source = self.source.read(path).map_err(|err| {
+            Diagnostic::error(format!("cannot read module `{}`: {}", path.display(), err))
+        })?;
+        let Parse {
+            tree,
+            program,
+            errors,
+        } = self.parser.parse_tree(&source);
+        diagnostics.extend(errors.into_iter().map(|err| {
+            let span = err.span;
+            Diagnostic::error(err.with_path(&path.display().to_string()).to_string())
+                .with_span(Some(span))
+        }));
+        let spans = AstIndex::new(&tree, &program).spans();
+
+        let mut name = module_name(path);
+        let mut requests = vec![];
+        let directory = path.parent().unwrap_or(Path::new(""));
+        for (i, statement) in program.statements.iter().enumerate() {
+            let span = spans.statement(statement);
+            match statement {
+                Statement::Module(declared) => {
+                    if i != 0 {
+                        return Err(Diagnostic::error(format!(
+                            "`module` must be the first statement of `{}`",
+                            path.display()
+                        ))
+                        .with_span(span));
+                    }
+                    if declared.last() != Some(&name) {
+                        return Err(Diagnostic::error(format!(
+                            "module `{}` is declared in `{}`",
+                            declared.join("::"),
+                            path.display()
+                        ))
+                        .with_span(span));
+                    }
+                    name = declared.join("::");
+                }
+                Statement::Import(import) => requests.push(ImportRequest {
+                    path: module_path(directory, Path::new(&import.source)),
+                    items: import.items.clone(),
+                    span,
+                }),
+                Statement::Use(u) => requests.push(ImportRequest {
+                    path: module_path(directory, &u.module.iter().collect::<PathBuf>()),
+                    items: vec![u.item.clone()],
+                    span,
+                }),
+                _ => {}
+            }
+        }
+        let module = Module {
+            name,
+            path: path.to_path_buf(),
+            program,
+            symbols: SymbolTable::default(),
+        };
+        // The statements stay where they are as the module moves, so the
+        // spans found by their address still hold.
+        Ok((module, requests, spans))
+    }
+}
+
+impl ModuleGraph {
+    /// Adds `item` from module `source` to the imports of module `index`.
+    fn import(&mut self, index: usize, source: usize, item: &ImportItem) -> Result<(), Diagnostic> {
+        let exporter = &self.modules[source];
+        if !exporter.symbols.exports.contains_key(&item.name) {
+            let declared = exporter
+                .program
+                .statements
+                .iter()
+                .any(|s| s.declared_name() == Some(&item.name));
+            return Err(Diagnostic::error(if declared {
+                format!(
+                    "`{}` is not exported by module `{}`",
+                    item.name, exporter.name
+                )
+            } else {
+                format!("module `{}` has no `{}`", exporter.name, item.name)
+            }));
+        }
+        let module = &mut self.modules[index];
+        let local = item.local_name().clone();
+        let duplicate = module.symbols.imports.contains_key(&local)
+            || module
+                .program
+                .statements
+                .iter()
+                .any(|s| s.declared_name() == Some(&local));
+        if duplicate {
+            return Err(Diagnostic::error(format!(
+                "`{}` is defined more than once in module `{}`",
+                local, module.name
+            )));
+        }
+        module.symbols.imports.insert(
+            local,
+            ImportedSymbol {
+                module: source,
+                name: item.name.clone(),
+            },
+        );
+        Ok(())
+    }
+}
+
+fn exports(program: &Program) -> HashMap<String, Statement> {
+    program
+        .statements
+        .iter()
+        .filter(|s| matches!(s, Statement::Export(_)))
+        .filter_map(|s| Some((s.declared_name()?.clone(), s.clone())))
+        .collect()
+}
+
+/// Reports exported declarations whose signatures use types the module
+/// declares but does not export, which importers could not name.
+fn private_types(module: &Module, spans: &Spans) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let exported = module
+        .program
+        .statements
+        .iter()
+        .filter(|s| matches!(s, Statement::Export(_)));
+    for statement in exported {
+        for name in signature_type_names(statement) {
+            let private = module
+                .program
+                .statements
+                .iter()
+                .any(|s| declares_type(s, &name) && !matches!(s, Statement::Export(_)));
+            if private {
+                diagnostics.push(
+                    Diagnostic::error(format!(
+                        "`{}` is exported by module `{}` but uses its private type `{}`",
+                        statement.declared_name().unwrap(),
+                        module.name,
+                        name
+                    ))
+                    .with_span(spans.statement(statement)),
+                );
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Places a problem with the module at `index` in the entry module, where
+/// the other modules are at the import they are found through.
+fn in_entry(diagnostic: Diagnostic, index: usize, origins: &[Option<Span>]) -> Diagnostic {
+    match index {
+        0 => diagnostic,
+        _ => diagnostic.with_span(origins[index]),
+    }
+}
+
+fn module_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// The file a module imported as `import` from `directory` lives in.
+fn module_path(directory: &Path, import: &Path) -> PathBuf {
+    normalize(&directory.join(import).with_extension("voe"))
+}
+
+/// Removes `.` and `..` components, so every module has one path.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{Analysis, TypeChecker};
+
+    fn sources(files: &[(&str, &str)]) -> HashMap<PathBuf, String> {
+        files
+            .iter()
+            .map(|(path, source)| (PathBuf::from(path), source.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_load_modules() {
+        let files = sources(&[
+            (
+                "src/main.voe",
+                "import { area, Square as S } from \"shapes/geometry\";
+                 use shapes::geometry::PI;
+                 let a: f64 = area(1.5f64);",
+            ),
+            (
+                "src/shapes/geometry.voe",
+                "module shapes::geometry;
+                 import { square } from \"../util/math\";
+                 export let PI: f64 = 3.14f64;
+                 export class Square { side: f64 }
+                 impl Square { fn grow(self) -> Square {} }
+                 export fn area(side: f64) -> f64 { square(side); }",
+            ),
+            (
+                "src/util/math.voe",
+                "export fn square(x: f64) -> f64 { x * x; }",
+            ),
+        ]);
+        let graph = ModuleLoader::new(files)
+            .load(Path::new("src/main.voe"))
+            .unwrap();
+        let names: Vec<&str> = graph.modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["main", "shapes::geometry", "math"]);
+        assert_eq!(graph.dependencies, vec![vec![1], vec![2], vec![]]);
+        assert_eq!(graph.order(), vec![2, 1, 0]);
+
+        let imports = &graph.entry().symbols.imports;
+        assert_eq!(imports["S"].name, "Square");
+        assert_eq!(imports["PI"].module, 1);
+
+        let declarations: Vec<String> = {
+            let mut d: Vec<String> = graph
+                .imported_declarations(0)
+                .iter()
+                .map(|s| s.to_string())
+                .collect();
+            d.sort();
+            d
+        };
+        assert!(declarations.contains(&"class S {\n    side: f64,\n}".to_string()));
+        assert!(declarations.contains(&"impl S {\n    fn grow(self) -> S {\n\n}\n}".to_string()));
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let files = sources(&[
+            ("a.voe", "import { f, g, h } from \"b\"; fn h() -> i32 {}"),
+            (
+                "b.voe",
+                "import { x } from \"a\"; export fn f() -> i32 {} fn g() -> i32 {}",
+            ),
+        ]);
+        let Err(diagnostics) = ModuleLoader::new(files).load(Path::new("a.voe")) else {
+            panic!("expected errors");
+        };
+        let messages: Vec<String> = diagnostics.into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "import cycle: a -> b -> a",
+                "`g` is not exported by module `b`",
+                "module `b` has no `h`",
+                "module `a` has no `x`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_types_keep_their_module() {
+        let files = sources(&[
+            (
+                "main.voe",
+                "import { mk, Q } from \"shapes\";
+                 class P { y: bool }
+                 let v: bool = mk().y;
+                 let w: i32 = mk().grow().x;
+                 let q: Q = mk().q;",
+            ),
+            (
+                "shapes.voe",
+                "export class Q { n: u8 }
+                 export class P { x: i32, q: Q }
+                 impl P { fn grow(self) -> P {} }
+                 export fn mk() -> P {}",
+            ),
+        ]);
+        let graph = ModuleLoader::new(files)
+            .load(Path::new("main.voe"))
+            .unwrap();
+        let mut declarations: Vec<String> = graph
+            .imported_declarations(0)
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        declarations.sort();
+        assert_eq!(
+            declarations,
+            vec![
+                "class Q {\n    n: u8,\n}",
+                "class shapes::P {\n    x: i32,\n    q: Q,\n}",
+                "fn mk() -> shapes::P {\n\n}",
+                "impl shapes::P {\n    fn grow(self) -> shapes::P {\n\n}\n}",
+            ]
+        );
+
+        let diagnostics = TypeChecker::new()
+            .analyze_module(&graph.entry().program, &graph.imported_declarations(0));
+        let messages: Vec<String> = diagnostics.into_iter().map(|d| d.message).collect();
+        assert_eq!(messages, vec!["class `shapes::P` has no field `y`"]);
+    }
+
+    #[test]
+    fn test_private_types_and_missing_modules() {
+        let files = sources(&[
+            (
+                "a.voe",
+                "import { mk } from \"b\"; import { f, g } from \"missing\";",
+            ),
+            ("b.voe", "class P { x: i32 } export fn mk() -> P {}"),
+        ]);
+        let Err(diagnostics) = ModuleLoader::new(files).load(Path::new("a.voe")) else {
+            panic!("expected errors");
+        };
+        // Both are placed at the import they are found through.
+        let found: Vec<(usize, String)> = diagnostics
+            .into_iter()
+            .map(|d| (d.span.unwrap().start, d.message))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    24,
+                    "cannot read module `missing.voe`: entity not found".to_string()
+                ),
+                (
+                    0,
+                    "`mk` is exported by module `b` but uses its private type `P`".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        // Both modules are parsed, so both of their errors are reported.
+        let files = sources(&[
+            (
+                "a.voe",
+                "import { f } from \"b\";\nlet x = ;\nlet y = 1i32 +;",
+            ),
+            ("b.voe", "export fn f() -> i32 { let z = ; }"),
+        ]);
+        let Err(diagnostics) = ModuleLoader::new(files).load(Path::new("a.voe")) else {
+            panic!("expected errors");
+        };
+        let locations: Vec<&str> = diagnostics
+            .iter()
+            .map(|d| d.message.lines().next().unwrap())
+            .collect();
+        assert_eq!(
+            locations,
+            vec![" --> a.voe:2:9", " --> a.voe:3:15", " --> b.voe:1:32"]
+        );
+    }
+}
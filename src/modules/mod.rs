@@ -0,0 +1,405 @@
+//! Programs spread over several files. Each file is a module; modules
+//! `export` declarations and bring other modules' exports into scope with
+//! `import { .. } from ".."` or `use a::b`.
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::parser::r#type::GType;
+use crate::parser::{FunctionDefinition, Pattern, Program, Statement, Type, VariableDeclaration};
+
+mod loader;
+pub use loader::{FileSystem, ModuleLoader, ModuleSource};
+
+/// A name imported into a module.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ImportedSymbol {
+    /// Index of the exporting module in its `ModuleGraph`.
+    pub module: usize,
+    /// The name the symbol is exported under.
+    pub name: String,
+}
+
+/// The names a module makes visible to others and the names it uses from
+/// others.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct SymbolTable {
+    pub exports: HashMap<String, Statement>,
+    /// Imported symbols, by the name they are known by locally.
+    pub imports: HashMap<String, ImportedSymbol>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Module {
+    pub name: String,
+    pub path: PathBuf,
+    pub program: Program,
+    pub symbols: SymbolTable,
+}
+
+/// Loaded modules and the imports between them. The entry module comes
+/// first.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ModuleGraph {
+    pub modules: Vec<Module>,
+    /// `dependencies[i]` are the modules that module `i` imports from.
+    pub dependencies: Vec<Vec<usize>>,
+}
+
+impl ModuleGraph {
+    pub fn entry(&self) -> &Module {
+        &self.modules[0]
+    }
+
+    /// A chain of imports leading from a module back to itself, if any.
+    pub fn cycle(&self) -> Option<Vec<usize>> {
+        let mut state = vec![VisitState::New; self.modules.len()];
+        let mut path = vec![];
+        (0..self.modules.len()).find_map(|i| self.find_cycle(i, &mut state, &mut path))
+    }
+
+    fn find_cycle(
+        &self,
+        module: usize,
+        state: &mut [VisitState],
+        path: &mut Vec<usize>,
+    ) -> Option<Vec<usize>> {
+        match state[module] {
+            VisitState::Done => return None,
+            VisitState::Visiting => {
+                let start = path.iter().position(|&m| m == module).unwrap();
+                let mut cycle = path[start..].to_vec();
+                cycle.push(module);
+                return Some(cycle);
+            }
+            VisitState::New => {}
+        }
+        state[module] = VisitState::Visiting;
+        path.push(module);
+        for &dependency in &self.dependencies[module] {
+            if let Some(cycle) = self.find_cycle(dependency, state, path) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        state[module] = VisitState::Done;
+        None
+    }
+
+    /// The modules ordered so that every module comes after the modules it
+    /// imports from. The graph must be acyclic.
+    pub fn order(&self) -> Vec<usize> {
+        let mut order = vec![];
+        let mut visited = vec![false; self.modules.len()];
+        for module in 0..self.modules.len() {
+            self.visit(module, &mut visited, &mut order);
+        }
+        order
+    }
+
+    fn visit(&self, module: usize, visited: &mut [bool], order: &mut Vec<usize>) {
+        if visited[module] {
+            return;
+        }
+        visited[module] = true;
+        for &dependency in &self.dependencies[module] {
+            self.visit(dependency, visited, order);
+        }
+        order.push(module);
+    }
+
+    /// The declarations module `index` imports, renamed to their local
+    /// names. Imported classes bring the methods implemented for them in
+    /// their own module. The types in their signatures are renamed to the
+    /// names module `index` knows them by; types it does not import are
+    /// declared too, under names qualified by their module like
+    /// `geometry::Point`, so they are never confused with its own.
+    pub fn imported_declarations(&self, index: usize) -> Vec<Statement> {
+        let mut declarations = vec![];
+        let mut qualified = vec![];
+        for (local, symbol) in &self.modules[index].symbols.imports {
+            declarations.extend(self.import_declaration(
+                index,
+                symbol.module,
+                &symbol.name,
+                local,
+                &mut qualified,
+            ));
+        }
+        let mut declared = HashSet::new();
+        while let Some((module, name)) = qualified.pop() {
+            if declared.insert((module, name.clone())) {
+                let local = format!("{}::{}", self.modules[module].name, name);
+                declarations.extend(self.import_declaration(
+                    index,
+                    module,
+                    &name,
+                    &local,
+                    &mut qualified,
+                ));
+            }
+        }
+        declarations
+    }
+
+    /// The export `name` of module `module` as module `index` sees it,
+    /// declared as `local`, and the impls of it if it is a class. Types it
+    /// uses that module `index` must know by qualified names are added to
+    /// `qualified`.
+    fn import_declaration(
+        &self,
+        index: usize,
+        module: usize,
+        name: &str,
+        local: &str,
+        qualified: &mut Vec<(usize, String)>,
+    ) -> Vec<Statement> {
+        let exporter = &self.modules[module];
+        let Some(statement) = exporter.symbols.exports.get(name) else {
+            return vec![];
+        };
+        let mut rename = |ty: &str| self.type_name_in(index, module, ty, qualified);
+        let mut declarations = vec![rename_types(&renamed(statement, local), &mut rename)];
+        if let Statement::Class(_) = statement.unexported() {
+            declarations.extend(
+                exporter
+                    .program
+                    .statements
+                    .iter()
+                    .filter(|s| matches!(s, Statement::Impl(ib) if ib.class() == name))
+                    .map(|s| {
+                        let Statement::Impl(mut ib) = rename_types(s, &mut rename) else {
+                            unreachable!();
+                        };
+                        ib.class = local.to_string();
+                        Statement::Impl(ib)
+                    }),
+            );
+        }
+        declarations
+    }
+
+    /// The module declaring the type that module `module` calls `name`,
+    /// and the type's name there, if there is such a type.
+    fn type_identity(&self, module: usize, name: &str) -> Option<(usize, String)> {
+        let statements = &self.modules[module].program.statements;
+        if statements.iter().any(|s| declares_type(s, name)) {
+            return Some((module, name.to_string()));
+        }
+        let symbol = self.modules[module].symbols.imports.get(name)?;
+        let exporter = &self.modules[symbol.module].program.statements;
+        exporter
+            .iter()
+            .any(|s| matches!(s, Statement::Export(_)) && declares_type(s, &symbol.name))
+            .then(|| (symbol.module, symbol.name.clone()))
+    }
+
+    /// The name module `index` knows the type `name` of module `module` by,
+    /// or `None` if it is not a type. Types it does not import are added
+    /// to `qualified` and named after their module.
+    fn type_name_in(
+        &self,
+        index: usize,
+        module: usize,
+        name: &str,
+        qualified: &mut Vec<(usize, String)>,
+    ) -> Option<String> {
+        let (module, name) = self.type_identity(module, name)?;
+        if module == index {
+            return Some(name);
+        }
+        let imports = &self.modules[index].symbols.imports;
+        if let Some((local, _)) = imports
+            .iter()
+            .find(|(_, symbol)| symbol.module == module && symbol.name == name)
+        {
+            return Some(local.clone());
+        }
+        let local = format!("{}::{}", self.modules[module].name, name);
+        qualified.push((module, name));
+        Some(local)
+    }
+}
+
+/// Whether `statement` declares the type `name`.
+pub(crate) fn declares_type(statement: &Statement, name: &str) -> bool {
+    matches!(
+        statement.unexported(),
+        Statement::Class(_)
+            | Statement::Interface(_)
+            | Statement::Enum(_)
+            | Statement::TypeAlias(_)
+    ) && statement.declared_name().is_some_and(|n| n == name)
+}
+
+/// The names of the types in the signature of a declaration, apart from
+/// its type and value parameters.
+pub(crate) fn signature_type_names(statement: &Statement) -> Vec<String> {
+    let mut names = vec![];
+    rename_types(statement, &mut |name| {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+        None
+    });
+    names
+}
+
+/// The declaration with the types in its signature renamed by `rename`,
+/// which leaves those it returns `None` for as they are.
+fn rename_types(
+    statement: &Statement,
+    rename: &mut impl FnMut(&str) -> Option<String>,
+) -> Statement {
+    let mut statement = statement.clone();
+    match &mut statement {
+        Statement::Export(inner) => **inner = rename_types(inner, rename),
+        Statement::Function(fd) => rename_function(fd, rename),
+        Statement::VariableDeclaration(vd) => rename_declaration(vd, &[], rename),
+        Statement::Class(class) => {
+            for field in &mut class.fields {
+                rename_declaration(field, &[], rename);
+            }
+        }
+        Statement::Interface(interface) => {
+            for method in &mut interface.methods {
+                for input in &mut method.inputs {
+                    rename_declaration(input, &[], rename);
+                }
+                method.return_type = rename_type(&method.return_type, &[], rename);
+            }
+        }
+        Statement::Enum(enumeration) => {
+            for variant in &mut enumeration.variants {
+                for field in &mut variant.fields {
+                    *field = rename_type(field, &[], rename);
+                }
+            }
+        }
+        Statement::TypeAlias(alias) => {
+            alias.target = rename_type(&alias.target, &alias.params, rename)
+        }
+        Statement::Impl(ib) => {
+            if let Some(interface) = &mut ib.interface {
+                *interface = rename(interface).unwrap_or(interface.clone());
+            }
+            for method in &mut ib.methods {
+                rename_function(method, rename);
+            }
+        }
+        _ => {}
+    }
+    statement
+}
+
+fn rename_function(fd: &mut FunctionDefinition, rename: &mut impl FnMut(&str) -> Option<String>) {
+    let bound: Vec<String> = fd
+        .type_params
+        .iter()
+        .map(|p| p.name.clone())
+        .chain(fd.dependent_params.iter().map(|p| p.name().clone()))
+        .collect();
+    for param in &mut fd.type_params {
+        for interface in &mut param.bounds {
+            *interface = rename(interface).unwrap_or(interface.clone());
+        }
+    }
+    for param in &mut fd.dependent_params {
+        rename_declaration(param, &bound, rename);
+    }
+    for input in &mut fd.inputs {
+        rename_declaration(input, &bound, rename);
+    }
+    fd.return_type = rename_type(&fd.return_type, &bound, rename);
+}
+
+fn rename_declaration(
+    vd: &mut VariableDeclaration,
+    bound: &[String],
+    rename: &mut impl FnMut(&str) -> Option<String>,
+) {
+    vd.var_type = vd
+        .var_type
+        .as_ref()
+        .map(|ty| rename_type(ty, bound, rename));
+}
+
+/// `ty` with the names of types other than those in `bound` renamed.
+fn rename_type(
+    ty: &Type,
+    bound: &[String],
+    rename: &mut impl FnMut(&str) -> Option<String>,
+) -> Type {
+    let mut rename_name = |name: &String| {
+        if bound.contains(name) {
+            name.clone()
+        } else {
+            rename(name).unwrap_or(name.clone())
+        }
+    };
+    match ty {
+        Type::Custom(name) => Type::Custom(rename_name(name)),
+        Type::Generic(gtype) => {
+            let name = rename_name(&gtype.name);
+            Type::Generic(GType::new(
+                name,
+                gtype
+                    .fields
+                    .iter()
+                    .map(|t| rename_type(t, bound, rename))
+                    .collect(),
+            ))
+        }
+        Type::Array(element, len) => {
+            Type::Array(Box::new(rename_type(element, bound, rename)), *len)
+        }
+        Type::Slice(element) => Type::Slice(Box::new(rename_type(element, bound, rename))),
+        Type::Tuple(elements) => Type::Tuple(
+            elements
+                .iter()
+                .map(|t| rename_type(t, bound, rename))
+                .collect(),
+        ),
+        Type::Function(params, ret) => Type::Function(
+            params
+                .iter()
+                .map(|t| rename_type(t, bound, rename))
+                .collect(),
+            Box::new(rename_type(ret, bound, rename)),
+        ),
+        Type::Dependent(dtype) => {
+            let mut bound = bound.to_vec();
+            bound.extend(dtype.params.iter().map(|p| p.name().clone()));
+            let mut dtype = dtype.clone();
+            for param in &mut dtype.params {
+                rename_declaration(param, &bound, rename);
+            }
+            dtype.body = Box::new(rename_type(&dtype.body, &bound, rename));
+            Type::Dependent(dtype)
+        }
+        _ => ty.clone(),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum VisitState {
+    New,
+    Visiting,
+    Done,
+}
+
+/// The declaration without `export` and under a new name.
+fn renamed(statement: &Statement, name: &str) -> Statement {
+    let mut statement = statement.unexported().clone();
+    let name = name.to_string();
+    match &mut statement {
+        Statement::Function(fd) => fd.name = name,
+        Statement::VariableDeclaration(vd) => vd.pattern = Pattern::Binding(name),
+        Statement::Class(class) => class.name = name,
+        Statement::Interface(interface) => interface.name = name,
+        Statement::Enum(enumeration) => enumeration.name = name,
+        Statement::TypeAlias(alias) => alias.name = name,
+        _ => {}
+    }
+    statement
+}
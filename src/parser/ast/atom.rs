@@ -1,14 +1,20 @@
+#[cfg(feature = "pest")]
 use pest::{error::Error, iterators::Pair};
 
+#[cfg(feature = "pest")]
 use crate::parser::Rule;
 
-use super::{expression::parse_expression, Expression, Type};
+#[cfg(feature = "pest")]
+use super::expression::parse_expression;
+use super::{Expression, Type};
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Atom {
     pub negative: bool,
     pub value: AtomValue,
     pub ty: Option<Type>,
+    /// How an integer literal was written, so it prints the same way.
+    pub radix: Radix,
 }
 
 impl Atom {
@@ -17,9 +23,14 @@ impl Atom {
             negative,
             value,
             ty,
+            radix: Radix::Decimal,
         }
     }
 
+    pub fn with_radix(self, radix: Radix) -> Atom {
+        Atom { radix, ..self }
+    }
+
     pub fn get_type(&self) -> Option<Type> {
         self.ty.clone()
     }
@@ -31,16 +42,31 @@ impl Atom {
     pub fn from_i128(i: i128, ty: Option<Type>) -> Atom {
         Atom {
             negative: i < 0,
-            value: AtomValue::Integer(i),
+            value: AtomValue::Integer(i.unsigned_abs()),
             ty,
+            radix: Radix::Decimal,
+        }
+    }
+
+    /// The value of an integer literal, if it is one that fits in an
+    /// `i128`.
+    pub fn signed_value(&self) -> Option<i128> {
+        let AtomValue::Integer(magnitude) = self.value else {
+            return None;
+        };
+        if self.negative {
+            0i128.checked_sub_unsigned(magnitude)
+        } else {
+            i128::try_from(magnitude).ok()
         }
     }
 
     pub fn from_f64(f: f64, ty: Option<Type>) -> Atom {
         Atom {
             negative: f < 0.0,
-            value: AtomValue::Float(f),
+            value: AtomValue::Float(f.abs()),
             ty,
+            radix: Radix::Decimal,
         }
     }
 }
@@ -52,17 +78,31 @@ impl std::fmt::Display for Atom {
             "{}{}{}",
             if self.negative { "-" } else { "" },
             match &self.value {
-                AtomValue::Integer(i) => i.abs().to_string(),
-                AtomValue::Float(f) => f.abs().to_string(),
-                AtomValue::String(s) => s.to_string(),
+                AtomValue::Integer(i) => match self.radix {
+                    Radix::Binary => format!("0b{:b}", i),
+                    Radix::Octal => format!("0o{:o}", i),
+                    Radix::Decimal => i.to_string(),
+                    Radix::Hexadecimal => format!("0x{:X}", i),
+                },
+                // Debug formatting keeps a `.0` or exponent, so the literal
+                // reads back as a float.
+                AtomValue::Float(f) => format!("{:?}", f.abs()),
+                AtomValue::String(s) => quote(s),
                 AtomValue::Boolean(b) => b.to_string(),
                 AtomValue::Identity(i) => i.to_string(),
                 AtomValue::ParExpr(e) => format!("({})", e),
+                AtomValue::Call(name, args) => format!(
+                    "{}({})",
+                    name,
+                    args.iter()
+                        .map(|a| format!("{}", a))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
             },
-            if self.ty.is_some() && self.value.is_simple() {
-                format!("{}", self.ty.as_ref().unwrap())
-            } else {
-                "".to_string()
+            match &self.ty {
+                Some(ty) if self.value.is_simple() => format!("{}", ty),
+                _ => "".to_string(),
             }
         )
     }
@@ -70,35 +110,223 @@ impl std::fmt::Display for Atom {
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum AtomValue {
-    Integer(i128),
+    /// The magnitude of an integer; the sign is the atom's.
+    Integer(u128),
     Float(f64),
     String(String),
     Boolean(bool),
     Identity(String),
     ParExpr(Box<Expression>),
+    Call(String, Vec<Expression>),
 }
 
 impl AtomValue {
     pub fn is_simple(&self) -> bool {
-        matches!(
-            self,
-            AtomValue::Integer(_) | AtomValue::Float(_)
-        )
+        matches!(self, AtomValue::Integer(_) | AtomValue::Float(_))
     }
 }
 
-fn parse_integer(pair: Pair<Rule>) -> Result<AtomValue, Error<Rule>> {
-    let repr = pair.as_str();
-    let var: i128 = repr.parse().unwrap();
-    Ok(AtomValue::Integer(var))
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum Radix {
+    Binary,
+    Octal,
+    #[default]
+    Decimal,
+    Hexadecimal,
+}
+
+#[cfg(feature = "pest")]
+fn literal_error(pair: &Pair<Rule>, message: String) -> Error<Rule> {
+    Error::new_from_span(
+        pest::error::ErrorVariant::CustomError { message },
+        pair.as_span(),
+    )
+}
+
+/// The value of integer digits written without their radix prefix, or
+/// `None` if it does not fit in a `u128`.
+pub fn integer_value(digits: &str, radix: Radix) -> Option<u128> {
+    let base = match radix {
+        Radix::Binary => 2,
+        Radix::Octal => 8,
+        Radix::Decimal => 10,
+        Radix::Hexadecimal => 16,
+    };
+    u128::from_str_radix(&digits.replace('_', ""), base).ok()
+}
+
+/// The value of a `decimal` literal, or `None` if it is too large.
+pub fn float_value(text: &str) -> Option<f64> {
+    let value: f64 = text.replace('_', "").parse().ok()?;
+    value.is_finite().then_some(value)
+}
+
+/// The value of an `integer`, which must fit in a `u128`.
+#[cfg(feature = "pest")]
+pub fn parse_integer(pair: Pair<Rule>) -> Result<(u128, Radix), Error<Rule>> {
+    let literal = pair.into_inner().next().unwrap();
+    let (radix, digits) = match literal.as_rule() {
+        Rule::hex_integer => (Radix::Hexadecimal, &literal.as_str()[2..]),
+        Rule::oct_integer => (Radix::Octal, &literal.as_str()[2..]),
+        Rule::bin_integer => (Radix::Binary, &literal.as_str()[2..]),
+        _ => (Radix::Decimal, literal.as_str()),
+    };
+    let value = integer_value(digits, radix).ok_or_else(|| {
+        literal_error(
+            &literal,
+            format!("integer literal `{}` is too large", literal.as_str()),
+        )
+    })?;
+    Ok((value, radix))
 }
 
+#[cfg(feature = "pest")]
 fn parse_float(pair: Pair<Rule>) -> Result<AtomValue, Error<Rule>> {
-    let repr = pair.as_str();
-    let var: f64 = repr.parse().unwrap();
-    Ok(AtomValue::Float(var))
+    let value = float_value(pair.as_str()).ok_or_else(|| {
+        literal_error(
+            &pair,
+            format!("float literal `{}` is too large", pair.as_str()),
+        )
+    })?;
+    Ok(AtomValue::Float(value))
+}
+
+/// The largest magnitudes of the negative and the positive values of an
+/// integer type.
+fn integer_bounds(ty: &Type) -> Option<(u128, u128)> {
+    Some(match ty {
+        Type::U8 => (0, u8::MAX as u128),
+        Type::I8 => (i8::MIN.unsigned_abs() as u128, i8::MAX as u128),
+        Type::U16 => (0, u16::MAX as u128),
+        Type::I16 => (i16::MIN.unsigned_abs() as u128, i16::MAX as u128),
+        Type::U32 => (0, u32::MAX as u128),
+        Type::I32 => (i32::MIN.unsigned_abs() as u128, i32::MAX as u128),
+        Type::U64 => (0, u64::MAX as u128),
+        Type::I64 => (i64::MIN.unsigned_abs() as u128, i64::MAX as u128),
+        Type::U128 => (0, u128::MAX),
+        Type::I128 => (i128::MIN.unsigned_abs(), i128::MAX as u128),
+        _ => return None,
+    })
+}
+
+/// Why a numeric literal cannot have the type `suffix` names, if it
+/// cannot: the suffix must be a numeric type suiting the kind of literal.
+pub fn suffix_error(value: &AtomValue, suffix: &str) -> Option<String> {
+    let Some(ty) = Type::parse_type(suffix) else {
+        return Some(format!("unsupported literal suffix `{}`", suffix));
+    };
+    let (kind, suits) = match value {
+        AtomValue::Float(_) => ("float", ty.is_decimal()),
+        _ => ("integer", ty.is_integral() || ty.is_decimal()),
+    };
+    (!suits).then(|| format!("{} literal cannot have the suffix `{}`", kind, suffix))
+}
+
+/// Whether a numeric literal fits the type given by its suffix.
+pub fn literal_fits(negative: bool, value: &AtomValue, ty: &Type) -> bool {
+    match value {
+        AtomValue::Integer(i) => match integer_bounds(ty) {
+            Some((min, _)) if negative => *i <= min,
+            Some((_, max)) => *i <= max,
+            None => true,
+        },
+        AtomValue::Float(f) => *ty != Type::F32 || (*f as f32).is_finite(),
+        _ => true,
+    }
+}
+
+/// Checks that a numeric literal fits the type given by its suffix.
+#[cfg(feature = "pest")]
+fn check_range(
+    pair: &Pair<Rule>,
+    negative: bool,
+    value: &AtomValue,
+    ty: &Type,
+) -> Result<(), Error<Rule>> {
+    if literal_fits(negative, value, ty) {
+        return Ok(());
+    }
+    Err(literal_error(
+        pair,
+        format!(
+            "literal `{}{}` is out of range for `{}`",
+            if negative { "-" } else { "" },
+            pair.as_str(),
+            ty
+        ),
+    ))
+}
+
+/// Writes `s` as a quoted string literal that reads back as `s`.
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            '\0' => quoted.push_str("\\0"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// The value of a quoted string literal, quotes included, with escapes
+/// replaced.
+pub fn unescape(text: &str) -> Result<String, String> {
+    let mut value = String::new();
+    let mut chars = text[1..text.len() - 1].chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next().unwrap() {
+            'n' => value.push('\n'),
+            't' => value.push('\t'),
+            'r' => value.push('\r'),
+            '0' => value.push('\0'),
+            'u' => {
+                let code: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                let c = u32::from_str_radix(&code, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("`\\u{{{}}}` is not a valid character", code))?;
+                value.push(c);
+            }
+            '\n' | '\r' => {
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+            }
+            c => value.push(c),
+        }
+    }
+    Ok(value)
 }
 
+/// The text between the quotes of a raw string literal.
+pub fn raw_string_value(text: &str) -> String {
+    let hashes = text[1..].find('"').unwrap();
+    text[hashes + 2..text.len() - hashes - 1].to_string()
+}
+
+/// The value of a `string` literal, with escapes replaced.
+#[cfg(feature = "pest")]
+pub fn parse_string(pair: Pair<Rule>) -> Result<String, Error<Rule>> {
+    let pair = pair.into_inner().next().unwrap();
+    if pair.as_rule() == Rule::raw_string {
+        return Ok(raw_string_value(pair.as_str()));
+    }
+    unescape(pair.as_str()).map_err(|message| literal_error(&pair, message))
+}
+
+#[cfg(feature = "pest")]
 pub fn parse_atom(pair: Pair<Rule>) -> Result<Atom, Error<Rule>> {
     let mut pair = pair.into_inner();
     let mut next = pair.next().unwrap();
@@ -109,12 +337,18 @@ pub fn parse_atom(pair: Pair<Rule>) -> Result<Atom, Error<Rule>> {
     }
 
     let mut ty = None;
+    let mut radix = Radix::Decimal;
     let value = match next.as_rule() {
         Rule::numeric => {
+            let numeric = next.clone();
             let mut inner = next.into_inner();
             let next = inner.next().unwrap();
             let val = match next.as_rule() {
-                Rule::integer => parse_integer(next)?,
+                Rule::integer => {
+                    let (value, literal_radix) = parse_integer(next)?;
+                    radix = literal_radix;
+                    AtomValue::Integer(value)
+                }
                 Rule::decimal => parse_float(next)?,
                 _ => Err(Error::new_from_span(
                     pest::error::ErrorVariant::CustomError {
@@ -123,20 +357,31 @@ pub fn parse_atom(pair: Pair<Rule>) -> Result<Atom, Error<Rule>> {
                     next.as_span(),
                 ))?,
             };
-            ty = inner.next().map(|t| Type::parse_type(t.as_str())).flatten();
+            if let Some(suffix) = inner.next() {
+                if let Some(message) = suffix_error(&val, suffix.as_str()) {
+                    return Err(literal_error(&suffix, message));
+                }
+                let suffix = Type::parse_type(suffix.as_str()).unwrap();
+                check_range(&numeric, negative, &val, &suffix)?;
+                ty = Some(suffix);
+            }
             val
         }
         Rule::string => {
-            let s = next.as_str();
-            let val = AtomValue::String(s[1..s.len() - 1].to_string()); // Remove quotes
             ty = Some(Type::String);
-            val
-        },
+            AtomValue::String(parse_string(next)?)
+        }
         Rule::bool => {
             ty = Some(Type::Bool);
             AtomValue::Boolean(next.as_str() == "true")
         }
         Rule::ident => AtomValue::Identity(next.as_str().to_string()),
+        Rule::call => {
+            let mut inner = next.into_inner();
+            let name = inner.next().unwrap().as_str().to_string();
+            let args = inner.map(parse_expression).collect::<Result<Vec<_>, _>>()?;
+            AtomValue::Call(name, args)
+        }
         Rule::expression => {
             let expr = parse_expression(next)?;
             ty = expr.return_type();
@@ -144,7 +389,7 @@ pub fn parse_atom(pair: Pair<Rule>) -> Result<Atom, Error<Rule>> {
                 return Ok(atom);
             }
             AtomValue::ParExpr(Box::new(expr))
-        },
+        }
         _ => Err(Error::new_from_span(
             pest::error::ErrorVariant::CustomError {
                 message: "expected atom".to_string(),
@@ -157,100 +402,185 @@ pub fn parse_atom(pair: Pair<Rule>) -> Result<Atom, Error<Rule>> {
         negative,
         value,
         ty,
+        radix,
     })
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "pest"))]
 mod tests {
     use pest::Parser;
 
     use super::*;
-    use crate::VoeParser;
+    use crate::parser::{PestParser, VoeParser};
 
     #[test]
     fn test_parse_atom() {
         let input = "123i32";
-        let mut pair = VoeParser::parse(Rule::atom, input).expect("No atom recognized");
+        let mut pair = PestParser::parse(Rule::atom, input).expect("No atom recognized");
         let atom = parse_atom(pair.next().unwrap()).unwrap();
         assert_eq!(
             atom,
             Atom {
                 negative: false,
                 value: AtomValue::Integer(123),
-                ty: Some(Type::I32)
+                ty: Some(Type::I32),
+                radix: Radix::Decimal,
             }
         );
 
         let input = "-123i32";
-        let mut pair = VoeParser::parse(Rule::atom, input).expect("No atom recognized");
+        let mut pair = PestParser::parse(Rule::atom, input).expect("No atom recognized");
         let atom = parse_atom(pair.next().unwrap()).unwrap();
         assert_eq!(
             atom,
             Atom {
                 negative: true,
                 value: AtomValue::Integer(123),
-                ty: Some(Type::I32)
+                ty: Some(Type::I32),
+                radix: Radix::Decimal,
             }
         );
 
         let input = "123.456f32";
-        let mut pair = VoeParser::parse(Rule::atom, input).expect("No atom recognized");
+        let mut pair = PestParser::parse(Rule::atom, input).expect("No atom recognized");
         let atom = parse_atom(pair.next().unwrap()).unwrap();
         assert_eq!(
             atom,
             Atom {
                 negative: false,
                 value: AtomValue::Float(123.456),
-                ty: Some(Type::F32)
+                ty: Some(Type::F32),
+                radix: Radix::Decimal,
             }
         );
 
         let input = "-123.456f64";
-        let mut pair = VoeParser::parse(Rule::atom, input).expect("No atom recognized");
+        let mut pair = PestParser::parse(Rule::atom, input).expect("No atom recognized");
         let atom = parse_atom(pair.next().unwrap()).unwrap();
         assert_eq!(
             atom,
             Atom {
                 negative: true,
                 value: AtomValue::Float(123.456),
-                ty: Some(Type::F64)
+                ty: Some(Type::F64),
+                radix: Radix::Decimal,
             }
         );
 
         let input = "\"hello\"";
-        let mut pair = VoeParser::parse(Rule::atom, input).expect("No atom recognized");
+        let mut pair = PestParser::parse(Rule::atom, input).expect("No atom recognized");
         let atom = parse_atom(pair.next().unwrap()).unwrap();
         assert_eq!(
             atom,
             Atom {
                 negative: false,
                 value: AtomValue::String("hello".to_string()),
-                ty: Some(Type::String)
+                ty: Some(Type::String),
+                radix: Radix::Decimal,
             }
         );
 
         let input = "true";
-        let mut pair = VoeParser::parse(Rule::atom, input).expect("No atom recognized");
+        let mut pair = PestParser::parse(Rule::atom, input).expect("No atom recognized");
         let atom = parse_atom(pair.next().unwrap()).unwrap();
         assert_eq!(
             atom,
             Atom {
                 negative: false,
                 value: AtomValue::Boolean(true),
-                ty: Some(Type::Bool)
+                ty: Some(Type::Bool),
+                radix: Radix::Decimal,
             }
         );
 
         let input = "false";
-        let mut pair = VoeParser::parse(Rule::atom, input).expect("No atom recognized");
+        let mut pair = PestParser::parse(Rule::atom, input).expect("No atom recognized");
         let atom = parse_atom(pair.next().unwrap()).unwrap();
         assert_eq!(
             atom,
             Atom {
                 negative: false,
                 value: AtomValue::Boolean(false),
-                ty: Some(Type::Bool)
+                ty: Some(Type::Bool),
+                radix: Radix::Decimal,
             }
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_string_literals() {
+        let cases = [
+            (
+                r#""tab\there \"quoted\" \\ \u{1F600}""#,
+                "tab\there \"quoted\" \\ \u{1F600}",
+            ),
+            ("\"two\nlines\"", "two\nlines"),
+            ("\"joined \\\n        here\"", "joined here"),
+            (r###"r#"raw "\n" text"#"###, "raw \"\\n\" text"),
+            (r#"r"C:\path""#, "C:\\path"),
+        ];
+        for (input, expected) in cases {
+            let mut pair = PestParser::parse(Rule::atom, input).expect("No atom recognized");
+            let atom = parse_atom(pair.next().unwrap()).unwrap();
+            assert_eq!(
+                atom.value,
+                AtomValue::String(expected.to_string()),
+                "{}",
+                input
+            );
+
+            // Printing gives a literal with the same value.
+            let printed = atom.to_string();
+            let mut pair = PestParser::parse(Rule::atom, &printed).unwrap();
+            assert_eq!(parse_atom(pair.next().unwrap()).unwrap(), atom);
+        }
+        assert!(PestParser::parse(Rule::atom, r#""bad \q escape""#).is_err());
+        assert_eq!(quote("a\"b\\c\nd\u{7}"), r#""a\"b\\c\nd\u{7}""#);
+        let program = VoeParser.parse_program("let s = \"hi\";").unwrap();
+        assert_eq!(program.to_string(), "let s = \"hi\";");
+    }
+
+    #[test]
+    fn test_parse_numeric_literals() {
+        let cases = [
+            ("0xFFu8", AtomValue::Integer(255), "0xFFu8"),
+            ("0o17", AtomValue::Integer(15), "0o17"),
+            ("0b1010_1010i32", AtomValue::Integer(170), "0b10101010i32"),
+            ("1_000_000", AtomValue::Integer(1_000_000), "1000000"),
+            ("-128i8", AtomValue::Integer(128), "-128i8"),
+            ("1e9", AtomValue::Float(1e9), "1000000000.0"),
+            ("2.5E-3f64", AtomValue::Float(2.5e-3), "0.0025f64"),
+            (".5", AtomValue::Float(0.5), "0.5"),
+            ("1.0f32", AtomValue::Float(1.0), "1.0f32"),
+            ("1.e5", AtomValue::Float(1e5), "100000.0"),
+            (
+                "0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffffu128",
+                AtomValue::Integer(u128::MAX),
+                "0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFu128",
+            ),
+        ];
+        for (input, value, printed) in cases {
+            let mut pair = PestParser::parse(Rule::atom, input).expect("No atom recognized");
+            let atom = parse_atom(pair.next().unwrap()).unwrap();
+            assert_eq!(atom.value, value, "{}", input);
+            assert_eq!(atom.to_string(), printed);
+        }
+
+        for (input, message) in [
+            ("300u8", "literal `300u8` is out of range for `u8`"),
+            ("-129i8", "literal `-129i8` is out of range for `i8`"),
+            ("-1u32", "literal `-1u32` is out of range for `u32`"),
+            ("1e40f32", "literal `1e40f32` is out of range for `f32`"),
+            ("2.5u8", "float literal cannot have the suffix `u8`"),
+            ("7char", "unsupported literal suffix `char`"),
+            (
+                "999999999999999999999999999999999999999999",
+                "integer literal `999999999999999999999999999999999999999999` is too large",
+            ),
+        ] {
+            let mut pair = PestParser::parse(Rule::atom, input).expect("No atom recognized");
+            let err = parse_atom(pair.next().unwrap()).unwrap_err();
+            assert_eq!(err.variant.message(), message, "{}", input);
+        }
+    }
+}
@@ -1,17 +1,36 @@
+#[cfg(feature = "pest")]
+use super::expression::parse_expression;
+#[cfg(feature = "pest")]
 use super::statement::parse_statement;
-use super::Statement;
+use super::{Expression, Statement};
+#[cfg(feature = "pest")]
 use crate::parser::Rule;
+#[cfg(feature = "pest")]
 use pest::error::{Error, ErrorVariant};
+#[cfg(feature = "pest")]
 use pest::iterators::Pairs;
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Block {
     pub statements: Vec<Statement>,
+    /// The expression the block evaluates to, written last without a `;`.
+    pub value: Option<Box<Expression>>,
 }
 
 impl Block {
     pub fn new(statements: Vec<Statement>) -> Block {
-        Block { statements }
+        Block {
+            statements,
+            value: None,
+        }
+    }
+
+    /// Makes the block evaluate to `value` after its statements run.
+    pub fn with_value(self, value: Option<Expression>) -> Block {
+        Block {
+            value: value.map(Box::new),
+            ..self
+        }
     }
 
     pub fn statements(&self) -> &Vec<Statement> {
@@ -27,19 +46,23 @@ impl std::fmt::Display for Block {
             self.statements
                 .iter()
                 .map(|s| format!("    {}", s))
+                .chain(self.value.iter().map(|v| format!("    {}", v)))
                 .collect::<Vec<_>>()
                 .join("\n")
         )
     }
 }
 
+#[cfg(feature = "pest")]
 pub fn parse_block(pairs: Pairs<Rule>) -> Result<Block, Error<Rule>> {
     let mut statements = vec![];
+    let mut value = None;
     for pair in pairs {
         match pair.as_rule() {
             Rule::statement => {
                 statements.push(parse_statement(pair.into_inner().next().unwrap())?);
             }
+            Rule::expression => value = Some(parse_expression(pair)?),
             _ => {
                 return Err(Error::new_from_span(
                     ErrorVariant::CustomError {
@@ -50,5 +73,5 @@ pub fn parse_block(pairs: Pairs<Rule>) -> Result<Block, Error<Rule>> {
             }
         }
     }
-    Ok(Block::new(statements))
+    Ok(Block::new(statements).with_value(value))
 }
@@ -0,0 +1,265 @@
+#[cfg(feature = "pest")]
+use pest::error::Error;
+#[cfg(feature = "pest")]
+use pest::iterators::Pair;
+
+use super::function::format_params;
+#[cfg(feature = "pest")]
+use super::r#type::parse_type;
+#[cfg(feature = "pest")]
+use super::statement::{parse_function_declaration, parse_inputs};
+use super::{FunctionDefinition, Type, VariableDeclaration};
+#[cfg(feature = "pest")]
+use crate::parser::Rule;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ClassDefinition {
+    pub name: String,
+    pub fields: Vec<VariableDeclaration>,
+}
+
+impl ClassDefinition {
+    pub fn new(name: String, fields: Vec<VariableDeclaration>) -> ClassDefinition {
+        ClassDefinition { name, fields }
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn fields(&self) -> &Vec<VariableDeclaration> {
+        &self.fields
+    }
+}
+
+impl std::fmt::Display for ClassDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "class {} {{", self.name)?;
+        for field in &self.fields {
+            write!(f, "\n    {},", format_params(std::slice::from_ref(field)))?;
+        }
+        write!(f, "\n}}")
+    }
+}
+
+/// A method declared by an interface, without a body.
+#[derive(PartialEq, Debug, Clone)]
+pub struct MethodSignature {
+    pub name: String,
+    pub inputs: Vec<VariableDeclaration>,
+    pub return_type: Type,
+}
+
+impl MethodSignature {
+    pub fn new(
+        name: String,
+        inputs: Vec<VariableDeclaration>,
+        return_type: Type,
+    ) -> MethodSignature {
+        MethodSignature {
+            name,
+            inputs,
+            return_type,
+        }
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    /// Whether `fd` implements this signature: same name, the same input
+    /// types in the same order, and the same return type. Parameter names
+    /// may differ.
+    pub fn matches(&self, fd: &FunctionDefinition) -> bool {
+        self.name == fd.name
+            && !fd.is_generic()
+            && self.return_type == fd.return_type
+            && self.inputs.len() == fd.inputs.len()
+            && self
+                .inputs
+                .iter()
+                .zip(&fd.inputs)
+                .all(|(a, b)| a.var_type() == b.var_type())
+    }
+}
+
+impl std::fmt::Display for MethodSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "fn {}({}) -> {};",
+            self.name,
+            format_params(&self.inputs),
+            self.return_type
+        )
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct InterfaceDefinition {
+    pub name: String,
+    pub methods: Vec<MethodSignature>,
+}
+
+impl InterfaceDefinition {
+    pub fn new(name: String, methods: Vec<MethodSignature>) -> InterfaceDefinition {
+        InterfaceDefinition { name, methods }
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn methods(&self) -> &Vec<MethodSignature> {
+        &self.methods
+    }
+}
+
+impl std::fmt::Display for InterfaceDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "interface {} {{", self.name)?;
+        for method in &self.methods {
+            write!(f, "\n    {}", method)?;
+        }
+        write!(f, "\n}}")
+    }
+}
+
+/// Methods attached to a class, either inherent (`impl Point`) or fulfilling
+/// an interface (`impl Shape for Point`). Each method takes `self` as its
+/// first input.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ImplBlock {
+    pub interface: Option<String>,
+    pub class: String,
+    pub methods: Vec<FunctionDefinition>,
+}
+
+impl ImplBlock {
+    pub fn new(class: String, methods: Vec<FunctionDefinition>) -> ImplBlock {
+        ImplBlock {
+            interface: None,
+            class,
+            methods,
+        }
+    }
+
+    pub fn for_interface(
+        interface: String,
+        class: String,
+        methods: Vec<FunctionDefinition>,
+    ) -> ImplBlock {
+        ImplBlock {
+            interface: Some(interface),
+            class,
+            methods,
+        }
+    }
+
+    pub fn interface(&self) -> &Option<String> {
+        &self.interface
+    }
+
+    pub fn class(&self) -> &String {
+        &self.class
+    }
+
+    pub fn methods(&self) -> &Vec<FunctionDefinition> {
+        &self.methods
+    }
+}
+
+impl std::fmt::Display for ImplBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "impl ")?;
+        if let Some(interface) = &self.interface {
+            write!(f, "{} for ", interface)?;
+        }
+        write!(
+            f,
+            "{} {{\n{}\n}}",
+            self.class,
+            self.methods
+                .iter()
+                .map(|m| format!("    {}", m))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    }
+}
+
+#[cfg(feature = "pest")]
+pub fn parse_class_declaration(pair: Pair<Rule>) -> Result<ClassDefinition, Error<Rule>> {
+    let mut pair = pair.into_inner();
+    let name = pair.next().unwrap().as_str().to_string();
+    let mut fields = vec![];
+    let mut inner = pair.next().unwrap().into_inner();
+    while let Some(field) = inner.next() {
+        let ty = parse_type(inner.next().unwrap())?;
+        fields.push(VariableDeclaration::new(
+            field.as_str().to_string(),
+            Some(ty),
+            None,
+        ));
+    }
+    Ok(ClassDefinition::new(name, fields))
+}
+
+#[cfg(feature = "pest")]
+pub fn parse_interface_declaration(pair: Pair<Rule>) -> Result<InterfaceDefinition, Error<Rule>> {
+    let mut pair = pair.into_inner();
+    let name = pair.next().unwrap().as_str().to_string();
+    let mut methods = vec![];
+    for method in pair {
+        let mut method = method.into_inner();
+        let name = method.next().unwrap().as_str().to_string();
+        let inputs = parse_inputs(method.next().unwrap())?;
+        let return_type = parse_type(method.next().unwrap())?;
+        methods.push(MethodSignature::new(name, inputs, return_type));
+    }
+    Ok(InterfaceDefinition::new(name, methods))
+}
+
+#[cfg(feature = "pest")]
+pub fn parse_impl_block(pair: Pair<Rule>) -> Result<ImplBlock, Error<Rule>> {
+    let mut pair = pair.into_inner().peekable();
+    let first = pair.next().unwrap().as_str().to_string();
+    let interface_impl = pair.peek().map(|p| p.as_rule()) == Some(Rule::ident);
+    let class = if interface_impl {
+        pair.next().unwrap().as_str().to_string()
+    } else {
+        first.clone()
+    };
+    let methods = pair
+        .map(parse_function_declaration)
+        .collect::<Result<Vec<_>, _>>()?;
+    if interface_impl {
+        Ok(ImplBlock::for_interface(first, class, methods))
+    } else {
+        Ok(ImplBlock::new(class, methods))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::VoeParser;
+
+    #[test]
+    fn test_class_round_trip() {
+        let input = "class Point {\n    x: i32,\n    y: i32,\n}\n\
+                     impl Point {\n    fn sum(self) -> i32 {\n    self.x + self.y;\n}\n}\n\
+                     let p = new Point { x: 1i32, y: 2i32 };\n\
+                     let s = p.sum() + p.x;";
+        let program = VoeParser.parse_program(input).unwrap();
+        assert_eq!(program.to_string(), input);
+    }
+
+    #[test]
+    fn test_interface_round_trip() {
+        let input = "interface Shape {\n    fn area(self) -> f64;\n}\n\
+                     impl Shape for Circle {\n    fn area(self) -> f64 {\n\n}\n}\n\
+                     fn total<T: Shape + Named, U>(a: T, b: U) -> f64 {\n\n}";
+        let program = VoeParser.parse_program(input).unwrap();
+        assert_eq!(program.to_string(), input);
+    }
+}
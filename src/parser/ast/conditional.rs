@@ -1,7 +1,21 @@
-use super::{Block, Expression};
+#[cfg(feature = "pest")]
+use super::block::parse_block;
+#[cfg(feature = "pest")]
+use super::expression::parse_expression;
+#[cfg(feature = "pest")]
+use super::pattern::parse_pattern;
+use super::{Block, Expression, Pattern};
+#[cfg(feature = "pest")]
+use crate::parser::Rule;
+#[cfg(feature = "pest")]
+use pest::error::Error;
+#[cfg(feature = "pest")]
+use pest::iterators::Pair;
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Conditional {
+    /// The pattern of an `if let`, which `condition` is matched against.
+    pub pattern: Option<Pattern>,
     pub condition: Expression,
     pub then_block: Block,
     pub else_block: Option<Block>,
@@ -10,18 +24,28 @@ pub struct Conditional {
 impl Conditional {
     pub fn new(condition: Expression, then_block: Block, else_block: Option<Block>) -> Conditional {
         Conditional {
+            pattern: None,
             condition,
             then_block,
             else_block,
         }
     }
+
+    /// Makes this an `if let pattern = condition`.
+    pub fn with_pattern(self, pattern: Option<Pattern>) -> Conditional {
+        Conditional { pattern, ..self }
+    }
 }
 
 impl std::fmt::Display for Conditional {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "if ")?;
+        if let Some(pattern) = &self.pattern {
+            write!(f, "let {} = ", pattern)?;
+        }
         write!(
             f,
-            "if {} {}{}",
+            "{} {}{}",
             self.condition,
             self.then_block,
             match &self.else_block {
@@ -31,3 +55,25 @@ impl std::fmt::Display for Conditional {
         )
     }
 }
+
+/// Parses an `if` statement or expression. An `else if` becomes an `else`
+/// block holding only the inner `if`.
+#[cfg(feature = "pest")]
+pub fn parse_conditional(pair: Pair<Rule>) -> Result<Conditional, Error<Rule>> {
+    let mut pair = pair.into_inner().peekable();
+    let mut pattern = None;
+    if pair.peek().map(|p| p.as_rule()) != Some(Rule::expression) {
+        pattern = Some(parse_pattern(pair.next().unwrap())?);
+    }
+    let condition = parse_expression(pair.next().unwrap())?;
+    let then_block = parse_block(pair.next().unwrap().into_inner())?;
+    let else_block = match pair.next() {
+        Some(pair) if pair.as_rule() == Rule::if_expr => {
+            let inner = Expression::If(Box::new(parse_conditional(pair)?));
+            Some(Block::new(vec![]).with_value(Some(inner)))
+        }
+        Some(pair) => Some(parse_block(pair.into_inner())?),
+        None => None,
+    };
+    Ok(Conditional::new(condition, then_block, else_block).with_pattern(pattern))
+}
@@ -0,0 +1,89 @@
+#[cfg(feature = "pest")]
+use pest::error::Error;
+#[cfg(feature = "pest")]
+use pest::iterators::Pair;
+
+#[cfg(feature = "pest")]
+use super::r#type::parse_type;
+use super::Type;
+#[cfg(feature = "pest")]
+use crate::parser::Rule;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct EnumVariant {
+    pub name: String,
+    pub fields: Vec<Type>,
+}
+
+impl EnumVariant {
+    pub fn new(name: String, fields: Vec<Type>) -> EnumVariant {
+        EnumVariant { name, fields }
+    }
+}
+
+impl std::fmt::Display for EnumVariant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.fields.is_empty() {
+            write!(
+                f,
+                "({})",
+                self.fields
+                    .iter()
+                    .map(|t| format!("{}", t))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// An algebraic data type: `enum Shape { Circle(f64), Rect(f64, f64) }`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct EnumDefinition {
+    pub name: String,
+    pub variants: Vec<EnumVariant>,
+}
+
+impl EnumDefinition {
+    pub fn new(name: String, variants: Vec<EnumVariant>) -> EnumDefinition {
+        EnumDefinition { name, variants }
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn variants(&self) -> &Vec<EnumVariant> {
+        &self.variants
+    }
+
+    pub fn variant(&self, name: &str) -> Option<&EnumVariant> {
+        self.variants.iter().find(|v| v.name == name)
+    }
+}
+
+impl std::fmt::Display for EnumDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "enum {} {{", self.name)?;
+        for variant in &self.variants {
+            write!(f, "\n    {},", variant)?;
+        }
+        write!(f, "\n}}")
+    }
+}
+
+#[cfg(feature = "pest")]
+pub fn parse_enum_declaration(pair: Pair<Rule>) -> Result<EnumDefinition, Error<Rule>> {
+    let mut pair = pair.into_inner();
+    let name = pair.next().unwrap().as_str().to_string();
+    let mut variants = vec![];
+    for variant in pair {
+        let mut variant = variant.into_inner();
+        let name = variant.next().unwrap().as_str().to_string();
+        let fields = variant.map(parse_type).collect::<Result<Vec<_>, _>>()?;
+        variants.push(EnumVariant::new(name, fields));
+    }
+    Ok(EnumDefinition::new(name, variants))
+}
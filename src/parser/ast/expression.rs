@@ -1,38 +1,110 @@
+#[cfg(feature = "pest")]
 use super::{
-    atom::{parse_atom, Atom, AtomValue},
-    Operator, Type,
+    atom::parse_atom, block::parse_block, conditional::parse_conditional, function::parse_lambda,
+    pattern::parse_match, r#type::parse_length,
 };
+use super::{
+    atom::Atom, block::Block, conditional::Conditional, function::Lambda, pattern::ArmBody,
+    pattern::MatchArm, r#type::format_tuple, Operator, Type, UnaryOp,
+};
+#[cfg(feature = "pest")]
 use crate::parser::Rule;
+#[cfg(feature = "pest")]
 use once_cell::sync::Lazy;
+#[cfg(feature = "pest")]
 use pest::error::Error;
+#[cfg(feature = "pest")]
 use pest::iterators::Pair;
 
+#[cfg(feature = "pest")]
 use pest::pratt_parser::{Assoc::*, Op, PrattParser};
 
+/// Operators from loosest to tightest binding:
+///
+/// | operators                       | associativity |
+/// |---------------------------------|---------------|
+/// | `\|\|`                          | left          |
+/// | `&&`                            | left          |
+/// | `==` `!=` `<` `<=` `>` `>=`     | none          |
+/// | `\|`                            | left          |
+/// | `~` (xor)                       | left          |
+/// | `&`                             | left          |
+/// | `<<` `>>`                       | left          |
+/// | `+` `-`                         | left          |
+/// | `*` `/` `%`                     | left          |
+/// | `^` (pow)                       | right         |
+/// | prefix `-` `!` `~`              |               |
+/// | `.field`, `.method()`, `[index]`|               |
+/// | `is some`, `is none`, `?`       |               |
+///
+/// Comparisons are parsed as left associative and chains of them are
+/// rejected in `parse_expression`.
+#[cfg(feature = "pest")]
 pub static EXPRESSION_PARSER: Lazy<PrattParser<Rule>> = Lazy::new(|| {
     PrattParser::new()
-        .op(Op::infix(Rule::bitwise_and, Left)
-            | Op::infix(Rule::bitwise_or, Left)
-            | Op::infix(Rule::logical_and, Left)
-            | Op::infix(Rule::logical_or, Left))
+        .op(Op::infix(Rule::logical_or, Left))
+        .op(Op::infix(Rule::logical_and, Left))
         .op(Op::infix(Rule::eq, Left)
             | Op::infix(Rule::ne, Left)
             | Op::infix(Rule::lt, Left)
             | Op::infix(Rule::le, Left)
             | Op::infix(Rule::gt, Left)
             | Op::infix(Rule::ge, Left))
+        .op(Op::infix(Rule::bitwise_or, Left))
+        .op(Op::infix(Rule::bitwise_xor, Left))
+        .op(Op::infix(Rule::bitwise_and, Left))
+        .op(Op::infix(Rule::shl, Left) | Op::infix(Rule::shr, Left))
         .op(Op::infix(Rule::add, Left) | Op::infix(Rule::sub, Left))
-        .op(Op::infix(Rule::mul, Left)
-            | Op::infix(Rule::div, Left)
-            | Op::infix(Rule::r#mod, Left)
-            | Op::infix(Rule::pow, Right))
-        .op(Op::prefix(Rule::unary_minus) | Op::prefix(Rule::not))
+        .op(Op::infix(Rule::mul, Left) | Op::infix(Rule::div, Left) | Op::infix(Rule::r#mod, Left))
+        .op(Op::infix(Rule::pow, Right))
+        .op(Op::prefix(Rule::neg) | Op::prefix(Rule::not) | Op::prefix(Rule::bitwise_not))
+        .op(Op::postfix(Rule::field_access)
+            | Op::postfix(Rule::method_call)
+            | Op::postfix(Rule::index)
+            | Op::postfix(Rule::is_some)
+            | Op::postfix(Rule::is_none)
+            | Op::postfix(Rule::try_op))
 });
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum Expression {
     BinaryOperation(Box<Expression>, Operator, Box<Expression>),
+    Unary(UnaryOp, Box<Expression>),
     Atom(Atom),
+    /// `new Class { field: value, ... }`
+    New(String, Vec<(String, Expression)>),
+    FieldAccess(Box<Expression>, String),
+    MethodCall(Box<Expression>, String, Vec<Expression>),
+    /// `Enum::Variant(args...)`
+    Variant(String, String, Vec<Expression>),
+    Match(Box<Expression>, Vec<MatchArm>),
+    /// `if c { a } else { b }`, evaluating to the value of a branch.
+    If(Box<Conditional>),
+    /// `{ statements; value }`, evaluated in a scope of its own.
+    Block(Box<Block>),
+    /// `(a, b, ...)`, with at least one element.
+    Tuple(Vec<Expression>),
+    Lambda(Box<Lambda>),
+    /// `[a, b, c]`
+    Array(Vec<Expression>),
+    /// `[value; n]`
+    Repeat(Box<Expression>, usize),
+    /// `array[index]`
+    Index(Box<Expression>, Box<Expression>),
+    /// `some(value)`
+    Some(Box<Expression>),
+    /// `none`
+    None,
+    /// `ok(value)`
+    Ok(Box<Expression>),
+    /// `err(error)`
+    Err(Box<Expression>),
+    /// `option is some`
+    IsSome(Box<Expression>),
+    /// `option is none`
+    IsNone(Box<Expression>),
+    /// `option?`
+    Try(Box<Expression>),
 }
 
 impl Expression {
@@ -40,6 +112,10 @@ impl Expression {
         Expression::BinaryOperation(Box::new(lhs), op, Box::new(rhs))
     }
 
+    pub fn unary(op: UnaryOp, operand: Expression) -> Expression {
+        Expression::Unary(op, Box::new(operand))
+    }
+
     pub fn atom(atom: Atom) -> Expression {
         Expression::Atom(atom)
     }
@@ -47,9 +123,12 @@ impl Expression {
     pub fn return_type(&self) -> Option<Type> {
         match self {
             Expression::BinaryOperation(lhs, op, rhs) => {
-                if op.is_comparison() {
+                if op.is_comparison() || op.is_logical() {
                     return Some(Type::Bool);
                 }
+                if op.is_shift() {
+                    return lhs.return_type();
+                }
                 let lhs_ty = lhs.return_type();
                 let rhs_ty = rhs.return_type();
                 match (lhs_ty, rhs_ty) {
@@ -57,7 +136,61 @@ impl Expression {
                     _ => None,
                 }
             }
+            Expression::Unary(UnaryOp::Not, _) => Some(Type::Bool),
+            Expression::Unary(_, operand) => operand.return_type(),
             Expression::Atom(atom) => atom.ty.clone(),
+            Expression::New(class, _) => Some(Type::Custom(class.clone())),
+            Expression::Variant(name, ..) => Some(Type::Custom(name.clone())),
+            Expression::Lambda(lambda) => {
+                let ret = match (&lambda.return_type, &lambda.body) {
+                    (Some(ty), _) => ty.clone(),
+                    (None, ArmBody::Expression(body)) => body.return_type()?,
+                    (None, ArmBody::Block(_)) => Type::Unit,
+                };
+                let params = lambda
+                    .params
+                    .iter()
+                    .map(|p| p.var_type().clone())
+                    .collect::<Option<Vec<_>>>()?;
+                Some(Type::Function(params, Box::new(ret)))
+            }
+            Expression::Tuple(elements) => Some(Type::Tuple(
+                elements
+                    .iter()
+                    .map(|e| e.return_type())
+                    .collect::<Option<Vec<_>>>()?,
+            )),
+            Expression::Array(elements) => {
+                let mut types = elements.iter().filter_map(|e| e.return_type());
+                let first = types.next()?;
+                let element =
+                    types.try_fold(
+                        first,
+                        |ty, next| {
+                            if ty == next {
+                                Some(ty)
+                            } else {
+                                ty.join(&next)
+                            }
+                        },
+                    )?;
+                Some(Type::Array(Box::new(element), elements.len()))
+            }
+            Expression::Repeat(value, len) => {
+                Some(Type::Array(Box::new(value.return_type()?), *len))
+            }
+            Expression::Index(array, _) => array.return_type()?.element_type().cloned(),
+            Expression::Some(value) => Some(Type::option(value.return_type()?)),
+            Expression::Block(block) => block.value.as_ref()?.return_type(),
+            Expression::IsSome(_) | Expression::IsNone(_) => Some(Type::Bool),
+            Expression::Try(option) => option.return_type()?.optional_inner().cloned(),
+            Expression::FieldAccess(..)
+            | Expression::MethodCall(..)
+            | Expression::Match(..)
+            | Expression::If(_)
+            | Expression::None
+            | Expression::Ok(_)
+            | Expression::Err(_) => None,
         }
     }
 }
@@ -66,15 +199,133 @@ impl std::fmt::Display for Expression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Expression::BinaryOperation(lhs, op, rhs) => write!(f, "{} {} {}", lhs, op, rhs),
+            Expression::Unary(op, operand) => match **operand {
+                Expression::BinaryOperation(..) => write!(f, "{}({})", op, operand),
+                _ => write!(f, "{}{}", op, operand),
+            },
             Expression::Atom(atom) => write!(f, "{}", atom),
+            Expression::New(class, fields) => {
+                write!(f, "new {} {{", class)?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    write!(f, "{} {}: {}", if i == 0 { "" } else { "," }, name, value)?;
+                }
+                write!(f, " }}")
+            }
+            Expression::FieldAccess(object, field) => write!(f, "{}.{}", object, field),
+            Expression::Tuple(elements) => write!(f, "{}", format_tuple(elements)),
+            Expression::Lambda(lambda) => write!(f, "{}", lambda),
+            Expression::Array(elements) => write!(
+                f,
+                "[{}]",
+                elements
+                    .iter()
+                    .map(|e| format!("{}", e))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Expression::Repeat(value, len) => write!(f, "[{}; {}]", value, len),
+            Expression::Index(array, index) => write!(f, "{}[{}]", array, index),
+            Expression::Some(value) => write!(f, "some({})", value),
+            Expression::None => write!(f, "none"),
+            Expression::Ok(value) => write!(f, "ok({})", value),
+            Expression::Err(error) => write!(f, "err({})", error),
+            Expression::IsSome(option) => write!(f, "{} is some", option),
+            Expression::IsNone(option) => write!(f, "{} is none", option),
+            Expression::Try(option) => write!(f, "{}?", option),
+            Expression::MethodCall(object, method, args) => write!(
+                f,
+                "{}.{}({})",
+                object,
+                method,
+                args.iter()
+                    .map(|a| format!("{}", a))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Expression::Variant(name, variant, args) => {
+                write!(f, "{}::{}", name, variant)?;
+                if !args.is_empty() {
+                    write!(
+                        f,
+                        "({})",
+                        args.iter()
+                            .map(|a| format!("{}", a))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )?;
+                }
+                Ok(())
+            }
+            Expression::Match(scrutinee, arms) => {
+                write!(f, "match {} {{", scrutinee)?;
+                for arm in arms {
+                    write!(f, "\n    {},", arm)?;
+                }
+                write!(f, "\n}}")
+            }
+            Expression::If(cond) => write!(f, "{}", cond),
+            Expression::Block(block) => write!(f, "{}", block),
         }
     }
 }
 
+#[cfg(feature = "pest")]
 pub fn parse_expression(pair: Pair<Rule>) -> Result<Expression, Error<Rule>> {
     EXPRESSION_PARSER
         .map_primary(|primary| match primary.as_rule() {
             Rule::atom => Ok(Expression::Atom(parse_atom(primary)?)),
+            Rule::new_expr => {
+                let mut inner = primary.into_inner();
+                let class = inner.next().unwrap().as_str().to_string();
+                let mut fields = vec![];
+                for field in inner {
+                    let mut field = field.into_inner();
+                    let name = field.next().unwrap().as_str().to_string();
+                    fields.push((name, parse_expression(field.next().unwrap())?));
+                }
+                Ok(Expression::New(class, fields))
+            }
+            Rule::variant_expr => {
+                let mut inner = primary.into_inner();
+                let name = inner.next().unwrap().as_str().to_string();
+                let variant = inner.next().unwrap().as_str().to_string();
+                let args = inner.map(parse_expression).collect::<Result<Vec<_>, _>>()?;
+                Ok(Expression::Variant(name, variant, args))
+            }
+            Rule::match_expr => parse_match(primary),
+            Rule::if_expr => Ok(Expression::If(Box::new(parse_conditional(primary)?))),
+            Rule::value_block => Ok(Expression::Block(Box::new(parse_block(
+                primary.into_inner(),
+            )?))),
+            Rule::lambda => Ok(Expression::Lambda(Box::new(parse_lambda(primary)?))),
+            Rule::tuple_expr => Ok(Expression::Tuple(
+                primary
+                    .into_inner()
+                    .map(parse_expression)
+                    .collect::<Result<Vec<_>, _>>()?,
+            )),
+            Rule::array_expr => Ok(Expression::Array(
+                primary
+                    .into_inner()
+                    .map(parse_expression)
+                    .collect::<Result<Vec<_>, _>>()?,
+            )),
+            Rule::repeat_expr => {
+                let mut inner = primary.into_inner();
+                let value = parse_expression(inner.next().unwrap())?;
+                let len = parse_length(inner.next().unwrap())?;
+                Ok(Expression::Repeat(Box::new(value), len))
+            }
+            Rule::some_expr => Ok(Expression::Some(Box::new(parse_expression(
+                primary.into_inner().next().unwrap(),
+            )?))),
+            Rule::none_expr => Ok(Expression::None),
+            Rule::ok_expr => Ok(Expression::Ok(Box::new(parse_expression(
+                primary.into_inner().next().unwrap(),
+            )?))),
+            Rule::err_expr => Ok(Expression::Err(Box::new(parse_expression(
+                primary.into_inner().next().unwrap(),
+            )?))),
             _ => Err(Error::new_from_span(
                 pest::error::ErrorVariant::CustomError {
                     message: "expected atom".to_string(),
@@ -82,33 +333,58 @@ pub fn parse_expression(pair: Pair<Rule>) -> Result<Expression, Error<Rule>> {
                 primary.as_span(),
             )),
         })
-        .map_prefix(|pf, t| match pf.as_rule() {
-            Rule::unary_minus => {
-                let expr = t?;
-                match expr {
-                    Expression::Atom(atom) => Ok(Expression::Atom(Atom::new(
-                        !atom.negative,
-                        atom.value,
-                        atom.ty,
-                    ))),
-                    Expression::BinaryOperation(..) => Ok(Expression::Atom(Atom::new(
-                        true,
-                        AtomValue::ParExpr(Box::new(expr)),
-                        None,
-                    ))),
+        .map_prefix(|pf, t| {
+            let op = match pf.as_rule() {
+                Rule::neg => UnaryOp::Neg,
+                Rule::not => UnaryOp::Not,
+                Rule::bitwise_not => UnaryOp::BitNot,
+                _ => {
+                    return Err(Error::new_from_span(
+                        pest::error::ErrorVariant::CustomError {
+                            message: "expected unary operator".to_string(),
+                        },
+                        pf.as_span(),
+                    ))
                 }
+            };
+            Ok(Expression::Unary(op, Box::new(t?)))
+        })
+        .map_postfix(|t, pf| {
+            let object = Box::new(t?);
+            match pf.as_rule() {
+                Rule::is_some => return Ok(Expression::IsSome(object)),
+                Rule::is_none => return Ok(Expression::IsNone(object)),
+                Rule::try_op => return Ok(Expression::Try(object)),
+                _ => {}
+            }
+            let mut inner = pf.clone().into_inner();
+            let first = inner.next().unwrap();
+            if pf.as_rule() == Rule::index {
+                return Ok(Expression::Index(
+                    object,
+                    Box::new(parse_expression(first)?),
+                ));
+            }
+            let name = first.as_str().to_string();
+            match pf.as_rule() {
+                Rule::field_access => Ok(Expression::FieldAccess(object, name)),
+                Rule::method_call => Ok(Expression::MethodCall(
+                    object,
+                    name,
+                    inner.map(parse_expression).collect::<Result<Vec<_>, _>>()?,
+                )),
+                _ => Err(Error::new_from_span(
+                    pest::error::ErrorVariant::CustomError {
+                        message: "expected postfix operator".to_string(),
+                    },
+                    pf.as_span(),
+                )),
             }
-            _ => Err(Error::new_from_span(
-                pest::error::ErrorVariant::CustomError {
-                    message: "expected unary operator".to_string(),
-                },
-                pf.as_span(),
-            )),
         })
-        .map_infix(|lhs, op, rhs| {
+        .map_infix(|lhs, op_pair, rhs| {
             let lhs = lhs?;
             let rhs = rhs?;
-            let op = match op.as_rule() {
+            let op = match op_pair.as_rule() {
                 Rule::add => Operator::Add,
                 Rule::sub => Operator::Subtract,
                 Rule::mul => Operator::Multiply,
@@ -121,9 +397,26 @@ pub fn parse_expression(pair: Pair<Rule>) -> Result<Expression, Error<Rule>> {
                 Rule::le => Operator::LessThanOrEqual,
                 Rule::gt => Operator::GreaterThan,
                 Rule::ge => Operator::GreaterThanOrEqual,
-                Rule::not => Operator::Not,
+                Rule::bitwise_and => Operator::And,
+                Rule::bitwise_or => Operator::Or,
+                Rule::bitwise_xor => Operator::Xor,
+                Rule::shl => Operator::ShiftLeft,
+                Rule::shr => Operator::ShiftRight,
+                Rule::logical_and => Operator::LogicalAnd,
+                Rule::logical_or => Operator::LogicalOr,
                 _ => unreachable!(),
             };
+            if let Expression::BinaryOperation(_, lhs_op, _) = &lhs {
+                if op.is_comparison() && lhs_op.is_comparison() {
+                    return Err(Error::new_from_span(
+                        pest::error::ErrorVariant::CustomError {
+                            message: "comparison operators cannot be chained; use parentheses"
+                                .to_string(),
+                        },
+                        op_pair.as_span(),
+                    ));
+                }
+            }
             Ok(Expression::BinaryOperation(
                 Box::new(lhs),
                 op,
@@ -132,3 +425,121 @@ pub fn parse_expression(pair: Pair<Rule>) -> Result<Expression, Error<Rule>> {
         })
         .parse(pair.into_inner())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Statement, VoeParser};
+
+    #[test]
+    fn test_unary_and_bitwise_operators() {
+        let input = "let a = -x + !b;\n\
+                     let c = ~(y << 2i32) ~ z >> 1i32 & 0xFFi32 | w;\n\
+                     let d = -(a + b).len();\n\
+                     let e = --5i32 - -x;";
+        let program = VoeParser.parse_program(input).unwrap();
+        assert_eq!(program.to_string(), input);
+    }
+
+    #[test]
+    fn test_arrays_and_indexing() {
+        let input = "let a: [i32; 3] = [1i32, 2i32, 3i32];\n\
+                     let b = [0u8; 16];\n\
+                     a[0] = a[1 + 1] * -grid[i][j].cells()[k];\n\
+                     p.items[n] = len(b);";
+        let program = VoeParser.parse_program(input).unwrap();
+        assert_eq!(program.to_string(), input);
+        assert_eq!(grouping("a[i] + b[j] * c"), "(a[i] + (b[j] * c))");
+
+        for source in ["f() = 1i32;", "a + b = 1i32;"] {
+            let err = VoeParser.parse_program(source).unwrap_err();
+            assert!(err.to_string().contains("cannot assign to"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_lambdas_and_function_types() {
+        let input = "let add: fn(i32, i32) -> i32 = |a: i32, b: i32| a + b;\n\
+                     let f = || -> () {\n    g();\n};\n\
+                     let h = compose(|x: i32| x | 1i32, |x: i32| x * 2i32);";
+        let program = VoeParser.parse_program(input).unwrap();
+        assert_eq!(program.to_string(), input);
+        assert_eq!(grouping("a || b"), "(a || b)");
+    }
+
+    #[test]
+    fn test_options() {
+        let input = "let a: i32? = some(1i32);\n\
+                     let b: Option<i32>?? = none;\n\
+                     let c = a is some && b is none;\n\
+                     let d = f(x?).y? + -z?;\n\
+                     let e: Option<fn() -> i32> = none;";
+        let program = VoeParser.parse_program(input).unwrap();
+        assert_eq!(
+            program.to_string(),
+            input.replace("Option<i32>??", "i32???")
+        );
+        assert_eq!(grouping("!a is some || b"), "(!a is some || b)");
+        assert!(VoeParser.parse_program("let x = y is something;").is_err());
+    }
+
+    /// Parses `source` as an expression and writes it back with every
+    /// binary operation parenthesized.
+    fn grouping(source: &str) -> String {
+        fn group(expr: &Expression) -> String {
+            match expr {
+                Expression::BinaryOperation(lhs, op, rhs) => {
+                    format!("({} {} {})", group(lhs), op, group(rhs))
+                }
+                Expression::Unary(op, operand) => format!("{}{}", op, group(operand)),
+                expr => expr.to_string(),
+            }
+        }
+        let program = VoeParser
+            .parse_program(&format!("{};", source))
+            .expect("parse failed");
+        match &program.statements[0] {
+            Statement::Expression(expr) => group(expr),
+            statement => panic!("expected an expression, found {}", statement),
+        }
+    }
+
+    #[test]
+    fn test_precedence_levels() {
+        let cases = [
+            ("a || b && c", "(a || (b && c))"),
+            ("a && b || c", "((a && b) || c)"),
+            ("a && b == c", "(a && (b == c))"),
+            ("a != b | c", "(a != (b | c))"),
+            ("a | b ~ c", "(a | (b ~ c))"),
+            ("a ~ b & c", "(a ~ (b & c))"),
+            ("a & b << c", "(a & (b << c))"),
+            ("a >> b - c", "(a >> (b - c))"),
+            ("a + b * c", "(a + (b * c))"),
+            ("a % b ^ c", "(a % (b ^ c))"),
+            ("-a ^ b", "(-a ^ b)"),
+            ("a - b - c", "((a - b) - c)"),
+            ("a / b * c", "((a / b) * c)"),
+            ("a ^ b ^ c", "(a ^ (b ^ c))"),
+            ("a < b || c >= d && !e", "((a < b) || ((c >= d) && !e))"),
+            ("(a < b) == c", "((a < b) == c)"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(grouping(source), expected, "grouping of `{}`", source);
+        }
+    }
+
+    #[test]
+    fn test_comparisons_do_not_chain() {
+        for source in ["a < b < c;", "a == b != c;", "x + 1i32 <= y > z;"] {
+            let err = VoeParser.parse_program(source).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("comparison operators cannot be chained"),
+                "{}",
+                err
+            );
+        }
+        assert!(VoeParser.parse_program("a < b && b < c;").is_ok());
+    }
+}
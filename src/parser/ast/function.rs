@@ -1,8 +1,49 @@
-use super::{Block, Type, VariableDeclaration};
+#[cfg(feature = "pest")]
+use pest::error::Error;
+#[cfg(feature = "pest")]
+use pest::iterators::Pair;
+
+#[cfg(feature = "pest")]
+use super::block::parse_block;
+#[cfg(feature = "pest")]
+use super::expression::parse_expression;
+#[cfg(feature = "pest")]
+use super::r#type::parse_type;
+use super::{ArmBody, Block, Type, VariableDeclaration};
+#[cfg(feature = "pest")]
+use crate::parser::Rule;
+
+/// A generic type parameter with the interfaces it must implement, e.g.
+/// `T: Shape + Named`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct TypeParam {
+    pub name: String,
+    pub bounds: Vec<String>,
+}
+
+impl TypeParam {
+    pub fn new(name: String, bounds: Vec<String>) -> TypeParam {
+        TypeParam { name, bounds }
+    }
+}
+
+impl std::fmt::Display for TypeParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.bounds.is_empty() {
+            write!(f, ": {}", self.bounds.join(" + "))?;
+        }
+        Ok(())
+    }
+}
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct FunctionDefinition {
     pub name: String,
+    pub type_params: Vec<TypeParam>,
+    /// Value parameters bound by a leading `forall`, usable in the types of
+    /// `inputs` and `return_type`.
+    pub dependent_params: Vec<VariableDeclaration>,
     pub inputs: Vec<VariableDeclaration>,
     pub return_type: Type,
     pub body: Block,
@@ -14,9 +55,21 @@ impl FunctionDefinition {
         inputs: Vec<VariableDeclaration>,
         return_type: Type,
         body: Block,
+    ) -> FunctionDefinition {
+        FunctionDefinition::dependent(name, vec![], inputs, return_type, body)
+    }
+
+    pub fn dependent(
+        name: String,
+        dependent_params: Vec<VariableDeclaration>,
+        inputs: Vec<VariableDeclaration>,
+        return_type: Type,
+        body: Block,
     ) -> FunctionDefinition {
         FunctionDefinition {
             name,
+            type_params: vec![],
+            dependent_params,
             inputs,
             return_type,
             body,
@@ -27,6 +80,29 @@ impl FunctionDefinition {
         &self.name
     }
 
+    pub fn with_type_params(self, type_params: Vec<TypeParam>) -> FunctionDefinition {
+        FunctionDefinition {
+            type_params,
+            ..self
+        }
+    }
+
+    pub fn type_params(&self) -> &Vec<TypeParam> {
+        &self.type_params
+    }
+
+    pub fn is_generic(&self) -> bool {
+        !self.type_params.is_empty()
+    }
+
+    pub fn dependent_params(&self) -> &Vec<VariableDeclaration> {
+        &self.dependent_params
+    }
+
+    pub fn is_dependent(&self) -> bool {
+        !self.dependent_params.is_empty()
+    }
+
     pub fn inputs(&self) -> &Vec<VariableDeclaration> {
         &self.inputs
     }
@@ -40,19 +116,97 @@ impl FunctionDefinition {
     }
 }
 
+/// Formats parameters as `name: type`, without the `let` that
+/// `VariableDeclaration`'s own `Display` adds.
+pub fn format_params(params: &[VariableDeclaration]) -> String {
+    params
+        .iter()
+        .map(|p| match p.var_type() {
+            Some(ty) => format!("{}: {}", p.name(), ty),
+            None => p.name().clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 impl std::fmt::Display for FunctionDefinition {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_dependent() {
+            write!(f, "forall {}. ", format_params(&self.dependent_params))?;
+        }
+        write!(f, "fn {}", self.name)?;
+        if self.is_generic() {
+            write!(
+                f,
+                "<{}>",
+                self.type_params
+                    .iter()
+                    .map(|p| format!("{}", p))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
         write!(
             f,
-            "fn {}({}) -> {} {}",
-            self.name,
-            self.inputs
-                .iter()
-                .map(|i| format!("{}", i))
-                .collect::<Vec<_>>()
-                .join(", "),
+            "({}) -> {} {}",
+            format_params(&self.inputs),
             self.return_type,
             self.body
         )
     }
 }
+
+/// An anonymous function, like `|x: i32| x + 1`. Lambdas may use the
+/// variables of the blocks they are written in.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Lambda {
+    pub params: Vec<VariableDeclaration>,
+    /// Written only for block bodies, whose type cannot be inferred.
+    pub return_type: Option<Type>,
+    pub body: ArmBody,
+}
+
+impl Lambda {
+    pub fn new(
+        params: Vec<VariableDeclaration>,
+        return_type: Option<Type>,
+        body: ArmBody,
+    ) -> Lambda {
+        Lambda {
+            params,
+            return_type,
+            body,
+        }
+    }
+}
+
+impl std::fmt::Display for Lambda {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "|{}| ", format_params(&self.params))?;
+        if let Some(ty) = &self.return_type {
+            write!(f, "-> {} ", ty)?;
+        }
+        write!(f, "{}", self.body)
+    }
+}
+
+#[cfg(feature = "pest")]
+pub fn parse_lambda(pair: Pair<Rule>) -> Result<Lambda, Error<Rule>> {
+    let mut params = vec![];
+    let mut return_type = None;
+    let mut body = None;
+    for pair in pair.into_inner() {
+        match pair.as_rule() {
+            Rule::lambda_param => {
+                let mut inner = pair.into_inner();
+                let name = inner.next().unwrap().as_str().to_string();
+                let ty = parse_type(inner.next().unwrap())?;
+                params.push(VariableDeclaration::new(name, Some(ty), None));
+            }
+            Rule::block => body = Some(ArmBody::Block(parse_block(pair.into_inner())?)),
+            Rule::expression => body = Some(ArmBody::Expression(parse_expression(pair)?)),
+            _ => return_type = Some(parse_type(pair)?),
+        }
+    }
+    Ok(Lambda::new(params, return_type, body.unwrap()))
+}
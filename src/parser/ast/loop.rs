@@ -0,0 +1,156 @@
+#[cfg(feature = "pest")]
+use pest::error::Error;
+#[cfg(feature = "pest")]
+use pest::iterators::Pair;
+
+#[cfg(feature = "pest")]
+use super::block::parse_block;
+#[cfg(feature = "pest")]
+use super::expression::parse_expression;
+#[cfg(feature = "pest")]
+use super::pattern::parse_pattern;
+use super::{Block, Expression, Pattern};
+#[cfg(feature = "pest")]
+use crate::parser::Rule;
+
+/// `while condition { ... }`, the loop every other loop is lowered to.
+#[derive(PartialEq, Debug, Clone)]
+pub struct WhileLoop {
+    pub condition: Expression,
+    pub body: Block,
+}
+
+impl WhileLoop {
+    pub fn new(condition: Expression, body: Block) -> WhileLoop {
+        WhileLoop { condition, body }
+    }
+}
+
+impl std::fmt::Display for WhileLoop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "while {} {}", self.condition, self.body)
+    }
+}
+
+/// `start..end`, `start..=end`, optionally followed by `step n`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Range {
+    pub start: Expression,
+    pub end: Expression,
+    pub inclusive: bool,
+    pub step: Option<Expression>,
+}
+
+impl std::fmt::Display for Range {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dots = if self.inclusive { "..=" } else { ".." };
+        write!(f, "{}{}{}", self.start, dots, self.end)?;
+        if let Some(step) = &self.step {
+            write!(f, " step {}", step)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a `for` loop iterates over.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Iterable {
+    Range(Box<Range>),
+    /// An array or slice.
+    Expression(Expression),
+}
+
+impl std::fmt::Display for Iterable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Iterable::Range(range) => write!(f, "{}", range),
+            Iterable::Expression(expr) => write!(f, "{}", expr),
+        }
+    }
+}
+
+/// `for pattern in iterable { ... }`. `LoopDesugaring` rewrites these into
+/// `while` loops.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ForLoop {
+    pub pattern: Pattern,
+    pub iterable: Iterable,
+    pub body: Block,
+}
+
+impl ForLoop {
+    pub fn new(pattern: Pattern, iterable: Iterable, body: Block) -> ForLoop {
+        ForLoop {
+            pattern,
+            iterable,
+            body,
+        }
+    }
+}
+
+impl std::fmt::Display for ForLoop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "for {} in {} {}", self.pattern, self.iterable, self.body)
+    }
+}
+
+#[cfg(feature = "pest")]
+pub fn parse_while_loop(pair: Pair<Rule>) -> Result<WhileLoop, Error<Rule>> {
+    let mut pair = pair.into_inner();
+    let condition = parse_expression(pair.next().unwrap())?;
+    let body = parse_block(pair.next().unwrap().into_inner())?;
+    Ok(WhileLoop::new(condition, body))
+}
+
+#[cfg(feature = "pest")]
+pub fn parse_for_loop(pair: Pair<Rule>) -> Result<ForLoop, Error<Rule>> {
+    let mut pair = pair.into_inner();
+    let pattern_pair = pair.next().unwrap();
+    let span = pattern_pair.as_span();
+    let pattern = parse_pattern(pattern_pair)?;
+    if !pattern.is_irrefutable() {
+        return Err(Error::new_from_span(
+            pest::error::ErrorVariant::CustomError {
+                message: format!("refutable pattern `{}` in `for`", pattern),
+            },
+            span,
+        ));
+    }
+    let iterable = pair.next().unwrap();
+    let iterable = match iterable.as_rule() {
+        Rule::range => {
+            let mut range = iterable.into_inner();
+            let start = parse_expression(range.next().unwrap())?;
+            let inclusive = range.next().unwrap().as_rule() == Rule::range_inclusive;
+            let end = parse_expression(range.next().unwrap())?;
+            let step = range.next().map(parse_expression).transpose()?;
+            Iterable::Range(Box::new(Range {
+                start,
+                end,
+                inclusive,
+                step,
+            }))
+        }
+        _ => Iterable::Expression(parse_expression(iterable)?),
+    };
+    let body = parse_block(pair.next().unwrap().into_inner())?;
+    Ok(ForLoop::new(pattern, iterable, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::VoeParser;
+
+    #[test]
+    fn test_loop_round_trip() {
+        let input = "while n > 0i32 {\n    n = n - 1i32;\n}\n\
+                     for i in 0..10 {\n    f(i);\n}\n\
+                     for j in n..=2i32 * n step 2i32 {\n    f(j);\n}\n\
+                     for (a, _) in pairs {\n    f(a);\n}";
+        let program = VoeParser.parse_program(input).unwrap();
+        assert_eq!(program.to_string(), input);
+
+        let err = VoeParser.parse_program("for 1i32 in xs {}").unwrap_err();
+        assert!(err.to_string().contains("refutable pattern"), "{}", err);
+    }
+}
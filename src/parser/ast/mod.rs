@@ -5,17 +5,32 @@ pub mod atom;
 pub mod block;
 pub use block::Block;
 
+pub mod class;
+pub use class::{ClassDefinition, ImplBlock, InterfaceDefinition, MethodSignature};
+
 pub mod conditional;
 pub use conditional::Conditional;
 
 pub mod expression;
 pub use expression::Expression;
 
+pub mod r#enum;
+pub use r#enum::{EnumDefinition, EnumVariant};
+
+pub mod r#loop;
+pub use r#loop::{ForLoop, Iterable, Range, WhileLoop};
+
 pub mod function;
-pub use function::FunctionDefinition;
+pub use function::{FunctionDefinition, Lambda, TypeParam};
+
+pub mod module;
+pub use module::{Import, ImportItem, Use};
 
 pub mod operator;
-pub use operator::Operator;
+pub use operator::{Operator, UnaryOp};
+
+pub mod pattern;
+pub use pattern::{ArmBody, MatchArm, Pattern};
 
 pub mod program;
 pub use program::Program;
@@ -24,7 +39,7 @@ pub mod statement;
 pub use statement::Statement;
 
 pub mod r#type;
-pub use r#type::Type;
+pub use r#type::{Type, TypeAlias};
 
 pub mod var;
-pub use var::VariableDeclaration;
+pub use var::{Assignment, VariableDeclaration};
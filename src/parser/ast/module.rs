@@ -0,0 +1,153 @@
+#[cfg(feature = "pest")]
+use pest::error::Error;
+#[cfg(feature = "pest")]
+use pest::iterators::Pair;
+
+#[cfg(feature = "pest")]
+use super::atom::parse_string;
+use super::atom::quote;
+#[cfg(feature = "pest")]
+use crate::parser::Rule;
+
+/// A name brought into scope from another module, optionally renamed.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ImportItem {
+    pub name: String,
+    pub alias: Option<String>,
+}
+
+impl ImportItem {
+    pub fn new(name: String, alias: Option<String>) -> ImportItem {
+        ImportItem { name, alias }
+    }
+
+    /// The name the item is known by in the importing module.
+    pub fn local_name(&self) -> &String {
+        self.alias.as_ref().unwrap_or(&self.name)
+    }
+}
+
+impl std::fmt::Display for ImportItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.alias {
+            Some(alias) => write!(f, "{} as {}", self.name, alias),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// `import { area, Point as P } from "geometry";`
+#[derive(PartialEq, Debug, Clone)]
+pub struct Import {
+    pub items: Vec<ImportItem>,
+    /// Path of the imported file, relative to the importing one and without
+    /// the `.voe` extension.
+    pub source: String,
+}
+
+impl Import {
+    pub fn new(items: Vec<ImportItem>, source: String) -> Import {
+        Import { items, source }
+    }
+}
+
+impl std::fmt::Display for Import {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "import {{ {} }} from {};",
+            self.items
+                .iter()
+                .map(|i| format!("{}", i))
+                .collect::<Vec<_>>()
+                .join(", "),
+            quote(&self.source)
+        )
+    }
+}
+
+/// `use geometry::area as a;`
+#[derive(PartialEq, Debug, Clone)]
+pub struct Use {
+    /// The module path, without the imported name.
+    pub module: Vec<String>,
+    pub item: ImportItem,
+}
+
+impl Use {
+    pub fn new(module: Vec<String>, item: ImportItem) -> Use {
+        Use { module, item }
+    }
+}
+
+impl std::fmt::Display for Use {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "use {}::{};", self.module.join("::"), self.item)
+    }
+}
+
+#[cfg(feature = "pest")]
+fn parse_module_path(pair: Pair<Rule>) -> Vec<String> {
+    pair.into_inner().map(|p| p.as_str().to_string()).collect()
+}
+
+#[cfg(feature = "pest")]
+pub fn parse_module_declaration(pair: Pair<Rule>) -> Result<Vec<String>, Error<Rule>> {
+    Ok(parse_module_path(pair.into_inner().next().unwrap()))
+}
+
+#[cfg(feature = "pest")]
+fn parse_import_item(pair: Pair<Rule>) -> ImportItem {
+    let mut pair = pair.into_inner();
+    let name = pair.next().unwrap().as_str().to_string();
+    let alias = pair.next().map(|p| p.as_str().to_string());
+    ImportItem::new(name, alias)
+}
+
+#[cfg(feature = "pest")]
+pub fn parse_import_declaration(pair: Pair<Rule>) -> Result<Import, Error<Rule>> {
+    let mut items = vec![];
+    let mut source = String::new();
+    for pair in pair.into_inner() {
+        match pair.as_rule() {
+            Rule::import_item => items.push(parse_import_item(pair)),
+            _ => source = parse_string(pair)?,
+        }
+    }
+    Ok(Import::new(items, source))
+}
+
+#[cfg(feature = "pest")]
+pub fn parse_use_declaration(pair: Pair<Rule>) -> Result<Use, Error<Rule>> {
+    let span = pair.as_span();
+    let mut pair = pair.into_inner();
+    let mut module = parse_module_path(pair.next().unwrap());
+    let alias = pair.next().map(|p| p.as_str().to_string());
+    if module.len() < 2 {
+        return Err(Error::new_from_span(
+            pest::error::ErrorVariant::CustomError {
+                message: "expected `module::name`".to_string(),
+            },
+            span,
+        ));
+    }
+    let name = module.pop().unwrap();
+    Ok(Use::new(module, ImportItem::new(name, alias)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::VoeParser;
+
+    #[test]
+    fn test_module_round_trip() {
+        let input = "module shapes::geometry;\n\
+                     import { area, Point as P } from \"../common/point\";\n\
+                     use geometry::area as a;\n\
+                     use std::math::sqrt;\n\
+                     export fn double(x: i32) -> i32 {\n\n}\n\
+                     export class Square {\n    side: f64,\n}";
+        let program = VoeParser.parse_program(input).unwrap();
+        assert_eq!(program.to_string(), input);
+    }
+}
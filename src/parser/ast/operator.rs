@@ -18,8 +18,10 @@ pub enum Operator {
     GreaterThanOrEqual,
     And,
     Or,
-    Not,
-    Neg,
+    /// Bitwise exclusive or, written `~` since `^` is `Pow`.
+    Xor,
+    ShiftLeft,
+    ShiftRight,
 }
 
 impl Operator {
@@ -35,6 +37,15 @@ impl Operator {
         )
     }
 
+    /// `&&` and `||`, which take and produce `bool`.
+    pub fn is_logical(&self) -> bool {
+        matches!(self, Operator::LogicalAnd | Operator::LogicalOr)
+    }
+
+    pub fn is_shift(&self) -> bool {
+        matches!(self, Operator::ShiftLeft | Operator::ShiftRight)
+    }
+
     pub fn return_type(&self, ctx_rhs: &Atom, ctx_lhs: &Atom) -> Option<Type> {
         match self {
             Operator::Equal
@@ -46,7 +57,6 @@ impl Operator {
             Operator::And | Operator::Or | Operator::LogicalAnd | Operator::LogicalOr => {
                 Some(Type::Bool)
             }
-            Operator::Not | Operator::Neg => None,
             _ => {
                 let (tyl, tyr) = (ctx_lhs.ty.clone()?, ctx_rhs.ty.clone()?);
                 if tyl.is_integral() && tyr.is_integral() || tyl.is_decimal() && tyr.is_decimal() {
@@ -77,9 +87,30 @@ impl std::fmt::Display for Operator {
             Operator::Or => write!(f, "|"),
             Operator::LogicalAnd => write!(f, "&&"),
             Operator::LogicalOr => write!(f, "||"),
-            Operator::Not => write!(f, "!"),
-            Operator::Neg => write!(f, "-"),
+            Operator::Xor => write!(f, "~"),
+            Operator::ShiftLeft => write!(f, "<<"),
+            Operator::ShiftRight => write!(f, ">>"),
             Operator::Pow => write!(f, "^"),
         }
     }
 }
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum UnaryOp {
+    /// `-x`
+    Neg,
+    /// `!x`, logical not.
+    Not,
+    /// `~x`, bitwise not.
+    BitNot,
+}
+
+impl std::fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnaryOp::Neg => write!(f, "-"),
+            UnaryOp::Not => write!(f, "!"),
+            UnaryOp::BitNot => write!(f, "~"),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+#[cfg(feature = "pest")]
+use pest::error::Error;
+#[cfg(feature = "pest")]
+use pest::iterators::Pair;
+
+#[cfg(feature = "pest")]
+use super::atom::parse_atom;
+use super::atom::Atom;
+#[cfg(feature = "pest")]
+use super::block::parse_block;
+#[cfg(feature = "pest")]
+use super::expression::parse_expression;
+use super::r#type::format_tuple;
+use super::{Block, Expression};
+#[cfg(feature = "pest")]
+use crate::parser::Rule;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Pattern {
+    /// `_`
+    Wildcard,
+    /// A name bound to the matched value.
+    Binding(String),
+    /// A number, boolean or string literal.
+    Literal(Atom),
+    /// `Enum::Variant(p1, p2, ...)`
+    Variant(String, String, Vec<Pattern>),
+    /// `(p1, p2, ...)`
+    Tuple(Vec<Pattern>),
+    /// `some(p)`
+    Some(Box<Pattern>),
+    /// `none`
+    None,
+    /// `ok(p)`
+    Ok(Box<Pattern>),
+    /// `err(p)`
+    Err(Box<Pattern>),
+}
+
+impl Pattern {
+    /// Names bound by the pattern, in order of appearance.
+    pub fn bindings(&self) -> Vec<&String> {
+        match self {
+            Pattern::Binding(name) => vec![name],
+            Pattern::Variant(_, _, fields) | Pattern::Tuple(fields) => {
+                fields.iter().flat_map(|p| p.bindings()).collect()
+            }
+            Pattern::Some(inner) | Pattern::Ok(inner) | Pattern::Err(inner) => inner.bindings(),
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::None => vec![],
+        }
+    }
+
+    /// Whether the pattern matches every value of its type.
+    pub fn is_irrefutable(&self) -> bool {
+        match self {
+            Pattern::Wildcard | Pattern::Binding(_) => true,
+            Pattern::Tuple(fields) => fields.iter().all(|p| p.is_irrefutable()),
+            Pattern::Variant(..)
+            | Pattern::Literal(_)
+            | Pattern::Some(_)
+            | Pattern::None
+            | Pattern::Ok(_)
+            | Pattern::Err(_) => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Binding(name) => write!(f, "{}", name),
+            Pattern::Literal(atom) => write!(f, "{}", atom),
+            Pattern::Variant(name, variant, fields) => {
+                write!(f, "{}::{}", name, variant)?;
+                if !fields.is_empty() {
+                    write!(
+                        f,
+                        "({})",
+                        fields
+                            .iter()
+                            .map(|p| format!("{}", p))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )?;
+                }
+                Ok(())
+            }
+            Pattern::Tuple(fields) => write!(f, "{}", format_tuple(fields)),
+            Pattern::Some(inner) => write!(f, "some({})", inner),
+            Pattern::None => write!(f, "none"),
+            Pattern::Ok(inner) => write!(f, "ok({})", inner),
+            Pattern::Err(inner) => write!(f, "err({})", inner),
+        }
+    }
+}
+
+/// The body of a `match` arm or a lambda.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ArmBody {
+    Expression(Expression),
+    Block(Block),
+}
+
+impl std::fmt::Display for ArmBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArmBody::Expression(expr) => write!(f, "{}", expr),
+            ArmBody::Block(block) => write!(f, "{}", block),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: ArmBody,
+}
+
+impl MatchArm {
+    pub fn new(pattern: Pattern, body: ArmBody) -> MatchArm {
+        MatchArm { pattern, body }
+    }
+}
+
+impl std::fmt::Display for MatchArm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} => {}", self.pattern, self.body)
+    }
+}
+
+#[cfg(feature = "pest")]
+pub fn parse_pattern(pair: Pair<Rule>) -> Result<Pattern, Error<Rule>> {
+    match pair.as_rule() {
+        Rule::wildcard => Ok(Pattern::Wildcard),
+        Rule::ident => Ok(Pattern::Binding(pair.as_str().to_string())),
+        Rule::literal_pattern => Ok(Pattern::Literal(parse_atom(pair)?)),
+        Rule::variant_pattern => {
+            let mut pair = pair.into_inner();
+            let name = pair.next().unwrap().as_str().to_string();
+            let variant = pair.next().unwrap().as_str().to_string();
+            let fields = pair.map(parse_pattern).collect::<Result<Vec<_>, _>>()?;
+            Ok(Pattern::Variant(name, variant, fields))
+        }
+        Rule::some_pattern => Ok(Pattern::Some(Box::new(parse_pattern(
+            pair.into_inner().next().unwrap(),
+        )?))),
+        Rule::none_pattern => Ok(Pattern::None),
+        Rule::ok_pattern => Ok(Pattern::Ok(Box::new(parse_pattern(
+            pair.into_inner().next().unwrap(),
+        )?))),
+        Rule::err_pattern => Ok(Pattern::Err(Box::new(parse_pattern(
+            pair.into_inner().next().unwrap(),
+        )?))),
+        Rule::tuple_pattern => Ok(Pattern::Tuple(
+            pair.into_inner()
+                .map(parse_pattern)
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        _ => Err(Error::new_from_span(
+            pest::error::ErrorVariant::CustomError {
+                message: "expected pattern".to_string(),
+            },
+            pair.as_span(),
+        )),
+    }
+}
+
+#[cfg(feature = "pest")]
+pub fn parse_match(pair: Pair<Rule>) -> Result<Expression, Error<Rule>> {
+    let mut pair = pair.into_inner();
+    let scrutinee = parse_expression(pair.next().unwrap())?;
+    let mut arms = vec![];
+    for case in pair {
+        let mut case = case.into_inner();
+        let pattern = parse_pattern(case.next().unwrap())?;
+        let body = case.next().unwrap();
+        let body = match body.as_rule() {
+            Rule::block => ArmBody::Block(parse_block(body.into_inner())?),
+            _ => ArmBody::Expression(parse_expression(body)?),
+        };
+        arms.push(MatchArm::new(pattern, body));
+    }
+    Ok(Expression::Match(Box::new(scrutinee), arms))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::VoeParser;
+
+    #[test]
+    fn test_match_round_trip() {
+        let input = "enum Shape {\n    Circle(f64),\n    Rect(f64, f64),\n    Empty,\n}\n\
+                     let s = Shape::Rect(1.5f64, 2.5f64);\n\
+                     let a = match s {\n    Shape::Circle(r) => r * r,\n    \
+                     Shape::Rect(w, 2.5f64) => {\n    w;\n},\n    _ => -1.5f64,\n};";
+        let program = VoeParser.parse_program(input).unwrap();
+        assert_eq!(program.to_string(), input);
+    }
+
+    #[test]
+    fn test_option_and_result_round_trip() {
+        let input = "fn parse(s: string) -> Result<i32, string> {\n    err(s);\n}\n\
+                     let r = match parse(s) {\n    ok(n) => some(n),\n    err(_) => none,\n};\n\
+                     if let (some(a), ok(b)) = (r, parse(t)) {\n    a + b;\n} else {\n    0i32;\n}";
+        let program = VoeParser.parse_program(input).unwrap();
+        assert_eq!(program.to_string(), input);
+    }
+
+    #[test]
+    fn test_tuple_round_trip() {
+        let input = "fn divmod(a: i32, b: i32) -> (i32, i32) {\n    (a / b, a % b);\n}\n\
+                     let (q, (r, _)): (i32, (i32, bool)) = (1i32, (divmod(7i32, 2i32).1, true));\n\
+                     let one: (f64,) = (1.5f64,);\n\
+                     let x = nested.0.1 + one.0;";
+        let program = VoeParser.parse_program(input).unwrap();
+        assert_eq!(program.to_string(), input);
+
+        let err = VoeParser.parse_program("let (a, 1i32) = p;").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("refutable pattern `(a, 1i32)` in `let`"),
+            "{}",
+            err
+        );
+    }
+}
@@ -1,8 +1,12 @@
+#[cfg(feature = "pest")]
 use crate::parser::Rule;
 
+#[cfg(feature = "pest")]
 use super::statement::parse_statement;
 use super::Statement;
+#[cfg(feature = "pest")]
 use pest::error::Error;
+#[cfg(feature = "pest")]
 use pest::iterators::Pair;
 
 #[derive(PartialEq, Debug, Clone)]
@@ -14,6 +18,13 @@ impl Program {
     pub fn new(statements: Vec<Statement>) -> Program {
         Program { statements }
     }
+
+    /// Whether any statement failed to parse.
+    pub fn has_errors(&self) -> bool {
+        self.statements
+            .iter()
+            .any(|s| matches!(s, Statement::Error(_)))
+    }
 }
 
 impl std::fmt::Display for Program {
@@ -30,6 +41,7 @@ impl std::fmt::Display for Program {
     }
 }
 
+#[cfg(feature = "pest")]
 pub fn parse_program(pairs: Pair<Rule>) -> Result<Program, Error<Rule>> {
     let mut statements = vec![];
     for pair in pairs.into_inner() {
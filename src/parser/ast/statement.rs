@@ -1,14 +1,42 @@
+#[cfg(feature = "pest")]
+use super::class::{parse_class_declaration, parse_impl_block, parse_interface_declaration};
+#[cfg(feature = "pest")]
+use super::conditional::parse_conditional;
+#[cfg(feature = "pest")]
+use super::module::{parse_import_declaration, parse_module_declaration, parse_use_declaration};
+#[cfg(feature = "pest")]
+use super::pattern::{parse_match, parse_pattern};
+#[cfg(feature = "pest")]
 use super::r#Type;
-use super::Conditional;
+#[cfg(feature = "pest")]
+use super::r#enum::parse_enum_declaration;
+#[cfg(feature = "pest")]
+use super::r#loop::{parse_for_loop, parse_while_loop};
+#[cfg(feature = "pest")]
+use super::r#type::parse_type_alias;
+use super::r#type::TypeAlias;
 use super::FunctionDefinition;
-use super::VariableDeclaration;
+#[cfg(feature = "pest")]
+use super::TypeParam;
+use super::{Assignment, VariableDeclaration};
+use super::{Block, Conditional, ForLoop, WhileLoop};
+use super::{ClassDefinition, EnumDefinition, ImplBlock, InterfaceDefinition};
+use super::{Import, Use};
+#[cfg(feature = "pest")]
 use pest::error::Error;
+#[cfg(feature = "pest")]
 use pest::iterators::Pair;
 
+#[cfg(feature = "pest")]
+use super::atom::AtomValue;
+#[cfg(feature = "pest")]
 use super::block::parse_block;
+#[cfg(feature = "pest")]
 use super::expression::parse_expression;
+#[cfg(feature = "pest")]
 use super::r#type::parse_type;
 use super::Expression;
+#[cfg(feature = "pest")]
 use crate::parser::Rule;
 
 #[derive(PartialEq, Debug, Clone)]
@@ -16,7 +44,25 @@ pub enum Statement {
     Function(FunctionDefinition),
     VariableDeclaration(VariableDeclaration),
     Expression(Expression),
+    Assignment(Assignment),
     Conditional(Conditional),
+    /// `{ ... }`, a nested scope.
+    Block(Block),
+    While(WhileLoop),
+    For(Box<ForLoop>),
+    Class(ClassDefinition),
+    Impl(ImplBlock),
+    Interface(InterfaceDefinition),
+    Enum(EnumDefinition),
+    TypeAlias(TypeAlias),
+    /// `module a::b;`
+    Module(Vec<String>),
+    Import(Import),
+    Use(Use),
+    /// A declaration visible to other modules.
+    Export(Box<Statement>),
+    /// Source text that failed to parse, kept as written.
+    Error(String),
 }
 
 impl Statement {
@@ -32,9 +78,82 @@ impl Statement {
         Statement::Expression(e)
     }
 
+    pub fn assignment(a: Assignment) -> Statement {
+        Statement::Assignment(a)
+    }
+
     pub fn conditional(c: Conditional) -> Statement {
         Statement::Conditional(c)
     }
+
+    pub fn block(b: Block) -> Statement {
+        Statement::Block(b)
+    }
+
+    pub fn while_loop(w: WhileLoop) -> Statement {
+        Statement::While(w)
+    }
+
+    pub fn for_loop(f: ForLoop) -> Statement {
+        Statement::For(Box::new(f))
+    }
+
+    pub fn class(c: ClassDefinition) -> Statement {
+        Statement::Class(c)
+    }
+
+    pub fn impl_block(i: ImplBlock) -> Statement {
+        Statement::Impl(i)
+    }
+
+    pub fn interface(i: InterfaceDefinition) -> Statement {
+        Statement::Interface(i)
+    }
+
+    pub fn enumeration(e: EnumDefinition) -> Statement {
+        Statement::Enum(e)
+    }
+
+    pub fn type_alias(t: TypeAlias) -> Statement {
+        Statement::TypeAlias(t)
+    }
+
+    pub fn module(path: Vec<String>) -> Statement {
+        Statement::Module(path)
+    }
+
+    pub fn import(i: Import) -> Statement {
+        Statement::Import(i)
+    }
+
+    pub fn use_declaration(u: Use) -> Statement {
+        Statement::Use(u)
+    }
+
+    pub fn export(s: Statement) -> Statement {
+        Statement::Export(Box::new(s))
+    }
+
+    /// The statement with any `export` removed.
+    pub fn unexported(&self) -> &Statement {
+        match self {
+            Statement::Export(s) => s.unexported(),
+            s => s,
+        }
+    }
+
+    /// The name introduced by a declaration, if the statement is one.
+    pub fn declared_name(&self) -> Option<&String> {
+        match self.unexported() {
+            Statement::Function(fd) => Some(fd.name()),
+            Statement::VariableDeclaration(vd) if vd.is_simple() => Some(vd.name()),
+            Statement::Class(c) => Some(c.name()),
+            Statement::Interface(i) => Some(i.name()),
+            Statement::Enum(e) => Some(e.name()),
+            Statement::TypeAlias(t) => Some(t.name()),
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for Statement {
@@ -43,80 +162,153 @@ impl std::fmt::Display for Statement {
             Statement::Function(fi) => write!(f, "{}", fi),
             Statement::VariableDeclaration(v) => write!(f, "{};", v),
             Statement::Expression(e) => write!(f, "{};", e),
+            Statement::Assignment(a) => write!(f, "{};", a),
             Statement::Conditional(c) => write!(f, "{}", c),
+            Statement::Block(b) => write!(f, "{}", b),
+            Statement::While(w) => write!(f, "{}", w),
+            Statement::For(l) => write!(f, "{}", l),
+            Statement::Class(c) => write!(f, "{}", c),
+            Statement::Impl(i) => write!(f, "{}", i),
+            Statement::Interface(i) => write!(f, "{}", i),
+            Statement::Enum(e) => write!(f, "{}", e),
+            Statement::TypeAlias(t) => write!(f, "{};", t),
+            Statement::Module(path) => write!(f, "module {};", path.join("::")),
+            Statement::Import(i) => write!(f, "{}", i),
+            Statement::Use(u) => write!(f, "{}", u),
+            Statement::Export(s) => write!(f, "export {}", s),
+            Statement::Error(text) => write!(f, "{}", text),
         }
     }
 }
 
+#[cfg(feature = "pest")]
 pub fn parse_inputs(pair: Pair<Rule>) -> Result<Vec<VariableDeclaration>, Error<Rule>> {
     let mut params = vec![];
-    for pair in pair.into_inner() {
-        let mut pair = pair.into_inner();
-        let name = pair.next().unwrap().as_str().to_string();
+    let mut pair = pair.into_inner();
+    while let Some(name) = pair.next() {
+        if name.as_rule() == Rule::self_param {
+            params.push(VariableDeclaration::new("self".to_string(), None, None));
+            continue;
+        }
+        let name = name.as_str().to_string();
         let ty = parse_type(pair.next().unwrap())?;
         params.push(VariableDeclaration::new(name, Some(ty), None));
     }
     Ok(params)
 }
 
+#[cfg(feature = "pest")]
+pub fn parse_function_declaration(pair: Pair<Rule>) -> Result<FunctionDefinition, Error<Rule>> {
+    let mut pair = pair.into_inner().peekable();
+    let name = pair.next().unwrap().as_str().to_string();
+    let mut type_params = vec![];
+    if pair.peek().map(|p| p.as_rule()) == Some(Rule::type_params) {
+        for param in pair.next().unwrap().into_inner() {
+            let mut param = param.into_inner();
+            let name = param.next().unwrap().as_str().to_string();
+            let bounds = param.map(|b| b.as_str().to_string()).collect();
+            type_params.push(TypeParam::new(name, bounds));
+        }
+    }
+    let inputs = parse_inputs(pair.next().unwrap())?;
+    let return_type: r#Type = parse_type(pair.next().unwrap())?;
+    let block = parse_block(pair.next().unwrap().into_inner())?;
+    Ok(FunctionDefinition::new(name, inputs, return_type, block).with_type_params(type_params))
+}
+
+#[cfg(feature = "pest")]
 pub fn parse_statement(pair: Pair<Rule>) -> Result<Statement, Error<Rule>> {
     match pair.as_rule() {
-        Rule::function_declaration => {
+        Rule::function_declaration => Ok(Statement::Function(parse_function_declaration(pair)?)),
+        Rule::dfunction_declaration => {
             let mut pair = pair.into_inner();
-            let name = pair.next().unwrap().as_str().to_string();
-            let inputs = parse_inputs(pair.next().unwrap())?;
-            let return_type: r#Type = parse_type(pair.next().unwrap())?;
-            let block = parse_block(pair.next().unwrap().into_inner())?;
-            Ok(Statement::Function(FunctionDefinition::new(
-                name,
-                inputs,
-                return_type,
-                block,
-            )))
+            let dependent_params = parse_inputs(pair.next().unwrap())?;
+            let fd = parse_function_declaration(pair.next().unwrap())?;
+            Ok(Statement::Function(FunctionDefinition {
+                dependent_params,
+                ..fd
+            }))
         }
         Rule::variable_declaration => {
             let mut pair = pair.into_inner();
-            let name = pair.next().unwrap().as_str().to_string();
+            let pattern_pair = pair.next().unwrap();
+            let span = pattern_pair.as_span();
+            let pattern = parse_pattern(pattern_pair)?;
+            if !pattern.is_irrefutable() {
+                return Err(Error::new_from_span(
+                    pest::error::ErrorVariant::CustomError {
+                        message: format!("refutable pattern `{}` in `let`", pattern),
+                    },
+                    span,
+                ));
+            }
             let mut ty = None;
             if let Some(pair) = pair.next() {
                 match pair.as_rule() {
                     Rule::expression => {
-                        return Ok(Statement::VariableDeclaration(VariableDeclaration::new(
-                            name,
-                            None,
-                            Some(parse_expression(pair)?),
-                        )));
+                        return Ok(Statement::VariableDeclaration(
+                            VariableDeclaration::destructuring(
+                                pattern,
+                                None,
+                                Some(parse_expression(pair)?),
+                            ),
+                        ));
                     }
                     _ => ty = Some(parse_type(pair)?),
                 }
             }
             if let Some(pair) = pair.next() {
-                return Ok(Statement::VariableDeclaration(VariableDeclaration::new(
-                    name,
-                    ty,
-                    Some(parse_expression(pair)?),
-                )));
+                return Ok(Statement::VariableDeclaration(
+                    VariableDeclaration::destructuring(pattern, ty, Some(parse_expression(pair)?)),
+                ));
             }
-            Ok(Statement::VariableDeclaration(VariableDeclaration::new(
-                name, ty, None,
-            )))
+            Ok(Statement::VariableDeclaration(
+                VariableDeclaration::destructuring(pattern, ty, None),
+            ))
         }
+        Rule::while_loop => Ok(Statement::While(parse_while_loop(pair)?)),
+        Rule::for_loop => Ok(Statement::for_loop(parse_for_loop(pair)?)),
+        Rule::class_declaration => Ok(Statement::Class(parse_class_declaration(pair)?)),
+        Rule::interface_declaration => Ok(Statement::Interface(parse_interface_declaration(pair)?)),
+        Rule::impl_block => Ok(Statement::Impl(parse_impl_block(pair)?)),
+        Rule::enum_declaration => Ok(Statement::Enum(parse_enum_declaration(pair)?)),
+        Rule::type_alias => Ok(Statement::TypeAlias(parse_type_alias(pair)?)),
+        Rule::module_declaration => Ok(Statement::Module(parse_module_declaration(pair)?)),
+        Rule::import_declaration => Ok(Statement::Import(parse_import_declaration(pair)?)),
+        Rule::use_declaration => Ok(Statement::Use(parse_use_declaration(pair)?)),
+        Rule::export_declaration => Ok(Statement::export(parse_statement(
+            pair.into_inner().next().unwrap(),
+        )?)),
         Rule::expression => Ok(Statement::Expression(parse_expression(pair)?)),
-        Rule::conditional => {
+        Rule::match_expr => Ok(Statement::Expression(parse_match(pair)?)),
+        Rule::value_block => Ok(Statement::Expression(Expression::Block(Box::new(
+            parse_block(pair.into_inner())?,
+        )))),
+        Rule::block => Ok(Statement::Block(parse_block(pair.into_inner())?)),
+        Rule::assignment => {
             let mut pair = pair.into_inner();
-            let condition = parse_expression(pair.next().unwrap())?;
-            let then_block = parse_block(pair.next().unwrap().into_inner())?;
-            if let Some(pair) = pair.next() {
-                return Ok(Statement::Conditional(Conditional::new(
-                    condition,
-                    then_block,
-                    Some(parse_block(pair.into_inner())?),
-                )));
+            let target_pair = pair.next().unwrap();
+            let span = target_pair.as_span();
+            let target = parse_expression(target_pair)?;
+            let assignable = match &target {
+                Expression::Atom(atom) => {
+                    !atom.negative && matches!(atom.value, AtomValue::Identity(_))
+                }
+                Expression::FieldAccess(..) | Expression::Index(..) => true,
+                _ => false,
+            };
+            if !assignable {
+                return Err(Error::new_from_span(
+                    pest::error::ErrorVariant::CustomError {
+                        message: format!("cannot assign to `{}`", span.as_str()),
+                    },
+                    span,
+                ));
             }
-            Ok(Statement::Conditional(Conditional::new(
-                condition, then_block, None,
-            )))
+            let value = parse_expression(pair.next().unwrap())?;
+            Ok(Statement::Assignment(Assignment::new(target, value)))
         }
+        Rule::conditional => Ok(Statement::Conditional(parse_conditional(pair)?)),
         _ => Err(Error::new_from_span(
             pest::error::ErrorVariant::CustomError {
                 message: "expected statement".to_string(),
@@ -1,8 +1,14 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "pest")]
 use pest::{error::Error, iterators::Pair};
 
+#[cfg(feature = "pest")]
 use crate::parser::Rule;
 
-use super::{statement::parse_inputs, VariableDeclaration};
+#[cfg(feature = "pest")]
+use super::{atom::parse_integer, statement::parse_inputs};
+use super::{function::format_params, VariableDeclaration};
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum Type {
@@ -10,16 +16,27 @@ pub enum Type {
     U16,
     U32,
     U64,
+    U128,
     I8,
     I16,
     I32,
     I64,
+    I128,
     F32,
     F64,
     Bool,
     String,
     Unit,
     Custom(String),
+    /// `[T; n]`
+    Array(Box<Type>, usize),
+    /// `[T]`, an array whose length is only known at runtime.
+    Slice(Box<Type>),
+    /// `(A, B, ...)`, with at least one element. The empty tuple is `Unit`.
+    Tuple(Vec<Type>),
+    /// `fn(A, B) -> R`, the type of functions and closures.
+    Function(Vec<Type>, Box<Type>),
+    Const(i128),
     Dependent(DType),
     Generic(GType),
 }
@@ -31,10 +48,12 @@ impl Type {
             "u16" => Some(Type::U16),
             "u32" => Some(Type::U32),
             "u64" => Some(Type::U64),
+            "u128" => Some(Type::U128),
             "i8" => Some(Type::I8),
             "i16" => Some(Type::I16),
             "i32" => Some(Type::I32),
             "i64" => Some(Type::I64),
+            "i128" => Some(Type::I128),
             "f32" => Some(Type::F32),
             "f64" => Some(Type::F64),
             "bool" => Some(Type::Bool),
@@ -51,10 +70,12 @@ impl Type {
                 | Type::U16
                 | Type::U32
                 | Type::U64
+                | Type::U128
                 | Type::I8
                 | Type::I16
                 | Type::I32
                 | Type::I64
+                | Type::I128
         )
     }
 
@@ -63,7 +84,60 @@ impl Type {
     }
 
     pub fn is_signed(&self) -> bool {
-        matches!(self, Type::I8 | Type::I16 | Type::I32 | Type::I64)
+        matches!(
+            self,
+            Type::I8 | Type::I16 | Type::I32 | Type::I64 | Type::I128
+        )
+    }
+
+    /// The number of bits in an integral type.
+    pub fn bit_width(&self) -> Option<u32> {
+        match self {
+            Type::U8 | Type::I8 => Some(8),
+            Type::U16 | Type::I16 => Some(16),
+            Type::U32 | Type::I32 => Some(32),
+            Type::U64 | Type::I64 => Some(64),
+            Type::U128 | Type::I128 => Some(128),
+            _ => None,
+        }
+    }
+
+    /// `Option<T>`, the type of values that may be absent.
+    pub fn option(inner: Type) -> Type {
+        Type::Generic(GType::new("Option".to_string(), vec![inner]))
+    }
+
+    /// The type of the value an option may hold, if this is `Option<T>`.
+    pub fn optional_inner(&self) -> Option<&Type> {
+        match self {
+            Type::Generic(gtype) if gtype.name == "Option" && gtype.fields.len() == 1 => {
+                Some(&gtype.fields[0])
+            }
+            _ => None,
+        }
+    }
+
+    /// `Result<T, E>`, the type of values that are either a `T` or an error.
+    pub fn result(ok: Type, err: Type) -> Type {
+        Type::Generic(GType::new("Result".to_string(), vec![ok, err]))
+    }
+
+    /// The value and error types, if this is `Result<T, E>`.
+    pub fn result_types(&self) -> Option<(&Type, &Type)> {
+        match self {
+            Type::Generic(gtype) if gtype.name == "Result" && gtype.fields.len() == 2 => {
+                Some((&gtype.fields[0], &gtype.fields[1]))
+            }
+            _ => None,
+        }
+    }
+
+    /// The type of the elements of an array or slice.
+    pub fn element_type(&self) -> Option<&Type> {
+        match self {
+            Type::Array(element, _) | Type::Slice(element) => Some(element),
+            _ => None,
+        }
     }
 
     fn join_integral(&self, other: &Type) -> Option<Type> {
@@ -87,6 +161,8 @@ impl Type {
             (Type::U64, Type::U32) => Some(Type::U64),
             (Type::U64, rhs) => Some(rhs.clone()),
 
+            (Type::U128, _) => Some(Type::U128),
+
             // Signed pairings, take larger type as result
             (Type::I8, rhs) => Some(rhs.clone()),
 
@@ -102,6 +178,8 @@ impl Type {
             (Type::I64, Type::I32) => Some(Type::I64),
             (Type::I64, rhs) => Some(rhs.clone()),
 
+            (Type::I128, _) => Some(Type::I128),
+
             _ => None,
         }
     }
@@ -114,6 +192,58 @@ impl Type {
         }
     }
 
+    /// Replaces every `Custom` name bound in `bindings` with its value, e.g.
+    /// `Vec<i32, n>` with `n = 3` becomes `Vec<i32, 3>`.
+    pub fn substitute(&self, bindings: &HashMap<String, Type>) -> Type {
+        match self {
+            Type::Custom(name) => bindings.get(name).cloned().unwrap_or_else(|| self.clone()),
+            Type::Generic(gtype) => Type::Generic(GType {
+                name: gtype.name.clone(),
+                fields: gtype
+                    .fields
+                    .iter()
+                    .map(|t| t.substitute(bindings))
+                    .collect(),
+            }),
+            Type::Array(element, len) => Type::Array(Box::new(element.substitute(bindings)), *len),
+            Type::Slice(element) => Type::Slice(Box::new(element.substitute(bindings))),
+            Type::Tuple(elements) => {
+                Type::Tuple(elements.iter().map(|t| t.substitute(bindings)).collect())
+            }
+            Type::Function(params, ret) => Type::Function(
+                params.iter().map(|t| t.substitute(bindings)).collect(),
+                Box::new(ret.substitute(bindings)),
+            ),
+            Type::Dependent(dtype) => {
+                // Parameters of the inner `forall` shadow outer bindings.
+                let mut inner = bindings.clone();
+                for param in &dtype.params {
+                    inner.remove(param.name());
+                }
+                Type::Dependent(DType {
+                    params: dtype.params.clone(),
+                    body: Box::new(dtype.body.substitute(&inner)),
+                })
+            }
+            _ => self.clone(),
+        }
+    }
+
+    /// Whether the type mentions any of the given names.
+    pub fn mentions(&self, names: &[String]) -> bool {
+        match self {
+            Type::Custom(name) => names.contains(name),
+            Type::Generic(gtype) => gtype.fields.iter().any(|t| t.mentions(names)),
+            Type::Array(element, _) | Type::Slice(element) => element.mentions(names),
+            Type::Tuple(elements) => elements.iter().any(|t| t.mentions(names)),
+            Type::Function(params, ret) => {
+                params.iter().any(|t| t.mentions(names)) || ret.mentions(names)
+            }
+            Type::Dependent(dtype) => dtype.body.mentions(names),
+            _ => false,
+        }
+    }
+
     pub fn join(&self, other: &Type) -> Option<Type> {
         if self.is_integral() && other.is_integral() {
             self.join_integral(other)
@@ -132,22 +262,45 @@ impl std::fmt::Display for Type {
             Type::U16 => write!(f, "u16"),
             Type::U32 => write!(f, "u32"),
             Type::U64 => write!(f, "u64"),
+            Type::U128 => write!(f, "u128"),
             Type::I8 => write!(f, "i8"),
             Type::I16 => write!(f, "i16"),
             Type::I32 => write!(f, "i32"),
             Type::I64 => write!(f, "i64"),
+            Type::I128 => write!(f, "i128"),
             Type::F32 => write!(f, "f32"),
             Type::F64 => write!(f, "f64"),
             Type::Bool => write!(f, "bool"),
             Type::String => write!(f, "string"),
             Type::Unit => write!(f, "()"),
             Type::Custom(name) => write!(f, "{}", name),
+            Type::Array(element, len) => write!(f, "[{}; {}]", element, len),
+            Type::Slice(element) => write!(f, "[{}]", element),
+            Type::Tuple(elements) => write!(f, "{}", format_tuple(elements)),
+            Type::Function(params, ret) => write!(
+                f,
+                "fn({}) -> {}",
+                params
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                ret
+            ),
+            Type::Const(value) => write!(f, "{}", value),
             Type::Dependent(dtype) => write!(f, "{}", dtype),
-            Type::Generic(gtype) => write!(f, "{}", gtype),
+            // `fn() -> T?` would read as a function returning an option.
+            Type::Generic(gtype) => match self.optional_inner() {
+                Some(inner) if !matches!(inner, Type::Function(..) | Type::Dependent(_)) => {
+                    write!(f, "{}?", inner)
+                }
+                _ => write!(f, "{}", gtype),
+            },
         }
     }
 }
 
+#[cfg(feature = "pest")]
 pub fn parse_type(pair: Pair<Rule>) -> Result<Type, Error<Rule>> {
     match pair.as_rule() {
         Rule::ident => {
@@ -163,27 +316,62 @@ pub fn parse_type(pair: Pair<Rule>) -> Result<Type, Error<Rule>> {
                 pair.as_span(),
             ))
         }
+        Rule::integer => {
+            let span = pair.as_span();
+            let (value, _) = parse_integer(pair)?;
+            i128::try_from(value).map(Type::Const).map_err(|_| {
+                Error::new_from_span(
+                    pest::error::ErrorVariant::CustomError {
+                        message: format!("integer literal `{}` is too large", span.as_str()),
+                    },
+                    span,
+                )
+            })
+        }
+        Rule::array_type => {
+            let mut pair = pair.into_inner();
+            let element = parse_type(pair.next().unwrap())?;
+            let len = parse_length(pair.next().unwrap())?;
+            Ok(Type::Array(Box::new(element), len))
+        }
+        Rule::tuple_type => Ok(Type::Tuple(
+            pair.into_inner()
+                .map(parse_type)
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        Rule::fn_type => {
+            let mut types = pair
+                .into_inner()
+                .map(parse_type)
+                .collect::<Result<Vec<_>, _>>()?;
+            let ret = types.pop().unwrap();
+            Ok(Type::Function(types, Box::new(ret)))
+        }
+        Rule::slice_type => Ok(Type::Slice(Box::new(parse_type(
+            pair.into_inner().next().unwrap(),
+        )?))),
         Rule::gtype => {
             let mut pair = pair.into_inner();
             let name = pair.next().unwrap().as_str();
-            let fst_par = pair.next().unwrap();
-            let mut fields = vec![parse_type(fst_par)?];
-            for inner in pair {
-                fields.push(parse_type(inner)?);
+            let fields = pair.map(parse_type).collect::<Result<Vec<_>, _>>()?;
+            if fields.is_empty() {
+                return Ok(Type::parse_type(name).unwrap_or(Type::Custom(name.to_string())));
             }
             Ok(Type::Generic(GType {
                 name: name.to_string(),
                 fields,
             }))
         }
+        Rule::optional_type => {
+            let mut pair = pair.into_inner();
+            let ty = parse_type(pair.next().unwrap())?;
+            Ok(pair.fold(ty, |ty, _| Type::option(ty)))
+        }
         Rule::dtype => {
             let mut pair = pair.into_inner();
             let params = parse_inputs(pair.next().unwrap())?;
-            let name = pair.next().unwrap().as_str();
-            Ok(Type::Dependent(DType {
-                name: name.to_string(),
-                fields: params,
-            }))
+            let body = parse_type(pair.next().unwrap())?;
+            Ok(Type::Dependent(DType::new(params, body)))
         }
         _ => Err(Error::new_from_span(
             pest::error::ErrorVariant::CustomError {
@@ -194,29 +382,138 @@ pub fn parse_type(pair: Pair<Rule>) -> Result<Type, Error<Rule>> {
     }
 }
 
+/// Writes tuple elements in parentheses, with the trailing comma a
+/// one-element tuple needs.
+pub fn format_tuple<T: std::fmt::Display>(elements: &[T]) -> String {
+    let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+    if elements.len() == 1 {
+        format!("({},)", elements[0])
+    } else {
+        format!("({})", elements.join(", "))
+    }
+}
+
+/// The length of an array type or repeat literal.
+#[cfg(feature = "pest")]
+pub fn parse_length(pair: Pair<Rule>) -> Result<usize, Error<Rule>> {
+    let span = pair.as_span();
+    let (len, _) = parse_integer(pair)?;
+    usize::try_from(len).map_err(|_| {
+        Error::new_from_span(
+            pest::error::ErrorVariant::CustomError {
+                message: format!("array length `{}` is too large", span.as_str()),
+            },
+            span,
+        )
+    })
+}
+
+/// A type quantified over value parameters, e.g. `forall n: u32. Vec<i32, n>`.
 #[derive(PartialEq, Debug, Clone)]
 pub struct DType {
-    name: String,
-    fields: Vec<VariableDeclaration>,
+    pub params: Vec<VariableDeclaration>,
+    pub body: Box<Type>,
+}
+
+impl DType {
+    pub fn new(params: Vec<VariableDeclaration>, body: Type) -> DType {
+        DType {
+            params,
+            body: Box::new(body),
+        }
+    }
+
+    /// Substitutes `args` for the parameters, in order. Returns `None` on an
+    /// arity mismatch.
+    pub fn instantiate(&self, args: &[Type]) -> Option<Type> {
+        if args.len() != self.params.len() {
+            return None;
+        }
+        let bindings = self
+            .params
+            .iter()
+            .map(|p| p.name().clone())
+            .zip(args.iter().cloned())
+            .collect();
+        Some(self.body.substitute(&bindings))
+    }
 }
 
 impl std::fmt::Display for DType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "forall ")?;
-        for (i, dec) in self.fields.iter().enumerate() {
-            if i != 0 {
-                write!(f, ", ")?;
-            }
-            write!(f, "{}", dec)?;
+        write!(f, "forall {}. {}", format_params(&self.params), self.body)
+    }
+}
+
+/// `type Name<Params> = Target;`
+#[derive(PartialEq, Debug, Clone)]
+pub struct TypeAlias {
+    pub name: String,
+    pub params: Vec<String>,
+    pub target: Type,
+}
+
+impl TypeAlias {
+    pub fn new(name: String, params: Vec<String>, target: Type) -> TypeAlias {
+        TypeAlias {
+            name,
+            params,
+            target,
         }
-        write!(f, ". {}", self.name)
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    /// The target with `args` substituted for the parameters. Returns `None`
+    /// on an arity mismatch.
+    pub fn instantiate(&self, args: &[Type]) -> Option<Type> {
+        if args.len() != self.params.len() {
+            return None;
+        }
+        let bindings = self
+            .params
+            .iter()
+            .cloned()
+            .zip(args.iter().cloned())
+            .collect();
+        Some(self.target.substitute(&bindings))
     }
 }
 
+impl std::fmt::Display for TypeAlias {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "type {}", self.name)?;
+        if !self.params.is_empty() {
+            write!(f, "<{}>", self.params.join(", "))?;
+        }
+        write!(f, " = {}", self.target)
+    }
+}
+
+#[cfg(feature = "pest")]
+pub fn parse_type_alias(pair: Pair<Rule>) -> Result<TypeAlias, Error<Rule>> {
+    let mut inner: Vec<Pair<Rule>> = pair
+        .into_inner()
+        .filter(|p| p.as_rule() != Rule::type_kw)
+        .collect();
+    let target = parse_type(inner.pop().unwrap())?;
+    let mut names = inner.into_iter().map(|p| p.as_str().to_string());
+    let name = names.next().unwrap();
+    Ok(TypeAlias::new(name, names.collect(), target))
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct GType {
-    name: String,
-    fields: Vec<Type>,
+    pub name: String,
+    pub fields: Vec<Type>,
+}
+
+impl GType {
+    pub fn new(name: String, fields: Vec<Type>) -> GType {
+        GType { name, fields }
+    }
 }
 
 impl std::fmt::Display for GType {
@@ -231,3 +528,68 @@ impl std::fmt::Display for GType {
         write!(f, ">")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "pest")]
+    use super::*;
+    #[cfg(feature = "pest")]
+    use crate::parser::PestParser;
+    use crate::parser::VoeParser;
+    #[cfg(feature = "pest")]
+    use pest::Parser;
+
+    #[test]
+    #[cfg(feature = "pest")]
+    fn test_parse_dependent_type() {
+        let input = "forall n: u32. Vec<i32, n>";
+        let mut pair = PestParser::parse(Rule::dtype, input).expect("No type recognized");
+        let ty = parse_type(pair.next().unwrap()).unwrap();
+        let Type::Dependent(dtype) = &ty else {
+            panic!("expected dependent type, got {:?}", ty);
+        };
+        assert_eq!(
+            *dtype.body,
+            Type::Generic(GType::new(
+                "Vec".to_string(),
+                vec![Type::I32, Type::Custom("n".to_string())]
+            ))
+        );
+        assert_eq!(ty.to_string(), input);
+        assert_eq!(
+            dtype.instantiate(&[Type::Const(3)]).unwrap().to_string(),
+            "Vec<i32, 3>"
+        );
+    }
+
+    #[test]
+    fn test_parse_dependent_function() {
+        let input = "forall n: u32. fn zeros(n: u32) -> Vec<i32, n> {\n\n}";
+        let program = VoeParser.parse_program(input).unwrap();
+        assert_eq!(program.to_string(), input);
+    }
+
+    #[test]
+    fn test_type_alias_round_trip() {
+        let input =
+            "type Meters = f64;\ntype Pair<T> = Tuple<T, T>;\nlet p: Pair<Meters> = make();";
+        let program = VoeParser.parse_program(input).unwrap();
+        assert_eq!(program.to_string(), input);
+    }
+
+    #[test]
+    #[cfg(feature = "pest")]
+    fn test_parse_array_types() {
+        let mut pair = PestParser::parse(Rule::array_type, "[[u8; 4]; 0x10]").unwrap();
+        let ty = parse_type(pair.next().unwrap()).unwrap();
+        assert_eq!(
+            ty,
+            Type::Array(Box::new(Type::Array(Box::new(Type::U8), 4)), 16)
+        );
+        assert_eq!(ty.to_string(), "[[u8; 4]; 16]");
+
+        let input = "fn sum(xs: [i32]) -> i32 {\n\n}";
+        let program = VoeParser.parse_program(input).unwrap();
+        assert_eq!(program.to_string(), input);
+    }
+}
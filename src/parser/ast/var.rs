@@ -1,8 +1,10 @@
-use super::{Expression, Type};
+use super::{Expression, Pattern, Type};
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct VariableDeclaration {
-    pub name: String,
+    /// The names bound, usually a single `Pattern::Binding`. Declarations
+    /// like `let (x, y) = pair;` destructure their value.
+    pub pattern: Pattern,
     pub var_type: Option<Type>,
     pub value: Option<Expression>,
 }
@@ -12,16 +14,35 @@ impl VariableDeclaration {
         name: String,
         var_type: Option<Type>,
         value: Option<Expression>,
+    ) -> VariableDeclaration {
+        VariableDeclaration::destructuring(Pattern::Binding(name), var_type, value)
+    }
+
+    pub fn destructuring(
+        pattern: Pattern,
+        var_type: Option<Type>,
+        value: Option<Expression>,
     ) -> VariableDeclaration {
         VariableDeclaration {
-            name,
+            pattern,
             var_type,
             value,
         }
     }
 
+    /// The name bound by a declaration that is not destructuring. Parameters
+    /// always have one.
     pub fn name(&self) -> &String {
-        &self.name
+        match &self.pattern {
+            Pattern::Binding(name) => name,
+            pattern => panic!("`let {}` binds more than one name", pattern),
+        }
+    }
+
+    /// Whether the declaration binds a single name rather than
+    /// destructuring.
+    pub fn is_simple(&self) -> bool {
+        matches!(self.pattern, Pattern::Binding(_))
     }
 
     pub fn var_type(&self) -> &Option<Type> {
@@ -34,7 +55,7 @@ impl std::fmt::Display for VariableDeclaration {
         write!(
             f,
             "let {}{}{}",
-            self.name,
+            self.pattern,
             match self.var_type() {
                 Some(ty) => format!(": {}", ty),
                 None => "".to_string(),
@@ -46,3 +67,23 @@ impl std::fmt::Display for VariableDeclaration {
         )
     }
 }
+
+/// `target = value;`, where the target is a variable, a field or an array
+/// element.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Assignment {
+    pub target: Expression,
+    pub value: Expression,
+}
+
+impl Assignment {
+    pub fn new(target: Expression, value: Expression) -> Assignment {
+        Assignment { target, value }
+    }
+}
+
+impl std::fmt::Display for Assignment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = {}", self.target, self.value)
+    }
+}
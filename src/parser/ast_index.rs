@@ -0,0 +1,459 @@
+//! Maps the nodes of a syntax tree to the AST parsed along with it. The
+//! AST does not know where its nodes came from and shares nothing with the
+//! tree, so the two are walked side by side, which lets what the analyses
+//! found out about an AST node be looked up by position.
+
+use std::collections::HashMap;
+
+use super::atom::AtomValue;
+use super::cst::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken};
+use super::lexer::{Span, TokenKind};
+use super::{
+    ArmBody, Block, Conditional, Expression, FunctionDefinition, Iterable, Pattern, Program,
+    Statement, VariableDeclaration,
+};
+
+/// An identifier that declares a name or refers to one.
+#[derive(Debug, Clone, Copy)]
+pub struct Name<'a> {
+    pub span: Span,
+    /// The name in the AST, which the analyses know it by.
+    pub name: &'a str,
+    pub declares: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Function<'a> {
+    pub span: Span,
+    pub name: Span,
+    pub definition: &'a FunctionDefinition,
+    /// Whether the function is a method of an `impl`.
+    pub is_method: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct AstIndex<'a> {
+    /// Statements by the span of their node, parents before children.
+    pub statements: Vec<(Span, &'a Statement)>,
+    /// Expressions by the span of their node, parents before children.
+    pub expressions: Vec<(Span, &'a Expression)>,
+    pub names: Vec<Name<'a>>,
+    pub functions: Vec<Function<'a>>,
+}
+
+impl<'a> AstIndex<'a> {
+    /// Indexes `tree` against the `program` parsed from the same source.
+    /// Nodes the two disagree on, like those of broken statements, are
+    /// left out.
+    pub fn new(tree: &SyntaxNode, program: &'a Program) -> AstIndex<'a> {
+        let mut index = AstIndex::default();
+        index.statements(&tree.children(), &program.statements);
+        index
+    }
+
+    /// The innermost expression containing `offset`.
+    pub fn expression_at(&self, offset: usize) -> Option<(Span, &'a Expression)> {
+        self.expressions
+            .iter()
+            .rev()
+            .find(|(span, _)| span.start <= offset && offset < span.end)
+            .copied()
+    }
+
+    /// The spans of the indexed nodes, by address, for analyses to place
+    /// what they report.
+    pub fn spans(&self) -> Spans {
+        let mut spans = Spans::default();
+        for (span, statement) in &self.statements {
+            spans.statements.entry(*statement).or_insert(*span);
+        }
+        // A parenthesized atom is indexed twice, parentheses first.
+        for (span, expr) in &self.expressions {
+            spans.expressions.entry(*expr).or_insert(*span);
+        }
+        spans
+    }
+
+    pub fn name_at(&self, offset: usize) -> Option<Name<'a>> {
+        self.names
+            .iter()
+            .find(|name| name.span.start <= offset && offset < name.span.end)
+            .copied()
+    }
+
+    fn name(&mut self, token: Option<SyntaxToken>, name: &'a str, declares: bool) {
+        if let Some(token) = token.filter(|token| token.text() == name) {
+            self.names.push(Name {
+                span: token.span(),
+                name,
+                declares,
+            });
+        }
+    }
+
+    fn statements(&mut self, nodes: &[SyntaxNode], statements: &'a [Statement]) {
+        for (node, statement) in nodes.iter().zip(statements) {
+            self.statement(node, statement);
+        }
+    }
+
+    fn statement(&mut self, node: &SyntaxNode, statement: &'a Statement) {
+        self.statements.push((node.span(), statement));
+        let children = node.children();
+        match (node.kind(), statement) {
+            (SyntaxKind::Let, Statement::VariableDeclaration(vd)) => {
+                self.pattern(&children[0], &vd.pattern);
+                if let (Some(value), Some(last)) = (&vd.value, children.last()) {
+                    self.expression(last, value);
+                }
+            }
+            (SyntaxKind::Assignment, Statement::Assignment(assignment)) => {
+                self.operands(node, vec![&assignment.target, &assignment.value])
+            }
+            (SyntaxKind::ExpressionStatement, Statement::Expression(expr)) => {
+                self.expression(&children[0], expr)
+            }
+            (SyntaxKind::Function, Statement::Function(fd)) => self.function(node, fd, false),
+            (
+                SyntaxKind::Class
+                | SyntaxKind::Interface
+                | SyntaxKind::Enum
+                | SyntaxKind::TypeAlias,
+                _,
+            ) => {
+                if let Some(name) = statement.declared_name() {
+                    self.name(idents(node).into_iter().next(), name, true);
+                }
+            }
+            (SyntaxKind::Impl, Statement::Impl(ib)) => {
+                self.name(idents(node).pop(), ib.class(), false);
+                for (node, method) in children.iter().zip(ib.methods()) {
+                    self.function(node, method, true);
+                }
+            }
+            (SyntaxKind::Export, Statement::Export(inner)) => self.statement(&children[0], inner),
+            (SyntaxKind::While, Statement::While(w)) => {
+                self.expression(&children[0], &w.condition);
+                self.block(&children[1], &w.body);
+            }
+            (SyntaxKind::For, Statement::For(l)) => {
+                self.pattern(&children[0], &l.pattern);
+                match &l.iterable {
+                    Iterable::Range(range) => {
+                        let bounds = [Some(&range.start), Some(&range.end), range.step.as_ref()];
+                        self.operands(&children[1], bounds.into_iter().flatten().collect());
+                    }
+                    Iterable::Expression(expr) => self.expression(&children[1], expr),
+                }
+                self.block(&children[2], &l.body);
+            }
+            (SyntaxKind::If, Statement::Conditional(cond)) => self.conditional(node, cond),
+            (SyntaxKind::Block, Statement::Block(block)) => self.block(node, block),
+            _ => {}
+        }
+    }
+
+    fn function(&mut self, node: &SyntaxNode, fd: &'a FunctionDefinition, is_method: bool) {
+        let name = idents(node).into_iter().next();
+        if let Some(token) = &name {
+            self.functions.push(Function {
+                span: node.span(),
+                name: token.span(),
+                definition: fd,
+                is_method,
+            });
+        }
+        self.name(name, fd.name(), true);
+        let params = node
+            .children()
+            .into_iter()
+            .filter(|child| child.kind() == SyntaxKind::ParamList)
+            .flat_map(|list| list.children());
+        self.params(params, fd.dependent_params().iter().chain(fd.inputs()));
+        if let Some(body) = node.children().last() {
+            self.block(body, fd.body());
+        }
+    }
+
+    fn params(
+        &mut self,
+        nodes: impl Iterator<Item = SyntaxNode>,
+        params: impl Iterator<Item = &'a VariableDeclaration>,
+    ) {
+        for (node, param) in nodes.zip(params) {
+            let token = node.tokens().into_iter().find(|t| !t.kind().is_trivia());
+            self.name(token, param.name(), true);
+        }
+    }
+
+    fn block(&mut self, node: &SyntaxNode, block: &'a Block) {
+        let children = node.children();
+        let statements = block.statements();
+        self.statements(&children, statements);
+        if let (Some(value), Some(node)) = (&block.value, children.get(statements.len())) {
+            self.expression(node, value);
+        }
+    }
+
+    fn conditional(&mut self, node: &SyntaxNode, cond: &'a Conditional) {
+        let mut children = node.children();
+        if let Some(pattern) = &cond.pattern {
+            self.pattern(&children.remove(0), pattern);
+        }
+        self.expression(&children[0], &cond.condition);
+        self.block(&children[1], &cond.then_block);
+        match (children.get(2), &cond.else_block) {
+            // An `else if` is a block holding just the inner `if`.
+            (Some(node), Some(block)) if node.kind() == SyntaxKind::If => {
+                if let Some(value) = &block.value {
+                    self.expression(node, value);
+                }
+            }
+            (Some(node), Some(block)) => self.block(node, block),
+            _ => {}
+        }
+    }
+
+    fn expression(&mut self, node: &SyntaxNode, expr: &'a Expression) {
+        self.expressions.push((node.span(), expr));
+        let token = idents(node).into_iter().next();
+        match (node.kind(), expr) {
+            // Parentheses around an atom are dropped from the AST.
+            (SyntaxKind::Paren, Expression::Atom(atom))
+                if !matches!(atom.value, AtomValue::ParExpr(_)) =>
+            {
+                self.expression(&node.children()[0], expr)
+            }
+            (SyntaxKind::Name, Expression::Atom(atom)) => {
+                if let AtomValue::Identity(name) = &atom.value {
+                    self.name(token, name, false);
+                }
+            }
+            (SyntaxKind::Call, Expression::Atom(atom)) => {
+                if let AtomValue::Call(name, args) = &atom.value {
+                    self.name(token, name, false);
+                    self.operands(node, args.iter().collect());
+                }
+            }
+            (SyntaxKind::New, Expression::New(class, fields)) => {
+                self.name(token, class, false);
+                self.operands(node, fields.iter().map(|(_, value)| value).collect());
+            }
+            (SyntaxKind::VariantExpression, Expression::Variant(name, _, args)) => {
+                self.name(token, name, false);
+                self.operands(node, args.iter().collect());
+            }
+            (SyntaxKind::Match, Expression::Match(scrutinee, arms)) => {
+                let children = node.children();
+                self.expression(&children[0], scrutinee);
+                for (node, arm) in children[1..].iter().zip(arms) {
+                    let children = node.children();
+                    self.pattern(&children[0], &arm.pattern);
+                    match &arm.body {
+                        ArmBody::Expression(expr) => self.expression(&children[1], expr),
+                        ArmBody::Block(block) => self.block(&children[1], block),
+                    }
+                }
+            }
+            (SyntaxKind::Lambda, Expression::Lambda(lambda)) => {
+                let children = node.children();
+                let params = children
+                    .iter()
+                    .filter(|child| child.kind() == SyntaxKind::Param)
+                    .cloned();
+                self.params(params, lambda.params.iter());
+                if let Some(body) = children.last() {
+                    match &lambda.body {
+                        ArmBody::Expression(expr) => self.expression(body, expr),
+                        ArmBody::Block(block) => self.block(body, block),
+                    }
+                }
+            }
+            (SyntaxKind::If, Expression::If(cond)) => self.conditional(node, cond),
+            (SyntaxKind::Block, Expression::Block(block)) => self.block(node, block),
+            _ => self.operands(node, subexpressions(expr)),
+        }
+    }
+
+    /// Pairs the expressions below `node`, in the order they are written,
+    /// with `exprs`.
+    fn operands(&mut self, node: &SyntaxNode, exprs: Vec<&'a Expression>) {
+        let mut nodes = vec![];
+        for child in node.children() {
+            match child.kind() {
+                SyntaxKind::ArgList | SyntaxKind::FieldInit => nodes.extend(
+                    child
+                        .children()
+                        .into_iter()
+                        .filter(|c| c.kind().is_expression()),
+                ),
+                kind if kind.is_expression() => nodes.push(child),
+                _ => {}
+            }
+        }
+        for (node, expr) in nodes.iter().zip(exprs) {
+            self.expression(node, expr);
+        }
+    }
+
+    fn pattern(&mut self, node: &SyntaxNode, pattern: &'a Pattern) {
+        let mut names = vec![];
+        pattern_names(pattern, &mut names);
+        let tokens = node
+            .tokens()
+            .into_iter()
+            .filter(|t| t.kind() == TokenKind::Ident);
+        for (token, name) in tokens.zip(names) {
+            if let Some((name, declares)) = name {
+                self.name(Some(token), name, declares);
+            }
+        }
+    }
+}
+
+/// Where the statements and expressions of a program are in its source,
+/// by address.
+#[derive(Debug, Clone, Default)]
+pub struct Spans {
+    statements: HashMap<*const Statement, Span>,
+    expressions: HashMap<*const Expression, Span>,
+}
+
+impl Spans {
+    pub fn statement(&self, statement: &Statement) -> Option<Span> {
+        self.statements.get(&(statement as *const _)).copied()
+    }
+
+    pub fn expression(&self, expr: &Expression) -> Option<Span> {
+        self.expressions.get(&(expr as *const _)).copied()
+    }
+}
+
+/// The identifiers directly in `node`, outside its child nodes.
+fn idents(node: &SyntaxNode) -> Vec<SyntaxToken> {
+    node.children_with_tokens()
+        .into_iter()
+        .filter_map(|element| match element {
+            SyntaxElement::Token(token) if token.kind() == TokenKind::Ident => Some(token),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The expressions directly inside `expr`, in the order they are written,
+/// for the kinds of expression that hold nothing else.
+fn subexpressions(expr: &Expression) -> Vec<&Expression> {
+    match expr {
+        Expression::BinaryOperation(lhs, _, rhs) | Expression::Index(lhs, rhs) => {
+            vec![&**lhs, &**rhs]
+        }
+        Expression::Atom(atom) => match &atom.value {
+            AtomValue::ParExpr(inner) => vec![&**inner],
+            _ => vec![],
+        },
+        Expression::MethodCall(object, _, args) => std::iter::once(&**object).chain(args).collect(),
+        Expression::Tuple(elements) | Expression::Array(elements) => elements.iter().collect(),
+        Expression::Unary(_, inner)
+        | Expression::FieldAccess(inner, _)
+        | Expression::Repeat(inner, _)
+        | Expression::Some(inner)
+        | Expression::Ok(inner)
+        | Expression::Err(inner)
+        | Expression::IsSome(inner)
+        | Expression::IsNone(inner)
+        | Expression::Try(inner) => vec![&**inner],
+        _ => vec![],
+    }
+}
+
+/// What each identifier in `pattern` stands for, in the order they are
+/// written: a name it binds or refers to, or `None` for the name of a
+/// variant, `ok` or `err`.
+fn pattern_names<'a>(pattern: &'a Pattern, names: &mut Vec<Option<(&'a str, bool)>>) {
+    match pattern {
+        Pattern::Binding(name) => names.push(Some((name, true))),
+        Pattern::Variant(name, _, fields) => {
+            names.extend([Some((name.as_str(), false)), None]);
+            for field in fields {
+                pattern_names(field, names);
+            }
+        }
+        Pattern::Tuple(fields) => {
+            for field in fields {
+                pattern_names(field, names);
+            }
+        }
+        Pattern::Some(inner) => pattern_names(inner, names),
+        Pattern::Ok(inner) | Pattern::Err(inner) => {
+            names.push(None);
+            pattern_names(inner, names);
+        }
+        Pattern::Wildcard | Pattern::Literal(_) | Pattern::None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::VoeParser;
+
+    #[test]
+    fn test_index_pairs_nodes() {
+        let source = "
+            enum Shape { Circle(i32), Square(i32) }
+            class P { x: i32 }
+            impl P { fn get(self) -> i32 { self.x; } }
+            fn area(s: Shape) -> i32 {
+                let a = match s { Shape::Circle(r) => (r) * 3i32, Shape::Square(w) => w * w };
+                let f = |k: i32| k + a;
+                for i in 0..a step 2i32 { f(i); }
+                let b = if a > 1i32 { a } else if a < 0i32 { -a } else { 0i32 };
+                if let ok(v) = check(b) { new P { x: v }.get(); }
+            }
+        ";
+        let parse = VoeParser.parse_tree(source);
+        assert!(parse.errors.is_empty(), "{:?}", parse.errors);
+        let index = AstIndex::new(&parse.tree, &parse.program);
+        let text = |span: Span| &source[span.start..span.end];
+
+        // Every identifier that names something is indexed, and only those.
+        let names: Vec<(&str, bool)> = index
+            .names
+            .iter()
+            .map(|name| {
+                assert_eq!(text(name.span), name.name);
+                (name.name, name.declares)
+            })
+            .collect();
+        let (declared, used): (Vec<_>, Vec<_>) = names.iter().partition(|(_, d)| *d);
+        let declared: Vec<&str> = declared.iter().map(|(name, _)| *name).collect();
+        let used: Vec<&str> = used.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            declared,
+            ["Shape", "P", "get", "self", "area", "s", "a", "r", "w", "f", "k", "i", "b", "v"]
+        );
+        assert_eq!(
+            used,
+            [
+                "P", "self", "s", "Shape", "r", "Shape", "w", "w", "k", "a", "a", "f", "i", "a",
+                "a", "a", "a", "check", "b", "P", "v"
+            ]
+        );
+
+        // The parenthesized `r` is one atom in the AST, found from either node.
+        let r = source.find("(r)").unwrap();
+        assert_eq!(
+            index.expression_at(r).unwrap().1,
+            index.expression_at(r + 1).unwrap().1
+        );
+        let (span, expr) = index.expression_at(source.find("-a").unwrap()).unwrap();
+        assert_eq!((text(span), expr.to_string()), ("-a", "-a".to_string()));
+
+        let functions: Vec<(&str, bool)> = index
+            .functions
+            .iter()
+            .map(|f| (text(f.name), f.is_method))
+            .collect();
+        assert_eq!(functions, [("get", true), ("area", false)]);
+    }
+}
@@ -0,0 +1,557 @@
+//! A lossless concrete syntax tree, which keeps every token of the source,
+//! comments and whitespace included, so tools that rewrite code can leave
+//! what they do not touch as it was.
+//!
+//! The tree comes in two layers. Green nodes hold a kind, their text's
+//! length and their children, and know nothing of where they are, so
+//! identical subtrees can be shared. `SyntaxNode`s wrap green nodes with
+//! their offset in the source and their parent, and are built on demand
+//! while walking the tree.
+//!
+//! The parser builds the AST alongside the tree, but the two share no
+//! nodes: `ast_index::AstIndex` pairs them up after the parse.
+
+use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+
+use super::lexer::{Span, Token, TokenKind};
+use super::{Program, SyntaxError};
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum SyntaxKind {
+    Program,
+    /// A statement that failed to parse.
+    Error,
+
+    // Statements
+    Let,
+    Assignment,
+    ExpressionStatement,
+    Function,
+    Class,
+    Interface,
+    Impl,
+    Enum,
+    TypeAlias,
+    Module,
+    Import,
+    Use,
+    Export,
+    While,
+    For,
+    /// An `if`, as a statement or a value.
+    If,
+    /// A block, as a statement, a value or the body of something.
+    Block,
+
+    // Parts of statements
+    ParamList,
+    Param,
+    TypeParams,
+    TypeParam,
+    Field,
+    MethodSignature,
+    Variant,
+    ImportItem,
+    Range,
+    MatchArm,
+    FieldInit,
+    ArgList,
+
+    // Expressions
+    Binary,
+    Unary,
+    Literal,
+    Name,
+    Call,
+    Paren,
+    New,
+    FieldAccess,
+    MethodCall,
+    Index,
+    VariantExpression,
+    Match,
+    Lambda,
+    Tuple,
+    Array,
+    Repeat,
+    Some,
+    None,
+    Ok,
+    Err,
+    IsSome,
+    IsNone,
+    Try,
+
+    Type,
+    Pattern,
+}
+
+impl SyntaxKind {
+    pub fn is_statement(&self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::Let
+                | SyntaxKind::Assignment
+                | SyntaxKind::ExpressionStatement
+                | SyntaxKind::Function
+                | SyntaxKind::Class
+                | SyntaxKind::Interface
+                | SyntaxKind::Impl
+                | SyntaxKind::Enum
+                | SyntaxKind::TypeAlias
+                | SyntaxKind::Module
+                | SyntaxKind::Import
+                | SyntaxKind::Use
+                | SyntaxKind::Export
+                | SyntaxKind::While
+                | SyntaxKind::For
+                | SyntaxKind::Error
+        )
+    }
+
+    pub fn is_expression(&self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::If
+                | SyntaxKind::Block
+                | SyntaxKind::Binary
+                | SyntaxKind::Unary
+                | SyntaxKind::Literal
+                | SyntaxKind::Name
+                | SyntaxKind::Call
+                | SyntaxKind::Paren
+                | SyntaxKind::New
+                | SyntaxKind::FieldAccess
+                | SyntaxKind::MethodCall
+                | SyntaxKind::Index
+                | SyntaxKind::VariantExpression
+                | SyntaxKind::Match
+                | SyntaxKind::Lambda
+                | SyntaxKind::Tuple
+                | SyntaxKind::Array
+                | SyntaxKind::Repeat
+                | SyntaxKind::Some
+                | SyntaxKind::None
+                | SyntaxKind::Ok
+                | SyntaxKind::Err
+                | SyntaxKind::IsSome
+                | SyntaxKind::IsNone
+                | SyntaxKind::Try
+        )
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct GreenToken {
+    pub kind: TokenKind,
+    pub text: String,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement {
+    fn width(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.width,
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct GreenNode {
+    pub kind: SyntaxKind,
+    /// The length of the node's text.
+    pub width: usize,
+    pub children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> GreenNode {
+        GreenNode {
+            kind,
+            width: children.iter().map(|c| c.width()).sum(),
+            children,
+        }
+    }
+
+    fn write_text(&self, text: &mut String) {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => node.write_text(text),
+                GreenElement::Token(token) => text.push_str(&token.text),
+            }
+        }
+    }
+}
+
+/// What the parser saw, in order, from which the tree is built.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Event {
+    Start(SyntaxKind),
+    /// A token other than trivia. It may be the first half of a source
+    /// token, as when `>>` closes two lists of type arguments.
+    Token(Token),
+    Finish,
+}
+
+/// Builds the green tree for `events`, putting back the trivia between the
+/// tokens the parser saw. Trivia goes to the innermost node open at the
+/// time, so no node but the root starts or ends with trivia.
+pub fn build(source: &str, tokens: &[Token], events: &[Event]) -> Rc<GreenNode> {
+    let mut stack: Vec<(SyntaxKind, Vec<GreenElement>)> = vec![];
+    let mut next = 0;
+    let push_token = |children: &mut Vec<GreenElement>, token: Token| {
+        children.push(GreenElement::Token(Rc::new(GreenToken {
+            kind: token.kind,
+            text: source[token.span.start..token.span.end].to_string(),
+        })))
+    };
+    let mut root = None;
+    for event in events {
+        // Trivia comes before whatever starts here, except at the end of
+        // the root, which takes what is left.
+        let flush = match event {
+            Event::Start(_) | Event::Token(_) => true,
+            Event::Finish => stack.len() == 1,
+        };
+        if let Some((_, children)) = stack.last_mut().filter(|_| flush) {
+            while next < tokens.len() && tokens[next].kind.is_trivia() {
+                push_token(children, tokens[next]);
+                next += 1;
+            }
+        }
+        match *event {
+            Event::Start(kind) => stack.push((kind, vec![])),
+            Event::Token(token) => {
+                let (_, children) = stack.last_mut().expect("token outside of a node");
+                push_token(children, token);
+                if tokens
+                    .get(next)
+                    .is_some_and(|t| t.span.end == token.span.end)
+                {
+                    next += 1;
+                }
+            }
+            Event::Finish => {
+                let (kind, children) = stack.pop().expect("unbalanced events");
+                let node = Rc::new(GreenNode::new(kind, children));
+                match stack.last_mut() {
+                    Some((_, children)) => children.push(GreenElement::Node(node)),
+                    None => root = Some(node),
+                }
+            }
+        }
+    }
+    root.expect("no root node")
+}
+
+/// The result of parsing a source into a syntax tree, along with its AST.
+/// `ast_index::AstIndex` maps the nodes of one to those of the other.
+#[derive(Debug, Clone)]
+pub struct Parse {
+    pub tree: SyntaxNode,
+    pub program: Program,
+    pub errors: Vec<SyntaxError>,
+}
+
+#[derive(Debug)]
+struct NodeData {
+    green: Rc<GreenNode>,
+    offset: usize,
+    parent: Option<SyntaxNode>,
+}
+
+/// A node of the tree, with its place in the source.
+#[derive(Debug, Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &SyntaxNode) -> bool {
+        Rc::ptr_eq(&self.0.green, &other.0.green) && self.0.offset == other.0.offset
+    }
+}
+
+/// A token of the tree, with its place in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    offset: usize,
+    parent: SyntaxNode,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Rc<GreenNode>) -> SyntaxNode {
+        SyntaxNode(Rc::new(NodeData {
+            green,
+            offset: 0,
+            parent: None,
+        }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind
+    }
+
+    pub fn green(&self) -> &Rc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.0.offset, self.0.offset + self.0.green.width)
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        self.0.green.write_text(&mut text);
+        text
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    /// This node's parent, its parent, and so on up to the root.
+    pub fn ancestors(&self) -> impl Iterator<Item = SyntaxNode> {
+        std::iter::successors(self.parent(), |node| node.parent())
+    }
+
+    pub fn children_with_tokens(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        let mut elements = vec![];
+        for child in &self.0.green.children {
+            elements.push(match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                    green: green.clone(),
+                    offset,
+                    parent: Some(self.clone()),
+                }))),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                    green: green.clone(),
+                    offset,
+                    parent: self.clone(),
+                }),
+            });
+            offset += child.width();
+        }
+        elements
+    }
+
+    pub fn children(&self) -> Vec<SyntaxNode> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|e| match e {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect()
+    }
+
+    /// This node and every node below it, parents before children.
+    pub fn descendants(&self) -> Vec<SyntaxNode> {
+        let mut nodes = vec![self.clone()];
+        for child in self.children() {
+            nodes.extend(child.descendants());
+        }
+        nodes
+    }
+
+    /// Every token below this node, trivia included, in source order.
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = vec![];
+        for element in self.children_with_tokens() {
+            match element {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    /// The token containing the byte at `offset`.
+    pub fn token_at(&self, offset: usize) -> Option<SyntaxToken> {
+        let mut node = self.clone();
+        'descend: loop {
+            for element in node.children_with_tokens() {
+                match element {
+                    SyntaxElement::Node(child) if child.span().end > offset => {
+                        if child.span().start > offset {
+                            return None;
+                        }
+                        node = child;
+                        continue 'descend;
+                    }
+                    SyntaxElement::Token(token) if token.span().end > offset => {
+                        return (token.span().start <= offset).then_some(token)
+                    }
+                    _ => {}
+                }
+            }
+            return None;
+        }
+    }
+}
+
+impl Display for SyntaxNode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> TokenKind {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.offset, self.offset + self.green.text.len())
+    }
+
+    pub fn parent(&self) -> SyntaxNode {
+        self.parent.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ast_index::AstIndex;
+    use crate::parser::{Statement, VoeParser};
+
+    const SOURCE: &str = "// Adds one.
+fn inc(x: i32) -> i32 {
+    let y = x + 1; // the answer
+}
+
+let v: Vec<Vec<i32>>= new_vec();
+let total = inc(2) * -3;
+";
+
+    #[test]
+    fn test_tree_is_lossless() {
+        let sources = [
+            SOURCE,
+            include_str!("../../test.voe"),
+            "  // only a comment  \n",
+            "",
+            "let = 1; // broken\nfn ok() -> i32 { return 1; }\n@ let y = 2;",
+            "let s = r#\"raw\"#; let t = \"a\\nb\";\n\n",
+        ];
+        for source in sources {
+            let parse = VoeParser.parse_tree(source);
+            assert_eq!(parse.tree.text(), source);
+            assert_eq!(parse.tree.span(), Span::new(0, source.len()));
+            let mut offset = 0;
+            for token in parse.tree.tokens() {
+                assert_eq!(token.span().start, offset);
+                assert_eq!(token.text(), &source[offset..token.span().end]);
+                offset = token.span().end;
+            }
+            assert_eq!(offset, source.len());
+        }
+    }
+
+    #[test]
+    fn test_tree_structure() {
+        let parse = VoeParser.parse_tree(SOURCE);
+        assert!(parse.errors.is_empty());
+        let root = parse.tree;
+        assert_eq!(root.kind(), SyntaxKind::Program);
+        let kinds: Vec<_> = root.children().iter().map(|n| n.kind()).collect();
+        assert_eq!(
+            kinds,
+            vec![SyntaxKind::Function, SyntaxKind::Let, SyntaxKind::Let]
+        );
+        // Comments between statements stay with the enclosing node, and no
+        // node starts or ends with trivia.
+        let comments: Vec<_> = root
+            .tokens()
+            .into_iter()
+            .filter(|t| t.kind() == TokenKind::Comment)
+            .map(|t| (t.text().to_string(), t.parent().kind()))
+            .collect();
+        assert_eq!(
+            comments,
+            vec![
+                ("// Adds one.".to_string(), SyntaxKind::Program),
+                ("// the answer".to_string(), SyntaxKind::Block),
+            ]
+        );
+        for node in root.descendants().iter().skip(1) {
+            let text = node.text();
+            assert_eq!(text.trim(), text, "{:?}", node.kind());
+        }
+        let function = &root.children()[0];
+        assert_eq!(
+            function.text(),
+            "fn inc(x: i32) -> i32 {\n    let y = x + 1; // the answer\n}"
+        );
+        // The `>>` closing both type arguments is split in two.
+        let ty = root.children()[1]
+            .descendants()
+            .into_iter()
+            .find(|n| n.kind() == SyntaxKind::Type)
+            .unwrap();
+        assert_eq!(ty.text(), "Vec<Vec<i32>>");
+        assert_eq!(ty.children()[0].text(), "Vec<i32>");
+
+        let errors = VoeParser.parse_tree("let = 1;\nlet x = 2;");
+        let kinds: Vec<_> = errors.tree.children().iter().map(|n| n.kind()).collect();
+        assert_eq!(kinds, vec![SyntaxKind::Error, SyntaxKind::Let]);
+        assert_eq!(errors.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_nodes_map_to_ast() {
+        for source in [SOURCE, include_str!("../../test.voe")] {
+            let parse = VoeParser.parse_tree(source);
+            let index = AstIndex::new(&parse.tree, &parse.program);
+            // Every expression node is paired with an expression of the
+            // program itself, except `if`s and blocks used as statements.
+            for node in parse.tree.descendants() {
+                let kind = node.kind();
+                if !kind.is_expression() || matches!(kind, SyntaxKind::If | SyntaxKind::Block) {
+                    continue;
+                }
+                let paired = index.expressions.iter().any(|(s, _)| *s == node.span());
+                assert!(paired, "{}", node);
+            }
+        }
+
+        let parse = VoeParser.parse_tree(SOURCE);
+        let offset = SOURCE.find("x + 1").unwrap();
+        let token = parse.tree.token_at(offset).unwrap();
+        assert_eq!(token.text(), "x");
+        let binary = token
+            .parent()
+            .ancestors()
+            .find(|n| n.kind() == SyntaxKind::Binary)
+            .unwrap();
+        let index = AstIndex::new(&parse.tree, &parse.program);
+        let (span, expr) = index.expression_at(offset + 2).unwrap();
+        assert_eq!(span, binary.span());
+        let Statement::Function(fd) = &parse.program.statements[0] else {
+            panic!("not a function");
+        };
+        let Statement::VariableDeclaration(vd) = &fd.body().statements()[0] else {
+            panic!("not a declaration");
+        };
+        // The very node of the program, not a copy of it.
+        assert!(std::ptr::eq(expr, vd.value.as_ref().unwrap()));
+    }
+}
@@ -0,0 +1,1609 @@
+//! A hand-written recursive-descent parser over the tokens from `Lexer`.
+//! It builds the same AST as the pest grammar in `voe.pest`, with binary
+//! operators parsed by precedence climbing:
+//!
+//! | operators                       | associativity |
+//! |---------------------------------|---------------|
+//! | `\|\|`                          | left          |
+//! | `&&`                            | left          |
+//! | `==` `!=` `<` `<=` `>` `>=`     | none          |
+//! | `\|`                            | left          |
+//! | `~` (xor)                       | left          |
+//! | `&`                             | left          |
+//! | `<<` `>>`                       | left          |
+//! | `+` `-`                         | left          |
+//! | `*` `/` `%`                     | left          |
+//! | `^` (pow)                       | right         |
+//!
+//! Prefix `-` `!` `~` bind tighter than any of these, and postfix `.field`,
+//! `.method()`, `[index]`, `is some`, `is none` and `?` tighter still.
+//!
+//! As it goes, the parser records the nodes it enters and the tokens it
+//! consumes, from which `parse_tree` builds a lossless syntax tree.
+
+use super::atom::{
+    float_value, integer_value, literal_fits, raw_string_value, suffix_error, unescape, Atom,
+    AtomValue, Radix,
+};
+use super::cst::{self, Event, Parse, SyntaxKind, SyntaxNode};
+use super::error::SyntaxError;
+use super::lexer::{Keyword, Lexer, Span, Token, TokenKind};
+use super::r#type::{DType, GType};
+use super::{
+    ArmBody, Assignment, Block, ClassDefinition, Conditional, EnumDefinition, EnumVariant,
+    Expression, ForLoop, FunctionDefinition, ImplBlock, Import, ImportItem, InterfaceDefinition,
+    Iterable, Lambda, MatchArm, MethodSignature, Operator, Pattern, Program, Range, Statement,
+    Type, TypeAlias, TypeParam, UnaryOp, Use, VariableDeclaration, WhileLoop,
+};
+
+type ParseResult<T> = Result<T, SyntaxError>;
+
+/// Names of the types a primitive type may be written as.
+const PRIMITIVE_TYPES: &[&str] = &[
+    "u8", "i8", "u16", "i16", "u32", "i32", "u64", "i64", "u128", "i128", "f32", "f64", "bool",
+    "char", "void",
+];
+
+/// What a statement inside a block turned out to be.
+enum Item {
+    Statement(Statement),
+    /// An expression directly before the closing `}`, which is the value of
+    /// the block.
+    Value(Expression),
+}
+
+/// A place in the parse, to which a node can later be opened.
+#[derive(Clone, Copy)]
+struct Marker {
+    token: usize,
+    event: usize,
+}
+
+pub struct DescentParser<'a> {
+    source: &'a str,
+    /// Every token of `source`, trivia included.
+    lossless: Vec<Token>,
+    /// The tokens of `source` without trivia, ending with `Eof`.
+    tokens: Vec<Token>,
+    /// Why each `Unknown` token could not be lexed.
+    lex_errors: Vec<SyntaxError>,
+    pos: usize,
+    events: Vec<Event>,
+    /// How many loop bodies of the current function the parser is in.
+    loops: usize,
+}
+
+impl<'a> DescentParser<'a> {
+    pub fn new(source: &'a str) -> DescentParser<'a> {
+        let (lossless, lex_errors) = Lexer::new(source).tokenize();
+        DescentParser {
+            source,
+            tokens: lossless
+                .iter()
+                .filter(|t| !t.kind.is_trivia())
+                .copied()
+                .collect(),
+            lossless,
+            lex_errors,
+            pos: 0,
+            events: vec![],
+            loops: 0,
+        }
+    }
+
+    pub fn parse_program(mut self) -> ParseResult<Program> {
+        let mut statements = vec![];
+        while self.peek() != TokenKind::Eof {
+            statements.push(self.statement()?);
+        }
+        Ok(Program::new(statements))
+    }
+
+    /// Parses as much of the source as possible. Each statement that fails
+    /// to parse becomes a `Statement::Error` holding its text, and parsing
+    /// resumes after it, so every syntax error is returned at once.
+    pub fn parse_program_recovering(mut self) -> (Program, Vec<SyntaxError>) {
+        self.statements_recovering()
+    }
+
+    /// Parses the source as `parse_program_recovering` does, along with a
+    /// syntax tree holding all of its text. Each `Statement::Error` has an
+    /// `Error` node holding its tokens.
+    pub fn parse_tree(mut self) -> Parse {
+        self.start_node(SyntaxKind::Program);
+        let (program, errors) = self.statements_recovering();
+        self.finish_node();
+        let green = cst::build(self.source, &self.lossless, &self.events);
+        Parse {
+            tree: SyntaxNode::new_root(green),
+            program,
+            errors,
+        }
+    }
+
+    /// Parses the whole source as one statement.
+    pub fn parse_statement(mut self) -> ParseResult<Statement> {
+        let statement = self.statement()?;
+        self.expect(TokenKind::Eof)?;
+        Ok(statement)
+    }
+
+    /// Parses the whole source as one expression.
+    pub fn parse_expression(mut self) -> ParseResult<Expression> {
+        let expression = self.expression()?;
+        self.expect(TokenKind::Eof)?;
+        Ok(expression)
+    }
+
+    /// Parses the whole source as one type.
+    pub fn parse_type(mut self) -> ParseResult<Type> {
+        let ty = self.ty()?;
+        self.expect(TokenKind::Eof)?;
+        Ok(ty)
+    }
+
+    /// Parses the whole source as one pattern.
+    pub fn parse_pattern(mut self) -> ParseResult<Pattern> {
+        let pattern = self.pattern()?;
+        self.expect(TokenKind::Eof)?;
+        Ok(pattern)
+    }
+
+    fn statements_recovering(&mut self) -> (Program, Vec<SyntaxError>) {
+        let mut statements = vec![];
+        let mut errors = vec![];
+        while self.peek() != TokenKind::Eof {
+            let start = self.marker();
+            match self.statement() {
+                Ok(statement) => statements.push(statement),
+                Err(err) => {
+                    let end = self.synchronize(start.token, self.pos.max(start.token));
+                    let span = self.tokens[start.token].span.to(self.tokens[end - 1].span);
+                    // Tokens of the broken statement may have been split, so
+                    // take them again from the source.
+                    let tokens: Vec<Event> = self
+                        .lossless
+                        .iter()
+                        .filter(|t| !t.kind.is_trivia())
+                        .filter(|t| span.start <= t.span.start && t.span.end <= span.end)
+                        .map(|t| Event::Token(*t))
+                        .collect();
+                    self.events.truncate(start.event);
+                    self.start_node(SyntaxKind::Error);
+                    self.events.extend(tokens);
+                    self.finish_node();
+                    statements.push(Statement::Error(
+                        self.source[span.start..span.end].to_string(),
+                    ));
+                    errors.push(err);
+                    self.pos = end;
+                }
+            }
+        }
+        (Program::new(statements), errors)
+    }
+
+    /// Where to resume after the statement starting at token `start` failed
+    /// to parse at token `failed`: just past the `;` or `}` that ends the
+    /// statement, or at the next `fn` that begins a line, whichever comes
+    /// first. Braces are counted from `start`, so a `;` or `}` inside a
+    /// broken function body does not end it early.
+    fn synchronize(&self, start: usize, failed: usize) -> usize {
+        let mut depth = 0;
+        for (i, token) in self.tokens.iter().enumerate().skip(start) {
+            match token.kind {
+                TokenKind::Eof => return i,
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace => {
+                    depth -= 1;
+                    if depth <= 0 && i >= failed {
+                        return i + 1;
+                    }
+                }
+                TokenKind::Semicolon if depth <= 0 && i >= failed => return i + 1,
+                TokenKind::Keyword(Keyword::Fn)
+                    if i > start && i >= failed && self.begins_line(token) =>
+                {
+                    return i
+                }
+                _ => {}
+            }
+        }
+        unreachable!("the last token is `Eof`")
+    }
+
+    fn begins_line(&self, token: &Token) -> bool {
+        let line_start = self.source[..token.span.start]
+            .rfind('\n')
+            .map_or(0, |n| n + 1);
+        self.source[line_start..token.span.start].trim().is_empty()
+    }
+
+    // Token access
+
+    fn token(&self) -> Token {
+        self.tokens[self.pos]
+    }
+
+    fn peek(&self) -> TokenKind {
+        self.tokens[self.pos].kind
+    }
+
+    fn peek_at(&self, n: usize) -> TokenKind {
+        self.tokens
+            .get(self.pos + n)
+            .map_or(TokenKind::Eof, |t| t.kind)
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.token();
+        if token.kind != TokenKind::Eof {
+            self.pos += 1;
+            self.events.push(Event::Token(token));
+        }
+        token
+    }
+
+    fn eat(&mut self, kind: TokenKind) -> bool {
+        if self.peek() == kind {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> ParseResult<Token> {
+        if self.peek() == kind {
+            Ok(self.advance())
+        } else {
+            Err(self.unexpected(&kind.describe()))
+        }
+    }
+
+    fn text(&self, token: Token) -> &'a str {
+        &self.source[token.span.start..token.span.end]
+    }
+
+    /// The span from the start of token `start` to the end of the last
+    /// token consumed.
+    fn span_from(&self, start: usize) -> Span {
+        let end = self.tokens[self.pos.max(start + 1) - 1].span;
+        self.tokens[start].span.to(end)
+    }
+
+    fn previous_span(&self) -> Span {
+        self.tokens[self.pos.max(1) - 1].span
+    }
+
+    fn is_word(&self, n: usize, word: &str) -> bool {
+        self.peek_at(n) == TokenKind::Ident && self.text(self.tokens[self.pos + n]) == word
+    }
+
+    // Errors
+
+    fn error(&self, span: Span, message: String) -> SyntaxError {
+        SyntaxError::new(self.source, span, message)
+    }
+
+    /// An error for the current token, which is not `expected`.
+    fn unexpected(&self, expected: &str) -> SyntaxError {
+        let token = self.token();
+        if token.kind == TokenKind::Unknown {
+            let within = |e: &&SyntaxError| {
+                token.span.start <= e.span.start && e.span.start < token.span.end
+            };
+            if let Some(err) = self.lex_errors.iter().find(within) {
+                return err.clone();
+            }
+        }
+        let found = match token.kind {
+            TokenKind::Eof => "end of input".to_string(),
+            TokenKind::Keyword(keyword) => format!("keyword `{}`", keyword.as_str()),
+            _ => format!("`{}`", self.text(token)),
+        };
+        self.error(
+            token.span,
+            format!("expected {}, found {}", expected, found),
+        )
+    }
+
+    /// Like `unexpected`, but explains a keyword found where a name could
+    /// have been.
+    fn unexpected_name(&self, expected: &str) -> SyntaxError {
+        match self.peek() {
+            TokenKind::Keyword(keyword @ (Keyword::Break | Keyword::Continue)) => {
+                self.loop_control_error(keyword, "cannot be used as a value")
+            }
+            TokenKind::Keyword(keyword) => self.error(
+                self.token().span,
+                format!(
+                    "keyword `{}` used as identifier; write `r#{}` to use it as a name",
+                    keyword.as_str(),
+                    keyword.as_str()
+                ),
+            ),
+            _ => self.unexpected(expected),
+        }
+    }
+
+    /// Explains a `break` or `continue`, which the language does not have,
+    /// found where `problem` describes.
+    fn loop_control_error(&self, keyword: Keyword, problem: &str) -> SyntaxError {
+        let message = if self.loops == 0 {
+            format!("`{}` outside of a loop", keyword.as_str())
+        } else {
+            format!("`{}` {}", keyword.as_str(), problem)
+        };
+        self.error(self.token().span, message)
+    }
+
+    /// Runs `parse` as if inside `loops` loop bodies.
+    fn with_loops<T>(&mut self, loops: usize, parse: impl FnOnce(&mut Self) -> T) -> T {
+        let outer = std::mem::replace(&mut self.loops, loops);
+        let result = parse(self);
+        self.loops = outer;
+        result
+    }
+
+    fn ident(&mut self) -> ParseResult<String> {
+        if self.peek() == TokenKind::Ident {
+            let token = self.advance();
+            Ok(self.text(token).to_string())
+        } else {
+            Err(self.unexpected_name("identifier"))
+        }
+    }
+
+    /// Parses `item`s separated by commas, up to and including `close`.
+    fn separated<T>(
+        &mut self,
+        close: TokenKind,
+        trailing_comma: bool,
+        mut item: impl FnMut(&mut Self) -> ParseResult<T>,
+    ) -> ParseResult<Vec<T>> {
+        let mut items = vec![];
+        if self.peek() != close {
+            loop {
+                items.push(item(self)?);
+                if !self.eat(TokenKind::Comma) || trailing_comma && self.peek() == close {
+                    break;
+                }
+            }
+        }
+        self.expect(close)?;
+        Ok(items)
+    }
+
+    /// Consumes a `>` closing a list of type arguments, splitting it off a
+    /// `>>` or `>=`.
+    fn expect_closing_angle(&mut self) -> ParseResult<()> {
+        let token = self.token();
+        let rest = match token.kind {
+            TokenKind::Gt => return self.expect(TokenKind::Gt).map(|_| ()),
+            TokenKind::Shr => TokenKind::Gt,
+            TokenKind::Ge => TokenKind::Eq,
+            _ => return Err(self.unexpected("`>`")),
+        };
+        self.events.push(Event::Token(Token {
+            kind: TokenKind::Gt,
+            span: Span::new(token.span.start, token.span.start + 1),
+        }));
+        self.tokens[self.pos] = Token {
+            kind: rest,
+            span: Span::new(token.span.start + 1, token.span.end),
+        };
+        Ok(())
+    }
+
+    // Syntax tree
+
+    fn marker(&self) -> Marker {
+        Marker {
+            token: self.pos,
+            event: self.events.len(),
+        }
+    }
+
+    fn start_node(&mut self, kind: SyntaxKind) {
+        self.events.push(Event::Start(kind));
+    }
+
+    fn finish_node(&mut self) {
+        self.events.push(Event::Finish);
+    }
+
+    /// Puts everything parsed since `marker` in a node of `kind`, for when
+    /// what it was is only known afterwards.
+    fn wrap(&mut self, marker: Marker, kind: SyntaxKind) {
+        self.events.insert(marker.event, Event::Start(kind));
+        self.finish_node();
+    }
+
+    // Statements
+
+    fn statement(&mut self) -> ParseResult<Statement> {
+        match self.block_item()? {
+            Item::Statement(statement) => Ok(statement),
+            Item::Value(_) => Err(self.unexpected("`;`")),
+        }
+    }
+
+    fn block_item(&mut self) -> ParseResult<Item> {
+        let start = self.marker();
+        let statement = match self.peek() {
+            TokenKind::Keyword(Keyword::Let) => {
+                Statement::VariableDeclaration(self.variable_declaration()?)
+            }
+            TokenKind::Keyword(Keyword::While) => Statement::While(self.while_loop()?),
+            TokenKind::Keyword(Keyword::For) => Statement::for_loop(self.for_loop()?),
+            TokenKind::Keyword(Keyword::Forall | Keyword::Fn) => {
+                Statement::Function(self.function()?)
+            }
+            TokenKind::Keyword(Keyword::Class) => Statement::Class(self.class()?),
+            TokenKind::Keyword(Keyword::Interface) => Statement::Interface(self.interface()?),
+            TokenKind::Keyword(Keyword::Impl) => Statement::Impl(self.impl_block()?),
+            TokenKind::Keyword(Keyword::Enum) => Statement::Enum(self.enum_definition()?),
+            TokenKind::Keyword(Keyword::Type) => Statement::TypeAlias(self.type_alias()?),
+            TokenKind::Keyword(Keyword::Module) => Statement::Module(self.module()?),
+            TokenKind::Keyword(Keyword::Import) => Statement::Import(self.import()?),
+            TokenKind::Keyword(Keyword::Use) => Statement::Use(self.use_declaration()?),
+            TokenKind::Keyword(Keyword::Export) => self.export()?,
+            TokenKind::Keyword(keyword @ (Keyword::Break | Keyword::Continue)) => {
+                return Err(self.loop_control_error(
+                    keyword,
+                    "is not supported; a loop runs until its condition is false",
+                ));
+            }
+            TokenKind::Keyword(Keyword::If) => {
+                let (conditional, is_value) = self.conditional(None)?;
+                if !is_value {
+                    return Ok(Item::Statement(Statement::Conditional(conditional)));
+                }
+                return self
+                    .expression_statement(Some(Expression::If(Box::new(conditional))), start);
+            }
+            TokenKind::LBrace => {
+                let block = self.block_any()?;
+                if block.value.is_none() {
+                    return Ok(Item::Statement(Statement::Block(block)));
+                }
+                return self.expression_statement(Some(Expression::Block(Box::new(block))), start);
+            }
+            _ => return self.expression_statement(None, start),
+        };
+        Ok(Item::Statement(statement))
+    }
+
+    /// An expression statement or assignment starting at `start`, of which
+    /// `primary` has already been parsed if given.
+    fn expression_statement(
+        &mut self,
+        primary: Option<Expression>,
+        start: Marker,
+    ) -> ParseResult<Item> {
+        let expression = self.binary(1, primary, start)?;
+        match self.peek() {
+            TokenKind::Eq => {
+                let span = self.span_from(start.token);
+                let assignable = match &expression {
+                    Expression::Atom(atom) => {
+                        !atom.negative && matches!(atom.value, AtomValue::Identity(_))
+                    }
+                    Expression::FieldAccess(..) | Expression::Index(..) => true,
+                    _ => false,
+                };
+                if !assignable {
+                    return Err(self.error(
+                        span,
+                        format!("cannot assign to `{}`", &self.source[span.start..span.end]),
+                    ));
+                }
+                self.advance();
+                let value = self.expression()?;
+                self.expect(TokenKind::Semicolon)?;
+                self.wrap(start, SyntaxKind::Assignment);
+                Ok(Item::Statement(Statement::Assignment(Assignment::new(
+                    expression, value,
+                ))))
+            }
+            TokenKind::Semicolon => {
+                self.advance();
+                self.wrap(start, SyntaxKind::ExpressionStatement);
+                Ok(Item::Statement(Statement::Expression(expression)))
+            }
+            // A `match` or block on its own needs no `;`.
+            _ if matches!(expression, Expression::Match(..) | Expression::Block(_)) => {
+                self.wrap(start, SyntaxKind::ExpressionStatement);
+                Ok(Item::Statement(Statement::Expression(expression)))
+            }
+            TokenKind::RBrace => Ok(Item::Value(expression)),
+            _ => Err(self.unexpected("`;`")),
+        }
+    }
+
+    fn variable_declaration(&mut self) -> ParseResult<VariableDeclaration> {
+        self.start_node(SyntaxKind::Let);
+        self.expect(TokenKind::Keyword(Keyword::Let))?;
+        let pattern = self.irrefutable_pattern("let")?;
+        let var_type = if self.eat(TokenKind::Colon) {
+            Some(self.ty()?)
+        } else {
+            None
+        };
+        let value = if self.eat(TokenKind::Eq) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.expect(TokenKind::Semicolon)?;
+        self.finish_node();
+        Ok(VariableDeclaration::destructuring(pattern, var_type, value))
+    }
+
+    fn irrefutable_pattern(&mut self, context: &str) -> ParseResult<Pattern> {
+        let start = self.pos;
+        let pattern = self.pattern()?;
+        if !pattern.is_irrefutable() {
+            return Err(self.error(
+                self.span_from(start),
+                format!("refutable pattern `{}` in `{}`", pattern, context),
+            ));
+        }
+        Ok(pattern)
+    }
+
+    fn while_loop(&mut self) -> ParseResult<WhileLoop> {
+        self.start_node(SyntaxKind::While);
+        self.expect(TokenKind::Keyword(Keyword::While))?;
+        let condition = self.expression()?;
+        let body = self.with_loops(self.loops + 1, Self::block)?;
+        self.finish_node();
+        Ok(WhileLoop::new(condition, body))
+    }
+
+    fn for_loop(&mut self) -> ParseResult<ForLoop> {
+        self.start_node(SyntaxKind::For);
+        self.expect(TokenKind::Keyword(Keyword::For))?;
+        let pattern = self.irrefutable_pattern("for")?;
+        self.expect(TokenKind::Keyword(Keyword::In))?;
+        let marker = self.marker();
+        let start = self.expression()?;
+        let inclusive = match self.peek() {
+            TokenKind::DotDot => false,
+            TokenKind::DotDotEq => true,
+            _ => {
+                let body = self.with_loops(self.loops + 1, Self::block)?;
+                self.finish_node();
+                return Ok(ForLoop::new(pattern, Iterable::Expression(start), body));
+            }
+        };
+        self.advance();
+        let end = self.expression()?;
+        let step = if self.is_word(0, "step") {
+            self.advance();
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        let range = Range {
+            start,
+            end,
+            inclusive,
+            step,
+        };
+        self.wrap(marker, SyntaxKind::Range);
+        let body = self.with_loops(self.loops + 1, Self::block)?;
+        self.finish_node();
+        Ok(ForLoop::new(
+            pattern,
+            Iterable::Range(Box::new(range)),
+            body,
+        ))
+    }
+
+    /// `(self, a: T, ...)` without the parentheses.
+    fn params(&mut self) -> ParseResult<Vec<VariableDeclaration>> {
+        self.start_node(SyntaxKind::ParamList);
+        let mut params = vec![];
+        match self.peek() {
+            TokenKind::Ident if self.is_word(0, "self") => {
+                self.start_node(SyntaxKind::Param);
+                self.advance();
+                self.finish_node();
+                params.push(VariableDeclaration::new("self".to_string(), None, None));
+            }
+            TokenKind::Ident => params.push(self.typed_name(SyntaxKind::Param)?),
+            TokenKind::Keyword(_) => return Err(self.unexpected_name("identifier")),
+            _ => {
+                self.finish_node();
+                return Ok(params);
+            }
+        }
+        while self.eat(TokenKind::Comma) {
+            params.push(self.typed_name(SyntaxKind::Param)?);
+        }
+        self.finish_node();
+        Ok(params)
+    }
+
+    /// `name: T`, in a node of `kind`.
+    fn typed_name(&mut self, kind: SyntaxKind) -> ParseResult<VariableDeclaration> {
+        self.start_node(kind);
+        let name = self.ident()?;
+        self.expect(TokenKind::Colon)?;
+        let ty = self.ty()?;
+        self.finish_node();
+        Ok(VariableDeclaration::new(name, Some(ty), None))
+    }
+
+    /// `fn name<T>(params) -> T { ... }`, after `forall params.` if the
+    /// function is dependent.
+    fn function(&mut self) -> ParseResult<FunctionDefinition> {
+        self.start_node(SyntaxKind::Function);
+        let dependent_params = if self.eat(TokenKind::Keyword(Keyword::Forall)) {
+            let params = self.params()?;
+            self.expect(TokenKind::Dot)?;
+            params
+        } else {
+            vec![]
+        };
+        self.expect(TokenKind::Keyword(Keyword::Fn))?;
+        let name = self.ident()?;
+        let mut type_params = vec![];
+        if self.peek() == TokenKind::Lt {
+            self.start_node(SyntaxKind::TypeParams);
+            self.advance();
+            loop {
+                self.start_node(SyntaxKind::TypeParam);
+                let name = self.ident()?;
+                let mut bounds = vec![];
+                if self.eat(TokenKind::Colon) {
+                    bounds.push(self.ident()?);
+                    while self.eat(TokenKind::Plus) {
+                        bounds.push(self.ident()?);
+                    }
+                }
+                self.finish_node();
+                type_params.push(TypeParam::new(name, bounds));
+                if !self.eat(TokenKind::Comma) {
+                    break;
+                }
+            }
+            self.expect_closing_angle()?;
+            self.finish_node();
+        }
+        self.expect(TokenKind::LParen)?;
+        let inputs = self.params()?;
+        self.expect(TokenKind::RParen)?;
+        self.expect(TokenKind::Arrow)?;
+        let return_type = self.ty()?;
+        let body = self.with_loops(0, Self::block)?;
+        self.finish_node();
+        let function = FunctionDefinition::new(name, inputs, return_type, body);
+        Ok(FunctionDefinition {
+            dependent_params,
+            ..function.with_type_params(type_params)
+        })
+    }
+
+    fn class(&mut self) -> ParseResult<ClassDefinition> {
+        self.start_node(SyntaxKind::Class);
+        self.expect(TokenKind::Keyword(Keyword::Class))?;
+        let name = self.ident()?;
+        self.expect(TokenKind::LBrace)?;
+        let fields =
+            self.separated(TokenKind::RBrace, true, |p| p.typed_name(SyntaxKind::Field))?;
+        self.finish_node();
+        Ok(ClassDefinition::new(name, fields))
+    }
+
+    fn interface(&mut self) -> ParseResult<InterfaceDefinition> {
+        self.start_node(SyntaxKind::Interface);
+        self.expect(TokenKind::Keyword(Keyword::Interface))?;
+        let name = self.ident()?;
+        self.expect(TokenKind::LBrace)?;
+        let mut methods = vec![];
+        while !self.eat(TokenKind::RBrace) {
+            self.start_node(SyntaxKind::MethodSignature);
+            self.expect(TokenKind::Keyword(Keyword::Fn))?;
+            let name = self.ident()?;
+            self.expect(TokenKind::LParen)?;
+            let inputs = self.params()?;
+            self.expect(TokenKind::RParen)?;
+            self.expect(TokenKind::Arrow)?;
+            let return_type = self.ty()?;
+            self.expect(TokenKind::Semicolon)?;
+            self.finish_node();
+            methods.push(MethodSignature::new(name, inputs, return_type));
+        }
+        self.finish_node();
+        Ok(InterfaceDefinition::new(name, methods))
+    }
+
+    fn impl_block(&mut self) -> ParseResult<ImplBlock> {
+        self.start_node(SyntaxKind::Impl);
+        self.expect(TokenKind::Keyword(Keyword::Impl))?;
+        let first = self.ident()?;
+        let interface = if self.eat(TokenKind::Keyword(Keyword::For)) {
+            Some(first.clone())
+        } else {
+            None
+        };
+        let class = match interface {
+            Some(_) => self.ident()?,
+            None => first,
+        };
+        self.expect(TokenKind::LBrace)?;
+        let mut methods = vec![];
+        while !self.eat(TokenKind::RBrace) {
+            // Methods cannot be dependent.
+            if self.peek() == TokenKind::Keyword(Keyword::Forall) {
+                return Err(self.unexpected("`fn`"));
+            }
+            methods.push(self.function()?);
+        }
+        self.finish_node();
+        Ok(match interface {
+            Some(interface) => ImplBlock::for_interface(interface, class, methods),
+            None => ImplBlock::new(class, methods),
+        })
+    }
+
+    fn enum_definition(&mut self) -> ParseResult<EnumDefinition> {
+        self.start_node(SyntaxKind::Enum);
+        self.expect(TokenKind::Keyword(Keyword::Enum))?;
+        let name = self.ident()?;
+        self.expect(TokenKind::LBrace)?;
+        let variants = self.separated(TokenKind::RBrace, true, |p| {
+            p.start_node(SyntaxKind::Variant);
+            let name = p.ident()?;
+            let mut fields = vec![];
+            if p.eat(TokenKind::LParen) {
+                fields.push(p.ty()?);
+                while p.eat(TokenKind::Comma) {
+                    fields.push(p.ty()?);
+                }
+                p.expect(TokenKind::RParen)?;
+            }
+            p.finish_node();
+            Ok(EnumVariant::new(name, fields))
+        })?;
+        self.finish_node();
+        Ok(EnumDefinition::new(name, variants))
+    }
+
+    fn type_alias(&mut self) -> ParseResult<TypeAlias> {
+        self.start_node(SyntaxKind::TypeAlias);
+        self.expect(TokenKind::Keyword(Keyword::Type))?;
+        let name = self.ident()?;
+        let mut params = vec![];
+        if self.peek() == TokenKind::Lt {
+            self.start_node(SyntaxKind::TypeParams);
+            self.advance();
+            loop {
+                self.start_node(SyntaxKind::TypeParam);
+                params.push(self.ident()?);
+                self.finish_node();
+                if !self.eat(TokenKind::Comma) {
+                    break;
+                }
+            }
+            self.expect_closing_angle()?;
+            self.finish_node();
+        }
+        self.expect(TokenKind::Eq)?;
+        let target = self.ty()?;
+        self.expect(TokenKind::Semicolon)?;
+        self.finish_node();
+        Ok(TypeAlias::new(name, params, target))
+    }
+
+    /// `a::b::c`
+    fn module_path(&mut self) -> ParseResult<Vec<String>> {
+        let mut path = vec![self.ident()?];
+        while self.eat(TokenKind::ColonColon) {
+            path.push(self.ident()?);
+        }
+        Ok(path)
+    }
+
+    fn module(&mut self) -> ParseResult<Vec<String>> {
+        self.start_node(SyntaxKind::Module);
+        self.expect(TokenKind::Keyword(Keyword::Module))?;
+        let path = self.module_path()?;
+        self.expect(TokenKind::Semicolon)?;
+        self.finish_node();
+        Ok(path)
+    }
+
+    fn import_item(&mut self) -> ParseResult<ImportItem> {
+        self.start_node(SyntaxKind::ImportItem);
+        let name = self.ident()?;
+        let alias = if self.eat(TokenKind::Keyword(Keyword::As)) {
+            Some(self.ident()?)
+        } else {
+            None
+        };
+        self.finish_node();
+        Ok(ImportItem::new(name, alias))
+    }
+
+    fn import(&mut self) -> ParseResult<Import> {
+        self.start_node(SyntaxKind::Import);
+        self.expect(TokenKind::Keyword(Keyword::Import))?;
+        self.expect(TokenKind::LBrace)?;
+        if self.peek() == TokenKind::RBrace {
+            return Err(self.unexpected_name("identifier"));
+        }
+        let items = self.separated(TokenKind::RBrace, true, Self::import_item)?;
+        self.expect(TokenKind::Keyword(Keyword::From))?;
+        let source = match self.peek() {
+            TokenKind::String | TokenKind::RawString => self.string()?,
+            _ => return Err(self.unexpected("string")),
+        };
+        self.expect(TokenKind::Semicolon)?;
+        self.finish_node();
+        Ok(Import::new(items, source))
+    }
+
+    fn use_declaration(&mut self) -> ParseResult<Use> {
+        let start = self.pos;
+        self.start_node(SyntaxKind::Use);
+        self.expect(TokenKind::Keyword(Keyword::Use))?;
+        let mut module = self.module_path()?;
+        let alias = if self.eat(TokenKind::Keyword(Keyword::As)) {
+            Some(self.ident()?)
+        } else {
+            None
+        };
+        self.expect(TokenKind::Semicolon)?;
+        if module.len() < 2 {
+            return Err(self.error(self.span_from(start), "expected `module::name`".to_string()));
+        }
+        let name = module.pop().unwrap();
+        self.finish_node();
+        Ok(Use::new(module, ImportItem::new(name, alias)))
+    }
+
+    fn export(&mut self) -> ParseResult<Statement> {
+        self.start_node(SyntaxKind::Export);
+        self.expect(TokenKind::Keyword(Keyword::Export))?;
+        let declaration = match self.peek() {
+            TokenKind::Keyword(Keyword::Let) => {
+                Statement::VariableDeclaration(self.variable_declaration()?)
+            }
+            TokenKind::Keyword(Keyword::Forall | Keyword::Fn) => {
+                Statement::Function(self.function()?)
+            }
+            TokenKind::Keyword(Keyword::Class) => Statement::Class(self.class()?),
+            TokenKind::Keyword(Keyword::Interface) => Statement::Interface(self.interface()?),
+            TokenKind::Keyword(Keyword::Enum) => Statement::Enum(self.enum_definition()?),
+            TokenKind::Keyword(Keyword::Type) => Statement::TypeAlias(self.type_alias()?),
+            _ => return Err(self.unexpected("declaration")),
+        };
+        self.finish_node();
+        Ok(Statement::export(declaration))
+    }
+
+    // Blocks and conditionals
+
+    /// `{ statements }`, ending in an expression if the block has a value.
+    fn block_any(&mut self) -> ParseResult<Block> {
+        self.start_node(SyntaxKind::Block);
+        self.expect(TokenKind::LBrace)?;
+        let mut statements = vec![];
+        let mut value = None;
+        while self.peek() != TokenKind::RBrace {
+            if self.peek() == TokenKind::Eof {
+                return Err(self.unexpected("`}`"));
+            }
+            match self.block_item()? {
+                Item::Statement(statement) => statements.push(statement),
+                Item::Value(expression) => value = Some(expression),
+            }
+        }
+        self.advance();
+        self.finish_node();
+        Ok(Block::new(statements).with_value(value))
+    }
+
+    /// A block without a value, like a function or loop body.
+    fn block(&mut self) -> ParseResult<Block> {
+        let block = self.block_any()?;
+        self.check_block(&block, false)?;
+        Ok(block)
+    }
+
+    /// Checks that the block just parsed has a value exactly when
+    /// `is_value` is set.
+    fn check_block(&self, block: &Block, is_value: bool) -> ParseResult<()> {
+        let close = self.previous_span();
+        match (&block.value, is_value) {
+            (None, true) => Err(self.error(close, "expected expression, found `}`".to_string())),
+            (Some(_), false) => Err(self.error(close, "expected `;`, found `}`".to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Parses an `if`, which is a value when its blocks have values. Unless
+    /// `is_value` says which it must be, the first block decides. Returns
+    /// the conditional and whether it is a value.
+    fn conditional(&mut self, is_value: Option<bool>) -> ParseResult<(Conditional, bool)> {
+        self.start_node(SyntaxKind::If);
+        self.expect(TokenKind::Keyword(Keyword::If))?;
+        let pattern = if self.eat(TokenKind::Keyword(Keyword::Let)) {
+            let pattern = self.pattern()?;
+            self.expect(TokenKind::Eq)?;
+            Some(pattern)
+        } else {
+            None
+        };
+        let condition = self.expression()?;
+        let then_block = self.block_any()?;
+        let is_value = is_value.unwrap_or(then_block.value.is_some());
+        self.check_block(&then_block, is_value)?;
+        let else_block = if !self.eat(TokenKind::Keyword(Keyword::Else)) {
+            None
+        } else if self.peek() == TokenKind::Keyword(Keyword::If) {
+            // Only an `if` used as a value may have an `else if`.
+            if !is_value {
+                return Err(self.unexpected("`{`"));
+            }
+            let (inner, _) = self.conditional(Some(true))?;
+            Some(Block::new(vec![]).with_value(Some(Expression::If(Box::new(inner)))))
+        } else {
+            let block = self.block_any()?;
+            self.check_block(&block, is_value)?;
+            Some(block)
+        };
+        self.finish_node();
+        let conditional = Conditional::new(condition, then_block, else_block).with_pattern(pattern);
+        Ok((conditional, is_value))
+    }
+
+    // Expressions
+
+    fn expression(&mut self) -> ParseResult<Expression> {
+        let start = self.marker();
+        self.binary(1, None, start)
+    }
+
+    /// Parses operators binding at least as tightly as `min_precedence`,
+    /// starting from `primary` if it has already been parsed from `start`.
+    fn binary(
+        &mut self,
+        min_precedence: u8,
+        primary: Option<Expression>,
+        start: Marker,
+    ) -> ParseResult<Expression> {
+        let mut lhs = match primary {
+            Some(primary) => self.postfix(primary, start)?,
+            None => self.unary()?,
+        };
+        while let Some((op, precedence)) = infix_operator(self.peek()) {
+            if precedence < min_precedence {
+                break;
+            }
+            let op_span = self.advance().span;
+            let next = if op == Operator::Pow {
+                precedence
+            } else {
+                precedence + 1
+            };
+            let rhs_start = self.marker();
+            let rhs = self.binary(next, None, rhs_start)?;
+            if let Expression::BinaryOperation(_, lhs_op, _) = &lhs {
+                if op.is_comparison() && lhs_op.is_comparison() {
+                    return Err(self.error(
+                        op_span,
+                        "comparison operators cannot be chained; use parentheses".to_string(),
+                    ));
+                }
+            }
+            self.wrap(start, SyntaxKind::Binary);
+            lhs = Expression::BinaryOperation(Box::new(lhs), op, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> ParseResult<Expression> {
+        let start = self.marker();
+        let op = match self.peek() {
+            // A `-` directly before a number is part of the literal.
+            TokenKind::Minus
+                if !matches!(self.peek_at(1), TokenKind::Integer(_) | TokenKind::Decimal) =>
+            {
+                UnaryOp::Neg
+            }
+            TokenKind::Bang => UnaryOp::Not,
+            TokenKind::Tilde => UnaryOp::BitNot,
+            _ => {
+                let primary = self.primary()?;
+                return self.postfix(primary, start);
+            }
+        };
+        self.advance();
+        let operand = self.unary()?;
+        self.wrap(start, SyntaxKind::Unary);
+        Ok(Expression::Unary(op, Box::new(operand)))
+    }
+
+    /// Parses the postfix operators after `expression`, which was parsed
+    /// from `start`.
+    fn postfix(&mut self, mut expression: Expression, start: Marker) -> ParseResult<Expression> {
+        loop {
+            let object = Box::new(expression);
+            expression = match self.peek() {
+                TokenKind::Dot => {
+                    self.advance();
+                    let name = match self.peek() {
+                        TokenKind::Integer(Radix::Decimal) => {
+                            let token = self.advance();
+                            self.text(token).to_string()
+                        }
+                        _ => self.ident()?,
+                    };
+                    if self.peek() == TokenKind::LParen {
+                        let args = self.arguments()?;
+                        self.wrap(start, SyntaxKind::MethodCall);
+                        Expression::MethodCall(object, name, args)
+                    } else {
+                        self.wrap(start, SyntaxKind::FieldAccess);
+                        Expression::FieldAccess(object, name)
+                    }
+                }
+                TokenKind::LBracket => {
+                    self.advance();
+                    let index = self.expression()?;
+                    self.expect(TokenKind::RBracket)?;
+                    self.wrap(start, SyntaxKind::Index);
+                    Expression::Index(object, Box::new(index))
+                }
+                TokenKind::Keyword(Keyword::Is) => {
+                    self.advance();
+                    let (expression, kind) = match self.peek() {
+                        TokenKind::Keyword(Keyword::Some) => {
+                            (Expression::IsSome(object), SyntaxKind::IsSome)
+                        }
+                        TokenKind::Keyword(Keyword::None) => {
+                            (Expression::IsNone(object), SyntaxKind::IsNone)
+                        }
+                        _ => return Err(self.unexpected("`some` or `none`")),
+                    };
+                    self.advance();
+                    self.wrap(start, kind);
+                    expression
+                }
+                TokenKind::Question => {
+                    self.advance();
+                    self.wrap(start, SyntaxKind::Try);
+                    Expression::Try(object)
+                }
+                _ => return Ok(*object),
+            };
+        }
+    }
+
+    /// `(a, b, ...)`, the arguments of a call.
+    fn arguments(&mut self) -> ParseResult<Vec<Expression>> {
+        self.start_node(SyntaxKind::ArgList);
+        self.expect(TokenKind::LParen)?;
+        let args = self.separated(TokenKind::RParen, false, Self::expression)?;
+        self.finish_node();
+        Ok(args)
+    }
+
+    fn primary(&mut self) -> ParseResult<Expression> {
+        match self.peek() {
+            TokenKind::Keyword(Keyword::New) => self.new_expression(),
+            TokenKind::Keyword(Keyword::Match) => self.match_expression(),
+            TokenKind::Keyword(Keyword::If) => {
+                let (conditional, _) = self.conditional(Some(true))?;
+                Ok(Expression::If(Box::new(conditional)))
+            }
+            TokenKind::LBrace => {
+                let block = self.block_any()?;
+                self.check_block(&block, true)?;
+                Ok(Expression::Block(Box::new(block)))
+            }
+            TokenKind::Ident if self.peek_at(1) == TokenKind::ColonColon => {
+                self.start_node(SyntaxKind::VariantExpression);
+                let name = self.ident()?;
+                self.advance();
+                let variant = self.ident()?;
+                let args = if self.peek() == TokenKind::LParen {
+                    self.arguments()?
+                } else {
+                    vec![]
+                };
+                self.finish_node();
+                Ok(Expression::Variant(name, variant, args))
+            }
+            TokenKind::Pipe | TokenKind::PipePipe => self.lambda(),
+            TokenKind::LBracket => self.array(),
+            TokenKind::LParen => {
+                let start = self.marker();
+                self.advance();
+                let first = self.expression()?;
+                if self.eat(TokenKind::Comma) {
+                    let mut elements = vec![first];
+                    elements.extend(self.separated(TokenKind::RParen, true, Self::expression)?);
+                    self.wrap(start, SyntaxKind::Tuple);
+                    return Ok(Expression::Tuple(elements));
+                }
+                self.expect(TokenKind::RParen)?;
+                self.wrap(start, SyntaxKind::Paren);
+                let ty = first.return_type();
+                if let Expression::Atom(atom) = first {
+                    return Ok(Expression::Atom(atom));
+                }
+                Ok(Expression::Atom(Atom {
+                    negative: false,
+                    value: AtomValue::ParExpr(Box::new(first)),
+                    ty,
+                    radix: Radix::Decimal,
+                }))
+            }
+            TokenKind::Keyword(Keyword::Some) => {
+                let inner = self.wrapper(SyntaxKind::Some)?;
+                Ok(Expression::Some(Box::new(inner)))
+            }
+            TokenKind::Keyword(Keyword::None) => {
+                self.start_node(SyntaxKind::None);
+                self.advance();
+                self.finish_node();
+                Ok(Expression::None)
+            }
+            // `ok` and `err` are not keywords, and only build results when
+            // called.
+            TokenKind::Ident if self.is_word(0, "ok") && self.peek_at(1) == TokenKind::LParen => {
+                let inner = self.wrapper(SyntaxKind::Ok)?;
+                Ok(Expression::Ok(Box::new(inner)))
+            }
+            TokenKind::Ident if self.is_word(0, "err") && self.peek_at(1) == TokenKind::LParen => {
+                let inner = self.wrapper(SyntaxKind::Err)?;
+                Ok(Expression::Err(Box::new(inner)))
+            }
+            TokenKind::Minus
+            | TokenKind::Integer(_)
+            | TokenKind::Decimal
+            | TokenKind::Keyword(Keyword::True | Keyword::False)
+            | TokenKind::String
+            | TokenKind::RawString
+            | TokenKind::Ident => Ok(Expression::Atom(self.atom()?)),
+            _ => Err(self.unexpected_name("expression")),
+        }
+    }
+
+    /// `some(e)`, `ok(e)` or `err(e)`, in a node of `kind`.
+    fn wrapper(&mut self, kind: SyntaxKind) -> ParseResult<Expression> {
+        self.start_node(kind);
+        self.advance();
+        self.expect(TokenKind::LParen)?;
+        let expression = self.expression()?;
+        self.expect(TokenKind::RParen)?;
+        self.finish_node();
+        Ok(expression)
+    }
+
+    fn new_expression(&mut self) -> ParseResult<Expression> {
+        self.start_node(SyntaxKind::New);
+        self.expect(TokenKind::Keyword(Keyword::New))?;
+        let class = self.ident()?;
+        self.expect(TokenKind::LBrace)?;
+        let fields = self.separated(TokenKind::RBrace, true, |p| {
+            p.start_node(SyntaxKind::FieldInit);
+            let name = p.ident()?;
+            p.expect(TokenKind::Colon)?;
+            let value = p.expression()?;
+            p.finish_node();
+            Ok((name, value))
+        })?;
+        self.finish_node();
+        Ok(Expression::New(class, fields))
+    }
+
+    fn match_expression(&mut self) -> ParseResult<Expression> {
+        self.start_node(SyntaxKind::Match);
+        self.expect(TokenKind::Keyword(Keyword::Match))?;
+        let scrutinee = self.expression()?;
+        self.expect(TokenKind::LBrace)?;
+        let arms = self.separated(TokenKind::RBrace, true, |p| {
+            p.start_node(SyntaxKind::MatchArm);
+            let pattern = p.pattern()?;
+            p.expect(TokenKind::FatArrow)?;
+            let body = if p.peek() == TokenKind::LBrace {
+                let start = p.marker();
+                let block = p.block_any()?;
+                match block.value {
+                    None => ArmBody::Block(block),
+                    Some(_) => {
+                        let block = Expression::Block(Box::new(block));
+                        ArmBody::Expression(p.binary(1, Some(block), start)?)
+                    }
+                }
+            } else {
+                ArmBody::Expression(p.expression()?)
+            };
+            p.finish_node();
+            Ok(MatchArm::new(pattern, body))
+        })?;
+        self.finish_node();
+        Ok(Expression::Match(Box::new(scrutinee), arms))
+    }
+
+    /// `|a: T, ...| body`, where the body is an expression or a return type
+    /// and block.
+    fn lambda(&mut self) -> ParseResult<Expression> {
+        self.start_node(SyntaxKind::Lambda);
+        let params = if self.eat(TokenKind::PipePipe) {
+            vec![]
+        } else {
+            self.expect(TokenKind::Pipe)?;
+            self.separated(TokenKind::Pipe, false, |p| p.typed_name(SyntaxKind::Param))?
+        };
+        let (return_type, body) = if self.eat(TokenKind::Arrow) {
+            let return_type = self.ty()?;
+            (
+                Some(return_type),
+                ArmBody::Block(self.with_loops(0, Self::block)?),
+            )
+        } else {
+            (
+                None,
+                ArmBody::Expression(self.with_loops(0, Self::expression)?),
+            )
+        };
+        self.finish_node();
+        Ok(Expression::Lambda(Box::new(Lambda::new(
+            params,
+            return_type,
+            body,
+        ))))
+    }
+
+    /// `[a, b, ...]` or `[value; n]`.
+    fn array(&mut self) -> ParseResult<Expression> {
+        let start = self.marker();
+        self.expect(TokenKind::LBracket)?;
+        if self.eat(TokenKind::RBracket) {
+            self.wrap(start, SyntaxKind::Array);
+            return Ok(Expression::Array(vec![]));
+        }
+        let first = self.expression()?;
+        if self.eat(TokenKind::Semicolon) {
+            let len = self.length()?;
+            self.expect(TokenKind::RBracket)?;
+            self.wrap(start, SyntaxKind::Repeat);
+            return Ok(Expression::Repeat(Box::new(first), len));
+        }
+        let mut elements = vec![first];
+        if self.eat(TokenKind::Comma) {
+            elements.extend(self.separated(TokenKind::RBracket, true, Self::expression)?);
+        } else {
+            self.expect(TokenKind::RBracket)?;
+        }
+        self.wrap(start, SyntaxKind::Array);
+        Ok(Expression::Array(elements))
+    }
+
+    /// The length of an array type or repeat literal.
+    fn length(&mut self) -> ParseResult<usize> {
+        let token = self.token();
+        let len = self.integer()?;
+        usize::try_from(len).map_err(|_| {
+            self.error(
+                token.span,
+                format!("array length `{}` is too large", self.text(token)),
+            )
+        })
+    }
+
+    /// An integer literal without a suffix.
+    fn integer(&mut self) -> ParseResult<u128> {
+        let TokenKind::Integer(radix) = self.peek() else {
+            return Err(self.unexpected("integer"));
+        };
+        let token = self.advance();
+        let text = self.text(token);
+        let digits = match radix {
+            Radix::Decimal => text,
+            _ => &text[2..],
+        };
+        integer_value(digits, radix).ok_or_else(|| {
+            self.error(
+                token.span,
+                format!("integer literal `{}` is too large", text),
+            )
+        })
+    }
+
+    fn string(&mut self) -> ParseResult<String> {
+        let token = self.advance();
+        let text = self.text(token);
+        match token.kind {
+            TokenKind::RawString => Ok(raw_string_value(text)),
+            _ => unescape(text).map_err(|message| self.error(token.span, message)),
+        }
+    }
+
+    /// A literal, variable or call.
+    fn atom(&mut self) -> ParseResult<Atom> {
+        let start = self.marker();
+        let negative = self.eat(TokenKind::Minus);
+        let (atom, kind) = match self.peek() {
+            TokenKind::Integer(_) | TokenKind::Decimal => {
+                (self.number(negative)?, SyntaxKind::Literal)
+            }
+            _ if negative => return Err(self.unexpected("number")),
+            TokenKind::Keyword(keyword @ (Keyword::True | Keyword::False)) => {
+                self.advance();
+                let value = AtomValue::Boolean(keyword == Keyword::True);
+                (
+                    Atom::new(false, value, Some(Type::Bool)),
+                    SyntaxKind::Literal,
+                )
+            }
+            TokenKind::String | TokenKind::RawString => {
+                let value = AtomValue::String(self.string()?);
+                (
+                    Atom::new(false, value, Some(Type::String)),
+                    SyntaxKind::Literal,
+                )
+            }
+            TokenKind::Ident => {
+                let name = self.ident()?;
+                if self.peek() == TokenKind::LParen {
+                    let args = self.arguments()?;
+                    (
+                        Atom::new(false, AtomValue::Call(name, args), None),
+                        SyntaxKind::Call,
+                    )
+                } else {
+                    (
+                        Atom::new(false, AtomValue::Identity(name), None),
+                        SyntaxKind::Name,
+                    )
+                }
+            }
+            _ => return Err(self.unexpected_name("expression")),
+        };
+        self.wrap(start, kind);
+        Ok(atom)
+    }
+
+    /// A numeric literal and its suffix, negated if `negative`.
+    fn number(&mut self, negative: bool) -> ParseResult<Atom> {
+        let token = self.token();
+        let (value, radix) = match token.kind {
+            TokenKind::Integer(radix) => (AtomValue::Integer(self.integer()?), radix),
+            _ => {
+                self.advance();
+                let text = self.text(token);
+                let value = float_value(text).ok_or_else(|| {
+                    self.error(token.span, format!("float literal `{}` is too large", text))
+                })?;
+                (AtomValue::Float(value), Radix::Decimal)
+            }
+        };
+        let mut ty = None;
+        if self.peek() == TokenKind::Suffix {
+            let suffix = self.advance();
+            if let Some(message) = suffix_error(&value, self.text(suffix)) {
+                return Err(self.error(suffix.span, message));
+            }
+            let suffix_type = Type::parse_type(self.text(suffix)).unwrap();
+            if !literal_fits(negative, &value, &suffix_type) {
+                let span = token.span.to(suffix.span);
+                return Err(self.error(
+                    span,
+                    format!(
+                        "literal `{}{}` is out of range for `{}`",
+                        if negative { "-" } else { "" },
+                        &self.source[span.start..span.end],
+                        suffix_type
+                    ),
+                ));
+            }
+            ty = Some(suffix_type);
+        }
+        Ok(Atom {
+            negative,
+            value,
+            ty,
+            radix,
+        })
+    }
+
+    // Patterns
+
+    fn pattern(&mut self) -> ParseResult<Pattern> {
+        self.start_node(SyntaxKind::Pattern);
+        let pattern = self.bare_pattern()?;
+        self.finish_node();
+        Ok(pattern)
+    }
+
+    /// A pattern, without its node in the syntax tree.
+    fn bare_pattern(&mut self) -> ParseResult<Pattern> {
+        match self.peek() {
+            TokenKind::Underscore => {
+                self.advance();
+                Ok(Pattern::Wildcard)
+            }
+            TokenKind::Minus
+            | TokenKind::Integer(_)
+            | TokenKind::Decimal
+            | TokenKind::Keyword(Keyword::True | Keyword::False)
+            | TokenKind::String
+            | TokenKind::RawString => Ok(Pattern::Literal(self.atom()?)),
+            TokenKind::Ident if self.peek_at(1) == TokenKind::ColonColon => {
+                let name = self.ident()?;
+                self.advance();
+                let variant = self.ident()?;
+                let mut fields = vec![];
+                if self.eat(TokenKind::LParen) {
+                    fields.push(self.pattern()?);
+                    while self.eat(TokenKind::Comma) {
+                        fields.push(self.pattern()?);
+                    }
+                    self.expect(TokenKind::RParen)?;
+                }
+                Ok(Pattern::Variant(name, variant, fields))
+            }
+            TokenKind::Keyword(Keyword::Some) => {
+                self.advance();
+                Ok(Pattern::Some(Box::new(self.parenthesized_pattern()?)))
+            }
+            TokenKind::Keyword(Keyword::None) => {
+                self.advance();
+                Ok(Pattern::None)
+            }
+            TokenKind::Ident if self.is_word(0, "ok") && self.peek_at(1) == TokenKind::LParen => {
+                self.advance();
+                Ok(Pattern::Ok(Box::new(self.parenthesized_pattern()?)))
+            }
+            TokenKind::Ident if self.is_word(0, "err") && self.peek_at(1) == TokenKind::LParen => {
+                self.advance();
+                Ok(Pattern::Err(Box::new(self.parenthesized_pattern()?)))
+            }
+            TokenKind::Ident => Ok(Pattern::Binding(self.ident()?)),
+            TokenKind::LParen => {
+                self.advance();
+                let first = self.pattern()?;
+                if !self.eat(TokenKind::Comma) {
+                    self.expect(TokenKind::RParen)?;
+                    return Ok(first);
+                }
+                let mut fields = vec![first];
+                fields.extend(self.separated(TokenKind::RParen, true, Self::pattern)?);
+                Ok(Pattern::Tuple(fields))
+            }
+            _ => Err(self.unexpected_name("pattern")),
+        }
+    }
+
+    fn parenthesized_pattern(&mut self) -> ParseResult<Pattern> {
+        self.expect(TokenKind::LParen)?;
+        let pattern = self.pattern()?;
+        self.expect(TokenKind::RParen)?;
+        Ok(pattern)
+    }
+
+    // Types
+
+    fn ty(&mut self) -> ParseResult<Type> {
+        self.start_node(SyntaxKind::Type);
+        let mut ty = self.base_type()?;
+        while self.eat(TokenKind::Question) {
+            ty = Type::option(ty);
+        }
+        self.finish_node();
+        Ok(ty)
+    }
+
+    fn base_type(&mut self) -> ParseResult<Type> {
+        match self.peek() {
+            TokenKind::LParen => {
+                self.advance();
+                if self.eat(TokenKind::RParen) {
+                    return Ok(Type::Unit);
+                }
+                // A one-element tuple needs a trailing comma, as in `(i32,)`.
+                let mut elements = vec![self.ty()?];
+                self.expect(TokenKind::Comma)?;
+                elements.extend(self.separated(TokenKind::RParen, true, Self::ty)?);
+                Ok(Type::Tuple(elements))
+            }
+            TokenKind::LBracket => {
+                self.advance();
+                let element = Box::new(self.ty()?);
+                if self.eat(TokenKind::Semicolon) {
+                    let len = self.length()?;
+                    self.expect(TokenKind::RBracket)?;
+                    return Ok(Type::Array(element, len));
+                }
+                self.expect(TokenKind::RBracket)?;
+                Ok(Type::Slice(element))
+            }
+            TokenKind::Keyword(Keyword::Fn) => {
+                self.advance();
+                self.expect(TokenKind::LParen)?;
+                let params = self.separated(TokenKind::RParen, false, Self::ty)?;
+                self.expect(TokenKind::Arrow)?;
+                let ret = self.ty()?;
+                Ok(Type::Function(params, Box::new(ret)))
+            }
+            TokenKind::Keyword(Keyword::Forall) => {
+                self.advance();
+                let params = self.params()?;
+                self.expect(TokenKind::Dot)?;
+                let body = self.ty()?;
+                Ok(Type::Dependent(DType::new(params, body)))
+            }
+            TokenKind::Ident => {
+                let token = self.token();
+                let name = self.ident()?;
+                if PRIMITIVE_TYPES.contains(&name.as_str()) {
+                    return Type::parse_type(&name)
+                        .ok_or_else(|| self.error(token.span, format!("unknown type: {}", name)));
+                }
+                if !self.eat(TokenKind::Lt) {
+                    return Ok(Type::parse_type(&name).unwrap_or(Type::Custom(name)));
+                }
+                let mut fields = vec![self.type_arg()?];
+                while self.eat(TokenKind::Comma) {
+                    fields.push(self.type_arg()?);
+                }
+                self.expect_closing_angle()?;
+                Ok(Type::Generic(GType::new(name, fields)))
+            }
+            _ => Err(self.unexpected_name("type")),
+        }
+    }
+
+    /// A type, or an integer for a value parameter.
+    fn type_arg(&mut self) -> ParseResult<Type> {
+        match self.peek() {
+            TokenKind::Integer(_) => {
+                let token = self.token();
+                let value = self.integer()?;
+                i128::try_from(value).map(Type::Const).map_err(|_| {
+                    self.error(
+                        token.span,
+                        format!("integer literal `{}` is too large", self.text(token)),
+                    )
+                })
+            }
+            _ => self.ty(),
+        }
+    }
+}
+
+/// The operator a token stands for between two operands, and how tightly it
+/// binds.
+fn infix_operator(kind: TokenKind) -> Option<(Operator, u8)> {
+    Some(match kind {
+        TokenKind::PipePipe => (Operator::LogicalOr, 1),
+        TokenKind::AmpAmp => (Operator::LogicalAnd, 2),
+        TokenKind::EqEq => (Operator::Equal, 3),
+        TokenKind::Ne => (Operator::NotEqual, 3),
+        TokenKind::Lt => (Operator::LessThan, 3),
+        TokenKind::Le => (Operator::LessThanOrEqual, 3),
+        TokenKind::Gt => (Operator::GreaterThan, 3),
+        TokenKind::Ge => (Operator::GreaterThanOrEqual, 3),
+        TokenKind::Pipe => (Operator::Or, 4),
+        TokenKind::Tilde => (Operator::Xor, 5),
+        TokenKind::Amp => (Operator::And, 6),
+        TokenKind::Shl => (Operator::ShiftLeft, 7),
+        TokenKind::Shr => (Operator::ShiftRight, 7),
+        TokenKind::Plus => (Operator::Add, 8),
+        TokenKind::Minus => (Operator::Subtract, 8),
+        TokenKind::Star => (Operator::Multiply, 9),
+        TokenKind::Slash => (Operator::Divide, 9),
+        TokenKind::Percent => (Operator::Modulo, 9),
+        TokenKind::Caret => (Operator::Pow, 10),
+        _ => return None,
+    })
+}
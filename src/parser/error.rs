@@ -0,0 +1,72 @@
+use std::fmt::{self, Display, Formatter};
+
+use super::lexer::Span;
+
+/// A syntax error with enough of the source to point at it.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: Span,
+    /// 1-based line and column of the start of `span`.
+    pub line: usize,
+    pub column: usize,
+    line_text: String,
+    /// How many characters of `line_text` to underline.
+    width: usize,
+    path: Option<String>,
+}
+
+impl SyntaxError {
+    pub fn new(source: &str, span: Span, message: String) -> SyntaxError {
+        let line_start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[span.start..]
+            .find('\n')
+            .map_or(source.len(), |i| span.start + i);
+        let line_text = source[line_start..line_end].trim_end_matches('\r');
+        let column = source[line_start..span.start].chars().count() + 1;
+        let width = source[span.start..span.end.clamp(span.start, line_end)]
+            .chars()
+            .count();
+        SyntaxError {
+            message,
+            span,
+            line: source[..span.start].matches('\n').count() + 1,
+            column,
+            line_text: line_text.to_string(),
+            width,
+            path: None,
+        }
+    }
+
+    /// Names the file the error is in when it is displayed.
+    pub fn with_path(mut self, path: &str) -> SyntaxError {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    pub fn line_col(&self) -> (usize, usize) {
+        (self.line, self.column)
+    }
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let pad = " ".repeat(self.line.to_string().len());
+        let underline = match self.width {
+            0 => "^---".to_string(),
+            1 => "^".to_string(),
+            n => format!("^{}^", "-".repeat(n - 2)),
+        };
+        let path = self
+            .path
+            .as_ref()
+            .map(|path| format!("{}:", path))
+            .unwrap_or_default();
+        writeln!(f, "{}--> {}{}:{}", pad, path, self.line, self.column)?;
+        writeln!(f, "{} |", pad)?;
+        writeln!(f, "{} | {}", self.line, self.line_text)?;
+        writeln!(f, "{} | {}{}", pad, " ".repeat(self.column - 1), underline)?;
+        writeln!(f, "{} |", pad)?;
+        write!(f, "{} = {}", pad, self.message)
+    }
+}
@@ -0,0 +1,630 @@
+//! Splits source text into tokens. The token stream is lossless: trivia
+//! (whitespace and comments) are tokens too, so concatenating the text of
+//! every token gives back the source.
+
+use super::atom::Radix;
+use super::error::SyntaxError;
+
+/// A range of bytes in the source.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// The span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start, other.end)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Keyword {
+    Let,
+    Fn,
+    If,
+    Else,
+    While,
+    Forall,
+    For,
+    Return,
+    Break,
+    Continue,
+    New,
+    Class,
+    Interface,
+    Import,
+    Impl,
+    Enum,
+    Export,
+    Type,
+    Module,
+    Use,
+    As,
+    From,
+    In,
+    Is,
+    Match,
+    None,
+    Some,
+    True,
+    False,
+}
+
+impl Keyword {
+    const ALL: &'static [(&'static str, Keyword)] = &[
+        ("let", Keyword::Let),
+        ("fn", Keyword::Fn),
+        ("if", Keyword::If),
+        ("else", Keyword::Else),
+        ("while", Keyword::While),
+        ("forall", Keyword::Forall),
+        ("for", Keyword::For),
+        ("return", Keyword::Return),
+        ("break", Keyword::Break),
+        ("continue", Keyword::Continue),
+        ("new", Keyword::New),
+        ("class", Keyword::Class),
+        ("interface", Keyword::Interface),
+        ("import", Keyword::Import),
+        ("impl", Keyword::Impl),
+        ("enum", Keyword::Enum),
+        ("export", Keyword::Export),
+        ("type", Keyword::Type),
+        ("module", Keyword::Module),
+        ("use", Keyword::Use),
+        ("as", Keyword::As),
+        ("from", Keyword::From),
+        ("in", Keyword::In),
+        ("is", Keyword::Is),
+        ("match", Keyword::Match),
+        ("none", Keyword::None),
+        ("some", Keyword::Some),
+        ("true", Keyword::True),
+        ("false", Keyword::False),
+    ];
+
+    pub fn from_word(word: &str) -> Option<Keyword> {
+        Keyword::ALL
+            .iter()
+            .find(|(text, _)| *text == word)
+            .map(|(_, keyword)| *keyword)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        Keyword::ALL
+            .iter()
+            .find(|(_, keyword)| keyword == self)
+            .map(|(text, _)| *text)
+            .unwrap()
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum TokenKind {
+    Whitespace,
+    /// `// ...`, up to but not including the line break.
+    Comment,
+    /// A name, including raw identifiers like `r#while`.
+    Ident,
+    Keyword(Keyword),
+    /// Integer digits with their radix prefix.
+    Integer(Radix),
+    /// A floating-point literal without its suffix.
+    Decimal,
+    /// The type written directly after a numeric literal, as in `1i32`.
+    Suffix,
+    /// A quoted string, escapes and all.
+    String,
+    /// `r"..."` or `r#"..."#`.
+    RawString,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Comma,
+    Semicolon,
+    Colon,
+    ColonColon,
+    Dot,
+    DotDot,
+    DotDotEq,
+    /// `->`
+    Arrow,
+    /// `=>`
+    FatArrow,
+    Eq,
+    EqEq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Shl,
+    Shr,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Caret,
+    Amp,
+    AmpAmp,
+    Pipe,
+    PipePipe,
+    Tilde,
+    Bang,
+    Question,
+    Underscore,
+    /// Text that is not a token; the lexer reports why.
+    Unknown,
+    Eof,
+}
+
+impl TokenKind {
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, TokenKind::Whitespace | TokenKind::Comment)
+    }
+
+    /// How tokens of this kind are referred to in error messages.
+    pub fn describe(&self) -> String {
+        match self {
+            TokenKind::Keyword(keyword) => format!("`{}`", keyword.as_str()),
+            TokenKind::Ident => "identifier".to_string(),
+            TokenKind::Eof => "end of input".to_string(),
+            kind => match PUNCTUATION.iter().find(|(_, k)| k == kind) {
+                Some((text, _)) => format!("`{}`", text),
+                None => format!("{:?}", kind).to_lowercase(),
+            },
+        }
+    }
+
+    /// Whether a token of this kind may end an operand, so that a `.`
+    /// after it is a field access rather than the start of a number.
+    fn ends_operand(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Ident
+                | TokenKind::Integer(_)
+                | TokenKind::Decimal
+                | TokenKind::Suffix
+                | TokenKind::String
+                | TokenKind::RawString
+                | TokenKind::RParen
+                | TokenKind::RBracket
+                | TokenKind::RBrace
+                | TokenKind::Question
+                | TokenKind::Keyword(Keyword::True | Keyword::False | Keyword::None)
+        )
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Types a numeric literal may be suffixed with, longest first where one
+/// is a prefix of another.
+const SUFFIXES: &[&str] = &[
+    "u128", "i128", "u8", "i8", "u16", "i16", "u32", "i32", "u64", "i64", "f32", "f64", "bool",
+    "char",
+];
+
+/// Punctuation, longest first where one is a prefix of another.
+const PUNCTUATION: &[(&str, TokenKind)] = &[
+    ("..=", TokenKind::DotDotEq),
+    ("..", TokenKind::DotDot),
+    ("::", TokenKind::ColonColon),
+    ("->", TokenKind::Arrow),
+    ("=>", TokenKind::FatArrow),
+    ("==", TokenKind::EqEq),
+    ("!=", TokenKind::Ne),
+    ("<<", TokenKind::Shl),
+    ("<=", TokenKind::Le),
+    (">>", TokenKind::Shr),
+    (">=", TokenKind::Ge),
+    ("&&", TokenKind::AmpAmp),
+    ("||", TokenKind::PipePipe),
+    ("(", TokenKind::LParen),
+    (")", TokenKind::RParen),
+    ("{", TokenKind::LBrace),
+    ("}", TokenKind::RBrace),
+    ("[", TokenKind::LBracket),
+    ("]", TokenKind::RBracket),
+    (",", TokenKind::Comma),
+    (";", TokenKind::Semicolon),
+    (":", TokenKind::Colon),
+    (".", TokenKind::Dot),
+    ("=", TokenKind::Eq),
+    ("<", TokenKind::Lt),
+    (">", TokenKind::Gt),
+    ("+", TokenKind::Plus),
+    ("-", TokenKind::Minus),
+    ("*", TokenKind::Star),
+    ("/", TokenKind::Slash),
+    ("%", TokenKind::Percent),
+    ("^", TokenKind::Caret),
+    ("&", TokenKind::Amp),
+    ("|", TokenKind::Pipe),
+    ("~", TokenKind::Tilde),
+    ("!", TokenKind::Bang),
+    ("?", TokenKind::Question),
+];
+
+pub struct Lexer<'a> {
+    source: &'a str,
+    pos: usize,
+    tokens: Vec<Token>,
+    errors: Vec<SyntaxError>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Lexer<'a> {
+        Lexer {
+            source,
+            pos: 0,
+            tokens: vec![],
+            errors: vec![],
+        }
+    }
+
+    /// Every token of the source, ending with `Eof`, and an error for each
+    /// `Unknown` token.
+    pub fn tokenize(mut self) -> (Vec<Token>, Vec<SyntaxError>) {
+        while self.pos < self.source.len() {
+            let start = self.pos;
+            let kind = self.token();
+            self.tokens.push(Token {
+                kind,
+                span: Span::new(start, self.pos),
+            });
+        }
+        let end = self.source.len();
+        self.tokens.push(Token {
+            kind: TokenKind::Eof,
+            span: Span::new(end, end),
+        });
+        (self.tokens, self.errors)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat_while(&mut self, f: impl Fn(char) -> bool) {
+        while self.peek().is_some_and(&f) {
+            self.bump();
+        }
+    }
+
+    fn error(&mut self, start: usize, message: String) -> TokenKind {
+        self.errors.push(SyntaxError::new(
+            self.source,
+            Span::new(start, self.pos),
+            message,
+        ));
+        TokenKind::Unknown
+    }
+
+    /// The kind of the last token that is not trivia.
+    fn previous(&self) -> Option<TokenKind> {
+        self.tokens
+            .iter()
+            .rev()
+            .map(|t| t.kind)
+            .find(|k| !k.is_trivia())
+    }
+
+    fn token(&mut self) -> TokenKind {
+        let start = self.pos;
+        let c = self.peek().unwrap();
+        if c.is_whitespace() {
+            self.eat_while(char::is_whitespace);
+            return TokenKind::Whitespace;
+        }
+        if self.rest().starts_with("//") {
+            self.eat_while(|c| c != '\n');
+            return TokenKind::Comment;
+        }
+        if self.suffix_follows_number() {
+            return TokenKind::Suffix;
+        }
+        if c == 'r' && self.rest()[1..].trim_start_matches('#').starts_with('"') {
+            return self.raw_string();
+        }
+        if c == 'r' && self.peek_at(1) == Some('#') {
+            return self.raw_ident();
+        }
+        if c.is_ascii_alphabetic() {
+            self.eat_while(|c| c.is_ascii_alphanumeric() || c == '_');
+            return match Keyword::from_word(&self.source[start..self.pos]) {
+                Some(keyword) => TokenKind::Keyword(keyword),
+                None => TokenKind::Ident,
+            };
+        }
+        // Names start with a letter, so `_x` is `_` followed by `x`.
+        if c == '_' {
+            self.bump();
+            return TokenKind::Underscore;
+        }
+        if c == '"' {
+            return self.string();
+        }
+        if self.previous() == Some(TokenKind::Dot) && c.is_ascii_digit() {
+            // A tuple index, as in `pair.0`.
+            self.eat_while(|c| c.is_ascii_digit());
+            return TokenKind::Integer(Radix::Decimal);
+        }
+        if c.is_ascii_digit()
+            || c == '.'
+                && self.peek_at(1).is_some_and(|c| c.is_ascii_digit())
+                && !self.previous().is_some_and(|k| k.ends_operand())
+        {
+            return self.number();
+        }
+        for (text, kind) in PUNCTUATION {
+            if self.rest().starts_with(text) {
+                self.pos += text.len();
+                return *kind;
+            }
+        }
+        self.bump();
+        self.error(start, format!("unexpected character `{}`", c))
+    }
+
+    fn raw_ident(&mut self) -> TokenKind {
+        let start = self.pos;
+        self.pos += 2;
+        self.eat_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        let word = &self.source[start + 2..self.pos];
+        if Keyword::from_word(word).is_some() {
+            TokenKind::Ident
+        } else {
+            self.error(
+                start,
+                format!(
+                    "`r#{}` is not a keyword, so it cannot be a raw identifier",
+                    word
+                ),
+            )
+        }
+    }
+
+    fn string(&mut self) -> TokenKind {
+        let start = self.pos;
+        self.bump();
+        loop {
+            match self.bump() {
+                None => return self.error(start, "unterminated string literal".to_string()),
+                Some('"') => return TokenKind::String,
+                Some('\\') => {
+                    let escape = self.pos - 1;
+                    match self.bump() {
+                        Some('n' | 't' | 'r' | '0' | '"' | '\\' | '\n' | '\r') => {}
+                        Some('u') if self.peek() == Some('{') => {
+                            self.bump();
+                            let digits = self.pos;
+                            self.eat_while(|c| c.is_ascii_hexdigit());
+                            let count = self.pos - digits;
+                            if !(1..=6).contains(&count) || self.bump() != Some('}') {
+                                return self.bad_escape(escape);
+                            }
+                        }
+                        _ => return self.bad_escape(escape),
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Reports a bad escape starting at `escape`, then skips to the end of
+    /// the string so that the rest of it is not read as code.
+    fn bad_escape(&mut self, escape: usize) -> TokenKind {
+        self.error(escape, "invalid escape sequence".to_string());
+        loop {
+            match self.bump() {
+                None | Some('"') => return TokenKind::Unknown,
+                Some('\\') => {
+                    self.bump();
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    fn raw_string(&mut self) -> TokenKind {
+        let start = self.pos;
+        self.bump();
+        let hashes = self.rest().chars().take_while(|c| *c == '#').count();
+        self.pos += hashes + 1;
+        let closing = format!("\"{}", "#".repeat(hashes));
+        match self.rest().find(&closing) {
+            Some(end) => {
+                self.pos += end + closing.len();
+                TokenKind::RawString
+            }
+            None => {
+                self.pos = self.source.len();
+                self.error(start, "unterminated raw string literal".to_string())
+            }
+        }
+    }
+
+    fn number(&mut self) -> TokenKind {
+        let rest = self.rest();
+        for (prefix, radix, is_digit) in [
+            (
+                "0x",
+                Radix::Hexadecimal,
+                (|c: char| c.is_ascii_hexdigit()) as fn(char) -> bool,
+            ),
+            ("0o", Radix::Octal, |c: char| ('0'..='7').contains(&c)),
+            ("0b", Radix::Binary, |c: char| c == '0' || c == '1'),
+        ] {
+            if rest.starts_with(prefix) && rest[2..].starts_with(is_digit) {
+                self.pos += 2;
+                self.eat_while(|c| is_digit(c) || c == '_');
+                return TokenKind::Integer(radix);
+            }
+        }
+
+        let mut decimal = false;
+        self.eat_while(|c| c.is_ascii_digit() || c == '_');
+        if self.peek() == Some('.') && self.peek_at(1).is_some_and(|c| c.is_ascii_digit()) {
+            decimal = true;
+            self.bump();
+            self.eat_while(|c| c.is_ascii_digit() || c == '_');
+        } else if self.peek() == Some('.') && self.exponent_at(1) {
+            // `1.e5` is a float, not the field `e5` of `1`.
+            decimal = true;
+            self.bump();
+        }
+        if self.exponent_at(0) {
+            decimal = true;
+            self.bump();
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.bump();
+            }
+            self.eat_while(|c| c.is_ascii_digit());
+        }
+        if decimal {
+            TokenKind::Decimal
+        } else {
+            TokenKind::Integer(Radix::Decimal)
+        }
+    }
+
+    /// Whether an exponent such as `e5` or `E-3` starts `n` characters
+    /// ahead.
+    fn exponent_at(&self, n: usize) -> bool {
+        if !matches!(self.peek_at(n), Some('e' | 'E')) {
+            return false;
+        }
+        match (self.peek_at(n + 1), self.peek_at(n + 2)) {
+            (Some('+' | '-'), Some(d)) => d.is_ascii_digit(),
+            (Some(d), _) => d.is_ascii_digit(),
+            _ => false,
+        }
+    }
+
+    /// Consumes a type suffix written directly after a numeric literal.
+    fn suffix_follows_number(&mut self) -> bool {
+        let Some(last) = self.tokens.last() else {
+            return false;
+        };
+        if !matches!(last.kind, TokenKind::Integer(_) | TokenKind::Decimal)
+            || last.span.end != self.pos
+        {
+            return false;
+        }
+        match SUFFIXES.iter().find(|s| self.rest().starts_with(*s)) {
+            Some(suffix) => {
+                self.pos += suffix.len();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert!(errors.is_empty(), "{:?}", errors);
+        tokens
+            .into_iter()
+            .map(|t| t.kind)
+            .filter(|k| !k.is_trivia())
+            .collect()
+    }
+
+    #[test]
+    fn test_lexer_is_lossless() {
+        let source = "let x: u8 = 0xFFu8; // max\nlet s = r#\"a \"b\"\"# + \"\\u{41}\";";
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let text: String = tokens
+            .iter()
+            .map(|t| &source[t.span.start..t.span.end])
+            .collect();
+        assert_eq!(text, source);
+    }
+
+    #[test]
+    fn test_numbers_and_dots() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("t.0.1 + .5 - 1..=2 1.5e-3f64 x.max(1)"),
+            vec![
+                Ident,
+                Dot,
+                Integer(Radix::Decimal),
+                Dot,
+                Integer(Radix::Decimal),
+                Plus,
+                Decimal,
+                Minus,
+                Integer(Radix::Decimal),
+                DotDotEq,
+                Integer(Radix::Decimal),
+                Decimal,
+                Suffix,
+                Ident,
+                Dot,
+                Ident,
+                LParen,
+                Integer(Radix::Decimal),
+                RParen,
+                Eof,
+            ]
+        );
+        assert_eq!(
+            kinds("r#while _ _x"),
+            vec![Ident, Underscore, Underscore, Ident, Eof]
+        );
+    }
+
+    #[test]
+    fn test_lexer_errors() {
+        for (source, message) in [
+            ("\"bad \\q escape\"", "invalid escape sequence"),
+            ("\"open", "unterminated string literal"),
+            (
+                "let r#x = 1;",
+                "`r#x` is not a keyword, so it cannot be a raw identifier",
+            ),
+            ("a @ b", "unexpected character `@`"),
+        ] {
+            let (_, errors) = Lexer::new(source).tokenize();
+            let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+            assert_eq!(messages, vec![message], "{}", source);
+        }
+    }
+}
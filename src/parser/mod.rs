@@ -1,18 +1,68 @@
-use pest::error::Error;
+#[cfg(feature = "pest")]
+use pest::error::{Error, ErrorVariant, InputLocation};
+#[cfg(feature = "pest")]
 use pest::Parser;
+#[cfg(feature = "pest")]
 use pest_derive::Parser;
 
 mod ast;
 pub use ast::*;
+pub mod ast_index;
+#[cfg(feature = "pest")]
 use program::parse_program;
+#[cfg(feature = "pest")]
+use statement::parse_statement;
+pub mod cst;
+mod descent;
+pub use descent::DescentParser;
+mod error;
+pub use error::SyntaxError;
+pub mod lexer;
+#[cfg(feature = "pest")]
+mod recovery;
+#[cfg(feature = "pest")]
+use recovery::{relocate, synchronize};
 
-#[derive(Parser)]
-#[grammar = "voe.pest"]
+/// Parses Voe source with the hand-written `DescentParser`.
+#[derive(Debug, Clone, Copy)]
 pub struct VoeParser;
 
 impl VoeParser {
+    pub fn parse_program(&self, source: &str) -> Result<Program, SyntaxError> {
+        let parsed = DescentParser::new(source).parse_program();
+        #[cfg(all(test, feature = "pest"))]
+        tests::check_against_pest(source, &parsed);
+        parsed
+    }
+
+    /// Parses as much of `source` as possible, returning every syntax error
+    /// at once. See `DescentParser::parse_program_recovering`.
+    pub fn parse_program_recovering(&self, source: &str) -> (Program, Vec<SyntaxError>) {
+        let parsed = DescentParser::new(source).parse_program_recovering();
+        #[cfg(all(test, feature = "pest"))]
+        tests::check_recovering_against_pest(source, &parsed);
+        parsed
+    }
+
+    /// Parses `source` into a lossless syntax tree, along with its AST and
+    /// every syntax error. See `DescentParser::parse_tree`.
+    pub fn parse_tree(&self, source: &str) -> cst::Parse {
+        DescentParser::new(source).parse_tree()
+    }
+}
+
+/// The parser generated from `voe.pest`, kept while the hand-written parser
+/// replaces it.
+#[cfg(feature = "pest")]
+#[derive(Parser)]
+#[grammar = "voe.pest"]
+pub struct PestParser;
+
+#[cfg(feature = "pest")]
+impl PestParser {
     pub fn parse_program(&self, source: &str) -> Result<Program, Error<Rule>> {
-        let mut pairs = VoeParser::parse(Rule::program, source)?;
+        let mut pairs =
+            PestParser::parse(Rule::program, source).map_err(|err| keyword_error(source, err))?;
         parse_program(pairs.next().ok_or(pest::error::Error::new_from_pos(
             pest::error::ErrorVariant::CustomError {
                 message: "expected program".to_string(),
@@ -20,4 +70,320 @@ impl VoeParser {
             pest::Position::from_start(source),
         ))?)
     }
+
+    /// Parses as much of `source` as possible. Each statement that fails to
+    /// parse becomes a `Statement::Error` holding its text, and parsing
+    /// resumes after it, so every syntax error is returned at once.
+    pub fn parse_program_recovering(&self, source: &str) -> (Program, Vec<Error<Rule>>) {
+        let mut statements = vec![];
+        let mut errors = vec![];
+        let mut pos = skip_trivia(source, 0);
+        while pos < source.len() {
+            let rest = &source[pos..];
+            let parsed = PestParser::parse(Rule::statement, rest)
+                .map_err(|err| keyword_error(rest, err))
+                .and_then(|mut pairs| {
+                    let pair = pairs.next().unwrap();
+                    let end = pair.as_span().end();
+                    Ok((parse_statement(pair.into_inner().next().unwrap())?, end))
+                });
+            match parsed {
+                Ok((statement, end)) => {
+                    statements.push(statement);
+                    pos += end;
+                }
+                Err(err) => {
+                    let err = relocate(source, pos, err);
+                    let failed_at = match err.location {
+                        InputLocation::Pos(at) | InputLocation::Span((at, _)) => at,
+                    };
+                    let end = synchronize(source, pos, failed_at);
+                    statements.push(Statement::Error(source[pos..end].trim_end().to_string()));
+                    errors.push(err);
+                    pos = end;
+                }
+            }
+            pos = skip_trivia(source, pos);
+        }
+        (Program::new(statements), errors)
+    }
+}
+
+/// The position of the first non-whitespace, non-comment text at or after
+/// `pos`.
+#[cfg(feature = "pest")]
+fn skip_trivia(source: &str, pos: usize) -> usize {
+    match PestParser::parse(Rule::trivia, &source[pos..]) {
+        Ok(mut pairs) => pos + pairs.next().unwrap().as_span().end(),
+        Err(_) => pos,
+    }
+}
+
+/// Explains a failure to parse a name where a keyword was found, which pest
+/// would report as just "expected ident".
+#[cfg(feature = "pest")]
+fn keyword_error(source: &str, err: Error<Rule>) -> Error<Rule> {
+    let (ErrorVariant::ParsingError { positives, .. }, InputLocation::Pos(pos)) =
+        (&err.variant, err.location.clone())
+    else {
+        return err;
+    };
+    if !positives.contains(&Rule::ident) {
+        return err;
+    }
+    let Ok(mut keyword) = PestParser::parse(Rule::keyword, &source[pos..]) else {
+        return err;
+    };
+    let keyword = keyword.next().unwrap().as_str();
+    Error::new_from_pos(
+        ErrorVariant::CustomError {
+            message: format!(
+                "keyword `{}` used as identifier; write `r#{}` to use it as a name",
+                keyword, keyword
+            ),
+        },
+        pest::Position::new(source, pos).unwrap(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the pest parser accepts exactly the sources the
+    /// hand-written parser does, with the same result. `VoeParser` calls
+    /// this in tests, so every source parsed in a test is compared.
+    #[cfg(feature = "pest")]
+    pub fn check_against_pest(source: &str, parsed: &Result<Program, SyntaxError>) {
+        match (parsed, PestParser.parse_program(source)) {
+            (Ok(program), Ok(expected)) => {
+                assert_eq!(*program, expected, "parsers disagree on:\n{}", source)
+            }
+            (Err(_), Err(_)) => {}
+            (parsed, expected) => panic!(
+                "parsers disagree on:\n{}\n\nhand-written: {:?}\n\npest: {:?}",
+                source, parsed, expected
+            ),
+        }
+    }
+
+    #[cfg(feature = "pest")]
+    pub fn check_recovering_against_pest(source: &str, parsed: &(Program, Vec<SyntaxError>)) {
+        let (expected, errors) = PestParser.parse_program_recovering(source);
+        assert_eq!(parsed.0, expected, "parsers disagree on:\n{}", source);
+        let lines: Vec<usize> = parsed.1.iter().map(|err| err.line).collect();
+        let expected_lines: Vec<usize> = errors
+            .iter()
+            .map(|err| match err.line_col {
+                pest::error::LineColLocation::Pos((line, _))
+                | pest::error::LineColLocation::Span((line, _), _) => line,
+            })
+            .collect();
+        assert_eq!(lines, expected_lines, "errors disagree on:\n{}", source);
+    }
+
+    #[test]
+    #[cfg(feature = "pest")]
+    fn test_parsers_agree() {
+        let valid = [
+            include_str!("../../test.voe"),
+            "let v: Vec<Vec<i32>>= make(); let o: i32?? = none; type P<T> = Map<T, Vec<T>>;",
+            "let x = t.0.1 + .5 - -1.5e3f64 ^ 2i32 ^ 3i32 * -y.len() % 0b1_0i8;",
+            "let s = r##\"a \"# b\"## + \"tab\\t \\u{1F600} \\\n    joined\";",
+            "// leading\nlet a = 1i32; // trailing\n// between\nfn f() -> i32 {\n    // inside\n}",
+            "let f = |x: i32, y: i32| x | y ~ x & y; let g = || -> i32 { h(); }; let k = |x: i32| x;",
+            "let a = if c { 1i32 } else if d { 2i32 } else { 3i32 };",
+            "fn f() -> i32 {\n    { g(); }\n    { 1i32 }\n    match x { _ => 1i32, }\n    x = y[0].z;\n}",
+            "let m = match r { ok(n) => n, err(_) => { 0i32 } + 1i32, Shape::Rect(w, _) => w, };",
+            "forall n: u32. fn zeros<T: A + B, U>(self, xs: [T; 0x10]) -> fn(T) -> [T]? {}",
+            "export let a = [1i32; 3]; import { a as b, c, } from r\"m.voe\"; use a::b::c as d;",
+            "for (i, _) in 0..=10 step 2i32 { f(i); } while x is some { x = next(x?); }",
+            "if let some((a, -1i32, \"s\", true)) = p { f(); } else { g(); }",
+            "let r#match = new Point { x: 1i32, y: (2i32), }; let t = (1i32,);",
+        ];
+        for source in valid {
+            let program = VoeParser.parse_program(source);
+            assert!(program.is_ok(), "{}\n{}", source, program.unwrap_err());
+        }
+
+        let invalid = [
+            "let a = ;",
+            "let x = a < b < c;",
+            "let u = ();",
+            "if a { f(); } else if b { g(); }",
+            "let a = if c { 1i32 } else { f(); };",
+            "fn f() -> i32 { 1i32 }",
+            "let x = 1i32",
+            "let (a, 1i32) = p;",
+            "for some(x) in xs {}",
+            "use a;",
+            "export while x {}",
+            "let s = \"bad \\q\";",
+            "let x = 300u8;",
+            "let y: [i32; 99999999999999999999999999999999999999999] = z;",
+            "f() = 1i32;",
+            "let r#x = 1i32;",
+            "import {} from \"m\";",
+            "let a = 1i32; }",
+            "let x = @;",
+        ];
+        for source in invalid {
+            assert!(VoeParser.parse_program(source).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_syntax_error_messages() {
+        for (source, message) in [
+            ("let a = ;", "expected expression, found `;`"),
+            (
+                "let x = 1i32\nlet y = 2i32;",
+                "expected `;`, found keyword `let`",
+            ),
+            ("fn f() -> i32 { 1i32 }", "expected `;`, found `}`"),
+            (
+                "let a = if c { f(); } else { 1i32 };",
+                "expected expression, found `}`",
+            ),
+            ("let x = 300u8;", "literal `300u8` is out of range for `u8`"),
+            ("let s = \"bad \\q\";", "invalid escape sequence"),
+            ("f() = 1i32;", "cannot assign to `f()`"),
+            ("let v: Vec<i32 = x;", "expected `>`, found `=`"),
+        ] {
+            let err = VoeParser.parse_program(source).unwrap_err();
+            assert_eq!(err.message, message, "{}", source);
+        }
+
+        let err = VoeParser
+            .parse_program("let a = 1i32;\nlet b = 2i32 +;")
+            .unwrap_err()
+            .with_path("m.voe");
+        assert_eq!(
+            err.to_string(),
+            " --> m.voe:2:15\n  |\n2 | let b = 2i32 +;\n  |               ^\n  |\n  = expected expression, found `;`"
+        );
+    }
+
+    #[test]
+    fn test_literal_suffixes() {
+        for (source, message) in [
+            (
+                "let a = 1.5i32;",
+                "float literal cannot have the suffix `i32`",
+            ),
+            (
+                "let a = 5bool;",
+                "integer literal cannot have the suffix `bool`",
+            ),
+            ("let a = 300char;", "unsupported literal suffix `char`"),
+            (
+                "let a = -1u128;",
+                "literal `-1u128` is out of range for `u128`",
+            ),
+            (
+                "let a = 170141183460469231731687303715884105728i128;",
+                "literal `170141183460469231731687303715884105728i128` is out of range for `i128`",
+            ),
+        ] {
+            let err = VoeParser.parse_program(source).unwrap_err();
+            assert_eq!(err.message, message, "{}", source);
+        }
+
+        let program = VoeParser
+            .parse_program(
+                "let a = 0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffffu128;
+let b = -170141183460469231731687303715884105728i128;
+let c = 1.e5 + 2f64;",
+            )
+            .unwrap();
+        assert_eq!(
+            program.to_string(),
+            "let a = 0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFu128;
+let b = -170141183460469231731687303715884105728i128;
+let c = 100000.0 + 2f64;"
+        );
+    }
+
+    #[test]
+    fn test_keywords_are_not_identifiers() {
+        for source in ["let while = 1;", "fn if() -> i32 {}", "let x = true1 + match;"] {
+            assert!(VoeParser.parse_program(source).is_err(), "{}", source);
+        }
+        let err = VoeParser.parse_program("let while = 1;").unwrap_err();
+        assert_eq!(
+            err.message,
+            "keyword `while` used as identifier; write `r#while` to use it as a name"
+        );
+
+        let program = VoeParser
+            .parse_program("let r#while = 1i32; let trueish = r#while; let input = false;")
+            .unwrap();
+        assert_eq!(
+            program.to_string(),
+            "let r#while = 1i32;\nlet trueish = r#while;\nlet input = false;"
+        );
+        assert!(VoeParser.parse_program("let r#x = 1i32;").is_err());
+
+        for (source, message) in [
+            ("break;", "`break` outside of a loop"),
+            (
+                "while c { fn f() -> i32 { continue; } }",
+                "`continue` outside of a loop",
+            ),
+            (
+                "while c { let x = break; }",
+                "`break` cannot be used as a value",
+            ),
+            (
+                "for i in 0..3 { continue; }",
+                "`continue` is not supported; a loop runs until its condition is false",
+            ),
+        ] {
+            let err = VoeParser.parse_program(source).unwrap_err();
+            assert_eq!(err.message, message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let source = "let a = 1i32;
+fn f() -> i32 {
+    let x = ;
+    x;
+}
+let b = ; // comment
+fn g() -> i32 { 1i32; }
+let c = 2i32
+fn h() -> i32 {}
+";
+        let (program, errors) = VoeParser.parse_program_recovering(source);
+        let lines: Vec<usize> = errors.iter().map(|err| err.line).collect();
+        assert_eq!(lines, vec![3, 6, 9]);
+        let errors: Vec<&String> = program
+            .statements
+            .iter()
+            .filter_map(|s| match s {
+                Statement::Error(text) => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "fn f() -> i32 {\n    let x = ;\n    x;\n}",
+                "let b = ;",
+                "let c = 2i32",
+            ]
+        );
+        let names: Vec<&String> = program
+            .statements
+            .iter()
+            .filter_map(|s| s.declared_name())
+            .collect();
+        assert_eq!(names, vec!["a", "g", "h"]);
+
+        let (program, errors) = VoeParser.parse_program_recovering("let a = 1i32; }");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(program.to_string(), "let a = 1i32;\n}");
+    }
 }
@@ -0,0 +1,69 @@
+//! Helpers for resuming parsing after a syntax error.
+
+use pest::error::{Error, InputLocation};
+use pest::{Position, Span};
+
+use super::Rule;
+
+/// Moves an error found while parsing `source[offset..]` to its place in
+/// `source`, so that its line and column are those of the whole file.
+pub fn relocate(source: &str, offset: usize, err: Error<Rule>) -> Error<Rule> {
+    match err.location {
+        InputLocation::Pos(pos) => {
+            Error::new_from_pos(err.variant, Position::new(source, offset + pos).unwrap())
+        }
+        InputLocation::Span((start, end)) => Error::new_from_span(
+            err.variant,
+            Span::new(source, offset + start, offset + end).unwrap(),
+        ),
+    }
+}
+
+/// Where to resume after the statement starting at `start` failed to parse
+/// at `failed_at`: just past the `;` or `}` that ends the statement, or at
+/// the next `fn` that begins a line, whichever comes first. Braces are
+/// counted from `start`, so a `;` or `}` inside a broken function body does
+/// not end it early.
+pub fn synchronize(source: &str, start: usize, failed_at: usize) -> usize {
+    let bytes = source.as_bytes();
+    let mut depth = 0;
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth <= 0 && i >= failed_at {
+                    return i + 1;
+                }
+            }
+            b';' if depth <= 0 && i >= failed_at => return i + 1,
+            b'f' if i > start && i >= failed_at && starts_fn_line(source, i) => return i,
+            _ => {}
+        }
+        i += 1;
+    }
+    bytes.len()
+}
+
+/// Whether a `fn` keyword at `i` is the first thing on its line.
+fn starts_fn_line(source: &str, i: usize) -> bool {
+    let line_start = source[..i].rfind('\n').map_or(0, |n| n + 1);
+    source[line_start..i].trim().is_empty()
+        && source[i..].starts_with("fn")
+        && !source[i + 2..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+}